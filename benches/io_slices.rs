@@ -0,0 +1,101 @@
+//! Measures the per-call overhead of `io_slices` for small messages, where
+//! building the `IoSlice` wrappers is a noticeable part of the cost.
+//!
+//! Run with `cargo bench --bench io_slices`.
+
+use std::hint::black_box;
+use std::io::{self, Read, Write};
+use std::time::{Duration, Instant};
+
+const ITERATIONS: u32 = 2_000_000;
+
+fn main() -> io::Result<()> {
+    for size in [64, 4096] {
+        bench(
+            &format!("io_slices {size:>4} byte messages"),
+            size,
+            |producer, consumer, msg, out| {
+                producer
+                    .io_slices(|bufs, _len| io::Cursor::new(&*msg).read_vectored(bufs))
+                    .map_err(io::Error::other)?;
+                consumer
+                    .io_slices(|bufs, _len| io::Cursor::new(&mut *out).write_vectored(bufs))
+                    .map_err(io::Error::other)?;
+                Ok(())
+            },
+        )?;
+        bench(
+            &format!("slices    {size:>4} byte messages"),
+            size,
+            |producer, consumer, msg, out| {
+                producer
+                    .slices(|bufs, _len| copy_into(bufs, msg))
+                    .map_err(io::Error::other)?;
+                consumer
+                    .slices(|bufs, _len| copy_from(bufs, out))
+                    .map_err(io::Error::other)?;
+                Ok(())
+            },
+        )?;
+    }
+    Ok(())
+}
+
+fn bench(
+    name: &str,
+    size: usize,
+    mut f: impl FnMut(
+        &mut bytering::Producer,
+        &mut bytering::Consumer,
+        &[u8],
+        &mut [u8],
+    ) -> io::Result<()>,
+) -> io::Result<()> {
+    // Not a multiple of the message size, so messages straddle the seam.
+    let (mut producer, mut consumer) = bytering::new(64 * 1024, 64).unwrap();
+    producer.write_all(&[0; 1000])?;
+    consumer.read_exact(&mut [0; 1000])?;
+
+    let msg = vec![0x5a; size];
+    let mut out = vec![0; size];
+
+    let start = Instant::now();
+    for _ in 0..ITERATIONS {
+        f(
+            &mut producer,
+            &mut consumer,
+            black_box(&msg),
+            black_box(&mut out),
+        )?;
+    }
+    report(name, start.elapsed());
+
+    Ok(())
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let per_iter = elapsed / ITERATIONS;
+    println!("{name}: {per_iter:?} per roundtrip");
+}
+
+fn copy_into(bufs: &mut [&mut [u8]], mut src: &[u8]) -> Result<usize, io::Error> {
+    let mut n = 0;
+    for buf in bufs {
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        src = &src[len..];
+        n += len;
+    }
+    Ok(n)
+}
+
+fn copy_from(bufs: &[&[u8]], mut dst: &mut [u8]) -> Result<usize, io::Error> {
+    let mut n = 0;
+    for buf in bufs {
+        let len = buf.len().min(dst.len());
+        dst[..len].copy_from_slice(&buf[..len]);
+        dst = &mut dst[len..];
+        n += len;
+    }
+    Ok(n)
+}
@@ -0,0 +1,98 @@
+//! Compares draining a ring into a file with `io::copy`, which goes through
+//! its 8 KiB stack buffer, against `Consumer::write_to`, which hands the
+//! ring's own slices to the file. Before timing, checks that `write_to`
+//! passes the ring's memory itself, so it copies nothing on the way.
+//!
+//! Run with `cargo bench --bench write_to`.
+
+use std::fs::File;
+use std::io::{self, BufRead, IoSlice, Write};
+use std::time::{Duration, Instant};
+
+const RING: usize = 256 * 1024;
+const ITERATIONS: u32 = 2_000;
+
+fn main() -> io::Result<()> {
+    check_zero_copy()?;
+
+    let path = std::env::temp_dir().join(format!("bytering-write_to-{}", std::process::id()));
+    let mut file = File::create(&path)?;
+    let result = (|| {
+        bench("io::copy          ", &mut file, |consumer, file| {
+            io::copy(consumer, file)
+        })?;
+        bench("Consumer::write_to", &mut file, |consumer, file| {
+            consumer.write_to(file)
+        })
+    })();
+    std::fs::remove_file(&path)?;
+    result
+}
+
+/// Asserts that every buffer `write_to` passes on points into the ring.
+fn check_zero_copy() -> io::Result<()> {
+    struct Check {
+        expected: *const u8,
+        written: usize,
+    }
+
+    impl Write for Check {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            assert_eq!(bufs[0].as_ptr(), self.expected, "write_to copied");
+            let n = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+            self.written += n;
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let (mut producer, mut consumer) = bytering::new(RING, 64).unwrap();
+    // Straddling the seam, so both slices are passed on.
+    for fill in [RING / 2, RING] {
+        producer.write_all(&vec![0x5a; fill])?;
+        let expected = consumer.fill_buf()?.as_ptr();
+        let mut check = Check {
+            expected,
+            written: 0,
+        };
+        assert_eq!(consumer.write_to(&mut check)?, u64::try_from(fill).unwrap());
+        assert_eq!(check.written, fill);
+    }
+    println!("write_to passes the ring's slices on without copying");
+    Ok(())
+}
+
+fn bench(
+    name: &str,
+    file: &mut File,
+    mut f: impl FnMut(&mut bytering::Consumer, &mut File) -> io::Result<u64>,
+) -> io::Result<()> {
+    let (mut producer, mut consumer) = bytering::new(RING, 64).unwrap();
+    let chunk = vec![0x5a; RING];
+
+    let mut elapsed = Duration::ZERO;
+    for _ in 0..ITERATIONS {
+        producer.write_all(&chunk)?;
+        file.set_len(0)?;
+        let start = Instant::now();
+        let n = f(&mut consumer, file)?;
+        elapsed += start.elapsed();
+        assert_eq!(n, u64::try_from(RING).unwrap());
+    }
+    report(name, elapsed);
+
+    Ok(())
+}
+
+fn report(name: &str, elapsed: Duration) {
+    let bytes = f64::from(ITERATIONS) * RING as f64;
+    let gib_per_sec = bytes / elapsed.as_secs_f64() / f64::from(1 << 30);
+    println!("{name}: {gib_per_sec:.2} GiB/s into a file");
+}
@@ -0,0 +1,214 @@
+//! Python bindings for the rings shared between processes of
+//! [`bytering::shm`], so Python code can exchange bytes with Rust services
+//! without sockets.
+//!
+//! ```python
+//! import bytering
+//!
+//! producer = bytering.Producer.create("/my-ring", 1 << 16)
+//! producer.write(b"hello")
+//!
+//! consumer = bytering.Consumer.open("/my-ring")
+//! if consumer.wait(1, timeout=1.0):
+//!     print(consumer.read())
+//! ```
+//!
+//! Either side may be a Rust process using [`bytering::shm`] directly.
+//! Waiting releases the GIL.
+
+#![deny(
+    clippy::undocumented_unsafe_blocks,
+    deprecated,
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery
+)]
+
+use std::ffi::CString;
+use std::io::{Read as _, Write as _};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use bytering::BufferError;
+use bytering::shm::{self, Segment};
+use pyo3::exceptions::{PyOSError, PyRuntimeError, PyValueError};
+use pyo3::prelude::*;
+use pyo3::types::PyBytes;
+
+fn name(name: &str) -> PyResult<CString> {
+    CString::new(name).map_err(|err| PyValueError::new_err(err.to_string()))
+}
+
+fn error(err: &BufferError) -> PyErr {
+    match err {
+        BufferError::MapFailed(code) => PyOSError::new_err((*code, err.to_string())),
+        BufferError::BadSize(_) => PyValueError::new_err(err.to_string()),
+        _ => PyOSError::new_err(err.to_string()),
+    }
+}
+
+fn timeout(seconds: Option<f64>) -> PyResult<Option<Duration>> {
+    seconds
+        .map(|seconds| {
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|err| PyValueError::new_err(err.to_string()))
+        })
+        .transpose()
+}
+
+fn lock<T>(half: &Mutex<T>) -> PyResult<MutexGuard<'_, T>> {
+    half.lock()
+        .map_err(|_| PyRuntimeError::new_err("ring half poisoned by a panic"))
+}
+
+/// A locked half, to wait on with the GIL released.
+struct Locked<'a, T>(&'a T);
+
+// SAFETY: `Python::allow_threads` runs the closure on the calling thread,
+//         which holds the half's lock throughout, so no other thread ever
+//         accesses the half meanwhile.
+unsafe impl<T> Send for Locked<'_, T> {}
+
+/// The writing half of a shared ring.
+#[pyclass(module = "bytering")]
+struct Producer {
+    // Halves are `Send` but not `Sync`, Python objects must be both.
+    inner: Mutex<shm::Producer>,
+    size: usize,
+}
+
+#[pymethods]
+impl Producer {
+    /// Creates the shared memory object `name`, e.g. "/my-ring", holding a
+    /// ring of `size` bytes, a power of two, and takes its producer half.
+    #[staticmethod]
+    fn create(name: &str, size: usize) -> PyResult<Self> {
+        let name = self::name(name)?;
+        // SAFETY: every process maps the object through bytering.
+        let segment = unsafe { Segment::create(&name, size) }.map_err(|err| error(&err))?;
+        Self::take(segment)
+    }
+
+    /// Maps the existing ring `name` and takes its producer half.
+    #[staticmethod]
+    fn open(name: &str) -> PyResult<Self> {
+        let name = self::name(name)?;
+        // SAFETY: every process maps the object through bytering.
+        let segment = unsafe { Segment::open(&name) }.map_err(|err| error(&err))?;
+        Self::take(segment)
+    }
+
+    /// The size of the ring.
+    #[getter]
+    const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Copies as much of `data` into the ring as fits and returns the
+    /// number of bytes copied, 0 if the ring is full.
+    fn write(&self, data: &[u8]) -> PyResult<usize> {
+        Ok(lock(&self.inner)?.write(data)?)
+    }
+
+    /// Blocks until at least `n` bytes are free, until `timeout` seconds
+    /// pass or the consumer's process died. Returns whether they are.
+    #[pyo3(signature = (n, timeout=None))]
+    fn wait(&self, py: Python<'_>, n: usize, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = self::timeout(timeout)?;
+        let producer = lock(&self.inner)?;
+        let locked = Locked(&*producer);
+        Ok(py.allow_threads(move || locked.0.wait(n, timeout)))
+    }
+}
+
+impl Producer {
+    fn take(segment: Segment) -> PyResult<Self> {
+        let size = segment.size();
+        let producer = segment.into_producer().map_err(|err| error(&err))?;
+        Ok(Producer {
+            inner: Mutex::new(producer),
+            size,
+        })
+    }
+}
+
+/// The reading half of a shared ring.
+#[pyclass(module = "bytering")]
+struct Consumer {
+    // See `Producer`.
+    inner: Mutex<shm::Consumer>,
+    size: usize,
+}
+
+#[pymethods]
+impl Consumer {
+    /// Creates the shared memory object `name`, see `Producer.create`, and
+    /// takes its consumer half.
+    #[staticmethod]
+    fn create(name: &str, size: usize) -> PyResult<Self> {
+        let name = self::name(name)?;
+        // SAFETY: every process maps the object through bytering.
+        let segment = unsafe { Segment::create(&name, size) }.map_err(|err| error(&err))?;
+        Self::take(segment)
+    }
+
+    /// Maps the existing ring `name` and takes its consumer half.
+    #[staticmethod]
+    fn open(name: &str) -> PyResult<Self> {
+        let name = self::name(name)?;
+        // SAFETY: every process maps the object through bytering.
+        let segment = unsafe { Segment::open(&name) }.map_err(|err| error(&err))?;
+        Self::take(segment)
+    }
+
+    /// The size of the ring.
+    #[getter]
+    const fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Consumes up to `n` bytes, all in the ring by default, and returns
+    /// them, empty if the ring is.
+    #[pyo3(signature = (n=None))]
+    fn read<'py>(&self, py: Python<'py>, n: Option<usize>) -> PyResult<Bound<'py, PyBytes>> {
+        let mut buf = vec![0; n.unwrap_or(self.size).min(self.size)];
+        let len = lock(&self.inner)?.read(&mut buf)?;
+        Ok(PyBytes::new(py, &buf[..len]))
+    }
+
+    /// Blocks until at least `n` bytes are filled, until `timeout` seconds
+    /// pass or the producer's process died. Returns whether they are.
+    #[pyo3(signature = (n, timeout=None))]
+    fn wait(&self, py: Python<'_>, n: usize, timeout: Option<f64>) -> PyResult<bool> {
+        let timeout = self::timeout(timeout)?;
+        let consumer = lock(&self.inner)?;
+        let locked = Locked(&*consumer);
+        Ok(py.allow_threads(move || locked.0.wait(n, timeout)))
+    }
+}
+
+impl Consumer {
+    fn take(segment: Segment) -> PyResult<Self> {
+        let size = segment.size();
+        let consumer = segment.into_consumer().map_err(|err| error(&err))?;
+        Ok(Consumer {
+            inner: Mutex::new(consumer),
+            size,
+        })
+    }
+}
+
+/// Removes the name of the shared memory object `name`, so it is freed
+/// once all processes unmapped it.
+#[pyfunction]
+fn unlink(name: &str) -> PyResult<()> {
+    Segment::unlink(&self::name(name)?).map_err(|err| error(&err))
+}
+
+#[pymodule]
+fn bytering(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<Producer>()?;
+    m.add_class::<Consumer>()?;
+    m.add_function(wrap_pyfunction!(unlink, m)?)?;
+    Ok(())
+}
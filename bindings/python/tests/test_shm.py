@@ -0,0 +1,19 @@
+import os
+
+import bytering
+
+
+def test_halves_exchange_bytes():
+    name = f"/bytering-py-{os.getpid()}"
+    producer = bytering.Producer.create(name, 16)
+    consumer = bytering.Consumer.open(name)
+    bytering.unlink(name)
+
+    assert producer.size == consumer.size == 16
+    assert producer.write(b"x" * 20) == 16
+    assert not producer.wait(1, timeout=0.01)
+    assert consumer.read(4) == b"xxxx"
+    assert producer.write(b"abcd") == 4
+    assert consumer.wait(16, timeout=0.01)
+    assert consumer.read() == b"x" * 12 + b"abcd"
+    assert consumer.read() == b""
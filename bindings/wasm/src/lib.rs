@@ -0,0 +1,187 @@
+//! Bridges between the async halves of [`bytering::asynch`] and the
+//! `ReadableStream` and `WritableStream` of the web platform, so
+//! WebAssembly applications can pump `fetch` bodies and other streams
+//! through the same ring code they run natively.
+//!
+//! [`pump_from`] and [`pump_into`] drive a stream from Rust, [`readable`]
+//! and [`writable`] hand a half to JavaScript as a stream. The halves wake
+//! the tasks of `wasm-bindgen-futures` and never block the thread, so all
+//! of them work on the main thread.
+
+#![deny(
+    clippy::undocumented_unsafe_blocks,
+    deprecated,
+    clippy::all,
+    clippy::pedantic,
+    clippy::nursery
+)]
+#![allow(
+    // JavaScript values never leave their thread.
+    clippy::future_not_send,
+)]
+
+use std::cell::Cell;
+use std::rc::Rc;
+
+use bytering::asynch::{Consumer, Producer};
+use js_sys::{Object, Promise, Reflect, Uint8Array};
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast as _, JsValue};
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
+use web_sys::{
+    ReadableStream, ReadableStreamDefaultController, ReadableStreamDefaultReader, WritableStream,
+};
+
+fn error(message: &str) -> JsValue {
+    js_sys::Error::new(message).into()
+}
+
+/// Copies all of `chunk` into the ring, waiting for room.
+async fn write_all(producer: &mut Producer, mut chunk: &[u8]) -> Result<(), JsValue> {
+    while !chunk.is_empty() {
+        let n = producer
+            .write(chunk)
+            .await
+            .map_err(|err| error(&err.to_string()))?;
+        chunk = &chunk[n..];
+    }
+    Ok(())
+}
+
+/// Copies the chunks of `stream`, `Uint8Array`s, into `producer` until the
+/// stream ends, waiting for room in the ring. Returns the number of bytes
+/// copied. The stream is locked meanwhile.
+///
+/// # Errors
+///
+/// Returns the stream's error, or an error if the consumer was dropped.
+pub async fn pump_from(stream: &ReadableStream, producer: &mut Producer) -> Result<u64, JsValue> {
+    let reader: ReadableStreamDefaultReader = stream.get_reader().unchecked_into();
+    let result = async {
+        let mut total = 0;
+        loop {
+            let next = JsFuture::from(reader.read()).await?;
+            if Reflect::get(&next, &JsValue::from_str("done"))?.is_truthy() {
+                return Ok(total);
+            }
+            let chunk = Uint8Array::new(&Reflect::get(&next, &JsValue::from_str("value"))?);
+            let chunk = chunk.to_vec();
+            write_all(producer, &chunk).await?;
+            total += chunk.len() as u64;
+        }
+    }
+    .await;
+    reader.release_lock();
+    result
+}
+
+/// Copies the bytes of `consumer` into `stream`, as `Uint8Array`s of at
+/// most `chunk` bytes, until the producer was dropped and the ring is
+/// empty, then closes the stream. Returns the number of bytes copied. The
+/// stream is locked meanwhile.
+///
+/// # Errors
+///
+/// Returns the stream's error.
+pub async fn pump_into(
+    consumer: &mut Consumer,
+    stream: &WritableStream,
+    chunk: usize,
+) -> Result<u64, JsValue> {
+    let writer = stream.get_writer()?;
+    let mut buf = vec![0; chunk.max(1)];
+    let result = async {
+        let mut total = 0;
+        loop {
+            let Ok(n) = consumer.read(&mut buf).await;
+            if n == 0 {
+                JsFuture::from(writer.close()).await?;
+                return Ok(total);
+            }
+            JsFuture::from(writer.write_with_chunk(&Uint8Array::from(&buf[..n]))).await?;
+            total += n as u64;
+        }
+    }
+    .await;
+    writer.release_lock();
+    result
+}
+
+/// A half shared by the callbacks of a stream, and taken out while one of
+/// them awaits it: streams never call the next before the last settled.
+type Slot<T> = Rc<Cell<Option<T>>>;
+
+/// A callback dropping the half in `slot`, for closing or cancelling.
+fn dropping<T: 'static>(slot: &Slot<T>) -> JsValue {
+    let slot = Rc::clone(slot);
+    Closure::<dyn FnMut()>::new(move || drop(slot.take())).into_js_value()
+}
+
+/// Turns `consumer` into a `ReadableStream` of `Uint8Array`s of at most
+/// `chunk` bytes, which closes once the producer was dropped and the ring
+/// is empty. Cancelling the stream drops the consumer.
+///
+/// # Errors
+///
+/// Returns an error if the stream cannot be created.
+pub fn readable(consumer: Consumer, chunk: usize) -> Result<ReadableStream, JsValue> {
+    let chunk = chunk.max(1);
+    let slot: Slot<Consumer> = Rc::new(Cell::new(Some(consumer)));
+    let pull = {
+        let slot = Rc::clone(&slot);
+        Closure::<dyn FnMut(ReadableStreamDefaultController) -> Promise>::new(
+            move |controller: ReadableStreamDefaultController| {
+                let slot = Rc::clone(&slot);
+                future_to_promise(async move {
+                    let Some(mut consumer) = slot.take() else {
+                        return Ok(JsValue::UNDEFINED);
+                    };
+                    let mut buf = vec![0; chunk];
+                    let Ok(n) = consumer.read(&mut buf).await;
+                    if n == 0 {
+                        controller.close()?;
+                    } else {
+                        controller.enqueue_with_chunk(&Uint8Array::from(&buf[..n]))?;
+                        slot.set(Some(consumer));
+                    }
+                    Ok(JsValue::UNDEFINED)
+                })
+            },
+        )
+    };
+    let source = Object::new();
+    Reflect::set(&source, &JsValue::from_str("pull"), &pull.into_js_value())?;
+    Reflect::set(&source, &JsValue::from_str("cancel"), &dropping(&slot))?;
+    ReadableStream::new_with_underlying_source(&source)
+}
+
+/// Turns `producer` into a `WritableStream` taking `Uint8Array`s, whose
+/// writes wait for room in the ring. Closing or aborting the stream drops
+/// the producer.
+///
+/// # Errors
+///
+/// Returns an error if the stream cannot be created.
+pub fn writable(producer: Producer) -> Result<WritableStream, JsValue> {
+    let slot: Slot<Producer> = Rc::new(Cell::new(Some(producer)));
+    let write = {
+        let slot = Rc::clone(&slot);
+        Closure::<dyn FnMut(JsValue) -> Promise>::new(move |chunk: JsValue| {
+            let slot = Rc::clone(&slot);
+            future_to_promise(async move {
+                let Some(mut producer) = slot.take() else {
+                    return Err(error("writing to a closed stream"));
+                };
+                let chunk = Uint8Array::new(&chunk).to_vec();
+                let result = write_all(&mut producer, &chunk).await;
+                slot.set(Some(producer));
+                result.map(|()| JsValue::UNDEFINED)
+            })
+        })
+    };
+    let sink = Object::new();
+    Reflect::set(&sink, &JsValue::from_str("write"), &write.into_js_value())?;
+    Reflect::set(&sink, &JsValue::from_str("close"), &dropping(&slot))?;
+    Reflect::set(&sink, &JsValue::from_str("abort"), &dropping(&slot))?;
+    WritableStream::new_with_underlying_sink(&sink)
+}
@@ -4,23 +4,16 @@ use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering::Relaxed;
 use std::{hint, thread};
 
+use bytering::testutil::{RandomReader, RandomWriter};
 use bytering::{ConsumerError, ProducerError};
-use rand::rngs::SmallRng;
-use rand::{Rng, RngExt as _, SeedableRng};
 
 fn main() -> io::Result<()> {
     const DATA_SIZE: usize = 10 << 30;
 
     let (mut producer, mut consumer) = bytering::new(4096, 4096).unwrap();
 
-    let mut input = DummyInput {
-        rng: SmallRng::seed_from_u64(12345),
-        data: DATA_SIZE,
-    };
-    let mut output = DummyOutput {
-        rng: SmallRng::seed_from_u64(54321),
-        data: 0,
-    };
+    let mut input = RandomReader::new(DATA_SIZE as u64, 12345);
+    let mut output = RandomWriter::new(54321);
     let done = Arc::new(AtomicBool::new(false));
     let done_check = Arc::clone(&done);
 
@@ -44,7 +37,9 @@ fn main() -> io::Result<()> {
                     })
                     .map_err(|err| match err {
                         ProducerError::Callback(err) => err,
-                        err @ ProducerError::InvalidCount { .. } => invalid_count_panic(err),
+                        err @ (ProducerError::InvalidCount { .. }
+                        | ProducerError::TornFrame { .. }
+                        | ProducerError::Poisoned) => invalid_count_panic(err),
                     })?;
 
                 if stop {
@@ -70,7 +65,9 @@ fn main() -> io::Result<()> {
                     })
                     .map_err(|err| match err {
                         ConsumerError::Callback(err) => err,
-                        err @ ConsumerError::InvalidCount { .. } => invalid_count_panic(err),
+                        err @ (ConsumerError::InvalidCount { .. }
+                        | ConsumerError::TornFrame { .. }
+                        | ConsumerError::Poisoned) => invalid_count_panic(err),
                     })?;
 
                 if consumer.is_empty() && done_check.load(Relaxed) {
@@ -83,8 +80,8 @@ fn main() -> io::Result<()> {
     let input = producer_thread.join().unwrap()?;
     let output = consumer_thread.join().unwrap()?;
 
-    assert_eq!(input.data, 0);
-    assert_eq!(output.data, DATA_SIZE);
+    assert_eq!(input.remaining(), 0);
+    assert_eq!(output.written(), DATA_SIZE as u64);
 
     Ok(())
 }
@@ -94,51 +91,3 @@ fn main() -> io::Result<()> {
 fn invalid_count_panic(err: impl std::fmt::Display) -> ! {
     panic!("{err}");
 }
-
-struct DummyInput<R: Rng> {
-    rng: R,
-    data: usize,
-}
-
-impl<R: Rng> io::Read for DummyInput<R> {
-    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
-        unimplemented!("unvectored read")
-    }
-
-    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
-        let len = bufs.iter().map(|b| b.len()).sum::<usize>().min(self.data);
-        let n = if len <= 10 {
-            len
-        } else {
-            self.rng.random_range(1..len)
-        };
-        self.data -= n;
-        Ok(n)
-    }
-}
-
-struct DummyOutput<R: Rng> {
-    rng: R,
-    data: usize,
-}
-
-impl<R: Rng> io::Write for DummyOutput<R> {
-    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
-        unimplemented!("unvectored write")
-    }
-
-    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
-        let len = bufs.iter().map(|b| b.len()).sum::<usize>();
-        let n = if len <= 10 {
-            len
-        } else {
-            self.rng.random_range(0..len)
-        };
-        self.data += n;
-        Ok(n)
-    }
-
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
-    }
-}
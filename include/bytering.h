@@ -0,0 +1,167 @@
+#ifndef BYTERING_H
+#define BYTERING_H
+
+/* Generated by cbindgen from src/ffi.rs. Do not edit. */
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Success.
+ */
+#define BYTERING_OK 0
+
+/**
+ * The size is not a power of two or too large, see
+ * [`BufferError::BadSize`].
+ */
+#define BYTERING_EBADSIZE -1
+
+/**
+ * The alignment is not a power of two, see
+ * [`BufferError::BadAlignment`].
+ */
+#define BYTERING_EBADALIGN -2
+
+/**
+ * The memory could not be allocated or mapped.
+ */
+#define BYTERING_ENOMEM -3
+
+/**
+ * The count exceeds the length acquired, see
+ * [`ProducerError::InvalidCount`].
+ */
+#define BYTERING_EINVALIDCOUNT -4
+
+/**
+ * The count is not a multiple of the frame size, see
+ * [`ProducerError::TornFrame`].
+ */
+#define BYTERING_ETORNFRAME -5
+
+/**
+ * Any other error.
+ */
+#define BYTERING_EOTHER -6
+
+typedef struct bytering_consumer bytering_consumer;
+
+typedef struct bytering_producer bytering_producer;
+
+/**
+ * The empty space of a ring, one or two slices, see
+ * [`bytering_producer_acquire`]. A missing second slice has a length of 0.
+ */
+typedef struct bytering_write_slices {
+  /**
+   * The starts of the slices.
+   */
+  uint8_t *ptr[2];
+  /**
+   * The lengths of the slices.
+   */
+  size_t len[2];
+} bytering_write_slices;
+
+/**
+ * The filled space of a ring, one or two slices, see
+ * [`bytering_consumer_acquire`]. A missing second slice has a length of 0.
+ */
+typedef struct bytering_read_slices {
+  /**
+   * The starts of the slices.
+   */
+  const uint8_t *ptr[2];
+  /**
+   * The lengths of the slices.
+   */
+  size_t len[2];
+} bytering_read_slices;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Creates a ring of `size` bytes aligned to `align`, see [`crate::new`],
+ * and stores its halves to `producer` and `consumer`. Stores nothing on
+ * failure.
+ *
+ * # Safety
+ *
+ * `producer` and `consumer` must be valid for writes.
+ */
+int bytering_new(size_t size,
+                 size_t align,
+                 bytering_producer **producer,
+                 bytering_consumer **consumer);
+
+/**
+ * Fills in `slices` with the empty space of the ring and returns its
+ * total length. Commits nothing.
+ *
+ * # Safety
+ *
+ * `producer` must come from [`bytering_new`] and not be destroyed yet,
+ * and `slices` must be valid for writes.
+ */
+size_t bytering_producer_acquire(bytering_producer *producer, bytering_write_slices *slices);
+
+/**
+ * Publishes the first `n` bytes of the slices last acquired to the
+ * consumer.
+ *
+ * # Safety
+ *
+ * See [`bytering_producer_acquire`].
+ */
+int bytering_producer_commit(bytering_producer *producer, size_t n);
+
+/**
+ * Frees the producer half, publishing all bytes committed. Does nothing
+ * if `producer` is null.
+ *
+ * # Safety
+ *
+ * `producer` must be null or come from [`bytering_new`] and not be
+ * destroyed yet. It must not be used afterwards.
+ */
+void bytering_producer_destroy(bytering_producer *producer);
+
+/**
+ * Fills in `slices` with the filled space of the ring and returns its
+ * total length. Releases nothing.
+ *
+ * # Safety
+ *
+ * `consumer` must come from [`bytering_new`] and not be destroyed yet,
+ * and `slices` must be valid for writes.
+ */
+size_t bytering_consumer_acquire(bytering_consumer *consumer, bytering_read_slices *slices);
+
+/**
+ * Releases the first `n` bytes of the slices last acquired to the
+ * producer.
+ *
+ * # Safety
+ *
+ * See [`bytering_consumer_acquire`].
+ */
+int bytering_consumer_commit(bytering_consumer *consumer, size_t n);
+
+/**
+ * Frees the consumer half. Does nothing if `consumer` is null.
+ *
+ * # Safety
+ *
+ * `consumer` must be null or come from [`bytering_new`] and not be
+ * destroyed yet. It must not be used afterwards.
+ */
+void bytering_consumer_destroy(bytering_consumer *consumer);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* BYTERING_H */
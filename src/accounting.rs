@@ -0,0 +1,167 @@
+//! Memory accounting hooks, see [`Builder::accounting`] and
+//! [`set_global_accounting`].
+//!
+//! [`Builder::accounting`]: crate::Builder::accounting
+//! [`set_global_accounting`]: crate::set_global_accounting
+
+use ::alloc::boxed::Box;
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::fmt;
+use ::core::marker::{Send, Sync};
+use ::core::ops::Drop;
+use ::core::option::Option;
+use ::core::ptr;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicPtr, AtomicUsize};
+
+use crate::BufferError;
+use crate::ordering::{AcqRel, Acquire, Relaxed};
+
+/// Receives the size of every buffer allocation and deallocation, e.g. to
+/// bound the total buffer memory of a proxy.
+pub trait Accounting: Send + Sync {
+    /// Called before `bytes` are allocated. Returning `false` refuses the
+    /// allocation, which then fails with [`BufferError::OverBudget`].
+    fn allocate(&self, bytes: usize) -> bool;
+
+    /// Called once the `bytes` of an earlier, accepted allocation are freed.
+    fn deallocate(&self, bytes: usize);
+}
+
+/// Refuses allocations once a total number of bytes is in use.
+#[derive(Debug)]
+pub struct Budget {
+    limit: usize,
+    used: AtomicUsize,
+}
+
+impl Budget {
+    /// Creates a budget of `limit` bytes.
+    #[must_use]
+    #[inline]
+    pub const fn new(limit: usize) -> Self {
+        Budget {
+            limit,
+            used: AtomicUsize::new(0),
+        }
+    }
+
+    /// Returns the number of bytes allowed in use at once.
+    #[must_use]
+    #[inline]
+    pub const fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Returns the number of bytes in use.
+    #[must_use]
+    #[inline]
+    pub fn used(&self) -> usize {
+        self.used.load(Relaxed)
+    }
+}
+
+impl Accounting for Budget {
+    #[inline]
+    fn allocate(&self, bytes: usize) -> bool {
+        self.used
+            .fetch_update(Relaxed, Relaxed, |used| {
+                used.checked_add(bytes).filter(|&used| used <= self.limit)
+            })
+            .is_ok()
+    }
+
+    #[inline]
+    fn deallocate(&self, bytes: usize) {
+        self.used.fetch_sub(bytes, Relaxed);
+    }
+}
+
+/// A shared accounting hook.
+#[derive(Clone)]
+pub struct Account(pub Arc<dyn Accounting>);
+
+impl fmt::Debug for Account {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Account")
+    }
+}
+
+/// Bytes accepted by an [`Account`], given back on drop.
+#[derive(Debug)]
+pub struct Charge {
+    account: Account,
+    bytes: usize,
+}
+
+impl Charge {
+    #[inline]
+    pub fn new(account: &Account, bytes: usize) -> Result<Self, BufferError> {
+        if !account.0.allocate(bytes) {
+            return Err(BufferError::OverBudget(bytes));
+        }
+        Ok(Charge {
+            account: account.clone(),
+            bytes,
+        })
+    }
+
+    #[inline]
+    pub fn account(&self) -> &Account {
+        &self.account
+    }
+}
+
+impl Drop for Charge {
+    #[inline]
+    fn drop(&mut self) {
+        self.account.0.deallocate(self.bytes);
+    }
+}
+
+/// The hook set by [`set_global`], leaked so it can be handed out for good.
+static GLOBAL: AtomicPtr<Account> = AtomicPtr::new(ptr::null_mut());
+
+/// Sets the accounting hook of every buffer allocated without one of its
+/// own, see [`Builder::accounting`].
+///
+/// This covers every kind of ring, the segments of unbounded rings, arenas,
+/// pools and resized buffers included. The hook can only be set once.
+///
+/// # Errors
+///
+/// Hands `account` back if a hook is set already.
+///
+/// [`Builder::accounting`]: crate::Builder::accounting
+#[inline]
+pub fn set_global(account: Arc<dyn Accounting>) -> Result<(), Arc<dyn Accounting>> {
+    let new = Box::into_raw(Box::new(Account(account)));
+    if GLOBAL
+        .compare_exchange(ptr::null_mut(), new, AcqRel, Acquire)
+        .is_err()
+    {
+        // SAFETY: `new` comes from `Box::into_raw` and was not shared.
+        let Account(account) = *unsafe { Box::from_raw(new) };
+        return Err(account);
+    }
+    Ok(())
+}
+
+/// Returns the hook set by [`set_global`], if any.
+#[inline]
+fn global<'a>() -> Option<&'a Account> {
+    // SAFETY: the pointer is null or set once from `Box::into_raw`, and
+    //         never freed.
+    unsafe { GLOBAL.load(Acquire).as_ref() }
+}
+
+/// Charges `bytes` to `account`, or to the global hook if there is none.
+#[inline]
+pub fn charge(account: Option<&Account>, bytes: usize) -> Result<Option<Charge>, BufferError> {
+    account
+        .or_else(global)
+        .map(|account| Charge::new(account, bytes))
+        .transpose()
+}
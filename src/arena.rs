@@ -0,0 +1,93 @@
+//! Many rings carved out of a single allocation, see [`Arena`].
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::{AlignedData, Backing, Buffer, BufferError, Consumer, Producer};
+
+/// The allocation carved up by an [`Arena`], kept alive by its rings.
+#[derive(Debug)]
+pub struct Region(AlignedData);
+
+/// Carves one aligned allocation into independent rings of equal size, for
+/// servers holding per-connection buffers by the ten thousand.
+///
+/// Besides saving allocator round-trips, the rings' memory ends up next to
+/// each other. The allocation is freed once the arena and all of its rings
+/// are dropped; slots of dropped rings are not reused.
+#[derive(Debug)]
+pub struct Arena {
+    region: Arc<Region>,
+    size: usize,
+    stride: usize,
+    next: usize,
+}
+
+impl Arena {
+    /// Allocates memory for `rings` rings of `size` bytes each, aligned to
+    /// `align`. Both `size` and `align` must be powers of two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `size` or `align` is not a power of two, when
+    /// `rings` is 0 or the total size overflows, or when the allocation
+    /// fails.
+    #[inline]
+    pub fn new(rings: usize, size: usize, align: usize) -> Result<Self, BufferError> {
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        if !align.is_power_of_two() {
+            return Err(BufferError::BadAlignment(align));
+        }
+
+        // Slots are aligned as long as their offsets are multiples of align.
+        let stride = size.max(align);
+        let total = match rings.checked_mul(stride) {
+            Some(total) if total != 0 => total,
+            _ => return Err(BufferError::BadSize(size)),
+        };
+
+        Ok(Arena {
+            region: Arc::new(Region(AlignedData::new(total, align, None)?)),
+            size,
+            stride,
+            next: 0,
+        })
+    }
+
+    /// Returns the number of rings not yet handed out.
+    #[must_use]
+    #[inline]
+    pub fn remaining(&self) -> usize {
+        (self.region.0.len() - self.next) / self.stride
+    }
+
+    /// Hands out the next ring as a producer-consumer pair, or `None` once
+    /// all of them have been.
+    #[inline]
+    pub fn pair(&mut self) -> Option<(Producer, Consumer)> {
+        if self.remaining() == 0 {
+            return None;
+        }
+
+        let parent = &self.region.0;
+        // SAFETY: the offset is within the allocation, at least `size` bytes
+        //         before its end, as a ring remains.
+        let ptr = unsafe { parent.ptr.add(self.next) };
+        self.next += self.stride;
+
+        let data = AlignedData {
+            ptr,
+            len: self.size,
+            align: parent.align(),
+            backing: Backing::Carved(Arc::clone(&self.region)),
+            // The arena's allocation is accounted for as a whole.
+            charge: None,
+        };
+        Some(crate::pair(Buffer::new(data, 1, 0, 0)))
+    }
+}
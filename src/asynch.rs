@@ -0,0 +1,341 @@
+//! Async halves of a ring, see [`new`], for executors without `std`.
+//!
+//! Each half registers the waker of a task that found nothing to do in an
+//! [`AtomicWaker`], which the other half wakes after committing or
+//! releasing bytes. Registering and waking never lock or allocate, so the
+//! halves work with embedded executors, and the `try_*` methods, which
+//! wake but never wait, may be called from interrupt handlers. The
+//! `read`, `write` and `flush` methods have the signatures of the
+//! `embedded_io_async` traits, so implementing those is a matter of
+//! forwarding.
+//!
+//! # Interrupt handlers
+//!
+//! The most common use on microcontrollers is an interrupt handler
+//! producing, e.g. a UART's receive interrupt, and a task consuming:
+//!
+//! 1. Create the halves at startup and move the [`Producer`] into a static
+//!    the handler owns, e.g. a `static` cell taken once, or a critical
+//!    section mutex on multi-core parts.
+//! 2. In the handler, copy the received bytes in with
+//!    [`Producer::try_write`], which wakes the task and returns at once;
+//!    bytes that do not fit are for the handler to drop or count.
+//! 3. The task awaits [`Consumer::read`], which sleeps until the handler
+//!    wrote something.
+//!
+//! The wake runs in the handler, so the executor's wakers must be safe to
+//! call from interrupt context, as those of embedded executors such as
+//! embassy's are. The reverse, a task producing for a transmit interrupt
+//! using [`Consumer::try_read`], works the same way.
+
+use ::alloc::sync::Arc;
+use ::core::cell::UnsafeCell;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::fmt;
+use ::core::future::poll_fn;
+use ::core::hint;
+use ::core::marker::{Send, Sync};
+use ::core::ops::Drop;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, AtomicUsize};
+use ::core::task::{Context, Poll, Waker};
+use ::core::{debug_assert, write};
+
+use crate::BufferError;
+use crate::ordering::{AcqRel, Acquire, Release};
+
+/// Creates a pair of async halves sharing a ring buffer of `size` bytes,
+/// aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    let shared = Arc::new(Shared {
+        reader: AtomicWaker::new(),
+        writer: AtomicWaker::new(),
+        producer_gone: AtomicBool::new(false),
+        consumer_gone: AtomicBool::new(false),
+    });
+    let producer = Producer {
+        inner: producer,
+        shared: Arc::clone(&shared),
+    };
+    let consumer = Consumer {
+        inner: consumer,
+        shared,
+    };
+    Ok((producer, consumer))
+}
+
+/// The error type returned by [`Producer::write`]: the consumer was
+/// dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Closed;
+
+impl fmt::Display for Closed {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "writing to a ring without consumer")
+    }
+}
+
+impl ::core::error::Error for Closed {}
+
+/// Nobody registers or wakes.
+const WAITING: usize = 0;
+/// A half is storing its waker.
+const REGISTERING: usize = 0b01;
+/// The other half is taking the waker to wake it.
+const WAKING: usize = 0b10;
+
+/// The slot for the waker of a half waiting for the other, shared without
+/// a lock: the `state` serializes registering and waking, and a wake
+/// coming in while a waker is being stored wakes it right after.
+#[derive(Debug)]
+pub(crate) struct AtomicWaker {
+    state: AtomicUsize,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// SAFETY: the waker is only accessed by whoever moved the state away from
+//         WAITING, and wakers are `Send` and `Sync`.
+unsafe impl Send for AtomicWaker {}
+// SAFETY: see above.
+unsafe impl Sync for AtomicWaker {}
+
+impl AtomicWaker {
+    #[inline]
+    pub(crate) const fn new() -> Self {
+        AtomicWaker {
+            state: AtomicUsize::new(WAITING),
+            waker: UnsafeCell::new(None),
+        }
+    }
+
+    /// Registers `waker`, replacing any registered before; the caller must
+    /// check for progress once more afterwards. Only one thread may
+    /// register at a time.
+    #[inline]
+    pub(crate) fn register(&self, waker: &Waker) {
+        match self
+            .state
+            .compare_exchange(WAITING, REGISTERING, Acquire, Acquire)
+        {
+            Ok(_) => {
+                // SAFETY: moving the state to REGISTERING locked the slot.
+                let slot = unsafe { &mut *self.waker.get() };
+                match slot {
+                    Some(old) if old.will_wake(waker) => {}
+                    _ => *slot = Some(waker.clone()),
+                }
+                // A wake came in meanwhile and left the waker alone: wake
+                // it now, unlocking the slot.
+                if self
+                    .state
+                    .compare_exchange(REGISTERING, WAITING, AcqRel, Acquire)
+                    .is_err()
+                {
+                    let waker = slot.take();
+                    let _ = self.state.swap(WAITING, AcqRel);
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+            // The other half is waking the previous waker right now, which
+            // may be stale: wake this one too.
+            Err(WAKING) => {
+                waker.wake_by_ref();
+                hint::spin_loop();
+            }
+            Err(state) => {
+                debug_assert!(state & REGISTERING != 0, "concurrent register");
+            }
+        }
+    }
+
+    /// Wakes the registered waker, if any, after the other half made
+    /// progress.
+    #[inline]
+    pub(crate) fn wake(&self) {
+        if self.state.fetch_or(WAKING, AcqRel) == WAITING {
+            // SAFETY: setting WAKING in state WAITING locked the slot.
+            let waker = unsafe { (*self.waker.get()).take() };
+            let _ = self.state.fetch_and(!WAKING, Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// The wakers of both halves, and whether either is gone.
+#[derive(Debug)]
+struct Shared {
+    reader: AtomicWaker,
+    writer: AtomicWaker,
+    producer_gone: AtomicBool,
+    consumer_gone: AtomicBool,
+}
+
+/// The writing half of an async ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    inner: crate::Producer,
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// Copies as much of `src` into the ring as fits and wakes the
+    /// consumer. Returns the number of bytes copied, 0 if the ring is full.
+    #[inline]
+    pub fn try_write(&mut self, src: &[u8]) -> usize {
+        let n = self.inner.slices(|bufs, len| {
+            let n = src.len().min(len);
+            crate::tee::copy_prefix(&[src], bufs, n);
+            Ok::<_, Infallible>(n)
+        });
+        let n = n.unwrap_or(0);
+        if n != 0 {
+            self.shared.reader.wake();
+        }
+        n
+    }
+
+    /// Attempts to copy bytes from `src` into the ring, registering the
+    /// task to be woken if there is no room.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Closed`] if the consumer was dropped.
+    #[inline]
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, src: &[u8]) -> Poll<Result<usize, Closed>> {
+        if src.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut registered = false;
+        loop {
+            if self.shared.consumer_gone.load(Acquire) {
+                return Poll::Ready(Err(Closed));
+            }
+            let n = self.try_write(src);
+            if n != 0 {
+                return Poll::Ready(Ok(n));
+            }
+            if registered {
+                return Poll::Pending;
+            }
+            self.shared.writer.register(cx.waker());
+            registered = true;
+        }
+    }
+
+    /// Copies bytes from `src` into the ring, waiting for room. Returns the
+    /// number of bytes copied, only 0 if `src` is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Closed`] if the consumer was dropped.
+    #[inline]
+    pub async fn write(&mut self, src: &[u8]) -> Result<usize, Closed> {
+        poll_fn(|cx| self.poll_write(cx, src)).await
+    }
+
+    /// Commits are published right away, there is nothing to flush.
+    ///
+    /// # Errors
+    ///
+    /// Never fails.
+    #[inline]
+    #[expect(clippy::unused_async, reason = "the signature of the trait method")]
+    pub async fn flush(&mut self) -> Result<(), Closed> {
+        self.inner.publish();
+        Ok(())
+    }
+}
+
+impl Drop for Producer {
+    #[inline]
+    fn drop(&mut self) {
+        self.inner.publish();
+        self.shared.producer_gone.store(true, Release);
+        self.shared.reader.wake();
+    }
+}
+
+/// The reading half of an async ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    inner: crate::Consumer,
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// Copies bytes from the ring into `dst`, consumes them and wakes the
+    /// producer. Returns the number of bytes copied, 0 if the ring is
+    /// empty.
+    #[inline]
+    pub fn try_read(&mut self, dst: &mut [u8]) -> usize {
+        let n = self.inner.slices(|bufs, len| {
+            let n = dst.len().min(len);
+            crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+            Ok::<_, Infallible>(n)
+        });
+        let n = n.unwrap_or(0);
+        if n != 0 {
+            self.shared.writer.wake();
+        }
+        n
+    }
+
+    /// Attempts to copy bytes from the ring into `dst`, registering the
+    /// task to be woken if there are none. Returns 0 once the producer was
+    /// dropped and the ring is empty.
+    #[inline]
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<usize> {
+        if dst.is_empty() {
+            return Poll::Ready(0);
+        }
+        let mut registered = false;
+        loop {
+            // Loaded first: the producer published its last bytes before.
+            let gone = self.shared.producer_gone.load(Acquire);
+            let n = self.try_read(dst);
+            if n != 0 || gone {
+                return Poll::Ready(n);
+            }
+            if registered {
+                return Poll::Pending;
+            }
+            self.shared.reader.register(cx.waker());
+            registered = true;
+        }
+    }
+
+    /// Copies bytes from the ring into `dst`, waiting for some. Returns the
+    /// number of bytes copied, 0 if `dst` is empty, or once the producer
+    /// was dropped and the ring is empty.
+    ///
+    /// # Errors
+    ///
+    /// Never fails.
+    #[inline]
+    pub async fn read(&mut self, dst: &mut [u8]) -> Result<usize, Infallible> {
+        Ok(poll_fn(|cx| self.poll_read(cx, dst)).await)
+    }
+}
+
+impl Drop for Consumer {
+    #[inline]
+    fn drop(&mut self) {
+        self.shared.consumer_gone.store(true, Release);
+        self.shared.writer.wake();
+    }
+}
@@ -0,0 +1,341 @@
+//! One producer feeding every byte to several consumers.
+//!
+//! Each [`Consumer`] tracks its own read position; the producer only reuses
+//! space all of them have drained, so the slowest one provides the
+//! backpressure. This fans a byte stream out, e.g. to disk and network,
+//! without copying it into a buffer per destination.
+
+use ::alloc::boxed::Box;
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::default::Default;
+use ::core::hint;
+use ::core::marker::PhantomData;
+use ::core::ops::{Drop, FnMut};
+use ::core::option::Option::None;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, AtomicUsize};
+use ::crossbeam_utils::CachePadded;
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{
+    AlignedData, BufferError, ConsumerError, ProducerError, SendNotSyncZst, empty_ranges,
+    filled_ranges,
+};
+
+/// Creates a producer feeding `consumers` consumers through a ring buffer
+/// of `size` bytes, aligned to `align`. Both must be powers of two.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(
+    size: usize,
+    align: usize,
+    consumers: usize,
+) -> Result<(Producer, Vec<Consumer>), BufferError> {
+    if !size.is_power_of_two() {
+        return Err(BufferError::BadSize(size));
+    }
+    if !align.is_power_of_two() {
+        return Err(BufferError::BadAlignment(align));
+    }
+
+    let mut cursors = Vec::with_capacity(consumers);
+    cursors.resize_with(consumers, CachePadded::<Cursor>::default);
+    let buffer = Arc::new(Buffer {
+        write: CachePadded::default(),
+        cursors: cursors.into_boxed_slice(),
+        mask: size - 1,
+        data: AlignedData::new(size, align, None)?,
+    });
+
+    let mut halves = Vec::with_capacity(consumers);
+    for index in 0..consumers {
+        halves.push(Consumer {
+            buffer: Arc::clone(&buffer),
+            index,
+            _notsync: PhantomData,
+        });
+    }
+    let producer = Producer {
+        buffer,
+        _notsync: PhantomData,
+    };
+    Ok((producer, halves))
+}
+
+#[derive(Debug)]
+struct Cursor {
+    read: AtomicUsize,
+    /// Cleared once the consumer is dropped, so it holds nothing back.
+    attached: AtomicBool,
+}
+
+/// The state shared by the halves. The slices handed out over `data` are
+/// never aliased mutably: the producer, the only writer, is handed the
+/// region behind the slowest attached cursor only, which no consumer reads
+/// from. Consumers only ever share filled bytes immutably. Each cursor is
+/// advanced only by its uniquely owned consumer, and counts returned by
+/// callbacks are checked before any counter is advanced.
+#[derive(Debug)]
+struct Buffer {
+    write: CachePadded<AtomicUsize>,
+    cursors: Box<[CachePadded<Cursor>]>,
+    mask: usize,
+    data: AlignedData,
+}
+
+impl Buffer {
+    /// Returns the read position of the slowest attached consumer, or `w`
+    /// if none is attached.
+    #[inline]
+    fn slowest(&self, w: usize) -> usize {
+        let mut filled = 0;
+        for cursor in &self.cursors {
+            if cursor.attached.load(Acquire) {
+                filled = filled.max(w.wrapping_sub(cursor.read.load(Acquire)));
+            }
+        }
+        w.wrapping_sub(filled)
+    }
+}
+
+impl Default for Cursor {
+    #[inline]
+    fn default() -> Self {
+        Cursor {
+            read: AtomicUsize::new(0),
+            attached: AtomicBool::new(true),
+        }
+    }
+}
+
+/// The writing half of a broadcast ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    buffer: Arc<Buffer>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Producer {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn produce_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let buffer = &*self.buffer;
+        let w = buffer.write.load(Relaxed);
+        let r = buffer.slowest(w);
+
+        let (mut ranges, mut len) = empty_ranges(buffer.data.len(), buffer.mask, r, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map the region behind the slowest attached consumer,
+        //         which no consumer reads from. Detached consumers don't read
+        //         at all.
+        let bufs = unsafe { buffer.data.slices_mut(ranges) };
+
+        let n = f(bufs, len).map_err(ProducerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ProducerError::InvalidCount { n, len });
+        }
+
+        buffer.write.store(w.wrapping_add(n), Release);
+        Ok(n)
+    }
+
+    /// Fills the buffer: calls the passed closure with a pair of `&mut [u8]`
+    /// mapping the space every consumer has drained, see
+    /// [`crate::Producer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(false, |mut bufs, len| f(&mut bufs, len))
+    }
+
+    /// Fills the buffer: calls the passed closure with a single `&mut [u8]`
+    /// mapping the contiguous part of the space every consumer has drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(true, |[buf, _], _| f(buf))
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Write for Producer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        let n = self.slice(|dst| {
+            let n = dst.len().min(src.len());
+            dst[..n].copy_from_slice(&src[..n]);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One of the reading halves of a broadcast ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    buffer: Arc<Buffer>,
+    index: usize,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Consumer {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn consume_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+        let cursor = &buffer.cursors[self.index];
+        let r = cursor.read.load(Relaxed);
+        let w = buffer.write.load(Acquire);
+
+        let (mut ranges, mut len) = filled_ranges(buffer.data.len(), buffer.mask, r, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map bytes this consumer has not drained yet, which
+        //         the producer does not write to. Other consumers may read
+        //         them at the same time, immutably as well.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+
+        let n = f(bufs, len).map_err(ConsumerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+
+        if n != 0 {
+            cursor.read.store(r.wrapping_add(n), Release);
+        }
+        Ok(n)
+    }
+
+    /// Drains this consumer's view of the buffer: calls the passed closure
+    /// with a pair of `&[u8]` mapping the bytes it has not consumed yet, see
+    /// [`crate::Consumer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len| f(&bufs, len))
+    }
+
+    /// Drains this consumer's view of the buffer: calls the passed closure
+    /// with a single `&[u8]` mapping the contiguous part of the bytes it has
+    /// not consumed yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _| f(buf))
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        let cursor = &self.buffer.cursors[self.index];
+        cursor.read.load(Relaxed) == self.buffer.write.load(Relaxed)
+    }
+}
+
+impl Drop for Consumer {
+    #[inline]
+    fn drop(&mut self) {
+        self.buffer.cursors[self.index]
+            .attached
+            .store(false, Release);
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let n = self.slice(|src| {
+            let n = dst.len().min(src.len());
+            dst[..n].copy_from_slice(&src[..n]);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+}
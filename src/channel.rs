@@ -0,0 +1,184 @@
+//! A channel facade over a packet ring, see [`channel`].
+//!
+//! Familiar semantics for users of `std::sync::mpsc` channels of `Vec<u8>`,
+//! without allocating per message: messages are copied into and out of the
+//! ring whole.
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::fmt;
+use ::core::ops::FnMut;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::fence;
+use ::core::write;
+
+use crate::ordering::Acquire;
+use crate::{BufferError, datagram};
+
+/// Creates a sender-receiver pair sharing a ring buffer of `size` bytes,
+/// holding up to `messages` messages. Both must be powers of two.
+///
+/// # Errors
+///
+/// Returns an error when a parameter is not a power of two, or when an
+/// allocation fails.
+#[inline]
+pub fn channel(size: usize, messages: usize) -> Result<(Sender, Receiver), BufferError> {
+    let (producer, consumer) = datagram::new(size, ::core::mem::align_of::<usize>(), messages)?;
+    let alive = Arc::new(());
+    let sender = Sender {
+        inner: producer,
+        alive: Arc::clone(&alive),
+    };
+    let receiver = Receiver {
+        inner: consumer,
+        alive,
+    };
+    Ok((sender, receiver))
+}
+
+/// The error type returned by [`Sender::try_send`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError {
+    /// There is no room for the message right now. A message larger than the
+    /// ring never fits.
+    Full,
+    /// The receiver was dropped.
+    Closed,
+    /// The message is empty. The ring cannot tell an empty message from no
+    /// message, so these are refused.
+    Empty,
+}
+
+impl fmt::Display for TrySendError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrySendError::Full => write!(f, "sending on a full channel"),
+            TrySendError::Closed => write!(f, "sending on a closed channel"),
+            TrySendError::Empty => write!(f, "sending an empty message"),
+        }
+    }
+}
+
+impl ::core::error::Error for TrySendError {}
+
+/// The error type returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There is no message right now.
+    Empty,
+    /// The sender was dropped and all messages were received.
+    Closed,
+    /// The next message is longer than the buffer passed in, it is left in
+    /// the channel. Carries its length.
+    TooSmall(usize),
+}
+
+impl fmt::Display for TryRecvError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "receiving on an empty channel"),
+            TryRecvError::Closed => write!(f, "receiving on a closed channel"),
+            TryRecvError::TooSmall(len) => {
+                write!(f, "buffer too small for a message of {len} bytes")
+            }
+        }
+    }
+}
+
+impl ::core::error::Error for TryRecvError {}
+
+/// The sending half of a [`channel`].
+#[derive(Debug)]
+pub struct Sender {
+    inner: datagram::Producer,
+    /// Shared with the receiver, to tell whether it was dropped.
+    alive: Arc<()>,
+}
+
+impl Sender {
+    /// Sends `message` without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TrySendError::Closed`] if the receiver was dropped,
+    /// [`TrySendError::Empty`] if `message` is empty, or
+    /// [`TrySendError::Full`] if there is no room for the message.
+    #[inline]
+    pub fn try_send(&mut self, message: &[u8]) -> Result<(), TrySendError> {
+        if Arc::strong_count(&self.alive) == 1 {
+            return Err(TrySendError::Closed);
+        }
+        if message.is_empty() {
+            return Err(TrySendError::Empty);
+        }
+        if self.inner.send(message) {
+            Ok(())
+        } else {
+            Err(TrySendError::Full)
+        }
+    }
+}
+
+/// The receiving half of a [`channel`].
+#[derive(Debug)]
+pub struct Receiver {
+    inner: datagram::Consumer,
+    /// Shared with the sender, to tell whether it was dropped.
+    alive: Arc<()>,
+}
+
+impl Receiver {
+    /// Receives the next message into `dst` without blocking. Returns its
+    /// length.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryRecvError::Empty`] if there is no message,
+    /// [`TryRecvError::Closed`] if there is none and the sender was dropped,
+    /// or [`TryRecvError::TooSmall`] if `dst` cannot hold the message.
+    #[inline]
+    pub fn try_recv(&mut self, dst: &mut [u8]) -> Result<usize, TryRecvError> {
+        self.try_recv_with(|message, len| {
+            if len > dst.len() {
+                return Err(TryRecvError::TooSmall(len));
+            }
+            let mut copied = 0;
+            for buf in message {
+                dst[copied..copied + buf.len()].copy_from_slice(buf);
+                copied += buf.len();
+            }
+            Ok(())
+        })
+    }
+
+    /// Passes the next message and its length to `f`, which consumes it
+    /// unless it returns an error. Returns its length.
+    #[inline]
+    pub(crate) fn try_recv_with(
+        &mut self,
+        f: impl FnMut(&[&[u8]], usize) -> Result<(), TryRecvError>,
+    ) -> Result<usize, TryRecvError> {
+        // Checked first: a sender dropped afterwards published its last
+        // message before letting go.
+        let closed = Arc::strong_count(&self.alive) == 1;
+        if closed {
+            fence(Acquire);
+        }
+        match self.inner.slices(f) {
+            Ok(0) if closed => Err(TryRecvError::Closed),
+            Ok(0) => Err(TryRecvError::Empty),
+            Ok(n) => Ok(n),
+            Err(crate::ConsumerError::Callback(e)) => Err(e),
+            Err(
+                crate::ConsumerError::InvalidCount { .. }
+                | crate::ConsumerError::TornFrame { .. }
+                | crate::ConsumerError::Poisoned,
+            ) => {
+                ::core::unreachable!("packets are consumed whole")
+            }
+        }
+    }
+}
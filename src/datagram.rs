@@ -0,0 +1,181 @@
+//! Rings carrying discrete packets, e.g. UDP datagrams or WebSocket frames.
+//!
+//! Every non-empty commit of the [`Producer`] is one packet, its length kept
+//! in a second, typed ring next to the bytes. The [`Consumer`] receives
+//! exactly one packet per call, never a partial or merged one.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::From as _;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::{BufferError, ConsumerError, ProducerError, typed};
+
+/// Creates a producer-consumer pair sharing a ring buffer of `size` bytes,
+/// aligned to `align`, holding up to `packets` packets.
+///
+/// # Errors
+///
+/// Returns an error when a parameter is not a power of two, or when an
+/// allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize, packets: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (data, bytes) = crate::new(size, align)?;
+    let (lengths, lengths_consumer) = typed::new(packets)?;
+    let producer = Producer { data, lengths };
+    let consumer = Consumer {
+        data: bytes,
+        lengths: lengths_consumer,
+    };
+    Ok((producer, consumer))
+}
+
+/// The writing half of a packet ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    data: crate::Producer,
+    lengths: typed::Producer<usize>,
+}
+
+impl Producer {
+    /// Writes a packet: calls the passed closure with a pair of `&mut [u8]`
+    /// mapping the empty space and their total length, which is 0 if no
+    /// more packets fit. The closure must return the length of the packet;
+    /// 0 commits none.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let data = &mut self.data;
+        let mut n = 0;
+        let result = self.lengths.slice(|slots| {
+            let Some(slot) = slots.first_mut() else {
+                let n = f(&mut [], 0).map_err(ProducerError::Callback)?;
+                if n != 0 {
+                    return Err(ProducerError::InvalidCount { n, len: 0 });
+                }
+                return Ok(0);
+            };
+            // The bytes are published by the time the length is.
+            n = data.slices(&mut f)?;
+            *slot = n;
+            Ok(usize::from(n != 0))
+        });
+        match result {
+            Ok(_) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned,
+            ) => {
+                ::core::unreachable!("one slot offered, one committed at most")
+            }
+        }
+    }
+
+    /// Copies `packet` into the ring as one packet. Returns `false`,
+    /// committing nothing, if it does not fit.
+    #[inline]
+    pub fn send(&mut self, packet: &[u8]) -> bool {
+        let n = self.slices(|bufs, len| {
+            if packet.is_empty() || packet.len() > len {
+                return Ok::<_, ()>(0);
+            }
+            let mut copied = 0;
+            for buf in bufs {
+                let k = buf.len().min(packet.len() - copied);
+                buf[..k].copy_from_slice(&packet[copied..copied + k]);
+                copied += k;
+            }
+            Ok(packet.len())
+        });
+        ::core::matches!(n, Ok(n) if n != 0)
+    }
+}
+
+/// The reading half of a packet ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    data: crate::Consumer,
+    lengths: typed::Consumer<usize>,
+}
+
+impl Consumer {
+    /// Receives a packet: calls the passed closure with a pair of `&[u8]`
+    /// mapping the oldest packet and its length. The packet is consumed once
+    /// the closure succeeds. Returns the length of the packet, 0 without
+    /// calling the closure if there was none.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged. The packet is left in the ring then.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<(), E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let data = &mut self.data;
+        let mut n = 0;
+        let result = self.lengths.slice(|lengths| {
+            let Some(&len) = lengths.first() else {
+                return Ok(0);
+            };
+            data.slices(|bufs, _| {
+                let first = bufs[0].len().min(len);
+                let packet = [&bufs[0][..first], &bufs[1][..len - first]];
+                f(&packet, len).map(|()| len)
+            })?;
+            n = len;
+            Ok(1)
+        });
+        match result {
+            Ok(_) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned,
+            ) => {
+                ::core::unreachable!("one slot offered, one committed at most")
+            }
+        }
+    }
+
+    /// Copies the oldest packet into `dst` and consumes it. Like `recv` on a
+    /// datagram socket, a packet longer than `dst` is truncated, the rest of
+    /// it is discarded. Returns the length of the packet, `None` if there
+    /// was none.
+    #[inline]
+    pub fn recv(&mut self, dst: &mut [u8]) -> Option<usize> {
+        let n = self.slices(|packet, _| {
+            let mut copied = 0;
+            for buf in packet {
+                let k = buf.len().min(dst.len() - copied);
+                dst[copied..copied + k].copy_from_slice(&buf[..k]);
+                copied += k;
+            }
+            Ok::<_, ()>(())
+        });
+        match n {
+            Ok(0) | Err(_) => None,
+            Ok(n) => Some(n),
+        }
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lengths.is_empty()
+    }
+}
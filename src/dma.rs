@@ -0,0 +1,221 @@
+//! Transfers between a ring and a DMA controller, see
+//! [`Producer::dma_grant`] and [`Consumer::dma_grant`].
+//!
+//! A transfer describes the empty or filled space as up to two
+//! [`Descriptor`]s, address and length, to program into a DMA controller,
+//! e.g. both buffers of a double-buffered stream. Once the controller is
+//! done, completing the transfer commits or releases the bytes it moved.
+//!
+//! # While a transfer is in flight
+//!
+//! The transfer borrows its half mutably, so the half hands out nothing
+//! else until it is completed or dropped, and holds no references into the
+//! ring, only addresses, so the compiler assumes nothing about the bytes
+//! the controller accesses. The other half goes on as usual meanwhile:
+//!
+//! - While filling, the consumer only ever touches the filled space, which
+//!   the descriptors never overlap. Releasing bytes only grows the empty
+//!   space past the descriptors' end, so they stay valid.
+//! - While draining, the producer only ever writes the empty space, and
+//!   committing only grows the filled space past the descriptors' end.
+//!
+//! None of the bytes moved are visible to the other half before the
+//! transfer is completed, publishing them with a release store. The caller
+//! must ensure the controller finished, and on parts with a data cache,
+//! that the cache was invalidated or cleaned, before completing. Dropping
+//! a transfer completes nothing; the controller must be stopped first, or
+//! it keeps accessing space the half may hand out again. The same holds if
+//! the transfer is leaked.
+//!
+//! # HAL DMA APIs
+//!
+//! HALs built on the `embedded-dma` traits take a single buffer, stable
+//! and usually `'static`: a transfer's [`WriteTransfer::write_buffer`] and
+//! [`ReadTransfer::read_buffer`] have the signatures of
+//! `WriteBuffer::write_buffer` and `ReadBuffer::read_buffer`, with words of
+//! `u8`, describing the first descriptor, so implementing the traits for a
+//! wrapper of the transfer is a matter of forwarding. A transfer of a half
+//! kept in a `static`, borrowed as `&'static mut`, is `'static` itself.
+
+use ::core::clone::Clone;
+use ::core::convert::Infallible;
+use ::core::marker::Copy;
+use ::core::ops::Range;
+use ::core::result::Result;
+use ::core::sync::atomic::Ordering::SeqCst;
+use ::core::sync::atomic::compiler_fence;
+
+use crate::{Buffer, Consumer, ConsumerError, Producer, ProducerError};
+
+/// A region of a ring to program into a DMA controller. Empty unless `len`
+/// is non-zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Descriptor {
+    /// The start of the region.
+    pub addr: *mut u8,
+    /// The length of the region in bytes.
+    pub len: usize,
+}
+
+impl Descriptor {
+    #[inline]
+    fn new(buffer: &Buffer, range: &Range<usize>) -> Self {
+        Descriptor {
+            // Only an address: the controller accesses the bytes, Rust
+            // code never does through it.
+            addr: buffer.data.ptr.as_ptr().wrapping_add(range.start),
+            len: crate::range_len(range),
+        }
+    }
+}
+
+impl Producer {
+    /// Describes the empty space of the ring, to be filled by a DMA
+    /// controller and committed with [`WriteTransfer::complete`].
+    #[must_use]
+    #[inline]
+    pub fn dma_grant(&mut self) -> WriteTransfer<'_> {
+        let (ranges, len) = self.buffer.grant_empty(self.write, self.limits);
+        let descriptors = [
+            Descriptor::new(&self.buffer, &ranges[0]),
+            Descriptor::new(&self.buffer, &ranges[1]),
+        ];
+        // The controller may start right after: whatever was written to
+        // the space before is not moved past the transfer's start.
+        compiler_fence(SeqCst);
+        WriteTransfer {
+            producer: self,
+            descriptors,
+            len,
+        }
+    }
+}
+
+/// The empty space of a ring, described for a DMA controller to fill, see
+/// [`Producer::dma_grant`].
+#[derive(Debug)]
+pub struct WriteTransfer<'a> {
+    producer: &'a mut Producer,
+    descriptors: [Descriptor; 2],
+    len: usize,
+}
+
+impl WriteTransfer<'_> {
+    /// Returns the descriptors of the empty space, in order. The second one
+    /// is empty unless the empty space wraps around.
+    #[must_use]
+    #[inline]
+    pub fn descriptors(&self) -> [Descriptor; 2] {
+        self.descriptors
+    }
+
+    /// Returns the first descriptor, the contiguous part of the empty
+    /// space, as address and length, see the [module docs](crate::dma#hal-dma-apis).
+    #[must_use]
+    #[inline]
+    pub fn write_buffer(&mut self) -> (*mut u8, usize) {
+        let [first, _] = self.descriptors;
+        (first.addr, first.len)
+    }
+
+    /// Returns the total length of the descriptors.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the ring is full.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Commits the first `n` bytes the controller wrote, in descriptor
+    /// order, once it finished, see the [module docs](crate::dma).
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::grant::WriteGrant::commit`].
+    #[inline]
+    pub fn complete(self, n: usize) -> Result<(), ProducerError<Infallible>> {
+        compiler_fence(SeqCst);
+        self.producer.commit_granted(n, self.len)
+    }
+}
+
+impl Consumer {
+    /// Describes the filled space of the ring, to be drained by a DMA
+    /// controller and released with [`ReadTransfer::complete`].
+    #[must_use]
+    #[inline]
+    pub fn dma_grant(&mut self) -> ReadTransfer<'_> {
+        let (ranges, len) = self.buffer.grant_filled(self.limits);
+        let descriptors = [
+            Descriptor::new(&self.buffer, &ranges[0]),
+            Descriptor::new(&self.buffer, &ranges[1]),
+        ];
+        compiler_fence(SeqCst);
+        ReadTransfer {
+            consumer: self,
+            descriptors,
+            len,
+        }
+    }
+}
+
+/// The filled space of a ring, described for a DMA controller to drain,
+/// see [`Consumer::dma_grant`].
+#[derive(Debug)]
+pub struct ReadTransfer<'a> {
+    consumer: &'a mut Consumer,
+    descriptors: [Descriptor; 2],
+    len: usize,
+}
+
+impl ReadTransfer<'_> {
+    /// Returns the descriptors of the filled space, in order. The second
+    /// one is empty unless the filled space wraps around. The controller
+    /// must only read from them.
+    #[must_use]
+    #[inline]
+    pub fn descriptors(&self) -> [Descriptor; 2] {
+        self.descriptors
+    }
+
+    /// Returns the first descriptor, the contiguous part of the filled
+    /// space, as address and length, see the [module docs](crate::dma#hal-dma-apis).
+    #[must_use]
+    #[inline]
+    pub fn read_buffer(&self) -> (*const u8, usize) {
+        let [first, _] = self.descriptors;
+        (first.addr.cast_const(), first.len)
+    }
+
+    /// Returns the total length of the descriptors.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the ring is empty.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Releases the first `n` bytes the controller read, in descriptor
+    /// order, once it finished, see the [module docs](crate::dma).
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::grant::ReadGrant::release`].
+    #[inline]
+    pub fn complete(self, n: usize) -> Result<(), ConsumerError<Infallible>> {
+        compiler_fence(SeqCst);
+        self.consumer.release_granted(n, self.len)
+    }
+}
@@ -0,0 +1,100 @@
+//! Hexdumps of the buffered bytes, see [`Producer::debug_dump`].
+
+use ::alloc::string::String;
+use ::core::cmp::Ord as _;
+use ::core::convert::From as _;
+use ::core::fmt::Write as _;
+use ::core::iter::{IntoIterator as _, Iterator as _};
+use ::core::{write, writeln};
+
+use crate::ordering::{Acquire, Relaxed};
+use crate::{Buffer, Consumer, Producer};
+
+/// The bytes per line of a dump.
+const LINE: usize = 16;
+
+/// Formats up to `max` of the bytes from counter `r` to `w` of `buffer` as
+/// `hexdump -C` does, offsets counted in stream positions.
+#[inline]
+fn dump(buffer: &Buffer, r: usize, w: usize, max: usize) -> String {
+    let filled = w.wrapping_sub(r);
+    let n = filled.min(max);
+    let (ranges, _) = if buffer.data.is_mirrored() {
+        crate::mirrored_ranges(buffer.mask, r, n)
+    } else {
+        crate::filled_ranges(buffer.data.len(), buffer.mask, r, r.wrapping_add(n))
+    };
+    // SAFETY: the bytes are filled and not yet past the read counter, so
+    //         the producer does not write them while it is borrowed. The
+    //         consumer scrubs them on release, but the producer is not
+    //         passed published bytes then, and the consumer is borrowed.
+    let [first, second] = unsafe { buffer.data.slices(ranges) };
+
+    let mut out = String::new();
+    let mut bytes = first.iter().chain(second).copied();
+    let mut offset = r;
+    let mut line = [0; LINE];
+    loop {
+        let mut len = 0;
+        for (slot, b) in line.iter_mut().zip(&mut bytes) {
+            *slot = b;
+            len += 1;
+        }
+        if len == 0 {
+            break;
+        }
+        let _ = write!(out, "{offset:08x}  ");
+        for (i, slot) in line.into_iter().enumerate() {
+            if i == LINE / 2 {
+                out.push(' ');
+            }
+            if i < len {
+                let _ = write!(out, "{slot:02x} ");
+            } else {
+                out.push_str("   ");
+            }
+        }
+        out.push_str(" |");
+        for &b in &line[..len] {
+            let printable = b.is_ascii_graphic() || b == b' ';
+            out.push(if printable { char::from(b) } else { '.' });
+        }
+        out.push_str("|\n");
+        offset = offset.wrapping_add(len);
+    }
+    if filled > n {
+        let _ = writeln!(out, "... {} more bytes", filled - n);
+    }
+    out
+}
+
+impl Producer {
+    /// Returns a hexdump of up to `max_bytes` of the bytes buffered,
+    /// including ones pending publication, oldest first, with offsets in
+    /// stream positions, e.g. to debug a protocol.
+    ///
+    /// With the `scrub` feature, only the bytes pending publication: the
+    /// consumer overwrites published bytes as it releases them, before the
+    /// producer learns of it.
+    #[must_use]
+    #[inline]
+    pub fn debug_dump(&self, max_bytes: usize) -> String {
+        #[cfg(not(feature = "scrub"))]
+        let r = self.buffer.read.load(Acquire);
+        #[cfg(feature = "scrub")]
+        let r = self.published;
+        dump(&self.buffer, r, self.write, max_bytes)
+    }
+}
+
+impl Consumer {
+    /// Returns a hexdump of up to `max_bytes` of the bytes published and
+    /// not yet consumed, see [`Producer::debug_dump`].
+    #[must_use]
+    #[inline]
+    pub fn debug_dump(&self, max_bytes: usize) -> String {
+        let r = self.buffer.read.load(Relaxed);
+        let w = self.buffer.write.load(Acquire);
+        dump(&self.buffer, r, w, max_bytes)
+    }
+}
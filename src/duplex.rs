@@ -0,0 +1,79 @@
+//! Bidirectional in-memory pipes, see [`Duplex`].
+
+use ::core::result::Result::{self, Ok};
+use ::std::io;
+
+use crate::{BufferError, Consumer, Producer};
+
+/// One endpoint of an in-memory socketpair replacement, built from two
+/// rings: bytes written to one endpoint are read from the other. Meant for
+/// tests and in-process services.
+///
+/// Reads return 0 while nothing is buffered, as [`Consumer`] does.
+///
+/// There are no async reads and writes: the crate has no async feature
+/// implementing the async I/O traits, and the halves have no wakers to
+/// forward to. For async endpoints use [`duplex`](crate::duplex), whose
+/// streams have `poll_read`, `poll_write` and `poll_flush`.
+#[derive(Debug)]
+pub struct Duplex {
+    tx: Producer,
+    rx: Consumer,
+}
+
+impl Duplex {
+    /// Creates two connected endpoints, each direction buffering up to
+    /// `capacity` bytes, a power of two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `capacity` is not a power of two, or when an
+    /// allocation fails.
+    #[inline]
+    pub fn pair(capacity: usize) -> Result<(Duplex, Duplex), BufferError> {
+        let align = ::core::mem::align_of::<usize>();
+        let (tx_a, rx_b) = crate::new(capacity, align)?;
+        let (tx_b, rx_a) = crate::new(capacity, align)?;
+        Ok((Duplex { tx: tx_a, rx: rx_a }, Duplex { tx: tx_b, rx: rx_b }))
+    }
+
+    /// Splits the endpoint into its writing and reading half.
+    #[must_use]
+    #[inline]
+    pub fn split(self) -> (Producer, Consumer) {
+        (self.tx, self.rx)
+    }
+
+    /// Returns the writing half.
+    #[must_use]
+    #[inline]
+    pub fn producer(&mut self) -> &mut Producer {
+        &mut self.tx
+    }
+
+    /// Returns the reading half.
+    #[must_use]
+    #[inline]
+    pub fn consumer(&mut self) -> &mut Consumer {
+        &mut self.rx
+    }
+}
+
+impl io::Read for Duplex {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut self.rx, dst)
+    }
+}
+
+impl io::Write for Duplex {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut self.tx, src)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.tx)
+    }
+}
@@ -0,0 +1,193 @@
+//! Draining several rings into one sink, see [`Merger`].
+
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::iter::Iterator as _;
+use ::core::mem;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, None, Some};
+#[cfg(feature = "std")]
+use ::core::result::Result::Err;
+use ::core::result::Result::{self, Ok};
+use ::core::sync::atomic::fence;
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::ordering::{Acquire, Relaxed};
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+/// Which source a [`Merger`] drains next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Fairness {
+    /// Each source in turn.
+    #[default]
+    RoundRobin,
+    /// The source with the most bytes in it, keeping the fullest ring from
+    /// stalling its producer.
+    LongestFirst,
+}
+
+/// Drains the consumers of several rings, e.g. fed by many producer threads,
+/// into one downstream sink or merged ring, one source per call. Meant for
+/// a single I/O thread servicing them all.
+///
+/// Sources whose producers are dropped are let go of once drained.
+#[derive(Debug, Default)]
+pub struct Merger {
+    sources: Vec<Consumer>,
+    fairness: Fairness,
+    /// Index of the source to be tried first for round-robin.
+    next: usize,
+}
+
+impl Merger {
+    /// Creates an empty merger.
+    #[must_use]
+    #[inline]
+    pub const fn new(fairness: Fairness) -> Self {
+        Merger {
+            sources: Vec::new(),
+            fairness,
+            next: 0,
+        }
+    }
+
+    /// Adds `source` to the drained rings.
+    #[inline]
+    pub fn push(&mut self, source: Consumer) {
+        self.sources.push(source);
+    }
+
+    /// Returns the number of sources still drained.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sources.len()
+    }
+
+    /// Returns whether no sources are left.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+
+    /// Drops the drained sources of dropped producers, then returns the
+    /// index of the next source to drain, if any has bytes in it.
+    #[inline]
+    fn select(&mut self) -> Option<usize> {
+        let mut i = 0;
+        while i < self.sources.len() {
+            let source = &self.sources[i];
+            // A ring left only to us belongs to a dropped producer, which
+            // published its last bytes before letting go of the ring.
+            if Arc::strong_count(&source.buffer) == 1 {
+                fence(Acquire);
+                if source.is_empty() {
+                    mem::drop(self.sources.swap_remove(i));
+                    continue;
+                }
+            }
+            i += 1;
+        }
+
+        let count = self.sources.len();
+        let index = match self.fairness {
+            Fairness::RoundRobin => (0..count)
+                .map(|i| (self.next + i) % count)
+                .find(|&index| !self.sources[index].is_empty())?,
+            Fairness::LongestFirst => {
+                let (index, filled) = self
+                    .sources
+                    .iter()
+                    .map(filled)
+                    .enumerate()
+                    .max_by_key(|&(_, filled)| filled)?;
+                if filled == 0 {
+                    return None;
+                }
+                index
+            }
+        };
+        self.next = index + 1;
+        Some(index)
+    }
+
+    /// Drains the next source with bytes in it, see [`Consumer::slices`].
+    /// The closure is called with empty slices if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        match self.select() {
+            Some(index) => self.sources[index].slices(f),
+            None => f(&[], 0).map_err(ConsumerError::Callback),
+        }
+    }
+
+    /// Moves as many bytes as fit out of the next source with bytes in it
+    /// into `sink`. Returns the number of bytes moved, a multiple of the
+    /// frame size or start alignment of both halves.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the source, or as [`ConsumerError::Callback`]
+    /// the error of `sink`, e.g. [`ProducerError::Poisoned`]. Nothing is
+    /// moved then.
+    #[inline]
+    pub fn pump(
+        &mut self,
+        sink: &mut Producer,
+    ) -> Result<usize, ConsumerError<ProducerError<Infallible>>> {
+        let Some(index) = self.select() else {
+            return Ok(0);
+        };
+        let source = &mut self.sources[index];
+        let frame = source.limits.frame.max(sink.limits.frame);
+        source.slices(|src, len| {
+            sink.slices(|dst, free| {
+                let n = len.min(free) & !(frame - 1);
+                crate::tee::copy_prefix(src, dst, n);
+                Ok::<_, Infallible>(n)
+            })
+        })
+    }
+
+    /// Writes the bytes of the next source with bytes in it to `sink`.
+    /// Returns the number of bytes written, 0 if no source had any.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `sink`, or of the source as in
+    /// [`io::Read::read`].
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to(&mut self, sink: &mut impl io::Write) -> io::Result<usize> {
+        let Some(index) = self.select() else {
+            return Ok(0);
+        };
+        match self.sources[index].io_slices(|bufs, _| sink.write_vectored(bufs)) {
+            Ok(n) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+}
+
+/// Returns the number of bytes in the ring of `source`.
+#[inline]
+fn filled(source: &Consumer) -> usize {
+    let r = source.buffer.read.load(Relaxed);
+    source.buffer.write.load(Acquire).wrapping_sub(r)
+}
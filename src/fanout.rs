@@ -0,0 +1,135 @@
+//! Splitting one ring across several, see [`Distributor`].
+
+use ::alloc::vec::Vec;
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::{BufferError, Consumer, ConsumerError, Producer, ProducerError};
+
+/// Splits one incoming stream across the producers of several rings, e.g.
+/// feeding worker threads, handing chunks of a fixed size to each in turn.
+#[derive(Debug)]
+pub struct Distributor {
+    sinks: Vec<Producer>,
+    chunk: usize,
+    /// Index of the sink receiving the next chunk.
+    next: usize,
+}
+
+impl Distributor {
+    /// Creates a distributor without sinks, handing out chunks of `chunk`
+    /// bytes, a power of two. With framing enabled, see
+    /// [`crate::Builder::frame_size`], the frame size distributes frame by
+    /// frame; chunks are raised to the frame size of every half involved.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadGranularity`] if `chunk` is not a power of
+    /// two.
+    #[inline]
+    pub fn new(chunk: usize) -> Result<Self, BufferError> {
+        if !chunk.is_power_of_two() {
+            return Err(BufferError::BadGranularity(chunk));
+        }
+        Ok(Distributor {
+            sinks: Vec::new(),
+            chunk,
+            next: 0,
+        })
+    }
+
+    /// Adds `sink` to the rings fed.
+    #[inline]
+    pub fn push(&mut self, sink: Producer) {
+        self.sinks.push(sink);
+    }
+
+    /// Returns the number of sinks.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.sinks.len()
+    }
+
+    /// Returns whether there are no sinks.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.sinks.is_empty()
+    }
+
+    /// Splits the sinks back off.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Vec<Producer> {
+        self.sinks
+    }
+
+    /// Moves whole chunks out of `source`, each into the next sink in turn,
+    /// until the source runs out of whole chunks or the next sink has no
+    /// room for one. Returns the number of bytes moved.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `source`, or as [`ConsumerError::Callback`] the
+    /// error of the first sink refusing a chunk, e.g.
+    /// [`ProducerError::Poisoned`]. Chunks moved before that sink are
+    /// consumed and counted, and the error is held back until the next
+    /// call then.
+    #[inline]
+    pub fn pump(
+        &mut self,
+        source: &mut Consumer,
+    ) -> Result<usize, ConsumerError<ProducerError<Infallible>>> {
+        let mut chunk = self.chunk.max(source.limits.frame);
+        for sink in &self.sinks {
+            chunk = chunk.max(sink.limits.frame);
+        }
+        let sinks = &mut self.sinks;
+        let next = &mut self.next;
+        if sinks.is_empty() {
+            return Ok(0);
+        }
+
+        source.slices(|src, len| {
+            let mut moved = 0;
+            while len - moved >= chunk {
+                let src = skip(src, moved);
+                let sent = sinks[*next].slices(|dst, free| {
+                    if free < chunk {
+                        return Ok::<_, Infallible>(0);
+                    }
+                    crate::tee::copy_prefix(&src, dst, chunk);
+                    Ok(chunk)
+                });
+                match sent {
+                    Ok(0) => break,
+                    Ok(_) => {}
+                    Err(err) if moved == 0 => return Err(err),
+                    Err(_) => break,
+                }
+                moved += chunk;
+                *next = (*next + 1) % sinks.len();
+            }
+            Ok(moved)
+        })
+    }
+}
+
+/// Skips the first `at` bytes of a pair of slices.
+#[inline]
+fn skip<'a>(src: &[&'a [u8]], mut at: usize) -> [&'a [u8]; 2] {
+    let mut out: [&[u8]; 2] = [&[], &[]];
+    let mut i = 0;
+    for &buf in src {
+        if at >= buf.len() {
+            at -= buf.len();
+            continue;
+        }
+        out[i] = &buf[at..];
+        at = 0;
+        i += 1;
+    }
+    out
+}
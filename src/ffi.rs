@@ -0,0 +1,241 @@
+//! C bindings, so C and C++ code can sit on either side of a ring.
+//!
+//! The functions are exported unmangled with the `bytering_` prefix; the
+//! header `include/bytering.h` declares them, generated by `cbindgen` with
+//! the repository's `cbindgen.toml`. Build the crate as a static or dynamic
+//! library with the `ffi` feature, e.g.
+//! `cargo rustc --release --features ffi --crate-type staticlib`.
+//!
+//! Halves are handed out as opaque pointers, created by [`bytering_new`]
+//! and freed by the matching `_destroy` function. Like the Rust halves,
+//! each may be used by one thread at a time, not necessarily the one that
+//! created it. Writing and reading take two calls: `_acquire` fills in the
+//! slices mapping the empty or filled space, `_commit` then publishes or
+//! releases a prefix of them. The slices stay valid until the next call on
+//! the same half.
+//!
+//! Functions returning an `int` return [`BYTERING_OK`] or one of the
+//! negative `BYTERING_E*` error codes.
+
+use ::alloc::boxed::Box;
+use ::core::convert::Infallible;
+use ::core::ffi::c_int;
+use ::core::iter::Iterator as _;
+use ::core::ptr;
+use ::core::result::Result::{Err, Ok};
+
+use crate::{BufferError, Consumer, ConsumerError, Producer, ProducerError};
+
+/// Success.
+pub const BYTERING_OK: c_int = 0;
+/// The size is not a power of two or too large, see
+/// [`BufferError::BadSize`].
+pub const BYTERING_EBADSIZE: c_int = -1;
+/// The alignment is not a power of two, see
+/// [`BufferError::BadAlignment`].
+pub const BYTERING_EBADALIGN: c_int = -2;
+/// The memory could not be allocated or mapped.
+pub const BYTERING_ENOMEM: c_int = -3;
+/// The count exceeds the length acquired, see
+/// [`ProducerError::InvalidCount`].
+pub const BYTERING_EINVALIDCOUNT: c_int = -4;
+/// The count is not a multiple of the frame size, see
+/// [`ProducerError::TornFrame`].
+pub const BYTERING_ETORNFRAME: c_int = -5;
+/// Any other error.
+pub const BYTERING_EOTHER: c_int = -6;
+
+/// The empty space of a ring, one or two slices, see
+/// [`bytering_producer_acquire`]. A missing second slice has a length of 0.
+#[repr(C)]
+#[derive(Debug)]
+pub struct WriteSlices {
+    /// The starts of the slices.
+    pub ptr: [*mut u8; 2],
+    /// The lengths of the slices.
+    pub len: [usize; 2],
+}
+
+/// The filled space of a ring, one or two slices, see
+/// [`bytering_consumer_acquire`]. A missing second slice has a length of 0.
+#[repr(C)]
+#[derive(Debug)]
+pub struct ReadSlices {
+    /// The starts of the slices.
+    pub ptr: [*const u8; 2],
+    /// The lengths of the slices.
+    pub len: [usize; 2],
+}
+
+#[inline]
+const fn buffer_error(err: &BufferError) -> c_int {
+    match err {
+        BufferError::BadSize(_) => BYTERING_EBADSIZE,
+        BufferError::BadAlignment(_) => BYTERING_EBADALIGN,
+        BufferError::AllocFailed | BufferError::MapFailed(_) | BufferError::OverBudget(_) => {
+            BYTERING_ENOMEM
+        }
+        BufferError::BadGranularity(_)
+        | BufferError::Mismatch
+        | BufferError::Incompatible
+        | BufferError::InUse => BYTERING_EOTHER,
+    }
+}
+
+/// Creates a ring of `size` bytes aligned to `align`, see [`crate::new`],
+/// and stores its halves to `producer` and `consumer`. Stores nothing on
+/// failure.
+///
+/// # Safety
+///
+/// `producer` and `consumer` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_new(
+    size: usize,
+    align: usize,
+    producer: *mut *mut Producer,
+    consumer: *mut *mut Consumer,
+) -> c_int {
+    match crate::new(size, align) {
+        Ok((p, c)) => {
+            // SAFETY: guaranteed by the caller.
+            unsafe {
+                producer.write(Box::into_raw(Box::new(p)));
+                consumer.write(Box::into_raw(Box::new(c)));
+            }
+            BYTERING_OK
+        }
+        Err(err) => buffer_error(&err),
+    }
+}
+
+/// Fills in `slices` with the empty space of the ring and returns its
+/// total length. Commits nothing.
+///
+/// # Safety
+///
+/// `producer` must come from [`bytering_new`] and not be destroyed yet,
+/// and `slices` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_producer_acquire(
+    producer: *mut Producer,
+    slices: *mut WriteSlices,
+) -> usize {
+    // SAFETY: guaranteed by the caller.
+    let producer = unsafe { &mut *producer };
+    let mut out = WriteSlices {
+        ptr: [ptr::null_mut(); 2],
+        len: [0; 2],
+    };
+    let mut total = 0;
+    let _ = producer.slices(|bufs, len| {
+        for (i, buf) in bufs.iter_mut().enumerate().take(2) {
+            out.ptr[i] = buf.as_mut_ptr();
+            out.len[i] = buf.len();
+        }
+        total = len;
+        Ok::<_, Infallible>(0)
+    });
+    // SAFETY: guaranteed by the caller.
+    unsafe { slices.write(out) };
+    total
+}
+
+/// Publishes the first `n` bytes of the slices last acquired to the
+/// consumer.
+///
+/// # Safety
+///
+/// See [`bytering_producer_acquire`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_producer_commit(producer: *mut Producer, n: usize) -> c_int {
+    // SAFETY: guaranteed by the caller.
+    let producer = unsafe { &mut *producer };
+    match producer.slices(|_, _| Ok::<_, Infallible>(n)) {
+        Ok(_) => BYTERING_OK,
+        Err(ProducerError::InvalidCount { .. }) => BYTERING_EINVALIDCOUNT,
+        Err(ProducerError::TornFrame { .. }) => BYTERING_ETORNFRAME,
+        Err(ProducerError::Poisoned) => BYTERING_EOTHER,
+        Err(ProducerError::Callback(never)) => match never {},
+    }
+}
+
+/// Frees the producer half, publishing all bytes committed. Does nothing
+/// if `producer` is null.
+///
+/// # Safety
+///
+/// `producer` must be null or come from [`bytering_new`] and not be
+/// destroyed yet. It must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_producer_destroy(producer: *mut Producer) {
+    if !producer.is_null() {
+        // SAFETY: guaranteed by the caller.
+        ::core::mem::drop(unsafe { Box::from_raw(producer) });
+    }
+}
+
+/// Fills in `slices` with the filled space of the ring and returns its
+/// total length. Releases nothing.
+///
+/// # Safety
+///
+/// `consumer` must come from [`bytering_new`] and not be destroyed yet,
+/// and `slices` must be valid for writes.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_consumer_acquire(
+    consumer: *mut Consumer,
+    slices: *mut ReadSlices,
+) -> usize {
+    // SAFETY: guaranteed by the caller.
+    let consumer = unsafe { &mut *consumer };
+    let mut out = ReadSlices {
+        ptr: [ptr::null(); 2],
+        len: [0; 2],
+    };
+    let mut total = 0;
+    let _ = consumer.slices(|bufs, len| {
+        for (i, buf) in bufs.iter().enumerate().take(2) {
+            out.ptr[i] = buf.as_ptr();
+            out.len[i] = buf.len();
+        }
+        total = len;
+        Ok::<_, Infallible>(0)
+    });
+    // SAFETY: guaranteed by the caller.
+    unsafe { slices.write(out) };
+    total
+}
+
+/// Releases the first `n` bytes of the slices last acquired to the
+/// producer.
+///
+/// # Safety
+///
+/// See [`bytering_consumer_acquire`].
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_consumer_commit(consumer: *mut Consumer, n: usize) -> c_int {
+    // SAFETY: guaranteed by the caller.
+    let consumer = unsafe { &mut *consumer };
+    match consumer.slices(|_, _| Ok::<_, Infallible>(n)) {
+        Ok(_) => BYTERING_OK,
+        Err(ConsumerError::InvalidCount { .. }) => BYTERING_EINVALIDCOUNT,
+        Err(ConsumerError::TornFrame { .. }) => BYTERING_ETORNFRAME,
+        Err(ConsumerError::Poisoned) => BYTERING_EOTHER,
+        Err(ConsumerError::Callback(never)) => match never {},
+    }
+}
+
+/// Frees the consumer half. Does nothing if `consumer` is null.
+///
+/// # Safety
+///
+/// `consumer` must be null or come from [`bytering_new`] and not be
+/// destroyed yet. It must not be used afterwards.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn bytering_consumer_destroy(consumer: *mut Consumer) {
+    if !consumer.is_null() {
+        // SAFETY: guaranteed by the caller.
+        ::core::mem::drop(unsafe { Box::from_raw(consumer) });
+    }
+}
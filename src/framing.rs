@@ -0,0 +1,224 @@
+//! Length-prefixed frames over a plain ring.
+//!
+//! Every frame is a payload length followed by the payload, see [`Prefix`].
+//! Unlike [`crate::records`] and [`crate::datagram`], frames of any size
+//! share the bytes of one ring, and may straddle its wrap seam.
+
+use ::alloc::vec::Vec;
+use ::core::cmp::Ord as _;
+use ::core::convert::{From as _, TryFrom as _};
+use ::core::iter::Iterator as _;
+use ::core::ops::FnOnce;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::Ok;
+
+use crate::mux::{get, put};
+use crate::{Consumer, Producer};
+
+/// The length of a [`Prefix::U32`] frame header.
+pub const HEADER: usize = 4;
+
+/// The maximum length of a [`Prefix::Varint`] frame header.
+pub const MAX_VARINT: usize = 5;
+
+/// The encoding of the payload length heading each frame. Both halves of a
+/// ring must agree on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Prefix {
+    /// A little-endian `u32`, see [`HEADER`].
+    #[default]
+    U32,
+    /// An unsigned LEB128 varint of up to [`MAX_VARINT`] bytes, as used by
+    /// `postcard`: small frames take a header of a single byte.
+    Varint,
+}
+
+impl Prefix {
+    /// Encodes `len` into the returned buffer, the first returned count
+    /// bytes of which are the header.
+    #[inline]
+    fn encode(self, len: u32) -> ([u8; MAX_VARINT], usize) {
+        let mut header = [0; MAX_VARINT];
+        match self {
+            Prefix::U32 => {
+                header[..HEADER].copy_from_slice(&len.to_le_bytes());
+                (header, HEADER)
+            }
+            Prefix::Varint => {
+                let mut rest = len;
+                let mut n = 0;
+                loop {
+                    let byte = (rest & 0x7f) as u8;
+                    rest >>= 7;
+                    if rest == 0 {
+                        header[n] = byte;
+                        return (header, n + 1);
+                    }
+                    header[n] = byte | 0x80;
+                    n += 1;
+                }
+            }
+        }
+    }
+
+    /// Decodes a header off the start of `src`. Returns the payload length
+    /// and the header length, or `None` if `src` holds no whole header.
+    #[inline]
+    fn decode(self, src: &[u8]) -> Option<(u32, usize)> {
+        match self {
+            Prefix::U32 => {
+                let header = src.get(..HEADER)?;
+                let mut bytes = [0; HEADER];
+                bytes.copy_from_slice(header);
+                Some((u32::from_le_bytes(bytes), HEADER))
+            }
+            Prefix::Varint => {
+                let mut len = 0;
+                for (i, &byte) in src.iter().take(MAX_VARINT).enumerate() {
+                    len |= u32::from(byte & 0x7f) << (7 * i);
+                    if byte & 0x80 == 0 {
+                        return Some((len, i + 1));
+                    }
+                }
+                None
+            }
+        }
+    }
+}
+
+/// Writes length-prefixed frames into a ring.
+#[derive(Debug)]
+pub struct FrameWriter {
+    inner: Producer,
+    prefix: Prefix,
+}
+
+impl FrameWriter {
+    /// Wraps `producer`, heading frames with a [`Prefix::U32`].
+    #[must_use]
+    #[inline]
+    pub const fn new(producer: Producer) -> Self {
+        Self::with_prefix(producer, Prefix::U32)
+    }
+
+    /// Wraps `producer`, heading frames with `prefix`.
+    #[must_use]
+    #[inline]
+    pub const fn with_prefix(producer: Producer, prefix: Prefix) -> Self {
+        FrameWriter {
+            inner: producer,
+            prefix,
+        }
+    }
+
+    /// Writes `payload` as one frame. Returns `false`, committing nothing,
+    /// if the frame does not fit into the empty space.
+    #[inline]
+    pub fn write_frame(&mut self, payload: &[u8]) -> bool {
+        let Ok(len) = u32::try_from(payload.len()) else {
+            return false;
+        };
+        let (header, h) = self.prefix.encode(len);
+        let n = self.inner.slices(|bufs, free| {
+            let n = h + payload.len();
+            if n > free {
+                return Ok::<_, ()>(0);
+            }
+            put(bufs, 0, &header[..h]);
+            put(bufs, h, payload);
+            Ok(n)
+        });
+        ::core::matches!(n, Ok(n) if n != 0)
+    }
+
+    /// Unwraps the producer.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Producer {
+        self.inner
+    }
+}
+
+/// Reads length-prefixed frames out of a ring.
+#[derive(Debug)]
+pub struct FrameReader {
+    inner: Consumer,
+    prefix: Prefix,
+    /// Reassembles frames straddling the wrap seam.
+    wrapped: Vec<u8>,
+}
+
+impl FrameReader {
+    /// Wraps `consumer`, reading frames headed by a [`Prefix::U32`].
+    #[must_use]
+    #[inline]
+    pub const fn new(consumer: Consumer) -> Self {
+        Self::with_prefix(consumer, Prefix::U32)
+    }
+
+    /// Wraps `consumer`, reading frames headed by `prefix`.
+    #[must_use]
+    #[inline]
+    pub const fn with_prefix(consumer: Consumer, prefix: Prefix) -> Self {
+        FrameReader {
+            inner: consumer,
+            prefix,
+            wrapped: Vec::new(),
+        }
+    }
+
+    /// Passes the payload of the next frame to `f` and consumes the frame
+    /// once `f` returns. Returns `None`, without calling `f`, until the
+    /// whole frame was written. A malformed varint header stalls the
+    /// reader the same way.
+    ///
+    /// The payload is passed in place when it is contiguous in the ring, a
+    /// payload straddling the wrap seam is copied first.
+    #[inline]
+    pub fn read_frame<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let (prefix, wrapped) = (self.prefix, &mut self.wrapped);
+        let mut f = Some(f);
+        let mut result = None;
+        let n = self.inner.slices(|bufs, len| {
+            let mut header = [0; MAX_VARINT];
+            let k = len.min(MAX_VARINT);
+            get(bufs, 0, &mut header[..k]);
+            let Some((payload, h)) = prefix.decode(&header[..k]) else {
+                return Ok::<_, ()>(0);
+            };
+            let Ok(payload) = usize::try_from(payload) else {
+                return Ok(0);
+            };
+            let n = h + payload;
+            let Some(f) = f.take().filter(|_| n <= len) else {
+                return Ok(0);
+            };
+            result = Some(match bufs {
+                [head, ..] if head.len() >= n => f(&head[h..n]),
+                [head, tail] if head.len() <= h => f(&tail[h - head.len()..][..payload]),
+                _ => {
+                    wrapped.resize(payload, 0);
+                    get(bufs, h, wrapped);
+                    f(wrapped)
+                }
+            });
+            Ok(n)
+        });
+        n.ok().and(result)
+    }
+
+    /// Returns `true` if the ring holds no bytes, not even those of a
+    /// partial frame.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Unwraps the consumer.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Consumer {
+        self.inner
+    }
+}
@@ -0,0 +1,265 @@
+//! Grants of a ring's empty or filled space, see [`Producer::grant`] and
+//! [`Consumer::grant`].
+//!
+//! A grant hands out the slices the closure-based methods pass to their
+//! callbacks, for code that cannot run inside a closure, e.g. a receive
+//! interrupt handler pushing bytes while the main loop drains them. Taking
+//! a grant loads the other half's counter once, committing or releasing
+//! checks the count and stores the own counter once: no locks, no loops,
+//! no allocation and no panics, so both are wait-free and safe to call from
+//! interrupt context, whatever the other half is doing meanwhile.
+//!
+//! A [poisoned](crate::Builder::poison_on_panic) ring grants no space, and
+//! committing or releasing fails with `Poisoned`.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::hint;
+use ::core::ops::Range;
+#[cfg(feature = "oplog")]
+use ::core::option::Option::Some;
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::ordering::Relaxed;
+use crate::{Buffer, Consumer, ConsumerError, Limits, Producer, ProducerError};
+
+impl Producer {
+    /// Grants the empty space of the ring, one or two slices, to be written
+    /// to and then committed with [`WriteGrant::commit`]. Dropping the
+    /// grant commits nothing.
+    ///
+    /// Wait-free, see the [module docs](crate::grant).
+    #[must_use]
+    #[inline]
+    pub fn grant(&mut self) -> WriteGrant<'_> {
+        let buffer: *const Buffer = ::alloc::sync::Arc::as_ptr(&self.buffer);
+        // SAFETY: the producer's `Arc`, borrowed for the grant's lifetime,
+        //         keeps the buffer alive.
+        let buffer = unsafe { &*buffer };
+        let (ranges, len) = buffer.grant_empty(self.write, self.limits);
+        // SAFETY: ranges map the empty region only, and the producer is
+        //         borrowed mutably while the slices are live, see
+        //         `Buffer::produce_fn`.
+        let bufs = unsafe { buffer.data.slices_mut(ranges) };
+        WriteGrant {
+            producer: self,
+            bufs,
+            len,
+        }
+    }
+}
+
+/// The empty space of a ring, granted to be written to, see
+/// [`Producer::grant`].
+#[derive(Debug)]
+pub struct WriteGrant<'a> {
+    producer: &'a mut Producer,
+    bufs: [&'a mut [u8]; 2],
+    len: usize,
+}
+
+impl WriteGrant<'_> {
+    /// Returns the granted slices, in order. The second one is empty unless
+    /// the empty space wraps around.
+    #[must_use]
+    #[inline]
+    pub fn bufs(&mut self) -> [&mut [u8]; 2] {
+        let [first, second] = &mut self.bufs;
+        [first, second]
+    }
+
+    /// Returns the total length of the slices.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the ring is full.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copies as much of `src` into the slices as fits and commits it.
+    /// Returns the number of bytes committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::TornFrame`] if the bytes that fit are not a
+    /// multiple of the required frame, see [`WriteGrant::commit`].
+    #[inline]
+    pub fn push(mut self, src: &[u8]) -> Result<usize, ProducerError<Infallible>> {
+        let n = src.len().min(self.len);
+        crate::tee::copy_prefix(&[src], &mut self.bufs, n);
+        self.commit(n).map(|()| n)
+    }
+
+    /// Commits the first `n` bytes written to the slices, publishing them
+    /// as [`Producer::slices`] does.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Poisoned`] if the ring is poisoned,
+    /// [`ProducerError::InvalidCount`] if `n` exceeds the granted length, or
+    /// [`ProducerError::TornFrame`] if it is not a multiple of the required
+    /// frame. Nothing is committed then.
+    #[inline]
+    pub fn commit(self, n: usize) -> Result<(), ProducerError<Infallible>> {
+        self.producer.commit_granted(n, self.len)
+    }
+}
+
+impl Buffer {
+    /// Returns the empty region starting at `w`, none if poisoned.
+    #[inline]
+    pub(crate) fn grant_empty(&self, w: usize, limits: Limits) -> ([Range<usize>; 2], usize) {
+        if self.is_poisoned() {
+            hint::cold_path();
+            return ([0..0, 0..0], 0);
+        }
+        self.empty(w, false, limits)
+    }
+
+    /// Returns the filled region, none if poisoned.
+    #[inline]
+    pub(crate) fn grant_filled(&self, limits: Limits) -> ([Range<usize>; 2], usize) {
+        if self.is_poisoned() {
+            hint::cold_path();
+            return ([0..0, 0..0], 0);
+        }
+        let (_, ranges, len) = self.filled(false, limits);
+        (ranges, len)
+    }
+}
+
+impl Producer {
+    /// Commits `n` bytes of the `len` granted.
+    #[inline]
+    pub(crate) fn commit_granted(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Result<(), ProducerError<Infallible>> {
+        #[cfg(feature = "oplog")]
+        if let Some(log) = &self.oplog {
+            log.push(crate::oplog::Side::Producer, len, n);
+        }
+        if self.buffer.is_poisoned() {
+            hint::cold_path();
+            return Err(ProducerError::Poisoned);
+        }
+        let frame = self.limits.frame;
+        if n > len {
+            hint::cold_path();
+            return Err(ProducerError::InvalidCount { n, len });
+        }
+        if n & frame.wrapping_sub(1) != 0 {
+            hint::cold_path();
+            return Err(ProducerError::TornFrame { n, frame });
+        }
+        self.advance(n);
+        Ok(())
+    }
+}
+
+impl Consumer {
+    /// Grants the filled space of the ring, one or two slices, to be read
+    /// and then released with [`ReadGrant::release`]. Dropping the grant
+    /// releases nothing.
+    ///
+    /// Wait-free, see the [module docs](crate::grant).
+    #[must_use]
+    #[inline]
+    pub fn grant(&mut self) -> ReadGrant<'_> {
+        let buffer: *const Buffer = ::alloc::sync::Arc::as_ptr(&self.buffer);
+        // SAFETY: the consumer's `Arc`, borrowed for the grant's lifetime,
+        //         keeps the buffer alive.
+        let buffer = unsafe { &*buffer };
+        let (ranges, len) = buffer.grant_filled(self.limits);
+        // SAFETY: ranges map the filled region only, and the consumer is
+        //         borrowed mutably while the slices are live, see
+        //         `Buffer::consume_fn`.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+        ReadGrant {
+            consumer: self,
+            bufs,
+            len,
+        }
+    }
+
+    /// Releases `n` bytes of the `len` granted.
+    #[inline]
+    pub(crate) fn release_granted(
+        &mut self,
+        n: usize,
+        len: usize,
+    ) -> Result<(), ConsumerError<Infallible>> {
+        #[cfg(feature = "oplog")]
+        if let Some(log) = &self.oplog {
+            log.push(crate::oplog::Side::Consumer, len, n);
+        }
+        if self.buffer.is_poisoned() {
+            hint::cold_path();
+            return Err(ConsumerError::Poisoned);
+        }
+        let frame = self.limits.frame;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+        if n & frame.wrapping_sub(1) != 0 {
+            hint::cold_path();
+            return Err(ConsumerError::TornFrame { n, frame });
+        }
+        self.release(self.buffer.read.load(Relaxed), n);
+        Ok(())
+    }
+}
+
+/// The filled space of a ring, granted to be read, see
+/// [`Consumer::grant`].
+#[derive(Debug)]
+pub struct ReadGrant<'a> {
+    consumer: &'a mut Consumer,
+    bufs: [&'a [u8]; 2],
+    len: usize,
+}
+
+impl ReadGrant<'_> {
+    /// Returns the granted slices, in order. The second one is empty unless
+    /// the filled space wraps around.
+    #[must_use]
+    #[inline]
+    pub fn bufs(&self) -> [&[u8]; 2] {
+        self.bufs
+    }
+
+    /// Returns the total length of the slices.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns whether the ring is empty.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Releases the first `n` bytes of the slices to the producer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Poisoned`] if the ring is poisoned,
+    /// [`ConsumerError::InvalidCount`] if `n` exceeds the granted length, or
+    /// [`ConsumerError::TornFrame`] if it is not a multiple of the required
+    /// frame. Nothing is released then.
+    #[inline]
+    pub fn release(self, n: usize) -> Result<(), ConsumerError<Infallible>> {
+        self.consumer.release_granted(n, self.len)
+    }
+}
@@ -0,0 +1,126 @@
+//! Watching a ring from a third thread, see [`Inspector`].
+//!
+//! An inspector observes the counters of a ring, never its data, so any
+//! number of them may watch from any thread, e.g. a monitoring loop
+//! sampling occupancy, without taking part in the single producer, single
+//! consumer protocol. It holds a weak reference: it neither keeps the ring
+//! alive nor makes a half look as if the other were still there, and it
+//! observes the buffer it was taken from, so take a new one after a
+//! [`resize`](crate::resize).
+
+use ::alloc::sync::{Arc, Weak};
+use ::core::clone::Clone;
+use ::core::cmp::{Eq, Ord as _, PartialEq};
+use ::core::fmt;
+use ::core::marker::Copy;
+use ::core::option::Option::{self, Some};
+use ::core::result::Result::{self, Ok};
+#[cfg(feature = "std")]
+use ::core::time::Duration;
+
+use crate::ordering::Acquire;
+use crate::{Buffer, BufferError, Builder, Consumer, Producer};
+
+/// A read-only handle to a ring, see the [module docs](self).
+#[derive(Clone)]
+pub struct Inspector(Weak<Buffer>);
+
+impl fmt::Debug for Inspector {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Inspector").field(&self.snapshot()).finish()
+    }
+}
+
+/// The counters of a ring at the time of [`Inspector::snapshot`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot {
+    /// The size of the ring in bytes.
+    pub capacity: usize,
+    /// The bytes published by the producer and not yet consumed.
+    pub filled: usize,
+    /// The write counter: the bytes ever published, wrapping around.
+    pub written: usize,
+    /// The read counter: the bytes ever consumed, wrapping around.
+    pub read: usize,
+}
+
+impl Snapshot {
+    /// Returns the bytes free for the producer, as far as published.
+    #[must_use]
+    #[inline]
+    pub const fn free(&self) -> usize {
+        self.capacity - self.filled
+    }
+}
+
+impl Inspector {
+    #[inline]
+    fn new(buffer: &Arc<Buffer>) -> Self {
+        Inspector(Arc::downgrade(buffer))
+    }
+
+    /// Returns whether either half of the ring is still alive.
+    #[must_use]
+    #[inline]
+    pub fn is_alive(&self) -> bool {
+        self.0.strong_count() != 0
+    }
+
+    /// Returns the counters of the ring, or `None` if both halves are gone.
+    #[must_use]
+    #[inline]
+    pub fn snapshot(&self) -> Option<Snapshot> {
+        let buffer = self.0.upgrade()?;
+        let read = buffer.read.load(Acquire);
+        let written = buffer.write.load(Acquire);
+        Some(Snapshot {
+            capacity: buffer.data.len(),
+            filled: written.wrapping_sub(read).min(buffer.data.len()),
+            written,
+            read,
+        })
+    }
+
+    /// Returns the age of the oldest unread byte, see
+    /// [`Consumer::oldest_age`].
+    #[cfg(feature = "std")]
+    #[must_use]
+    #[inline]
+    pub fn oldest_age(&self) -> Option<Duration> {
+        crate::age::oldest_age(&*self.0.upgrade()?)
+    }
+}
+
+impl Builder {
+    /// Creates the producer-consumer pair as [`Builder::build`] does, with
+    /// an [`Inspector`] of it.
+    ///
+    /// # Errors
+    ///
+    /// See [`Builder::build`].
+    #[inline]
+    pub fn build_with_inspector(self) -> Result<(Producer, Consumer, Inspector), BufferError> {
+        let (producer, consumer) = self.build()?;
+        let inspector = producer.inspector();
+        Ok((producer, consumer, inspector))
+    }
+}
+
+impl Producer {
+    /// Returns an [`Inspector`] of the ring.
+    #[must_use]
+    #[inline]
+    pub fn inspector(&self) -> Inspector {
+        Inspector::new(&self.buffer)
+    }
+}
+
+impl Consumer {
+    /// Returns an [`Inspector`] of the ring.
+    #[must_use]
+    #[inline]
+    pub fn inspector(&self) -> Inspector {
+        Inspector::new(&self.buffer)
+    }
+}
@@ -0,0 +1,155 @@
+//! Validating the state of a ring, see [`Producer::assert_invariants`].
+
+use ::alloc::string::String;
+use ::alloc::vec::Vec;
+use ::core::fmt::Write as _;
+use ::core::iter::Iterator as _;
+use ::core::ops::Range;
+use ::core::option::Option::{self, None, Some};
+use ::core::{panic, write};
+
+use crate::ordering::Acquire;
+use crate::{Buffer, Consumer, Producer, empty_ranges, filled_ranges, mirrored_ranges, range_len};
+
+/// The counters of the producer, if known to the caller.
+#[derive(Clone, Copy)]
+struct Local {
+    write: usize,
+    published: usize,
+}
+
+/// Returns the invariants of `buffer` violated, given the producer's
+/// counters if the producer asks.
+fn violations(buffer: &Buffer, local: Option<Local>) -> Vec<&'static str> {
+    let mut violated = Vec::new();
+    let mut check = |ok: bool, what| {
+        if !ok {
+            violated.push(what);
+        }
+    };
+    let size = buffer.data.len();
+    let r = buffer.read.load(Acquire);
+    let w = buffer.write.load(Acquire);
+    check(size.is_power_of_two(), "size is a power of two");
+    check(buffer.mask == size.wrapping_sub(1), "mask is size - 1");
+    check(buffer.frame.is_power_of_two(), "frame is a power of two");
+    check(buffer.frame <= size, "frame <= size");
+    check(w.wrapping_sub(r) <= size, "read <= write <= read + size");
+    check(
+        r & buffer.frame.wrapping_sub(1) == 0,
+        "read is a multiple of the frame",
+    );
+    check(
+        w & buffer.frame.wrapping_sub(1) == 0,
+        "write is a multiple of the frame",
+    );
+    if let Some(local) = local {
+        check(
+            local.published == w,
+            "published is the shared write counter",
+        );
+        check(
+            local.write.wrapping_sub(local.published) <= size,
+            "published <= local write",
+        );
+        check(
+            local.write.wrapping_sub(r) <= size,
+            "read <= local write <= read + size",
+        );
+    }
+    if w.wrapping_sub(r) <= size {
+        let mapped = if buffer.data.is_mirrored() {
+            2 * size
+        } else {
+            size
+        };
+        let within = |ranges: &[Range<usize>; 2]| {
+            ranges
+                .iter()
+                .all(|range| range.start <= range.end && range.end <= mapped)
+        };
+        let (filled, len) = if buffer.data.is_mirrored() {
+            mirrored_ranges(buffer.mask, r, w.wrapping_sub(r))
+        } else {
+            filled_ranges(size, buffer.mask, r, w)
+        };
+        check(within(&filled), "filled ranges within the buffer");
+        check(
+            range_len(&filled[0]) + range_len(&filled[1]) == len,
+            "filled ranges add up",
+        );
+        if !buffer.data.is_mirrored() {
+            let (empty, len) = empty_ranges(size, buffer.mask, r, w);
+            check(within(&empty), "empty ranges within the buffer");
+            check(len == size - w.wrapping_sub(r), "empty ranges add up");
+        }
+    }
+    violated
+}
+
+/// Panics with a report of the state of `buffer` unless it is valid.
+#[track_caller]
+fn assert_valid(buffer: &Buffer, local: Option<Local>) {
+    let violated = violations(buffer, local);
+    if violated.is_empty() {
+        return;
+    }
+    let mut report = String::new();
+    for what in &violated {
+        let _ = write!(report, "\n  violated: {what}");
+    }
+    let _ = write!(
+        report,
+        "\n  size {}, mask {:#x}, frame {}, mirrored {}\n  read {}, write {}",
+        buffer.data.len(),
+        buffer.mask,
+        buffer.frame,
+        buffer.data.is_mirrored(),
+        buffer.read.load(Acquire),
+        buffer.write.load(Acquire),
+    );
+    if let Some(local) = local {
+        let _ = write!(
+            report,
+            ", local write {}, published {}",
+            local.write, local.published
+        );
+    }
+    panic!("ring invariants violated:{report}");
+}
+
+impl Producer {
+    /// Checks the internal invariants of the ring, e.g. in the tests of
+    /// code built on it or to attach the report to a bug: the counters are
+    /// at most the size apart, the ranges handed out lie within the
+    /// buffer, the mask and frame are consistent with the size.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a report of the violations and the state of the ring if
+    /// any invariant does not hold.
+    #[track_caller]
+    #[inline]
+    pub fn assert_invariants(&self) {
+        let local = Local {
+            write: self.write,
+            published: self.published,
+        };
+        assert_valid(&self.buffer, Some(local));
+    }
+}
+
+impl Consumer {
+    /// Checks the internal invariants of the ring, see
+    /// [`Producer::assert_invariants`], except those of the producer's own
+    /// counters.
+    ///
+    /// # Panics
+    ///
+    /// Panics with a report if any invariant does not hold.
+    #[track_caller]
+    #[inline]
+    pub fn assert_invariants(&self) {
+        assert_valid(&self.buffer, None);
+    }
+}
@@ -0,0 +1,136 @@
+//! A control lane and a bulk lane behind one pair of halves.
+//!
+//! Protocol implementations commonly send small control messages, e.g.
+//! acknowledgements or window updates, next to bulk data. The [`Consumer`]
+//! always drains the control lane first, so control messages never wait
+//! behind bulk data.
+
+use ::core::ops::FnMut;
+use ::core::result::Result::{self, Ok};
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::{BufferError, ConsumerError, ProducerError};
+
+/// One of the two lanes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Lane {
+    /// Drained first.
+    Control,
+    /// Drained once the control lane is empty.
+    Bulk,
+}
+
+/// Creates a producer-consumer pair sharing a control ring of `control`
+/// bytes and a bulk ring of `bulk` bytes, both aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when a size or `align` is not a power of two, or when
+/// an allocation fails.
+#[inline]
+pub fn new(control: usize, bulk: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (control_producer, control_consumer) = crate::new(control, align)?;
+    let (bulk_producer, bulk_consumer) = crate::new(bulk, align)?;
+    let producer = Producer {
+        control: control_producer,
+        bulk: bulk_producer,
+    };
+    let consumer = Consumer {
+        control: control_consumer,
+        bulk: bulk_consumer,
+    };
+    Ok((producer, consumer))
+}
+
+/// The writing half of a lane pair, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    control: crate::Producer,
+    bulk: crate::Producer,
+}
+
+impl Producer {
+    /// Returns the producer of `lane`.
+    #[must_use]
+    #[inline]
+    pub fn lane(&mut self, lane: Lane) -> &mut crate::Producer {
+        match lane {
+            Lane::Control => &mut self.control,
+            Lane::Bulk => &mut self.bulk,
+        }
+    }
+
+    /// Fills `lane`, see [`crate::Producer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        lane: Lane,
+        f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.lane(lane).slices(f)
+    }
+}
+
+/// The reading half of a lane pair, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    control: crate::Consumer,
+    bulk: crate::Consumer,
+}
+
+impl Consumer {
+    /// Returns the lane drained next: the control lane unless it is empty.
+    #[must_use]
+    #[inline]
+    pub fn next_lane(&self) -> Lane {
+        if self.control.is_empty() {
+            Lane::Bulk
+        } else {
+            Lane::Control
+        }
+    }
+
+    /// Drains the control lane, or the bulk lane if the control lane is
+    /// empty, see [`crate::Consumer::slices`]. The closure is passed the lane
+    /// drained.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(Lane, &[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let lane = self.next_lane();
+        let consumer = match lane {
+            Lane::Control => &mut self.control,
+            Lane::Bulk => &mut self.bulk,
+        };
+        consumer.slices(|bufs, len| f(lane, bufs, len))
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.control.is_empty() && self.bulk.is_empty()
+    }
+}
+
+/// Reads from the control lane first; one call never mixes the lanes.
+#[cfg(feature = "std")]
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        match self.next_lane() {
+            Lane::Control => io::Read::read(&mut self.control, dst),
+            Lane::Bulk => io::Read::read(&mut self.bulk, dst),
+        }
+    }
+}
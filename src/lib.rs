@@ -13,98 +13,588 @@
 )]
 #![cfg_attr(not(feature = "std"), no_std)]
 #![no_implicit_prelude]
+#![cfg_attr(
+    all(feature = "wasm", target_arch = "wasm32", target_feature = "atomics"),
+    feature(stdarch_wasm_atomic_wait)
+)]
 
 extern crate alloc;
 
 use ::alloc::alloc::{Layout, alloc_zeroed, dealloc};
+use ::alloc::boxed::Box;
 use ::alloc::sync::Arc;
 use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::From as _;
 use ::core::default::Default as _;
 use ::core::fmt;
 use ::core::hint;
 use ::core::marker::{PhantomData, Send, Sync};
-use ::core::ops::{Drop, FnMut, Range};
+use ::core::ops::{Drop, FnMut, FnOnce, Range};
 use ::core::option::Option::{self, None, Some};
 use ::core::ptr::{self, NonNull};
 use ::core::result::Result::{self, Err, Ok};
-use ::core::sync::atomic::AtomicUsize;
-use ::core::sync::atomic::Ordering::{Acquire, Relaxed, Release};
+use ::core::sync::atomic::{AtomicBool, AtomicUsize};
 use ::core::{debug_assert, write};
 use ::crossbeam_utils::CachePadded;
 #[cfg(feature = "std")]
 use ::std::io;
 
+use crate::ordering::{Acquire, Relaxed, Release};
+
+mod accounting;
+#[cfg(feature = "std")]
+pub mod age;
+mod arena;
+pub mod asynch;
+pub mod broadcast;
+mod channel;
+pub mod datagram;
+pub mod dma;
+mod dump;
+#[cfg(feature = "std")]
+mod duplex;
+mod fanin;
+mod fanout;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod framing;
+pub mod grant;
+pub mod inspect;
+mod invariants;
+pub mod lanes;
+#[cfg(feature = "std")]
+pub mod logwriter;
+pub mod lossy;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "std")]
+pub mod mpsc;
+pub mod mux;
+#[cfg(feature = "oplog")]
+pub mod oplog;
+mod ordering;
+#[cfg(all(feature = "mmap", feature = "std", unix))]
+pub mod persist;
+#[cfg(feature = "std")]
+mod pipe;
+pub mod poison;
+pub mod polling;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(kani)]
+mod proofs;
+#[cfg(feature = "std")]
+pub mod proxy;
+#[cfg(feature = "std")]
+pub mod ratelimit;
+pub mod records;
+#[cfg(feature = "registry")]
+pub mod registry;
+pub mod replay;
+pub mod ring;
+mod router;
+pub mod scoped;
+#[cfg(feature = "scrub")]
+pub mod scrub;
+#[cfg(feature = "std")]
+mod shared;
+#[cfg(all(feature = "mmap", any(unix, windows)))]
+pub mod shm;
+#[cfg(feature = "stats")]
+pub mod stats;
+#[cfg(feature = "std")]
+mod stream;
+pub mod tap;
+mod tee;
+#[cfg(feature = "testutil")]
+pub mod testutil;
+#[cfg(feature = "std")]
+pub mod throughput;
+pub mod transform;
+pub mod typed;
+#[cfg(feature = "std")]
+mod unbounded;
+#[cfg(all(
+    feature = "wasm",
+    any(
+        all(target_arch = "wasm32", target_feature = "atomics"),
+        feature = "std"
+    )
+))]
+pub mod wasm;
+#[cfg(feature = "std")]
+pub mod work;
+
+pub use accounting::{Accounting, Budget, set_global as set_global_accounting};
+pub use arena::Arena;
+pub use channel::{Receiver, Sender, TryRecvError, TrySendError, channel};
+#[cfg(feature = "std")]
+pub use duplex::Duplex;
+pub use fanin::{Fairness, Merger};
+pub use fanout::Distributor;
+#[cfg(feature = "std")]
+pub use pipe::{PipeReader, PipeWriter, pipe};
+#[cfg(feature = "std")]
+pub use pool::Pool;
+pub use router::Router;
+#[cfg(feature = "std")]
+pub use shared::{SharedConsumer, SharedProducer};
+#[cfg(feature = "std")]
+pub use stream::{DuplexStream, duplex};
+pub use tee::{Tee, tee};
+#[cfg(feature = "std")]
+pub use unbounded::{UnboundedConsumer, UnboundedProducer};
+
 /// Creates a producer-consumer pair sharing a ring buffer.
 ///
+/// Shorthand for `Builder::new(size).align(align).build()`.
+///
 /// # Errors
 ///
 /// Returns an error when `size` or `align` is not a power of two, or when
 /// the allocation fails.
 #[inline]
 pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
-    // implies != 0
-    if !size.is_power_of_two() {
-        return Err(BufferError::BadSize(size));
+    Builder::new(size).align(align).build()
+}
+
+/// Creates a producer-consumer pair sharing an unbounded ring, a chain of
+/// ring buffers of `size` bytes each.
+///
+/// The producer never runs out of space: it appends a new segment once the
+/// current one is full. The consumer follows and releases drained segments
+/// to a free list the producer takes from before allocating. Meant for
+/// pipelines where the producer can't be slowed down; memory use grows with
+/// the backlog and is not returned until the halves are dropped.
+///
+/// Slices are never longer than a segment, and never span two.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation of the first segment fails.
+#[cfg(feature = "std")]
+#[inline]
+pub fn new_unbounded(
+    size: usize,
+    align: usize,
+) -> Result<(UnboundedProducer, UnboundedConsumer), BufferError> {
+    Builder::new(size).align(align).build_unbounded()
+}
+
+/// Creates a producer-consumer pair sharing a mirrored ring buffer.
+///
+/// Shorthand for `Builder::new(size).mirrored().build()`, see
+/// [`Builder::mirrored`].
+///
+/// # Errors
+///
+/// Returns an error when `size` is not a power of two or not a multiple of
+/// the page size (the allocation granularity on Windows), or when the
+/// memory cannot be mapped.
+#[cfg(feature = "mmap")]
+#[inline]
+pub fn new_mirrored(size: usize) -> Result<(Producer, Consumer), BufferError> {
+    Builder::new(size).mirrored().build()
+}
+
+/// Configures and creates a producer-consumer pair sharing a ring buffer.
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Builder {
+    size: usize,
+    align: usize,
+    frame: usize,
+    #[cfg(feature = "mmap")]
+    mirrored: bool,
+    account: Option<accounting::Account>,
+    name: Option<Box<str>>,
+    #[cfg(feature = "std")]
+    age: bool,
+    poison: bool,
+}
+
+impl Builder {
+    /// Starts configuring a buffer of `size` bytes, which must be a power of
+    /// two.
+    #[inline]
+    pub fn new(size: usize) -> Self {
+        Builder {
+            size,
+            align: ::core::mem::align_of::<usize>(),
+            frame: 1,
+            #[cfg(feature = "mmap")]
+            mirrored: false,
+            account: None,
+            name: None,
+            #[cfg(feature = "std")]
+            age: false,
+            poison: false,
+        }
+    }
+
+    /// Sets the alignment of the buffer's memory, which must be a power of
+    /// two. Defaults to the alignment of `usize`.
+    #[inline]
+    pub fn align(mut self, align: usize) -> Self {
+        self.align = align;
+        self
     }
-    if !align.is_power_of_two() {
-        return Err(BufferError::BadAlignment(align));
+
+    /// Sets the frame size, a power of two not larger than the buffer. Both
+    /// halves then only hand out slices and only accept commits that are
+    /// multiples of it, so no frame is ever torn across the wrap seam.
+    /// Defaults to 1.
+    #[inline]
+    pub fn frame_size(mut self, bytes: usize) -> Self {
+        self.frame = bytes;
+        self
     }
 
-    let mask = size.wrapping_sub(1);
+    /// Maps the buffer's memory twice, back-to-back, so the empty and the
+    /// filled region are always handed out as one contiguous slice; the
+    /// second slice of every pair is empty. The size must be a multiple of
+    /// the page size (the allocation granularity on Windows) and the memory
+    /// is aligned to it, overriding [`Builder::align`].
+    #[cfg(feature = "mmap")]
+    #[inline]
+    pub fn mirrored(mut self) -> Self {
+        self.mirrored = true;
+        self
+    }
 
-    let data = AlignedData::new(size, align)?;
+    /// Reports the size of the buffer to `account` before allocating it and
+    /// once it is freed, also across [`resize`]. The hook may refuse the
+    /// allocation, e.g. a shared [`Budget`] bounding the total buffer memory.
+    /// Takes the place of the global hook, see [`set_global_accounting`].
+    #[inline]
+    pub fn accounting(mut self, account: Arc<dyn Accounting>) -> Self {
+        self.account = Some(accounting::Account(account));
+        self
+    }
 
-    let buffer = Arc::new(Buffer {
-        read: CachePadded::default(),
-        write: CachePadded::default(),
-        mask,
-        data,
-    });
+    /// Names the buffer, e.g. after the connection or stage it serves, see
+    /// [`Producer::name`] and the `registry` module.
+    #[inline]
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(Box::from(name));
+        self
+    }
 
-    let producer = Producer {
-        buffer: Arc::clone(&buffer),
-        _notsync: PhantomData,
-    };
-    let consumer = Consumer {
-        buffer,
-        _notsync: PhantomData,
+    /// Creates the producer-consumer pair.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when a parameter is out of range, or when the
+    /// allocation fails.
+    #[inline]
+    pub fn build(self) -> Result<(Producer, Consumer), BufferError> {
+        let Builder {
+            size, align, frame, ..
+        } = self;
+
+        // implies != 0
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        if !frame.is_power_of_two() || frame > size {
+            return Err(BufferError::BadGranularity(frame));
+        }
+
+        #[cfg(feature = "mmap")]
+        if self.mirrored {
+            if !size.is_multiple_of(mmap::granularity()) {
+                return Err(BufferError::BadSize(size));
+            }
+            let data = AlignedData::mirrored(size, self.account.as_ref())?;
+            let buffer = Buffer::new(data, frame, 0, 0)
+                .poisoning(self.poison.then(|| AtomicBool::new(false)));
+            #[cfg(feature = "std")]
+            let buffer = buffer.aged(self.age.then(|| Arc::new(age::Stamps::new())));
+            return Ok(pair(buffer.named(self.name)));
+        }
+
+        if !align.is_power_of_two() {
+            return Err(BufferError::BadAlignment(align));
+        }
+
+        let data = AlignedData::new(size, align, self.account.as_ref())?;
+
+        let buffer =
+            Buffer::new(data, frame, 0, 0).poisoning(self.poison.then(|| AtomicBool::new(false)));
+        #[cfg(feature = "std")]
+        let buffer = buffer.aged(self.age.then(|| Arc::new(age::Stamps::new())));
+        Ok(pair(buffer.named(self.name)))
+    }
+
+    /// Creates an unbounded ring, see [`new_unbounded`], whose segments are
+    /// all built by this builder, with the same size, alignment, frame size,
+    /// name, accounting hook, poisoning and age tracking.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Builder::build`] for the first segment.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn build_unbounded(self) -> Result<(UnboundedProducer, UnboundedConsumer), BufferError> {
+        unbounded::new(self)
+    }
+}
+
+#[inline]
+fn pair(buffer: Buffer) -> (Producer, Consumer) {
+    #[cfg(feature = "scrub")]
+    let buffer = scrub::filled(buffer);
+    let buffer = Arc::new(buffer);
+    #[cfg(feature = "registry")]
+    registry::register(&buffer);
+
+    let producer = Producer::new(Arc::clone(&buffer));
+    let consumer = Consumer::new(buffer);
+
+    (producer, consumer)
+}
+
+/// Resizes the buffer shared by `producer` and `consumer` to `size` bytes.
+///
+/// `size` must be a power of two. The buffered bytes, including ones still
+/// pending publication, see [`Producer::set_coalesce_threshold`], are moved
+/// into a new allocation with the same alignment and frame size, mirrored or
+/// not. Positions are preserved. The new allocation is always a heap or
+/// mapped one: a buffer carved from an [`Arena`] or taken from a [`Pool`]
+/// leaves it.
+///
+/// Taking both halves by mutable reference rules out any access to the
+/// buffer for the duration of the move. Halves living on different threads
+/// must be brought together first.
+///
+/// # Errors
+///
+/// Returns [`BufferError::Mismatch`] if the halves don't share a buffer,
+/// [`BufferError::BadSize`] if `size` is not a power of two, too small for
+/// the buffered bytes, or smaller than the granularity or start alignment of
+/// a half, or an error if the allocation fails or is refused by the
+/// buffer's accounting hook, see [`Builder::accounting`]. The halves are
+/// unchanged on error.
+#[inline]
+pub fn resize(
+    producer: &mut Producer,
+    consumer: &mut Consumer,
+    size: usize,
+) -> Result<(), BufferError> {
+    let old = &producer.buffer;
+    if !Arc::ptr_eq(old, &consumer.buffer) {
+        return Err(BufferError::Mismatch);
+    }
+
+    let r = old.read.load(Relaxed);
+    let w = producer.write;
+    let filled = w.wrapping_sub(r);
+    if !size.is_power_of_two()
+        || size < filled
+        || size < old.frame
+        || size < producer.limits.granularity()
+        || size < consumer.limits.granularity()
+    {
+        return Err(BufferError::BadSize(size));
+    }
+
+    let account = old.data.account();
+    #[cfg(feature = "mmap")]
+    let data = if old.data.is_mirrored() {
+        if !size.is_multiple_of(mmap::granularity()) {
+            return Err(BufferError::BadSize(size));
+        }
+        AlignedData::mirrored(size, account)?
+    } else {
+        AlignedData::new(size, old.data.align(), account)?
     };
+    #[cfg(not(feature = "mmap"))]
+    let data = AlignedData::new(size, old.data.align(), account)?;
+
+    let buffer = Buffer::new(data, old.frame, r, producer.published)
+        .named(old.name.clone())
+        .poisoning(
+            old.poison
+                .as_ref()
+                .map(|poison| AtomicBool::new(poison.load(Relaxed))),
+        );
+    #[cfg(feature = "std")]
+    let buffer = buffer.aged(old.stamps.clone());
+    #[cfg(feature = "scrub")]
+    let buffer = scrub::filled(buffer);
+
+    // SAFETY: both halves are borrowed mutably, so no slices of the old
+    //         buffer are live, and the new buffer is not shared yet. The
+    //         ranges map the same positions in both buffers.
+    unsafe {
+        let (src, _) = filled_ranges(old.data.len(), old.mask, r, w);
+        let (dst, _) = filled_ranges(buffer.data.len(), buffer.mask, r, w);
+        copy_slices(old.data.slices(src), buffer.data.slices_mut(dst));
+    }
 
-    Ok((producer, consumer))
+    let buffer = Arc::new(buffer);
+    #[cfg(feature = "registry")]
+    registry::register(&buffer);
+    producer.buffer = Arc::clone(&buffer);
+    producer.occupancy = Occupancy::default();
+    consumer.buffer = buffer;
+
+    Ok(())
+}
+
+/// Applies the [`Producer::suggested_capacity`] with [`resize`].
+///
+/// The size never drops below the granularity of either half or the
+/// buffered bytes. Returns whether the buffer was resized.
+///
+/// Meant to be called periodically, every call after a resize judges the
+/// occupancy seen since.
+///
+/// # Errors
+///
+/// Returns the errors of [`resize`].
+#[inline]
+pub fn autotune(producer: &mut Producer, consumer: &mut Consumer) -> Result<bool, BufferError> {
+    let size = producer.buffer.data.len();
+    let filled = producer
+        .write
+        .wrapping_sub(producer.buffer.read.load(Relaxed));
+    let suggested = producer
+        .suggested_capacity()
+        .max(producer.limits.granularity())
+        .max(consumer.limits.granularity())
+        .max(filled.next_power_of_two());
+    if suggested == size {
+        return Ok(false);
+    }
+    resize(producer, consumer, suggested)?;
+    Ok(true)
+}
+
+/// Copies the bytes of `src` into `dst`, which must have the same total
+/// length but may be split at a different point.
+#[inline]
+fn copy_slices([first, second]: [&[u8]; 2], [mut dst0, mut dst1]: [&mut [u8]; 2]) {
+    debug_assert!(first.len() + second.len() == dst0.len() + dst1.len());
+
+    for mut src in [first, second] {
+        while !src.is_empty() {
+            if dst0.is_empty() {
+                dst0 = ::core::mem::take(&mut dst1);
+            }
+            let n = src.len().min(dst0.len());
+            let (head, tail) = ::core::mem::take(&mut dst0).split_at_mut(n);
+            head.copy_from_slice(&src[..n]);
+            dst0 = tail;
+            src = &src[n..];
+        }
+    }
 }
 
 // TODO: put data and counters into same heap allocation. This would also
 //       remove the `Arc` allocation and with it the only remaining abort
 //       path: `Arc::new` calls `handle_alloc_error` when out of memory.
+/// The state shared by the halves, `Sync` as its fields are.
+///
+/// The slices handed out over `data` are never aliased, which the `unsafe`
+/// accessors of [`AlignedData`] require: each counter is advanced only
+/// through its uniquely owned, non-`Clone` half (`Producer` for `write`,
+/// `Consumer` for `read`), the slice-vending methods take `&mut self` so a
+/// half cannot re-enter them while its slices are live, and the counter
+/// protocol keeps the producer's empty ranges and the consumer's filled
+/// ranges disjoint. A callback's returned count is checked against the
+/// offered length before a counter is advanced, so the wrapping distance
+/// `write - read` stays within `0..=size` even with a buggy callback.
 #[derive(Debug)]
 struct Buffer {
     read: CachePadded<AtomicUsize>,
     write: CachePadded<AtomicUsize>,
     mask: usize,
+    /// Commits must be multiples of this power of two.
+    frame: usize,
     data: AlignedData,
+    name: Option<Box<str>>,
+    #[cfg(feature = "std")]
+    stamps: Option<Arc<age::Stamps>>,
+    /// Set when a callback panics, if poisoning is enabled.
+    poison: Option<AtomicBool>,
 }
 
-// SAFETY: Sync is safe because the slices handed out over `data` are never
-//         aliased: each counter is advanced only through its uniquely owned,
-//         non-`Clone` half (`Producer` for `write`, `Consumer` for `read`), the
-//         slice-vending methods take `&mut self` so a half cannot re-enter
-//         them while its slices are live, and the counter protocol keeps the
-//         producer's empty ranges and the consumer's filled ranges disjoint.
-//         A callback's returned count is checked against the offered length
-//         before a counter is advanced, so the wrapping distance
-//         `write - read` stays within `0..=size` even with a buggy callback.
-unsafe impl Sync for Buffer {}
-
 impl Buffer {
+    #[inline]
+    fn new(data: AlignedData, frame: usize, read: usize, write: usize) -> Self {
+        Buffer {
+            read: CachePadded::new(AtomicUsize::new(read)),
+            write: CachePadded::new(AtomicUsize::new(write)),
+            mask: data.len().wrapping_sub(1),
+            frame,
+            data,
+            name: None,
+            #[cfg(feature = "std")]
+            stamps: None,
+            poison: None,
+        }
+    }
+
+    #[inline]
+    fn named(mut self, name: Option<Box<str>>) -> Self {
+        self.name = name;
+        self
+    }
+
+    #[inline]
+    fn poisoning(mut self, poison: Option<AtomicBool>) -> Self {
+        self.poison = poison;
+        self
+    }
+
+    #[cfg(feature = "std")]
+    #[inline]
+    fn aged(mut self, stamps: Option<Arc<age::Stamps>>) -> Self {
+        self.stamps = stamps;
+        self
+    }
+
+    /// Returns the ranges of the empty region starting at `w` and their
+    /// total length. With `contiguous` set only the first range is offered.
+    #[inline]
+    fn empty(&self, w: usize, contiguous: bool, limits: Limits) -> ([Range<usize>; 2], usize) {
+        let r = self.read.load(Acquire);
+
+        let (ranges, len) = if self.data.is_mirrored() {
+            let len = self.data.len().wrapping_sub(w.wrapping_sub(r));
+            mirrored_ranges(self.mask, w, len)
+        } else {
+            empty_ranges(self.data.len(), self.mask, r, w)
+        };
+        let granularity = limits.granularity();
+        if contiguous || granularity != 1 {
+            restrict_ranges(ranges, contiguous, granularity)
+        } else {
+            (ranges, len)
+        }
+    }
+
+    /// Offers the empty region starting at `w` to `f`. Does not advance the
+    /// write counter; publishing is up to the [`Producer`].
+    /// With `contiguous` set only the first range is offered.
     #[inline]
     fn produce_fn<E>(
         &self,
+        w: usize,
+        contiguous: bool,
+        limits: Limits,
         mut f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
     ) -> Result<usize, ProducerError<E>> {
-        let w = self.write.load(Relaxed);
-        let r = self.read.load(Acquire);
-
-        let (ranges, len) = empty_ranges(self.data.len(), self.mask, r, w);
+        if self.is_poisoned() {
+            hint::cold_path();
+            return Err(ProducerError::Poisoned);
+        }
+        let (ranges, len) = self.empty(w, contiguous, limits);
         if len == 0 {
             // TODO: feature gated WouldBlock
         }
@@ -114,28 +604,62 @@ impl Buffer {
         //         same time.
         let bufs = unsafe { self.data.slices_mut(ranges) };
 
-        let n = f(bufs, len).map_err(ProducerError::Callback)?;
+        // Only dropped if `f` unwinds.
+        let guard = self.poison.as_ref().map(poison::Guard);
+        let n = f(bufs, len);
+        ::core::mem::forget(guard);
+        let n = n.map_err(ProducerError::Callback)?;
         if n > len {
             hint::cold_path();
             return Err(ProducerError::InvalidCount { n, len });
         }
-
-        if n != 0 {
-            self.write.store(w.wrapping_add(n), Release);
+        if n & limits.frame.wrapping_sub(1) != 0 {
+            hint::cold_path();
+            return Err(ProducerError::TornFrame {
+                n,
+                frame: limits.frame,
+            });
         }
 
         Ok(n)
     }
 
+    /// Returns the read counter, the ranges of the filled region and their
+    /// total length. With `contiguous` set only the first range is offered.
     #[inline]
-    fn consume_fn<E>(
-        &self,
-        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
-    ) -> Result<usize, ConsumerError<E>> {
+    fn filled(&self, contiguous: bool, limits: Limits) -> (usize, [Range<usize>; 2], usize) {
         let r = self.read.load(Relaxed);
         let w = self.write.load(Acquire);
 
-        let (ranges, len) = filled_ranges(self.data.len(), self.mask, r, w);
+        let (ranges, len) = if self.data.is_mirrored() {
+            mirrored_ranges(self.mask, r, w.wrapping_sub(r))
+        } else {
+            filled_ranges(self.data.len(), self.mask, r, w)
+        };
+        let granularity = limits.granularity();
+        let (ranges, len) = if contiguous || granularity != 1 {
+            restrict_ranges(ranges, contiguous, granularity)
+        } else {
+            (ranges, len)
+        };
+        (r, ranges, len)
+    }
+
+    /// Offers the filled region to `f`. Returns the read counter and the
+    /// count, but does not advance the counter; releasing is up to the
+    /// [`Consumer`]. With `contiguous` set only the first range is offered.
+    #[inline]
+    fn consume_fn<E>(
+        &self,
+        contiguous: bool,
+        limits: Limits,
+        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<(usize, usize), ConsumerError<E>> {
+        if self.is_poisoned() {
+            hint::cold_path();
+            return Err(ConsumerError::Poisoned);
+        }
+        let (r, ranges, len) = self.filled(contiguous, limits);
         if len == 0 {
             // TODO: feature gated WouldBlock
         }
@@ -145,17 +669,24 @@ impl Buffer {
         //         same time.
         let bufs = unsafe { self.data.slices(ranges) };
 
-        let n = f(bufs, len).map_err(ConsumerError::Callback)?;
+        // Only dropped if `f` unwinds.
+        let guard = self.poison.as_ref().map(poison::Guard);
+        let n = f(bufs, len);
+        ::core::mem::forget(guard);
+        let n = n.map_err(ConsumerError::Callback)?;
         if n > len {
             hint::cold_path();
             return Err(ConsumerError::InvalidCount { n, len });
         }
-
-        if n != 0 {
-            self.read.store(r.wrapping_add(n), Release);
+        if n & limits.frame.wrapping_sub(1) != 0 {
+            hint::cold_path();
+            return Err(ConsumerError::TornFrame {
+                n,
+                frame: limits.frame,
+            });
         }
 
-        Ok(n)
+        Ok((r, n))
     }
 }
 
@@ -174,6 +705,19 @@ pub enum ProducerError<E> {
         /// The total length the callback was offered.
         len: usize,
     },
+    /// The callback returned a count that is not a multiple of the frame
+    /// size, see [`Builder::frame_size`], or of the start alignment, see
+    /// [`Producer::set_start_alignment`]. The count was discarded and the buffer
+    /// is unchanged, so the half stays usable.
+    TornFrame {
+        /// The count the callback returned.
+        n: usize,
+        /// The required multiple.
+        frame: usize,
+    },
+    /// A callback panicked before, see [`Builder::poison_on_panic`]. The
+    /// callback was not called.
+    Poisoned,
 }
 
 impl<E: fmt::Display> fmt::Display for ProducerError<E> {
@@ -187,6 +731,13 @@ impl<E: fmt::Display> fmt::Display for ProducerError<E> {
                     "callback returned a count of {n}, but only {len} bytes were available"
                 )
             }
+            ProducerError::TornFrame { n, frame } => {
+                write!(
+                    f,
+                    "callback returned a count of {n}, which is not a multiple of the frame size {frame}"
+                )
+            }
+            ProducerError::Poisoned => f.write_str("ring poisoned by a panicking callback"),
         }
     }
 }
@@ -196,7 +747,9 @@ impl<E: ::core::error::Error> ::core::error::Error for ProducerError<E> {
     fn source(&self) -> Option<&(dyn ::core::error::Error + 'static)> {
         match self {
             ProducerError::Callback(e) => e.source(),
-            ProducerError::InvalidCount { .. } => None,
+            ProducerError::InvalidCount { .. }
+            | ProducerError::TornFrame { .. }
+            | ProducerError::Poisoned => None,
         }
     }
 }
@@ -216,6 +769,19 @@ pub enum ConsumerError<E> {
         /// The total length the callback was offered.
         len: usize,
     },
+    /// The callback returned a count that is not a multiple of the frame
+    /// size, see [`Builder::frame_size`], or of the start alignment, see
+    /// [`Consumer::set_start_alignment`]. The count was discarded and the buffer
+    /// is unchanged, so the half stays usable.
+    TornFrame {
+        /// The count the callback returned.
+        n: usize,
+        /// The required multiple.
+        frame: usize,
+    },
+    /// A callback panicked before, see [`Builder::poison_on_panic`]. The
+    /// callback was not called.
+    Poisoned,
 }
 
 impl<E: fmt::Display> fmt::Display for ConsumerError<E> {
@@ -229,6 +795,13 @@ impl<E: fmt::Display> fmt::Display for ConsumerError<E> {
                     "callback returned a count of {n}, but only {len} bytes were available"
                 )
             }
+            ConsumerError::TornFrame { n, frame } => {
+                write!(
+                    f,
+                    "callback returned a count of {n}, which is not a multiple of the frame size {frame}"
+                )
+            }
+            ConsumerError::Poisoned => f.write_str("ring poisoned by a panicking callback"),
         }
     }
 }
@@ -238,7 +811,9 @@ impl<E: ::core::error::Error> ::core::error::Error for ConsumerError<E> {
     fn source(&self) -> Option<&(dyn ::core::error::Error + 'static)> {
         match self {
             ConsumerError::Callback(e) => e.source(),
-            ConsumerError::InvalidCount { .. } => None,
+            ConsumerError::InvalidCount { .. }
+            | ConsumerError::TornFrame { .. }
+            | ConsumerError::Poisoned => None,
         }
     }
 }
@@ -247,11 +822,27 @@ impl<E: ::core::error::Error> ::core::error::Error for ConsumerError<E> {
 #[derive(Debug, Clone)]
 pub enum BufferError {
     /// The requested size is not a power of two, or too large to allocate.
+    /// Mirrored buffers must also be a multiple of the page size.
     BadSize(usize),
     /// The requested alignment is not a power of two.
     BadAlignment(usize),
+    /// The requested granularity is not a power of two, or larger than the
+    /// buffer.
+    BadGranularity(usize),
+    /// The producer and consumer passed in don't share a buffer.
+    Mismatch,
+    /// The accounting hook refused the allocation of this many bytes, see
+    /// [`Builder::accounting`].
+    OverBudget(usize),
     /// The allocator failed to provide the requested memory.
     AllocFailed,
+    /// The operating system failed to map the requested memory. Carries the
+    /// raw OS error code.
+    MapFailed(i32),
+    /// The shared memory does not hold a ring, or not yet.
+    Incompatible,
+    /// The half of the shared ring is taken by another process.
+    InUse,
 }
 
 impl fmt::Display for BufferError {
@@ -264,7 +855,19 @@ impl fmt::Display for BufferError {
             BufferError::BadAlignment(align) => {
                 write!(f, "alignment is not a power of two: {align}")
             }
+            BufferError::BadGranularity(bytes) => {
+                write!(f, "granularity is not a power of two or too large: {bytes}")
+            }
+            BufferError::OverBudget(bytes) => {
+                write!(f, "accounting refused an allocation of {bytes} bytes")
+            }
+            BufferError::Mismatch => write!(f, "producer and consumer don't share a buffer"),
             BufferError::AllocFailed => write!(f, "allocation failed"),
+            BufferError::MapFailed(code) => {
+                write!(f, "memory mapping failed: os error {code}")
+            }
+            BufferError::Incompatible => write!(f, "shared memory holds no compatible ring"),
+            BufferError::InUse => write!(f, "half of the shared ring is in use"),
         }
     }
 }
@@ -321,115 +924,679 @@ const fn empty_ranges(
     (ranges, len)
 }
 
-/// Keeps the containing half `Send` while suppressing `Sync`, without an
-/// unsafe impl.
-type SendNotSyncZst = ::core::cell::Cell<()>;
+/// Maps `len` bytes from counter position `start` onto a single range of
+/// mirrored data, which is mapped twice and thus `2 * (mask + 1)` long.
+#[must_use]
+#[inline]
+const fn mirrored_ranges(mask: usize, start: usize, len: usize) -> ([Range<usize>; 2], usize) {
+    debug_assert!(len <= mask.wrapping_add(1));
 
-#[derive(Debug)]
-pub struct Producer {
-    buffer: Arc<Buffer>,
-    _notsync: PhantomData<SendNotSyncZst>,
+    let start = start & mask;
+    ([start..start.wrapping_add(len), 0..0], len)
 }
 
-impl Producer {
-    /// Fills the buffer: calls the passed closure with a pair of
-    /// [`io::IoSliceMut`] mapping the empty space, meant to be used with
-    /// [`io::Read::read_vectored`] and async variants, and the total length
-    /// of both slices.
-    /// The closure must return the number of bytes read on success.
-    ///
-    /// # Errors
-    ///
-    /// Returns [`ProducerError::Callback`] with the closure's error
-    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
-    /// a count greater than the total length it was given.
-    #[cfg(feature = "std")]
-    #[inline]
-    pub fn io_slices(
-        &mut self,
-        mut f: impl FnMut(&mut [io::IoSliceMut<'_>], usize) -> io::Result<usize>,
-    ) -> Result<usize, ProducerError<io::Error>> {
-        self.buffer.produce_fn(|bufs, len| {
-            let mut bufs = bufs.map(io::IoSliceMut::new);
-            f(&mut bufs, len)
-        })
+/// Restricts ranges to the first one if `contiguous` is set, and truncates
+/// each range to a multiple of `granularity`, a power of two. A truncated
+/// first range drops the second, which would not follow it seamlessly.
+#[must_use]
+#[inline]
+const fn restrict_ranges(
+    [first, second]: [Range<usize>; 2],
+    contiguous: bool,
+    granularity: usize,
+) -> ([Range<usize>; 2], usize) {
+    debug_assert!(granularity.is_power_of_two());
+
+    let mask = !granularity.wrapping_sub(1);
+    let len = range_len(&first) & mask;
+    let end = first.start.wrapping_add(len);
+    if contiguous || end != first.end {
+        return ([first.start..end, 0..0], len);
     }
 
-    /// Fills the buffer: calls the passed closure with a pair of `&mut [u8]`
-    /// mapping the empty space, meant to be used with non `std::io` vectored
-    /// read operations.
-    ///
-    /// # Errors
-    ///
-    /// Returns [`ProducerError::Callback`] with the closure's error
-    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
-    /// a count greater than the total length it was given.
+    let second_len = range_len(&second) & mask;
+    let second_end = second.start.wrapping_add(second_len);
+    (
+        [first, second.start..second_end],
+        len.wrapping_add(second_len),
+    )
+}
+
+/// Per-half restrictions on the slices handed out and the commits accepted.
+#[derive(Debug, Clone, Copy)]
+struct Limits {
+    /// Requested multiple of the slice lengths.
+    truncate: usize,
+    /// Required multiple of the commits, the buffer's frame size or the
+    /// half's start alignment, whichever is larger.
+    frame: usize,
+}
+
+impl Limits {
     #[inline]
-    pub fn slices<E>(
-        &mut self,
-        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
-    ) -> Result<usize, ProducerError<E>> {
-        self.buffer.produce_fn(|mut bufs, len| f(&mut bufs, len))
+    fn new(buffer: &Buffer) -> Self {
+        Limits {
+            truncate: 1,
+            frame: buffer.frame,
+        }
     }
 
-    #[doc(hidden)]
+    /// The multiple slices are truncated to. Powers of two are multiples of
+    /// all smaller powers of two.
     #[must_use]
     #[inline]
-    pub fn position(&self) -> usize {
-        self.buffer.write.load(Relaxed)
+    fn granularity(self) -> usize {
+        self.truncate.max(self.frame)
     }
-}
 
-// TODO: impl write_vectored
-#[cfg(feature = "std")]
-impl io::Write for Producer {
     #[inline]
-    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
-        use io::Read;
-        let mut src = io::Cursor::new(src);
-        match self.io_slices(move |dsts, _| src.read_vectored(dsts)) {
-            Ok(n) => Ok(n),
-            Err(ProducerError::Callback(e)) => Err(e),
-            Err(err @ ProducerError::InvalidCount { .. }) => Err(io::Error::other(err)),
+    fn set_granularity(&mut self, buffer: &Buffer, bytes: usize) -> Result<(), BufferError> {
+        if !bytes.is_power_of_two() || bytes > buffer.data.len() {
+            return Err(BufferError::BadGranularity(bytes));
         }
+        self.truncate = bytes;
+        Ok(())
     }
 
     #[inline]
-    fn flush(&mut self) -> io::Result<()> {
+    fn set_start_alignment(
+        &mut self,
+        buffer: &Buffer,
+        position: usize,
+        align: usize,
+    ) -> Result<(), BufferError> {
+        if !align.is_power_of_two()
+            || align > buffer.data.align()
+            || align > buffer.data.len()
+            || !position.is_multiple_of(align)
+        {
+            return Err(BufferError::BadAlignment(align));
+        }
+        self.frame = align.max(buffer.frame);
         Ok(())
     }
 }
 
-#[derive(Debug)]
+/// Occupancy seen by a producer, see [`Producer::suggested_capacity`].
+#[derive(Debug, Clone, Copy, Default)]
+struct Occupancy {
+    /// Most bytes filled after a commit.
+    peak: usize,
+    /// Commits that found no empty space.
+    stalls: usize,
+}
+
+impl Occupancy {
+    #[inline]
+    fn record(&mut self, filled: usize, stalled: bool) {
+        self.peak = self.peak.max(filled);
+        self.stalls = self.stalls.saturating_add(usize::from(stalled));
+    }
+}
+
+/// Keeps the containing half `Send` while suppressing `Sync`, without an
+/// unsafe impl.
+type SendNotSyncZst = ::core::cell::Cell<()>;
+
+#[derive(Debug)]
+pub struct Producer {
+    buffer: Arc<Buffer>,
+    /// Local write counter, ahead of the shared one by the pending bytes.
+    write: usize,
+    /// Last value stored into the shared write counter.
+    published: usize,
+    threshold: usize,
+    limits: Limits,
+    occupancy: Occupancy,
+    #[cfg(feature = "stats")]
+    stats: stats::Stats,
+    #[cfg(feature = "stats")]
+    sizes: Option<Box<stats::Histogram>>,
+    tap: Option<tap::Tap>,
+    #[cfg(feature = "std")]
+    meter: Option<throughput::Throughput>,
+    #[cfg(feature = "oplog")]
+    oplog: Option<oplog::OpLog>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Producer {
+    #[inline]
+    fn new(buffer: Arc<Buffer>) -> Self {
+        let write = buffer.write.load(Relaxed);
+        let limits = Limits::new(&buffer);
+        Producer {
+            buffer,
+            write,
+            published: write,
+            threshold: 0,
+            limits,
+            occupancy: Occupancy::default(),
+            #[cfg(feature = "stats")]
+            stats: stats::Stats::default(),
+            #[cfg(feature = "stats")]
+            sizes: None,
+            tap: None,
+            #[cfg(feature = "std")]
+            meter: None,
+            #[cfg(feature = "oplog")]
+            oplog: None,
+            _notsync: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn produce_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        #[cfg(feature = "oplog")]
+        let mut seen = None;
+        let result = self
+            .buffer
+            .produce_fn(self.write, contiguous, self.limits, |bufs, len| {
+                let n = f(bufs, len)?;
+                #[cfg(feature = "oplog")]
+                {
+                    seen = Some((len, n));
+                }
+                Ok(n)
+            });
+        #[cfg(feature = "oplog")]
+        if let (Some(log), Some((len, n))) = (&self.oplog, seen) {
+            log.push(oplog::Side::Producer, len, n);
+        }
+        let n = result?;
+        self.advance(n);
+        Ok(n)
+    }
+
+    /// Advances the local write counter past `n` bytes just committed, and
+    /// publishes if due.
+    #[inline]
+    fn advance(&mut self, n: usize) {
+        let pos = self.write;
+        self.write = self.write.wrapping_add(n);
+
+        let free = self.free_len();
+        let full = free < self.limits.granularity();
+        let size = self.buffer.data.len();
+        self.occupancy.record(size - free, n == 0 && full);
+        #[cfg(feature = "stats")]
+        {
+            self.stats.record(pos, n, size, size - free);
+            if let Some(sizes) = &mut self.sizes {
+                sizes.record(n);
+            }
+        }
+        if n != 0
+            && let Some(tap) = &mut self.tap
+        {
+            tap.call(&self.buffer, pos, n);
+        }
+        #[cfg(feature = "std")]
+        if n != 0 {
+            self.record_throughput(n);
+            if let Some(stamps) = &self.buffer.stamps {
+                stamps.stamp(self.write, self.buffer.read.load(Relaxed));
+            }
+        }
+
+        // Publish unconditionally once the producer's view is full: the
+        // consumer could not free any space otherwise.
+        if self.pending() >= self.threshold || full {
+            self.publish();
+        }
+    }
+
+    #[inline]
+    fn free_len(&self) -> usize {
+        let r = self.buffer.read.load(Relaxed);
+        self.buffer.data.len() - self.write.wrapping_sub(r)
+    }
+
+    /// Sets the number of bytes that must be pending before they are
+    /// published to the consumer. Smaller commits are held back and
+    /// coalesced until the threshold is reached, the empty space runs out,
+    /// or [`Producer::publish`] is called.
+    ///
+    /// The default of 0 publishes every commit right away.
+    #[inline]
+    pub fn set_coalesce_threshold(&mut self, bytes: usize) {
+        self.threshold = bytes;
+    }
+
+    /// Truncates the slices handed out to multiples of `bytes`, e.g. the
+    /// block size required by `O_DIRECT` reads. Slice starts are multiples
+    /// too, relative to the buffer's alignment, as long as all commits are.
+    ///
+    /// The default of 1 does not truncate. Values smaller than the frame
+    /// size, see [`Builder::frame_size`], or the start alignment, see
+    /// [`Producer::set_start_alignment`], are raised to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadGranularity`] if `bytes` is not a power of
+    /// two or larger than the buffer.
+    #[inline]
+    pub fn set_granularity(&mut self, bytes: usize) -> Result<(), BufferError> {
+        self.limits.set_granularity(&self.buffer, bytes)
+    }
+
+    /// Guarantees that the first slice handed out always starts at an
+    /// address aligned to `align`, e.g. for aligned SIMD loads or DMA. This
+    /// half then only accepts commits that are multiples of `align` and
+    /// truncates its slices accordingly.
+    ///
+    /// A value of 1 lifts the restriction, leaving only the frame size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadAlignment`] if `align` is not a power of
+    /// two, is larger than the buffer or its alignment, or if the current
+    /// position is not a multiple of it.
+    #[inline]
+    pub fn set_start_alignment(&mut self, align: usize) -> Result<(), BufferError> {
+        let position = self.write;
+        self.limits
+            .set_start_alignment(&self.buffer, position, align)
+    }
+
+    /// Recommends a buffer size from the occupancy seen since the buffer was
+    /// created or last resized: double the size if the producer found the
+    /// buffer full, half the size or less if it never got more than a
+    /// quarter full, the current size otherwise or if no bytes were
+    /// written. Apply it with [`resize`] or [`autotune`].
+    #[must_use]
+    #[inline]
+    pub fn suggested_capacity(&self) -> usize {
+        let size = self.buffer.data.len();
+        let Occupancy { peak, stalls } = self.occupancy;
+        if stalls != 0 {
+            return size.checked_mul(2).unwrap_or(size);
+        }
+        // Nothing written yet is no evidence of a too large buffer.
+        if peak == 0 || peak > size / 4 {
+            return size;
+        }
+
+        let min = self.limits.granularity();
+        #[cfg(feature = "mmap")]
+        let min = if self.buffer.data.is_mirrored() {
+            min.max(mmap::granularity())
+        } else {
+            min
+        };
+        peak.wrapping_mul(2).next_power_of_two().max(min).min(size)
+    }
+
+    /// Returns the number of bytes written, but not yet published to the
+    /// consumer.
+    #[must_use]
+    #[inline]
+    pub fn pending(&self) -> usize {
+        self.write.wrapping_sub(self.published)
+    }
+
+    /// Publishes all pending bytes to the consumer regardless of the
+    /// coalesce threshold. Called by [`io::Write::flush`] and on drop.
+    #[inline]
+    pub fn publish(&mut self) {
+        if self.write != self.published {
+            self.buffer.write.store(self.write, Release);
+            self.published = self.write;
+        }
+    }
+
+    /// Fills the buffer: calls the passed closure with one or two
+    /// [`io::IoSliceMut`] mapping the empty space, meant to be used with
+    /// [`io::Read::read_vectored`] and async variants, and the total length
+    /// of the slices. An empty second slice is left out.
+    /// The closure must return the number of bytes read on success.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given, or
+    /// [`ProducerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn io_slices(
+        &mut self,
+        mut f: impl FnMut(&mut [io::IoSliceMut<'_>], usize) -> io::Result<usize>,
+    ) -> Result<usize, ProducerError<io::Error>> {
+        self.produce_fn(false, |[first, second], len| {
+            let count = 1 + usize::from(!second.is_empty());
+            let mut bufs = [io::IoSliceMut::new(first), io::IoSliceMut::new(second)];
+            f(&mut bufs[..count], len)
+        })
+    }
+
+    /// Fills the buffer: calls the passed closure with a pair of `&mut [u8]`
+    /// mapping the empty space, meant to be used with non `std::io` vectored
+    /// read operations.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given, or
+    /// [`ProducerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
+    ///
+    /// The closure cannot call back into the producer: it is borrowed
+    /// mutably for the call, so the slices handed out are the only ones.
+    /// If it panics, nothing is committed and the producer stays usable: the
+    /// bytes it wrote are offered as empty space again, never published.
+    ///
+    /// ```compile_fail
+    /// let (mut producer, _consumer) = bytering::new(16, 1).unwrap();
+    /// producer.slices(|_, _| producer.slices(|_, len| Ok::<_, ()>(len)));
+    /// ```
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(false, |mut bufs, len| f(&mut bufs, len))
+    }
+
+    /// Fills the buffer: calls the passed closure with a single `&mut [u8]`
+    /// mapping the contiguous part of the empty space. In mirrored mode, see
+    /// [`new_mirrored`], this is all of the empty space.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given, or
+    /// [`ProducerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(true, |[buf, _], _| f(buf))
+    }
+
+    /// Hints the operating system that the memory of the empty space is not
+    /// needed, so that an idle buffer does not keep it resident. Only whole
+    /// pages are released; they are transparently provided again, with
+    /// undefined content, once written to.
+    ///
+    /// Returns the number of bytes released.
+    #[cfg(feature = "mmap")]
+    #[inline]
+    pub fn reclaim(&mut self) -> usize {
+        let r = self.buffer.read.load(Acquire);
+        let data = &self.buffer.data;
+        let (ranges, _) = empty_ranges(data.len(), self.buffer.mask, r, self.write);
+
+        let mut n = 0;
+        for range in ranges {
+            // SAFETY: ranges map the empty region only, which is guaranteed
+            //         to not overlap with the filled region `consume_fn` uses
+            //         at the same time, and `&mut self` rules out slices of
+            //         the empty region being live.
+            n += unsafe { data.discard(range) };
+        }
+        n
+    }
+
+    /// Returns the name of the buffer, see [`Builder::name`].
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.buffer.name.as_deref()
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.write
+    }
+}
+
+impl Drop for Producer {
+    #[inline]
+    fn drop(&mut self) {
+        self.publish();
+    }
+}
+
+// TODO: impl write_vectored
+#[cfg(feature = "std")]
+impl io::Write for Producer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        use io::Read;
+        let mut src = io::Cursor::new(src);
+        match self.io_slices(move |dsts, _| src.read_vectored(dsts)) {
+            Ok(n) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.publish();
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
 pub struct Consumer {
     buffer: Arc<Buffer>,
+    limits: Limits,
+    #[cfg(feature = "stats")]
+    stats: stats::Stats,
+    #[cfg(feature = "stats")]
+    sizes: Option<Box<stats::Histogram>>,
+    tap: Option<tap::Tap>,
+    #[cfg(feature = "std")]
+    meter: Option<throughput::Throughput>,
+    #[cfg(feature = "oplog")]
+    oplog: Option<oplog::OpLog>,
+    on_drop: Option<DropHook>,
     _notsync: PhantomData<SendNotSyncZst>,
 }
 
+/// A hook called with the bytes a consumer dropped unconsumed, see
+/// [`Consumer::on_drop`].
+struct DropHook(Box<dyn FnOnce(usize) + Send>);
+
+impl fmt::Debug for DropHook {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("DropHook")
+    }
+}
+
 impl Consumer {
-    /// Drains the buffer: calls the passed closure with a pair of
+    #[inline]
+    fn new(buffer: Arc<Buffer>) -> Self {
+        let limits = Limits::new(&buffer);
+        Consumer {
+            buffer,
+            limits,
+            #[cfg(feature = "stats")]
+            stats: stats::Stats::default(),
+            #[cfg(feature = "stats")]
+            sizes: None,
+            tap: None,
+            #[cfg(feature = "std")]
+            meter: None,
+            #[cfg(feature = "oplog")]
+            oplog: None,
+            on_drop: None,
+            _notsync: PhantomData,
+        }
+    }
+
+    #[inline]
+    fn consume_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        #[cfg(feature = "oplog")]
+        let mut seen = None;
+        let result = self
+            .buffer
+            .consume_fn(contiguous, self.limits, |bufs, len| {
+                let n = f(bufs, len)?;
+                #[cfg(feature = "oplog")]
+                {
+                    seen = Some((len, n));
+                }
+                Ok(n)
+            });
+        #[cfg(feature = "oplog")]
+        if let (Some(log), Some((len, n))) = (&self.oplog, seen) {
+            log.push(oplog::Side::Consumer, len, n);
+        }
+        let (r, n) = result?;
+        self.release(r, n);
+        Ok(n)
+    }
+
+    /// Releases the `n` bytes past the read counter `r` to the producer,
+    /// after passing them to the tap, if any.
+    #[inline]
+    fn release(&mut self, r: usize, n: usize) {
+        if n != 0 {
+            if let Some(tap) = &mut self.tap {
+                tap.call(&self.buffer, r, n);
+            }
+            #[cfg(feature = "scrub")]
+            scrub::release(&self.buffer, r, n);
+            self.buffer.read.store(r.wrapping_add(n), Release);
+            #[cfg(feature = "std")]
+            self.record_throughput(n);
+        }
+        #[cfg(feature = "stats")]
+        self.record(r, n);
+    }
+
+    /// Records the release of `n` bytes at `pos`.
+    #[cfg(feature = "stats")]
+    #[inline]
+    fn record(&mut self, pos: usize, n: usize) {
+        let filled = self.buffer.write.load(Relaxed).wrapping_sub(pos);
+        self.stats.record(pos, n, self.buffer.data.len(), filled);
+        if let Some(sizes) = &mut self.sizes {
+            sizes.record(n);
+        }
+    }
+
+    /// Truncates the slices handed out to multiples of `bytes`, e.g. the
+    /// block size required by `O_DIRECT` writes. Slice starts are multiples
+    /// too, relative to the buffer's alignment, as long as all commits are.
+    ///
+    /// The default of 1 does not truncate. Values smaller than the frame
+    /// size, see [`Builder::frame_size`], or the start alignment, see
+    /// [`Consumer::set_start_alignment`], are raised to it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadGranularity`] if `bytes` is not a power of
+    /// two or larger than the buffer.
+    #[inline]
+    pub fn set_granularity(&mut self, bytes: usize) -> Result<(), BufferError> {
+        self.limits.set_granularity(&self.buffer, bytes)
+    }
+
+    /// Guarantees that the first slice handed out always starts at an
+    /// address aligned to `align`, e.g. for aligned SIMD loads or DMA. This
+    /// half then only accepts commits that are multiples of `align` and
+    /// truncates its slices accordingly.
+    ///
+    /// A value of 1 lifts the restriction, leaving only the frame size.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadAlignment`] if `align` is not a power of
+    /// two, is larger than the buffer or its alignment, or if the current
+    /// position is not a multiple of it.
+    #[inline]
+    pub fn set_start_alignment(&mut self, align: usize) -> Result<(), BufferError> {
+        let position = self.buffer.read.load(Relaxed);
+        self.limits
+            .set_start_alignment(&self.buffer, position, align)
+    }
+
+    /// Drains the buffer: calls the passed closure with one or two
     /// [`io::IoSlice`] mapping the filled space, meant to be used with
     /// [`io::Write::write_vectored`] and async variants, and the total length
-    /// of both slices.
+    /// of the slices. An empty second slice is left out.
     /// The closure must return the number of bytes written on success.
     ///
     /// # Errors
     ///
     /// Returns [`ConsumerError::Callback`] with the closure's error
-    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
-    /// a count greater than the total length it was given.
+    /// unchanged, [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given, or
+    /// [`ConsumerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
     #[cfg(feature = "std")]
     #[inline]
     pub fn io_slices(
         &mut self,
         mut f: impl FnMut(&[io::IoSlice<'_>], usize) -> io::Result<usize>,
     ) -> Result<usize, ConsumerError<io::Error>> {
-        self.buffer.consume_fn(|bufs, len| {
-            let bufs = bufs.map(io::IoSlice::new);
-            f(&bufs, len)
+        self.consume_fn(false, |[first, second], len| {
+            let count = 1 + usize::from(!second.is_empty());
+            let bufs = [io::IoSlice::new(first), io::IoSlice::new(second)];
+            f(&bufs[..count], len)
         })
     }
 
+    /// Writes the filled space straight to `sink` until the ring is empty,
+    /// without an intermediate buffer. Returns the number of bytes written.
+    ///
+    /// Prefer this over [`io::copy`], which only skips its stack buffer for
+    /// readers of the standard library, not for [`io::BufRead`] in general.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `sink`, or [`io::ErrorKind::WriteZero`] if it
+    /// accepts no bytes. The bytes written before are released.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn write_to(&mut self, sink: &mut impl io::Write) -> io::Result<u64> {
+        let mut total = 0;
+        loop {
+            let n = self.io_slices(|bufs, len| match len {
+                0 => Ok(0),
+                _ => match sink.write_vectored(bufs)? {
+                    0 => Err(io::Error::from(io::ErrorKind::WriteZero)),
+                    n => Ok(n),
+                },
+            });
+            match n {
+                Ok(0) => return Ok(total),
+                Ok(n) => total += n as u64,
+                Err(ConsumerError::Callback(e)) if e.kind() == io::ErrorKind::Interrupted => {}
+                Err(ConsumerError::Callback(e)) => return Err(e),
+                Err(
+                    err @ (ConsumerError::InvalidCount { .. }
+                    | ConsumerError::TornFrame { .. }
+                    | ConsumerError::Poisoned),
+                ) => return Err(io::Error::other(err)),
+            }
+        }
+    }
+
     /// Drains the buffer: calls the passed closure with a pair of `&[u8]`
     /// mapping the filled space, meant to be used with non `std::io` vectored
     /// write operations.
@@ -437,14 +1604,59 @@ impl Consumer {
     /// # Errors
     ///
     /// Returns [`ConsumerError::Callback`] with the closure's error
-    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
-    /// a count greater than the total length it was given.
+    /// unchanged, [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given, or
+    /// [`ConsumerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
+    ///
+    /// The closure cannot call back into the consumer, see
+    /// [`Producer::slices`]. If it panics, nothing is released.
+    ///
+    /// ```compile_fail
+    /// let (_producer, mut consumer) = bytering::new(16, 1).unwrap();
+    /// consumer.slices(|_, _| consumer.slices(|_, len| Ok::<_, ()>(len)));
+    /// ```
     #[inline]
     pub fn slices<E>(
         &mut self,
         mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
     ) -> Result<usize, ConsumerError<E>> {
-        self.buffer.consume_fn(|bufs, len| f(&bufs, len))
+        self.consume_fn(false, |bufs, len| f(&bufs, len))
+    }
+
+    /// Drains the buffer: calls the passed closure with a single `&[u8]`
+    /// mapping the contiguous part of the filled space. In mirrored mode, see
+    /// [`new_mirrored`], this is all of the filled space.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given, or
+    /// [`ConsumerError::TornFrame`] if the count is not a multiple of the
+    /// required frame.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _| f(buf))
+    }
+
+    /// Returns the name of the buffer, see [`Builder::name`].
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.buffer.name.as_deref()
+    }
+
+    /// Calls `hook` once the consumer is dropped with the number of bytes
+    /// published and not consumed by then, replacing any hook set before,
+    /// e.g. to log tail data lost at shutdown. Bytes the producer commits
+    /// afterwards are not counted.
+    #[inline]
+    pub fn on_drop(&mut self, hook: impl FnOnce(usize) + Send + 'static) {
+        self.on_drop = Some(DropHook(Box::new(hook)));
     }
 
     #[doc(hidden)]
@@ -464,6 +1676,16 @@ impl Consumer {
     }
 }
 
+impl Drop for Consumer {
+    #[inline]
+    fn drop(&mut self) {
+        if let Some(DropHook(hook)) = self.on_drop.take() {
+            let r = self.buffer.read.load(Relaxed);
+            hook(self.buffer.write.load(Acquire).wrapping_sub(r));
+        }
+    }
+}
+
 // TODO: impl write_vectored
 #[cfg(feature = "std")]
 impl io::Read for Consumer {
@@ -474,27 +1696,127 @@ impl io::Read for Consumer {
         match self.io_slices(move |srcs, _| dst.write_vectored(srcs)) {
             Ok(n) => Ok(n),
             Err(ConsumerError::Callback(e)) => Err(e),
-            Err(err @ ConsumerError::InvalidCount { .. }) => Err(io::Error::other(err)),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
         }
     }
 }
 
-#[derive(Debug)]
-struct AlignedData {
-    ptr: NonNull<u8>,
-    layout: Layout,
-}
-
-// SAFETY: Send is safe because pointer cannot be accessed directly.
-//         Aliasing rules are followed by requiring a mut ref xor non-mut refs
-//         to access the pointed to data.
-unsafe impl Send for AlignedData {}
-
-impl AlignedData {
+/// Hands out the contiguous part of the filled space, as
+/// [`Consumer::slice`] does. To copy it all into a writer, use
+/// [`Consumer::write_to`] rather than [`io::copy`].
+#[cfg(feature = "std")]
+impl io::BufRead for Consumer {
     #[inline]
-    fn new(size: usize, align: usize) -> Result<Self, BufferError> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if self.buffer.is_poisoned() {
+            hint::cold_path();
+            return Err(io::Error::other(
+                ConsumerError::<::core::convert::Infallible>::Poisoned,
+            ));
+        }
+        let (_, ranges, _) = self.buffer.filled(true, self.limits);
+        // SAFETY: ranges map the filled region only, which the producer
+        //         does not touch until it is released, and releasing takes
+        //         the `&mut self` the slice borrows.
+        let [buf, _] = unsafe { self.buffer.data.slices(ranges) };
+        Ok(buf)
+    }
+
+    /// Releases `amt` bytes, rounded down to the bytes [`fill_buf`] returned.
+    /// Releases nothing if the ring is poisoned, which [`fill_buf`]
+    /// reports.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the bytes released are not a multiple of the frame size and
+    /// start alignment, as a torn frame would be lost otherwise.
+    ///
+    /// [`fill_buf`]: io::BufRead::fill_buf
+    #[inline]
+    fn consume(&mut self, amt: usize) {
+        if self.buffer.is_poisoned() {
+            hint::cold_path();
+            return;
+        }
+        let (r, _, len) = self.buffer.filled(true, self.limits);
+        let n = amt.min(len);
+        let frame = self.limits.frame;
+        ::core::assert!(
+            n & frame.wrapping_sub(1) == 0,
+            "consumed {n} bytes, not a multiple of the frame size {frame}"
+        );
+        #[cfg(feature = "oplog")]
+        if let Some(log) = &self.oplog {
+            log.push(oplog::Side::Consumer, len, n);
+        }
+        self.release(r, n);
+    }
+}
+
+#[derive(Debug)]
+struct AlignedData {
+    ptr: NonNull<u8>,
+    len: usize,
+    align: usize,
+    backing: Backing,
+    /// Accounted for `len` bytes, given back on drop.
+    charge: Option<accounting::Charge>,
+}
+
+#[derive(Debug)]
+enum Backing {
+    Heap(Layout),
+    /// Mirrored mappings are `2 * len` long.
+    #[cfg(feature = "mmap")]
+    Mapped(mmap::Mapping),
+    /// A slot of an [`Arena`], which owns the memory.
+    Carved(#[expect(dead_code, reason = "only kept for its drop")] Arc<arena::Region>),
+    /// Heap memory handed out by a [`Pool`], returned to it on drop.
+    #[cfg(feature = "std")]
+    Pooled(Layout, ::alloc::sync::Weak<pool::Idle>),
+}
+
+// SAFETY: Send and Sync are safe because the pointer cannot be accessed
+//         directly: the pointed to data is only reached through the unsafe
+//         `slices`, `slices_mut` and `discard`, whose callers must rule out
+//         overlapping accesses, from whichever threads they call. The other
+//         fields are immutable once allocated.
+unsafe impl Send for AlignedData {}
+// SAFETY: see above.
+unsafe impl Sync for AlignedData {}
+
+impl AlignedData {
+    /// Alignments beyond the page size are served by mmap when the `mmap`
+    /// feature is enabled, as the global allocator may not honor them.
+    ///
+    /// The size is charged to `account`, or the global hook if there is
+    /// none, before allocating.
+    #[inline]
+    fn new(
+        size: usize,
+        align: usize,
+        account: Option<&accounting::Account>,
+    ) -> Result<Self, BufferError> {
         debug_assert!(size != 0, "size cannot be zero");
 
+        let charge = accounting::charge(account, size)?;
+
+        #[cfg(feature = "mmap")]
+        if align > mmap::granularity() {
+            let mapping = mmap::Mapping::aligned(size, align)?;
+            return Ok(AlignedData {
+                ptr: mapping.ptr(),
+                len: size,
+                align,
+                backing: Backing::Mapped(mapping),
+                charge,
+            });
+        }
+
         let Ok(layout) = Layout::from_size_align(size, align) else {
             return Err(BufferError::BadSize(size));
         };
@@ -510,30 +1832,121 @@ impl AlignedData {
             "aligned alloc failed"
         );
 
-        Ok(AlignedData { ptr, layout })
+        Ok(AlignedData {
+            ptr,
+            len: size,
+            align,
+            backing: Backing::Heap(layout),
+            charge,
+        })
+    }
+
+    #[cfg(feature = "mmap")]
+    #[inline]
+    fn mirrored(size: usize, account: Option<&accounting::Account>) -> Result<Self, BufferError> {
+        debug_assert!(size != 0, "size cannot be zero");
+
+        let charge = accounting::charge(account, size)?;
+        let mapping = mmap::Mapping::mirrored(size)?;
+
+        Ok(AlignedData {
+            ptr: mapping.ptr(),
+            len: size,
+            align: mmap::granularity(),
+            backing: Backing::Mapped(mapping),
+            charge,
+        })
+    }
+
+    /// Returns the account charged for the memory, if any.
+    #[inline]
+    fn account(&self) -> Option<&accounting::Account> {
+        self.charge.as_ref().map(accounting::Charge::account)
     }
 
     #[must_use]
     #[inline]
     fn len(&self) -> usize {
-        self.layout.size()
+        self.len
+    }
+
+    #[must_use]
+    #[inline]
+    fn align(&self) -> usize {
+        self.align
+    }
+
+    #[must_use]
+    #[inline]
+    fn is_mirrored(&self) -> bool {
+        match self.backing {
+            Backing::Heap(_) | Backing::Carved(_) => false,
+            #[cfg(feature = "std")]
+            Backing::Pooled(..) => false,
+            #[cfg(feature = "mmap")]
+            Backing::Mapped(ref mapping) => mapping.is_mirrored(),
+        }
+    }
+
+    /// The length of the addressable region, twice the length of the data
+    /// when mirrored.
+    #[must_use]
+    #[inline]
+    fn mapped_len(&self) -> usize {
+        if self.is_mirrored() {
+            self.len.wrapping_mul(2)
+        } else {
+            self.len
+        }
+    }
+
+    /// Releases the whole pages within `range`, which must not exceed `len`.
+    /// Returns the number of bytes released.
+    ///
+    /// # Safety
+    /// The passed range must not overlap with any ranges passed to `slices`
+    /// or `slices_mut` at the same time.
+    #[cfg(feature = "mmap")]
+    #[inline]
+    unsafe fn discard(&self, range: Range<usize>) -> usize {
+        debug_assert!(range.end <= self.len());
+
+        let page = mmap::granularity();
+        let base = self.ptr.as_ptr().addr();
+        let start = base.wrapping_add(range.start).next_multiple_of(page);
+        let end = base.wrapping_add(range.end) & !(page - 1);
+        if end <= start {
+            return 0;
+        }
+
+        let len = end - start;
+        // SAFETY: start and end are rounded inwards to page boundaries within
+        //         the range, so only whole pages of the allocated data are
+        //         released. Mirrored views share their pages, discarding the
+        //         first view covers both.
+        unsafe {
+            let ptr = self.ptr.add(start - base);
+            mmap::discard(ptr, len, self.is_mirrored());
+        }
+        len
     }
 
     /// # Safety
     /// * The passed ranges must both define non-overlapping regions of the
-    ///   allocated data.
+    ///   allocated data. For mirrored data, ranges overlap if they do modulo
+    ///   `len`.
     /// * The passed ranges must not overlap with any other ranges passed to
     ///   `slices` or `slices_mut` at the same time.
     #[must_use]
     #[inline]
     unsafe fn slices(&self, ranges: [Range<usize>; 2]) -> [&[u8]; 2] {
-        // SAFETY: the pointer is acquired through alloc_zeroed and is checked
-        //         to be non-null. Provided the safety rules of the method are
-        //         followed then the added pointer offset and the used length
-        //         map a valid region of the allocated data.
+        // SAFETY: the pointer is acquired through alloc_zeroed or mmap and is
+        //         checked to be non-null. Provided the safety rules of the
+        //         method are followed then the added pointer offset and the
+        //         used length map a valid region of the allocated data.
         unsafe {
             ranges.map(|s| {
-                debug_assert!(s.end <= self.len());
+                debug_assert!(s.end <= self.mapped_len());
                 &*ptr::slice_from_raw_parts(self.ptr.as_ptr().add(s.start), range_len(&s))
             })
         }
@@ -541,7 +1954,8 @@ impl AlignedData {
 
     /// # Safety
     /// * The passed ranges must both define non-overlapping regions of the
-    ///   allocated data.
+    ///   allocated data. For mirrored data, ranges overlap if they do modulo
+    ///   `len`.
     /// * The passed ranges must not overlap with any other ranges passed to
     ///   `slices` or `slices_mut` at the same time.
     #[must_use]
@@ -551,13 +1965,13 @@ impl AlignedData {
         reason = "aliasing is ruled out by the # Safety contract"
     )]
     unsafe fn slices_mut(&self, ranges: [Range<usize>; 2]) -> [&mut [u8]; 2] {
-        // SAFETY: the pointer is acquired through alloc_zeroed and is checked
-        //         to be non-null. Provided the safety rules of the method are
-        //         followed then the added pointer offset and the used length
-        //         map a valid region of the allocated data.
+        // SAFETY: the pointer is acquired through alloc_zeroed or mmap and is
+        //         checked to be non-null. Provided the safety rules of the
+        //         method are followed then the added pointer offset and the
+        //         used length map a valid region of the allocated data.
         unsafe {
             ranges.map(|s| {
-                debug_assert!(s.end <= self.len());
+                debug_assert!(s.end <= self.mapped_len());
                 &mut *ptr::slice_from_raw_parts_mut(self.ptr.as_ptr().add(s.start), range_len(&s))
             })
         }
@@ -567,10 +1981,29 @@ impl AlignedData {
 impl Drop for AlignedData {
     #[inline]
     fn drop(&mut self) {
-        // SAFETY: dealloc is called with the non-null pointer returned by
-        //         alloc and the same layout.
-        unsafe {
-            dealloc(self.ptr.as_ptr(), self.layout);
+        match self.backing {
+            // SAFETY: dealloc is called with the non-null pointer returned by
+            //         alloc and the same layout.
+            Backing::Heap(layout) => unsafe {
+                dealloc(self.ptr.as_ptr(), layout);
+            },
+            // Mappings unmap themselves, arenas free themselves once all of
+            // their slots are dropped.
+            #[cfg(feature = "mmap")]
+            Backing::Mapped(_) => {}
+            Backing::Carved(_) => {}
+            #[cfg(feature = "std")]
+            Backing::Pooled(layout, ref idle) => pool::recycle(
+                AlignedData {
+                    ptr: self.ptr,
+                    len: self.len,
+                    align: self.align,
+                    backing: Backing::Heap(layout),
+                    // Idle memory stays allocated, and accounted for.
+                    charge: self.charge.take(),
+                },
+                idle,
+            ),
         }
     }
 }
@@ -595,6 +2028,22 @@ mod tests {
     assert_not_impl_any!(Producer: Sync);
     assert_impl_all!(Consumer: Send);
     assert_not_impl_any!(Consumer: Sync);
+    #[cfg(feature = "std")]
+    assert_impl_all!(SharedProducer: Send, Sync, Clone);
+    #[cfg(feature = "std")]
+    assert_impl_all!(SharedConsumer: Send, Sync, Clone);
+    assert_impl_all!(typed::Producer<f32>: Send);
+    assert_not_impl_any!(typed::Producer<f32>: Sync);
+    assert_impl_all!(typed::Consumer<f32>: Send);
+    assert_not_impl_any!(typed::Consumer<f32>: Sync);
+    assert_impl_all!(lossy::Producer: Send);
+    assert_not_impl_any!(lossy::Producer: Sync);
+    assert_impl_all!(lossy::Consumer: Send);
+    assert_not_impl_any!(lossy::Consumer: Sync);
+    assert_impl_all!(broadcast::Producer: Send);
+    assert_not_impl_any!(broadcast::Producer: Sync);
+    assert_impl_all!(broadcast::Consumer: Send);
+    assert_not_impl_any!(broadcast::Consumer: Sync);
 
     #[test]
     fn test_filled_ranges() {
@@ -721,25 +2170,41 @@ mod tests {
         assert_eq!(len, 7);
     }
 
+    #[test]
+    fn test_restrict_ranges() {
+        // Untouched.
+        let (ranges, len) = restrict_ranges([10..16, 0..4], false, 1);
+        assert_eq!(ranges, [10..16, 0..4]);
+        assert_eq!(len, 10);
+
+        let (ranges, len) = restrict_ranges([10..16, 0..4], true, 1);
+        assert_eq!(ranges, [10..16, 0..0]);
+        assert_eq!(len, 6);
+
+        // The first range ends at the seam, so the second one follows.
+        let (ranges, len) = restrict_ranges([8..16, 0..7], false, 4);
+        assert_eq!(ranges, [8..16, 0..4]);
+        assert_eq!(len, 12);
+
+        // A truncated first range drops the second.
+        let (ranges, len) = restrict_ranges([2..16, 0..7], false, 4);
+        assert_eq!(ranges, [2..14, 0..0]);
+        assert_eq!(len, 12);
+
+        let (ranges, len) = restrict_ranges([4..7, 0..0], false, 4);
+        assert_eq!(ranges, [4..4, 0..0]);
+        assert_eq!(len, 0);
+    }
+
     const RING: usize = 16;
 
     /// Builds a pair over a 16-byte ring whose counters both start at
     /// `start`, to exercise arbitrary counter positions.
     fn seeded_pair(start: usize) -> (Producer, Consumer) {
-        let buffer = Arc::new(Buffer {
-            read: CachePadded::new(AtomicUsize::new(start)),
-            write: CachePadded::new(AtomicUsize::new(start)),
-            mask: RING - 1,
-            data: AlignedData::new(RING, RING).unwrap(),
-        });
-        let producer = Producer {
-            buffer: Arc::clone(&buffer),
-            _notsync: PhantomData,
-        };
-        let consumer = Consumer {
-            buffer,
-            _notsync: PhantomData,
-        };
+        let data = AlignedData::new(RING, RING, None).unwrap();
+        let buffer = Arc::new(Buffer::new(data, 1, start, start));
+        let producer = Producer::new(Arc::clone(&buffer));
+        let consumer = Consumer::new(buffer);
         (producer, consumer)
     }
 
@@ -864,6 +2329,3779 @@ mod tests {
         assert_eq!(producer.position(), 0);
     }
 
+    #[test]
+    fn coalesce_threshold_defers_publishing() {
+        let (mut producer, consumer) = new(16, 16).unwrap();
+        producer.set_coalesce_threshold(8);
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(5)).unwrap();
+        assert_eq!(producer.pending(), 5);
+        assert!(consumer.is_empty());
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(3)).unwrap();
+        assert_eq!(producer.pending(), 0);
+        assert_eq!(consumer.buffer.write.load(Relaxed), 8);
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(2)).unwrap();
+        assert_eq!(producer.pending(), 2);
+        producer.publish();
+        assert_eq!(producer.pending(), 0);
+        assert_eq!(consumer.buffer.write.load(Relaxed), 10);
+    }
+
+    #[test]
+    fn coalesce_threshold_publishes_when_full() {
+        let (mut producer, consumer) = new(16, 16).unwrap();
+        producer.set_coalesce_threshold(64);
+
+        producer.slices(|_bufs, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(producer.pending(), 0);
+        assert_eq!(consumer.buffer.write.load(Relaxed), 16);
+    }
+
+    #[test]
+    fn dropping_producer_publishes_pending() {
+        let (mut producer, consumer) = new(16, 16).unwrap();
+        producer.set_coalesce_threshold(8);
+        producer.slices(|_bufs, _len| Ok::<_, ()>(3)).unwrap();
+        assert!(consumer.is_empty());
+        ::core::mem::drop(producer);
+        assert!(!consumer.is_empty());
+    }
+
+    #[test]
+    fn slice_offers_contiguous_part_only() {
+        let (mut producer, mut consumer) = seeded_pair(12);
+
+        let n = producer
+            .slice(|buf| {
+                assert_eq!(buf.len(), 4);
+                Ok::<_, ()>(buf.len())
+            })
+            .unwrap();
+        assert_eq!(n, 4);
+
+        let res = producer.slice(|buf| Ok::<_, ()>(buf.len() + 1));
+        assert!(matches!(
+            res,
+            Err(ProducerError::InvalidCount { n: 13, len: 12 })
+        ));
+
+        let n = consumer.slice(|buf| Ok::<_, ()>(buf.len())).unwrap();
+        assert_eq!(n, 4);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mirrored_hands_out_single_slices() {
+        let size = mmap::granularity();
+        let (mut producer, mut consumer) = new_mirrored(size).unwrap();
+
+        // Move the counters close to the end so both regions wrap.
+        producer.slices(|_bufs, len| Ok::<_, ()>(len - 3)).unwrap();
+        consumer.slices(|_bufs, len| Ok::<_, ()>(len)).unwrap();
+
+        producer
+            .slices(|bufs, len| {
+                assert_eq!(bufs[0].len(), size);
+                assert!(bufs[1].is_empty());
+                bufs[0][..8].copy_from_slice(&[0, 1, 2, 3, 4, 5, 6, 7]);
+                assert_eq!(len, size);
+                Ok::<_, ()>(8)
+            })
+            .unwrap();
+        consumer
+            .slice(|buf| {
+                assert_eq!(buf, &[0, 1, 2, 3, 4, 5, 6, 7]);
+                Ok::<_, ()>(buf.len())
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mirrored_roundtrip_pattern_across_wraps() {
+        let size = mmap::granularity();
+        let total = size * 3 + 17;
+
+        let (mut producer, mut consumer) = new_mirrored(size).unwrap();
+        pump_pattern(&mut producer, &mut consumer, total);
+        assert!(consumer.is_empty());
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn large_alignment_falls_back_to_mmap() {
+        const ALIGN: usize = 2 << 20;
+
+        let (mut producer, mut consumer) = new(64, ALIGN).unwrap();
+        assert!(matches!(producer.buffer.data.backing, Backing::Mapped(_)));
+        assert!(
+            producer
+                .buffer
+                .data
+                .ptr
+                .as_ptr()
+                .addr()
+                .is_multiple_of(ALIGN)
+        );
+        pump_pattern(&mut producer, &mut consumer, 200);
+    }
+
+    #[cfg(feature = "mmap")]
+    fn reclaim_keeps_filled_bytes(producer: &mut Producer, consumer: &mut Consumer) {
+        let size = producer.buffer.data.len();
+        let page = mmap::granularity();
+
+        producer.slices(|_bufs, len| Ok::<_, ()>(len)).unwrap();
+        consumer.slices(|_bufs, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(producer.reclaim(), size);
+
+        // Half full: the filled pages must stay intact.
+        producer
+            .slices(|bufs, _len| {
+                bufs[0][..page].fill(0x5a);
+                Ok::<_, ()>(page)
+            })
+            .unwrap();
+        assert_eq!(producer.reclaim(), size - page);
+        consumer
+            .slices(|bufs, len| {
+                assert_eq!(len, page);
+                for &b in bufs[0] {
+                    assert_eq!(b, 0x5a);
+                }
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn reclaim_releases_empty_pages() {
+        let size = mmap::granularity() * 4;
+        let (mut producer, mut consumer) = new(size, mmap::granularity()).unwrap();
+        reclaim_keeps_filled_bytes(&mut producer, &mut consumer);
+
+        let (mut producer, mut consumer) = new_mirrored(size).unwrap();
+        reclaim_keeps_filled_bytes(&mut producer, &mut consumer);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn reclaim_skips_partial_pages() {
+        let (mut producer, _consumer) = seeded_pair(3);
+        assert_eq!(producer.reclaim(), 0);
+    }
+
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn mirrored_rejects_bad_size() {
+        let size = mmap::granularity();
+        assert!(matches!(
+            new_mirrored(size / 2),
+            Err(BufferError::BadSize(_))
+        ));
+        assert!(matches!(
+            new_mirrored(size + 1),
+            Err(BufferError::BadSize(_))
+        ));
+    }
+
+    #[test]
+    fn granularity_truncates_slices() {
+        let (mut producer, mut consumer) = seeded_pair(8);
+        producer.set_granularity(4).unwrap();
+        consumer.set_granularity(4).unwrap();
+
+        producer
+            .slices(|bufs, len| {
+                assert_eq!(bufs[0].len(), 8);
+                assert_eq!(bufs[1].len(), 8);
+                assert_eq!(len, 16);
+                Ok::<_, ()>(10)
+            })
+            .unwrap();
+        consumer
+            .slices(|bufs, len| {
+                assert_eq!(bufs[0].len(), 8);
+                assert!(bufs[1].is_empty());
+                assert_eq!(len, 8);
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+
+        assert!(matches!(
+            producer.set_granularity(3),
+            Err(BufferError::BadGranularity(3))
+        ));
+        assert!(matches!(
+            consumer.set_granularity(32),
+            Err(BufferError::BadGranularity(32))
+        ));
+    }
+
+    #[test]
+    fn frame_size_keeps_frames_whole() {
+        let (mut producer, mut consumer) = Builder::new(16).frame_size(4).build().unwrap();
+        // Granularities smaller than the frame size are raised to it.
+        producer.set_granularity(2).unwrap();
+
+        let res = producer.slices(|_bufs, _len| Ok::<_, ()>(6));
+        assert!(matches!(
+            res,
+            Err(ProducerError::TornFrame { n: 6, frame: 4 })
+        ));
+        assert_eq!(producer.position(), 0);
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(8)).unwrap();
+        consumer.slices(|_bufs, _len| Ok::<_, ()>(4)).unwrap();
+        producer
+            .slices(|bufs, len| {
+                // 8..16 and 0..4; the half frame before the seam is skipped.
+                assert_eq!(bufs[0].len(), 8);
+                assert_eq!(bufs[1].len(), 4);
+                assert_eq!(len, 12);
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+
+        let res = consumer.slices(|_bufs, _len| Ok::<_, ()>(3));
+        assert!(matches!(
+            res,
+            Err(ConsumerError::TornFrame { n: 3, frame: 4 })
+        ));
+        assert_eq!(consumer.position(), 4);
+    }
+
+    #[test]
+    fn start_alignment_keeps_starts_aligned() {
+        let (mut producer, mut consumer) = Builder::new(64).align(8).build().unwrap();
+        consumer.set_start_alignment(8).unwrap();
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(3)).unwrap();
+        let n = consumer.slices(|_bufs, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(n, 0);
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(14)).unwrap();
+        let res = consumer.slices(|_bufs, _len| Ok::<_, ()>(4));
+        assert!(matches!(
+            res,
+            Err(ConsumerError::TornFrame { n: 4, frame: 8 })
+        ));
+        consumer
+            .slices(|bufs, len| {
+                assert_eq!(len, 16);
+                assert!(bufs[0].as_ptr().addr().is_multiple_of(8));
+                Ok::<_, ()>(8)
+            })
+            .unwrap();
+        consumer
+            .slice(|buf| {
+                assert!(buf.as_ptr().addr().is_multiple_of(8));
+                Ok::<_, ()>(8)
+            })
+            .unwrap();
+
+        // Larger than the buffer's alignment.
+        assert!(matches!(
+            consumer.set_start_alignment(16),
+            Err(BufferError::BadAlignment(16))
+        ));
+        // The producer is at 17.
+        assert!(matches!(
+            producer.set_start_alignment(4),
+            Err(BufferError::BadAlignment(4))
+        ));
+        consumer.set_start_alignment(1).unwrap();
+    }
+
+    #[test]
+    fn builder_rejects_bad_frame_size() {
+        assert!(matches!(
+            Builder::new(16).frame_size(3).build(),
+            Err(BufferError::BadGranularity(3))
+        ));
+        assert!(matches!(
+            Builder::new(16).frame_size(32).build(),
+            Err(BufferError::BadGranularity(32))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn io_slices_leave_out_empty_second_slice() {
+        let (mut producer, mut consumer) = seeded_pair(0);
+
+        let expect = |count, n| {
+            move |bufs: &mut [io::IoSliceMut<'_>], _len| {
+                assert_eq!(bufs.len(), count);
+                Ok(n)
+            }
+        };
+        producer.io_slices(expect(1, 12)).unwrap();
+        consumer
+            .io_slices(|bufs, len| {
+                assert_eq!(bufs.len(), 1);
+                Ok(len)
+            })
+            .unwrap();
+
+        producer.io_slices(expect(2, 8)).unwrap();
+        consumer
+            .io_slices(|bufs, len| {
+                assert_eq!(bufs.len(), 2);
+                Ok(len)
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn resize_moves_buffered_bytes() {
+        let (mut producer, mut consumer) = seeded_pair(usize::MAX - 5);
+
+        // 10 bytes across the seam, the last 3 of them coalesced.
+        let fill = |producer: &mut Producer, from: u8, n: usize| {
+            producer
+                .slices(|bufs, _len| {
+                    let mut v = from;
+                    for buf in bufs.iter_mut() {
+                        for b in buf.iter_mut() {
+                            *b = v;
+                            v += 1;
+                        }
+                    }
+                    Ok::<_, ()>(n)
+                })
+                .unwrap();
+        };
+        fill(&mut producer, 0, 7);
+        producer.set_coalesce_threshold(8);
+        fill(&mut producer, 7, 3);
+        assert_eq!(producer.pending(), 3);
+
+        resize(&mut producer, &mut consumer, 64).unwrap();
+        assert_eq!(producer.buffer.data.len(), 64);
+        assert_eq!(producer.pending(), 3);
+        producer.publish();
+
+        consumer
+            .slices(|bufs, len| {
+                assert_eq!(len, 10);
+                assert_eq!(bufs[0], &[0, 1, 2, 3, 4, 5]);
+                assert_eq!(bufs[1], &[6, 7, 8, 9]);
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+        assert_eq!(consumer.position(), (usize::MAX - 5).wrapping_add(10));
+
+        producer.set_coalesce_threshold(0);
+        pump_pattern(&mut producer, &mut consumer, 300);
+    }
+
+    #[test]
+    fn resize_rejects_bad_parameters() {
+        let (mut producer, mut consumer) = new(16, 16).unwrap();
+        let (_, mut other) = new(16, 16).unwrap();
+        assert!(matches!(
+            resize(&mut producer, &mut other, 32),
+            Err(BufferError::Mismatch)
+        ));
+
+        producer.slices(|_bufs, _len| Ok::<_, ()>(9)).unwrap();
+        assert!(matches!(
+            resize(&mut producer, &mut consumer, 8),
+            Err(BufferError::BadSize(8))
+        ));
+        assert!(matches!(
+            resize(&mut producer, &mut consumer, 24),
+            Err(BufferError::BadSize(24))
+        ));
+        assert_eq!(producer.buffer.data.len(), 16);
+        resize(&mut producer, &mut consumer, 16).unwrap();
+    }
+
+    #[test]
+    fn autotune_follows_occupancy() {
+        let (mut producer, mut consumer) = new(64, 8).unwrap();
+        assert_eq!(producer.suggested_capacity(), 64);
+
+        // Never more than a quarter full.
+        producer.slices(|_, _| Ok::<_, ()>(10)).unwrap();
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(producer.suggested_capacity(), 32);
+        assert!(autotune(&mut producer, &mut consumer).unwrap());
+        assert_eq!(producer.buffer.data.len(), 32);
+        assert!(!autotune(&mut producer, &mut consumer).unwrap());
+
+        // Full, with a commit finding no room.
+        producer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        producer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(producer.suggested_capacity(), 64);
+        assert!(autotune(&mut producer, &mut consumer).unwrap());
+        assert_eq!(producer.buffer.data.len(), 64);
+        assert_eq!(producer.suggested_capacity(), 64);
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        pump_pattern(&mut producer, &mut consumer, 300);
+
+        // Not below the producer's granularity, though the consumer's is
+        // smaller.
+        let (mut producer, mut consumer) = new(64, 8).unwrap();
+        producer.set_granularity(64).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(8)).unwrap();
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert!(!autotune(&mut producer, &mut consumer).unwrap());
+        assert_eq!(producer.buffer.data.len(), 64);
+    }
+
+    #[test]
+    fn broadcast_waits_for_slowest_consumer() {
+        let (mut producer, mut consumers) = broadcast::new(16, 8, 2).unwrap();
+        let fill = |producer: &mut broadcast::Producer, from: u8| {
+            producer
+                .slices(|bufs, len| {
+                    let mut v = from;
+                    for buf in bufs.iter_mut() {
+                        for b in buf.iter_mut() {
+                            *b = v;
+                            v += 1;
+                        }
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap()
+        };
+        assert_eq!(fill(&mut producer, 0), 16);
+
+        // Both consumers see every byte.
+        let drain = |consumer: &mut broadcast::Consumer, n: usize, from: usize| {
+            consumer
+                .slices(|bufs, len| {
+                    let mut i = from;
+                    for buf in bufs {
+                        for &b in *buf {
+                            assert_eq!(usize::from(b), i);
+                            i += 1;
+                        }
+                    }
+                    Ok::<_, ()>(len.min(n))
+                })
+                .unwrap()
+        };
+        assert_eq!(drain(&mut consumers[0], 16, 0), 16);
+        assert_eq!(drain(&mut consumers[1], 4, 0), 4);
+        assert_eq!(fill(&mut producer, 16), 4);
+        assert_eq!(fill(&mut producer, 0), 0);
+
+        // A dropped consumer no longer holds the producer back.
+        let slow = consumers.pop().unwrap();
+        ::core::mem::drop(slow);
+        assert_eq!(fill(&mut producer, 20), 12);
+        assert_eq!(drain(&mut consumers[0], 16, 16), 16);
+        assert!(consumers[0].is_empty());
+    }
+
+    #[test]
+    fn broadcast_invalid_count_errors() {
+        let (mut producer, mut consumers) = broadcast::new(16, 8, 2).unwrap();
+        assert!(matches!(
+            producer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ProducerError::InvalidCount { n: 17, len: 16 })
+        ));
+        assert_eq!(producer.slices(|_, _| Ok::<_, ()>(4)).unwrap(), 4);
+        assert!(matches!(
+            consumers[0].slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ConsumerError::InvalidCount { n: 5, len: 4 })
+        ));
+        assert_eq!(consumers[0].slices(|_, len| Ok::<_, ()>(len)).unwrap(), 4);
+        assert_eq!(consumers[1].slices(|_, len| Ok::<_, ()>(len)).unwrap(), 4);
+    }
+
+    #[test]
+    fn tee_copies_into_both_rings() {
+        let (mut producer, consumer) = seeded_pair(10);
+        let (a, mut out_a) = new(16, 8).unwrap();
+        let (mut b, mut out_b) = new(8, 8).unwrap();
+        b.slices(|_, _| Ok::<_, ()>(2)).unwrap();
+
+        producer
+            .slices(|bufs, len| {
+                let mut v = 0;
+                for buf in bufs.iter_mut() {
+                    for x in buf.iter_mut() {
+                        *x = v;
+                        v += 1;
+                    }
+                }
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+
+        let mut tee = Tee::new(consumer, a, b);
+        // b only has room for 6 bytes.
+        assert_eq!(tee.pump().unwrap(), 6);
+        assert_eq!(tee.pump().unwrap(), 0);
+        out_b.slices(|_, _| Ok::<_, ()>(2)).unwrap();
+        let check = |consumer: &mut Consumer, from: u8, n: usize| {
+            consumer
+                .slices(|bufs, len| {
+                    assert_eq!(len, n);
+                    let mut v = from;
+                    for buf in bufs {
+                        for &x in *buf {
+                            assert_eq!(x, v);
+                            v += 1;
+                        }
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap();
+        };
+        check(&mut out_b, 0, 6);
+        assert_eq!(tee.pump().unwrap(), 8);
+        check(&mut out_a, 0, 14);
+        check(&mut out_b, 6, 8);
+
+        let (source, _, _) = tee.into_inner();
+        assert_eq!(source.position(), 10 + 14);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn mpsc_keeps_commits_whole() {
+        use ::core::iter::Iterator as _;
+
+        const RECORDS: usize = 1000;
+
+        let (first, mut consumer) = mpsc::new(64, 8).unwrap();
+        let mut producers = ::alloc::vec![first];
+        for _ in 1..4 {
+            let next = producers[0].try_clone().unwrap();
+            producers.push(next);
+        }
+        assert_eq!(consumer.producers(), 4);
+
+        // Every producer writes 4-byte records: its id and a sequence number.
+        let mut threads = ::alloc::vec::Vec::new();
+        for (id, mut producer) in (0..).zip(producers) {
+            threads.push(::std::thread::spawn(move || {
+                let mut seq = 0;
+                while seq < RECORDS {
+                    let n = producer
+                        .slice(|buf| {
+                            if buf.len() < 4 {
+                                return Ok::<_, ()>(0);
+                            }
+                            buf[0] = id;
+                            buf[1..4].copy_from_slice(&seq.to_le_bytes()[..3]);
+                            seq += 1;
+                            Ok(4)
+                        })
+                        .unwrap();
+                    if n == 0 {
+                        ::std::thread::yield_now();
+                    }
+                }
+            }));
+        }
+
+        let mut seqs = [0; 4];
+        while consumer.producers() != 0 {
+            let n = consumer
+                .slice(|buf| {
+                    let n = buf.len() & !3;
+                    let mut i = 0;
+                    while i < n {
+                        let id = usize::from(buf[i]);
+                        let mut seq = [0; 8];
+                        seq[..3].copy_from_slice(&buf[i + 1..i + 4]);
+                        assert_eq!(usize::from_le_bytes(seq), seqs[id]);
+                        seqs[id] += 1;
+                        i += 4;
+                    }
+                    Ok::<_, ()>(n)
+                })
+                .unwrap();
+            if n == 0 {
+                ::std::thread::yield_now();
+            }
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(seqs, [RECORDS; 4]);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn shared_halves_from_thread_pool() {
+        use ::std::io::{Read as _, Write as _};
+
+        let (producer, consumer) = new(64, 8).unwrap();
+        let producer = SharedProducer::from(producer);
+        let consumer = SharedConsumer::from(consumer);
+
+        let mut threads = ::alloc::vec::Vec::new();
+        for _ in 0..4 {
+            let producer = producer.clone();
+            threads.push(::std::thread::spawn(move || {
+                for _ in 0..8 {
+                    // Each write is whole as the buffer never fills up.
+                    (&producer).write_all(&[1, 2]).unwrap();
+                }
+            }));
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let mut dst = [0; 64];
+        let n = (&consumer).read(&mut dst).unwrap();
+        assert_eq!(n, 64);
+        for pair in dst.chunks_exact(2) {
+            assert_eq!(pair, [1, 2]);
+        }
+        assert!(consumer.lock().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn work_hands_back_space_in_order() {
+        let (mut producer, worker) = work::new(16, 8, 4).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(14)).unwrap_err();
+        producer.slices(|_, _| Ok::<_, ()>(12)).unwrap();
+
+        let other = worker.clone();
+        let n = worker
+            .chunk(5, |_, len| {
+                assert_eq!(len, 4);
+                // Committed ahead of the enclosing chunk.
+                let n = other.chunk(8, |_, _| Ok::<_, ()>(())).unwrap();
+                assert_eq!(n, 8);
+                assert_eq!(producer.free_len(), 4);
+                assert!(other.is_empty());
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(n, 4);
+        assert_eq!(producer.free_len(), 16);
+        assert_eq!(worker.chunk(8, |_, _| Ok::<_, ()>(())).unwrap(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn work_consumes_every_frame_once() {
+        use ::core::sync::atomic::AtomicU32;
+
+        const FRAMES: u32 = 5000;
+
+        let (mut producer, worker) = work::new(64, 8, 4).unwrap();
+        let seen = Arc::new(::std::sync::Mutex::new(::alloc::vec![0_u8; 5000]));
+        let processed = Arc::new(AtomicU32::new(0));
+
+        let mut threads = ::alloc::vec::Vec::new();
+        for _ in 0..3 {
+            let worker = worker.clone();
+            let seen = Arc::clone(&seen);
+            let processed = Arc::clone(&processed);
+            threads.push(::std::thread::spawn(move || {
+                while processed.load(Relaxed) < FRAMES {
+                    let n = worker
+                        .chunk(12, |bufs, _| {
+                            let mut frame = [0; 4];
+                            let mut i = 0;
+                            for buf in bufs {
+                                for &b in *buf {
+                                    frame[i] = b;
+                                    i = (i + 1) % 4;
+                                    if i == 0 {
+                                        let frame = u32::from_le_bytes(frame);
+                                        let frame = usize::try_from(frame).unwrap();
+                                        seen.lock().unwrap()[frame] += 1;
+                                        processed.fetch_add(1, Relaxed);
+                                    }
+                                }
+                            }
+                            Ok::<_, ()>(())
+                        })
+                        .unwrap();
+                    if n == 0 {
+                        ::std::thread::yield_now();
+                    }
+                }
+            }));
+        }
+
+        let mut next = 0;
+        while next < FRAMES {
+            let n = producer
+                .slice(|buf| {
+                    let mut n = 0;
+                    while n + 4 <= buf.len() && next < FRAMES {
+                        buf[n..n + 4].copy_from_slice(&next.to_le_bytes());
+                        next += 1;
+                        n += 4;
+                    }
+                    Ok::<_, ()>(n)
+                })
+                .unwrap();
+            if n == 0 {
+                ::std::thread::yield_now();
+            }
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        for &count in seen.lock().unwrap().iter() {
+            assert_eq!(count, 1);
+        }
+        assert!(worker.is_empty());
+    }
+
+    #[test]
+    fn typed_roundtrip_across_wraps() {
+        #[derive(Debug, Clone, Copy, Default)]
+        struct Sample {
+            t: usize,
+            v: f32,
+            flags: u8,
+        }
+
+        let (mut producer, mut consumer) = typed::new::<Sample>(8).unwrap();
+        let mut written = 0;
+        let mut read = 0;
+        for round in 0..10 {
+            written += producer
+                .slices(|bufs, len| {
+                    let n = len.min(5);
+                    let mut i = 0;
+                    for buf in bufs.iter_mut() {
+                        for s in buf.iter_mut() {
+                            if i == n {
+                                return Ok::<_, ()>(n);
+                            }
+                            let t = written + i;
+                            *s = Sample {
+                                t,
+                                v: 0.5,
+                                flags: u8::try_from(round).unwrap(),
+                            };
+                            i += 1;
+                        }
+                    }
+                    Ok(n)
+                })
+                .unwrap();
+            read += consumer
+                .slices(|bufs, len| {
+                    let mut t = read;
+                    for buf in bufs {
+                        for s in *buf {
+                            assert_eq!(s.t, t);
+                            assert_eq!(s.v.to_bits(), 0.5_f32.to_bits());
+                            assert!(s.flags <= 10);
+                            t += 1;
+                        }
+                    }
+                    Ok::<_, ()>(len.min(3))
+                })
+                .unwrap();
+        }
+        assert!(written > 8 * 2);
+        read += consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(read, written);
+
+        assert!(matches!(typed::new::<u32>(6), Err(BufferError::BadSize(6))));
+        assert!(matches!(typed::new::<()>(8), Err(BufferError::BadSize(8))));
+    }
+
+    #[test]
+    fn typed_invalid_count_errors() {
+        let (mut producer, mut consumer) = typed::new::<u32>(8).unwrap();
+        assert!(matches!(
+            producer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ProducerError::InvalidCount { n: 9, len: 8 })
+        ));
+        assert_eq!(producer.slices(|_, _| Ok::<_, ()>(3)).unwrap(), 3);
+        assert!(matches!(
+            consumer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ConsumerError::InvalidCount { n: 4, len: 3 })
+        ));
+        assert_eq!(consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 3);
+    }
+
+    #[test]
+    fn lossy_keeps_most_recent_bytes() {
+        use ::core::iter::Iterator as _;
+
+        let (mut producer, mut consumer) = lossy::new(8, 8).unwrap();
+        let fill = |producer: &mut lossy::Producer, from: u8, n: usize| {
+            producer
+                .slices(|bufs, len| {
+                    assert_eq!(len, 8);
+                    let mut value = from;
+                    let mut left = n;
+                    for buf in bufs.iter_mut() {
+                        for b in buf.iter_mut().take(left) {
+                            *b = value;
+                            value += 1;
+                            left -= 1;
+                        }
+                    }
+                    Ok::<_, ()>(n)
+                })
+                .unwrap()
+        };
+        assert_eq!(fill(&mut producer, 0, 5), 5);
+        assert_eq!(fill(&mut producer, 5, 6), 6);
+        assert_eq!(producer.dropped(), 3);
+        assert_eq!(consumer.dropped(), 3);
+
+        let mut read = ::alloc::vec::Vec::new();
+        consumer
+            .slices(|bufs, len| {
+                for buf in bufs {
+                    read.extend_from_slice(buf);
+                }
+                Ok::<_, ()>(len)
+            })
+            .unwrap();
+        assert_eq!(read, [3, 4, 5, 6, 7, 8, 9, 10]);
+
+        // Shut out while the consumer reads, the producer gets the empty
+        // space only.
+        fill(&mut producer, 11, 2);
+        consumer
+            .slices(|_, len| {
+                assert_eq!(len, 2);
+                let n = producer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+                assert_eq!(n, 6);
+                Ok::<_, ()>(0)
+            })
+            .unwrap();
+        assert_eq!(producer.dropped(), 3);
+    }
+
+    #[test]
+    fn lossy_invalid_count_errors() {
+        let (mut producer, mut consumer) = lossy::new(8, 8).unwrap();
+        assert!(matches!(
+            producer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ProducerError::InvalidCount { n: 9, len: 8 })
+        ));
+        assert_eq!(producer.dropped(), 0);
+        assert_eq!(producer.slices(|_, _| Ok::<_, ()>(5)).unwrap(), 5);
+        assert!(matches!(
+            consumer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ConsumerError::InvalidCount { n: 6, len: 5 })
+        ));
+        assert_eq!(consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 5);
+    }
+
+    #[test]
+    fn replay_rereads_until_acked() {
+        let (mut producer, mut consumer) = replay::new(8, 8).unwrap();
+        let take = |consumer: &mut replay::Consumer, n: usize| {
+            let mut read = ::alloc::vec::Vec::new();
+            consumer
+                .slices(|bufs, len| {
+                    for buf in bufs {
+                        read.extend_from_slice(buf);
+                    }
+                    read.truncate(n);
+                    Ok::<_, ()>(n.min(len))
+                })
+                .unwrap();
+            read
+        };
+
+        producer
+            .slice(|buf| {
+                buf[..6].copy_from_slice(&[1, 2, 3, 4, 5, 6]);
+                Ok::<_, ()>(6)
+            })
+            .unwrap();
+        assert_eq!(take(&mut consumer, 4), [1, 2, 3, 4]);
+        assert_eq!(consumer.unacked(), 4);
+        assert_eq!(producer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 2);
+
+        consumer.rewind();
+        assert_eq!(take(&mut consumer, 3), [1, 2, 3]);
+        consumer.ack(1).unwrap();
+        assert!(matches!(
+            consumer.ack(3),
+            Err(ConsumerError::InvalidCount { n: 3, len: 2 })
+        ));
+        consumer.rewind();
+        #[cfg(not(feature = "scrub"))]
+        let blank = 0;
+        #[cfg(feature = "scrub")]
+        let blank = scrub::PATTERN;
+        assert_eq!(take(&mut consumer, 8), [2, 3, 4, 5, 6, blank, blank]);
+        consumer.ack(7).unwrap();
+        assert_eq!(consumer.unacked(), 0);
+        assert!(consumer.is_empty());
+        assert_eq!(producer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 8);
+    }
+
+    #[test]
+    fn datagram_keeps_packet_boundaries() {
+        let (mut producer, mut consumer) = datagram::new(16, 8, 4).unwrap();
+        let mut buf = [0; 16];
+        assert_eq!(consumer.recv(&mut buf), None);
+
+        assert!(producer.send(b"hello"));
+        assert!(producer.send(b"wide"));
+        assert!(!producer.send(b"too long to fit"));
+        assert!(!producer.send(b""));
+        assert_eq!(consumer.recv(&mut buf), Some(5));
+        assert_eq!(&buf[..5], b"hello");
+
+        // Wraps around the seam, and is still received whole.
+        assert!(producer.send(b"seamless"));
+        let n = consumer
+            .slices(|packet, len| {
+                assert_eq!(len, 4);
+                assert_eq!(packet.concat(), b"wide");
+                Err(())
+            })
+            .unwrap_err();
+        assert!(matches!(n, ConsumerError::Callback(())));
+        assert_eq!(consumer.recv(&mut buf), Some(4));
+        let n = consumer
+            .slices(|packet, len| {
+                assert_eq!(packet.concat(), b"seamless");
+                assert!(!packet[1].is_empty());
+                assert_eq!(len, 8);
+                Ok::<_, ()>(())
+            })
+            .unwrap();
+        assert_eq!(n, 8);
+
+        // Packets are capped, and truncated into short buffers.
+        for _ in 0..4 {
+            assert!(producer.send(b"ab"));
+        }
+        assert!(!producer.send(b"ab"));
+        assert_eq!(consumer.recv(&mut buf[..1]), Some(2));
+        assert!(!consumer.is_empty());
+    }
+
+    #[test]
+    fn mux_routes_frames_into_streams() {
+        let (mut mux, mut demux) = mux::new(64, 8).unwrap();
+        let mut a = demux.open(1, 8).unwrap();
+        let mut b = demux.open(2, 16).unwrap();
+        let drain = |consumer: &mut Consumer| {
+            let mut read = ::alloc::vec::Vec::new();
+            consumer
+                .slices(|bufs, len| {
+                    for buf in bufs {
+                        read.extend_from_slice(buf);
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap();
+            read
+        };
+
+        assert!(mux.send(1, b"abc"));
+        assert!(mux.send(3, b"lost"));
+        assert!(mux.send(2, b""));
+        assert!(!mux.send(2, &[0; 64]));
+        assert_eq!(demux.poll().unwrap(), 3);
+        assert_eq!(demux.dropped(), 1);
+        assert_eq!(drain(&mut a), b"abc");
+        assert!(b.is_empty());
+
+        // Frames wrap the seam, and wait for a full stream ring.
+        assert!(mux.send(1, b"0123456"));
+        assert!(mux.send(2, b"tail"));
+        assert!(mux.send(1, b"xy"));
+        assert_eq!(demux.poll().unwrap(), 2);
+        assert_eq!(drain(&mut b), b"tail");
+        assert_eq!(drain(&mut a), b"0123456");
+        assert_eq!(demux.poll().unwrap(), 1);
+        assert_eq!(drain(&mut a), b"xy");
+
+        demux.close(1);
+        assert!(mux.send(1, b"z"));
+        assert_eq!(demux.poll().unwrap(), 1);
+        assert_eq!(demux.dropped(), 2);
+
+        // Too large for the stream's ring, even empty: dropped rather than
+        // holding back the frames behind it.
+        assert!(mux.send(2, &[7; 17]));
+        assert!(mux.send(2, b"next"));
+        assert_eq!(demux.poll().unwrap(), 2);
+        assert_eq!(demux.dropped(), 3);
+        assert_eq!(drain(&mut b), b"next");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn merger_drains_sources_fairly() {
+        let fill = |producer: &mut Producer, byte: u8, n: usize| {
+            producer
+                .slice(|buf| {
+                    buf[..n].fill(byte);
+                    Ok::<_, ()>(n)
+                })
+                .unwrap();
+        };
+        let (mut a, a_consumer) = new(16, 8).unwrap();
+        let (mut b, b_consumer) = new(16, 8).unwrap();
+        let (mut sink, downstream) = new(64, 8).unwrap();
+
+        let mut merger = Merger::new(Fairness::RoundRobin);
+        merger.push(a_consumer);
+        merger.push(b_consumer);
+        fill(&mut a, 1, 2);
+        fill(&mut b, 2, 6);
+        assert_eq!(merger.pump(&mut sink).unwrap(), 2);
+        assert_eq!(merger.pump(&mut sink).unwrap(), 6);
+        assert_eq!(merger.pump(&mut sink).unwrap(), 0);
+
+        let mut merger = Merger::new(Fairness::LongestFirst);
+        merger.push(downstream);
+        fill(&mut sink, 3, 4);
+        let (c, c_consumer) = new(16, 8).unwrap();
+        merger.push(c_consumer);
+        let mut out = ::alloc::vec::Vec::new();
+        assert_eq!(merger.write_to(&mut out).unwrap(), 12);
+        assert_eq!(out, [1, 1, 2, 2, 2, 2, 2, 2, 3, 3, 3, 3]);
+
+        // Drained sources of dropped producers are let go of.
+        assert_eq!(merger.len(), 2);
+        ::core::mem::drop((sink, c));
+        assert_eq!(merger.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 0);
+        assert!(merger.is_empty());
+        ::core::mem::drop((a, b));
+    }
+
+    #[test]
+    fn distributor_hands_out_frames_in_turn() {
+        use ::core::iter::Iterator as _;
+
+        let (mut producer, mut source) = Builder::new(16).frame_size(2).build().unwrap();
+        let mut distributor = Distributor::new(1).unwrap();
+        let (a, mut a_consumer) = new(8, 8).unwrap();
+        let (b, mut b_consumer) = new(4, 8).unwrap();
+        distributor.push(a);
+        distributor.push(b);
+        assert_eq!(distributor.len(), 2);
+
+        producer
+            .slices(|bufs, _| {
+                for (b, i) in bufs[0][..14].iter_mut().zip(0..) {
+                    *b = i;
+                }
+                Ok::<_, ()>(14)
+            })
+            .unwrap();
+        // The second sink fills up after two frames.
+        assert_eq!(distributor.pump(&mut source).unwrap(), 10);
+        let drain = |consumer: &mut Consumer| {
+            let mut read = ::alloc::vec::Vec::new();
+            consumer
+                .slices(|bufs, len| {
+                    for buf in bufs {
+                        read.extend_from_slice(buf);
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap();
+            read
+        };
+        assert_eq!(drain(&mut a_consumer), [0, 1, 4, 5, 8, 9]);
+        assert_eq!(drain(&mut b_consumer), [2, 3, 6, 7]);
+        assert_eq!(distributor.pump(&mut source).unwrap(), 4);
+        assert_eq!(drain(&mut b_consumer), [10, 11]);
+        assert_eq!(drain(&mut a_consumer), [12, 13]);
+
+        assert!(matches!(
+            Distributor::new(3),
+            Err(BufferError::BadGranularity(3))
+        ));
+    }
+
+    #[test]
+    fn lanes_drain_control_first() {
+        use lanes::Lane;
+
+        let (mut producer, mut consumer) = lanes::new(8, 16, 8).unwrap();
+        let send = |producer: &mut lanes::Producer, lane, bytes: &[u8]| {
+            producer
+                .slices(lane, |bufs, _| {
+                    bufs[0][..bytes.len()].copy_from_slice(bytes);
+                    Ok::<_, ()>(bytes.len())
+                })
+                .unwrap();
+        };
+        let recv = |consumer: &mut lanes::Consumer| {
+            let mut read = ::alloc::vec::Vec::new();
+            let mut lane = Lane::Bulk;
+            consumer
+                .slices(|l, bufs, len| {
+                    lane = l;
+                    for buf in bufs {
+                        read.extend_from_slice(buf);
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap();
+            (lane, read)
+        };
+
+        send(&mut producer, Lane::Bulk, b"data");
+        send(&mut producer, Lane::Control, b"ack");
+        assert_eq!(consumer.next_lane(), Lane::Control);
+        assert_eq!(recv(&mut consumer), (Lane::Control, b"ack".to_vec()));
+        send(&mut producer, Lane::Bulk, b"more");
+        assert_eq!(recv(&mut consumer), (Lane::Bulk, b"datamore".to_vec()));
+        assert!(consumer.is_empty());
+        assert_eq!(producer.lane(Lane::Control).pending(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn router_forwards_frames_by_content() {
+        let (mut producer, mut source) = Builder::new(16).frame_size(4).build().unwrap();
+        let mut router = Router::new(|frame: &[u8]| match frame[0] {
+            b'a' => Some(0),
+            b'b' => Some(1),
+            b'c' => Some(7),
+            _ => None,
+        });
+        let (a, mut a_consumer) = new(4, 8).unwrap();
+        let (b, mut b_consumer) = new(16, 8).unwrap();
+        router.push(a);
+        router.push(b);
+
+        producer
+            .slices(|bufs, _| {
+                bufs[0][..16].copy_from_slice(b"b1..a1..c1..a2..");
+                Ok::<_, ()>(16)
+            })
+            .unwrap();
+        // The second frame for `a` waits for room.
+        assert_eq!(router.route(&mut source).unwrap(), 3);
+        assert_eq!(router.dropped(), 1);
+        let mut frame = [0; 4];
+        assert_eq!(io::Read::read(&mut a_consumer, &mut frame).unwrap(), 4);
+        assert_eq!(&frame, b"a1..");
+        assert_eq!(router.route(&mut source).unwrap(), 1);
+        assert_eq!(io::Read::read(&mut a_consumer, &mut frame).unwrap(), 4);
+        assert_eq!(&frame, b"a2..");
+        assert_eq!(io::Read::read(&mut b_consumer, &mut frame).unwrap(), 4);
+        assert_eq!(&frame, b"b1..");
+        assert_eq!(router.route(&mut source).unwrap(), 0);
+        assert_eq!(router.into_inner().len(), 2);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn router_steps_by_start_alignment() {
+        let (mut producer, mut source) = Builder::new(16).align(8).build().unwrap();
+        source.set_start_alignment(4).unwrap();
+        let mut router = Router::new(|frame: &[u8]| (frame[0] != b'x').then_some(0));
+        let (a, mut a_consumer) = new(8, 8).unwrap();
+        router.push(a);
+
+        assert_eq!(
+            io::Write::write(&mut producer, b"abcdefghijkl").unwrap(),
+            12
+        );
+        // `a` fills up after two frames; the third waits, nothing is sent
+        // twice.
+        assert_eq!(router.route(&mut source).unwrap(), 2);
+        assert_eq!(router.route(&mut source).unwrap(), 0);
+        let mut buf = [0; 16];
+        assert_eq!(io::Read::read(&mut a_consumer, &mut buf).unwrap(), 8);
+        assert_eq!(&buf[..8], b"abcdefgh");
+        assert_eq!(router.route(&mut source).unwrap(), 1);
+        assert_eq!(io::Read::read(&mut a_consumer, &mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"ijkl");
+
+        // A target that only takes whole 8-byte frames refuses to split
+        // the source's 4-byte ones.
+        let (b, _b_consumer) = Builder::new(16).frame_size(8).build().unwrap();
+        let mut router = Router::new(|_: &[u8]| Some(0));
+        router.push(b);
+        assert_eq!(io::Write::write(&mut producer, b"mnop").unwrap(), 4);
+        assert!(matches!(
+            router.route(&mut source),
+            Err(ConsumerError::Callback(ProducerError::TornFrame {
+                n: 4,
+                frame: 8
+            }))
+        ));
+        assert_eq!(source.position(), 12);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn duplex_carries_both_directions() {
+        use io::{Read as _, Write as _};
+
+        let (mut a, mut b) = Duplex::pair(8).unwrap();
+        a.write_all(b"ping").unwrap();
+        b.write_all(b"pong!").unwrap();
+        let mut buf = [0; 8];
+        assert_eq!(b.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"ping");
+        assert_eq!(a.read(&mut buf).unwrap(), 5);
+        assert_eq!(&buf[..5], b"pong!");
+        assert_eq!(a.read(&mut buf).unwrap(), 0);
+
+        assert_eq!(a.write(&[0; 9]).unwrap(), 8);
+        assert!(!b.consumer().is_empty());
+        let (_, consumer) = b.split();
+        assert_eq!(consumer.position(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pipe_blocks_and_reports_eof() {
+        use ::core::iter::Iterator as _;
+        use io::{Read as _, Write as _};
+
+        let (mut writer, mut reader) = pipe(8).unwrap();
+        let sender = ::std::thread::spawn(move || {
+            for i in 0..100_u8 {
+                writer.write_all(&[i; 3]).unwrap();
+            }
+        });
+        let mut read = ::alloc::vec::Vec::new();
+        reader.read_to_end(&mut read).unwrap();
+        sender.join().unwrap();
+        assert_eq!(read.len(), 300);
+        assert!(read.chunks_exact(3).zip(0..).all(|(c, i)| c == [i; 3]));
+
+        let (mut writer, reader) = pipe(8).unwrap();
+        ::core::mem::drop(reader);
+        let err = writer.write(b"x").unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+
+        let (mut writer, mut reader) = pipe(8).unwrap();
+        let bufs = [io::IoSlice::new(b"ab"), io::IoSlice::new(b"cde")];
+        assert_eq!(writer.write_vectored(&bufs).unwrap(), 5);
+        let (mut x, mut y) = ([0; 3], [0; 3]);
+        let mut dsts = [io::IoSliceMut::new(&mut x), io::IoSliceMut::new(&mut y)];
+        assert_eq!(reader.read_vectored(&mut dsts).unwrap(), 5);
+        assert_eq!((&x, &y[..2]), (b"abc", &b"de"[..]));
+    }
+
+    #[test]
+    fn grants_commit_and_release_prefixes() {
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        let mut grant = producer.grant();
+        assert_eq!(grant.len(), 8);
+        grant.bufs()[0][..3].copy_from_slice(b"abc");
+        assert!(matches!(
+            grant.commit(9),
+            Err(ProducerError::InvalidCount { n: 9, len: 8 })
+        ));
+        assert!(consumer.grant().is_empty());
+        let mut grant = producer.grant();
+        grant.bufs()[0][..3].copy_from_slice(b"abc");
+        grant.commit(3).unwrap();
+        assert_eq!(producer.grant().push(b"defgh").unwrap(), 5);
+        assert!(producer.grant().is_empty());
+
+        let grant = consumer.grant();
+        assert_eq!(grant.bufs(), [&b"abcdefgh"[..], &[][..]]);
+        grant.release(6).unwrap();
+        assert_eq!(producer.grant().push(b"ijklmn").unwrap(), 6);
+        let grant = consumer.grant();
+        assert_eq!(
+            (grant.len(), grant.bufs()),
+            (8, [&b"gh"[..], &b"ijklmn"[..]])
+        );
+        assert!(matches!(
+            grant.release(9),
+            Err(ConsumerError::InvalidCount { n: 9, len: 8 })
+        ));
+        assert_eq!(consumer.grant().len(), 8);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn grants_move_bytes_from_interrupt_to_main_loop() {
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        // Stands in for a receive interrupt: pushes a byte at a time and
+        // drops what does not fit.
+        let interrupt = ::std::thread::spawn(move || {
+            let mut dropped = 0;
+            for i in 0..=255_u8 {
+                if producer.grant().push(&[i]).unwrap() == 0 {
+                    dropped += 1;
+                    ::std::thread::yield_now();
+                }
+            }
+            dropped
+        });
+        let mut received = ::alloc::vec::Vec::new();
+        loop {
+            let done = interrupt.is_finished();
+            let grant = consumer.grant();
+            let n = grant.len();
+            for buf in grant.bufs() {
+                received.extend_from_slice(buf);
+            }
+            grant.release(n).unwrap();
+            if done && n == 0 {
+                break;
+            }
+        }
+        let dropped = interrupt.join().unwrap();
+        assert_eq!(received.len() + dropped, 256);
+        assert!(received.is_sorted());
+    }
+
+    #[test]
+    fn dma_transfers_complete_around_the_other_half() {
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        assert_eq!(producer.grant().push(b"abcdef").unwrap(), 6);
+        consumer.grant().release(4).unwrap();
+
+        let transfer = producer.dma_grant();
+        let [first, second] = transfer.descriptors();
+        assert_eq!((transfer.len(), first.len, second.len), (6, 2, 4));
+        // SAFETY: stands in for the controller, writing the described
+        //         space only.
+        unsafe {
+            ::core::ptr::copy_nonoverlapping(b"gh".as_ptr(), first.addr, 2);
+            ::core::ptr::copy_nonoverlapping(b"ij".as_ptr(), second.addr, 2);
+        }
+        // The consumer goes on meanwhile, and sees nothing of the transfer.
+        let grant = consumer.grant();
+        assert_eq!(grant.bufs(), [&b"ef"[..], &[][..]]);
+        grant.release(2).unwrap();
+        assert!(matches!(
+            transfer.complete(7),
+            Err(ProducerError::InvalidCount { n: 7, len: 6 })
+        ));
+        producer.dma_grant().complete(4).unwrap();
+
+        let transfer = consumer.dma_grant();
+        let [first, second] = transfer.descriptors();
+        assert_eq!((first.len, second.len), (2, 2));
+        // SAFETY: stands in for the controller, reading the described
+        //         space only.
+        let bytes = unsafe {
+            [
+                ::core::slice::from_raw_parts(first.addr, 2),
+                ::core::slice::from_raw_parts(second.addr, 2),
+            ]
+        };
+        assert_eq!(bytes, [b"gh", b"ij"]);
+        assert_eq!(transfer.read_buffer(), (first.addr.cast_const(), 2));
+        transfer.complete(3).unwrap();
+        let mut transfer = producer.dma_grant();
+        let [first, _] = transfer.descriptors();
+        assert_eq!(transfer.write_buffer(), (first.addr, 6));
+        assert_eq!(consumer.grant().bufs(), [&b"j"[..], &[][..]]);
+    }
+
+    #[test]
+    fn polling_halves_would_block() {
+        let (mut producer, mut consumer) = polling::new(4, 1).unwrap();
+        assert_eq!(consumer.read(), Err(polling::Error::WouldBlock));
+        assert_eq!(producer.write_slice(b"abc"), Ok(3));
+        producer.write(b'd').unwrap();
+        assert_eq!(producer.write(b'e'), Err(polling::Error::WouldBlock));
+        assert_eq!(producer.write_slice(b""), Ok(0));
+        producer.flush().unwrap();
+
+        assert_eq!(polling::block(|| consumer.read()), Ok(b'a'));
+        let mut buf = [0; 8];
+        assert_eq!(consumer.read_slice(&mut buf), Ok(3));
+        assert_eq!(&buf[..3], b"bcd");
+        assert_eq!(
+            consumer.read_slice(&mut buf),
+            Err(polling::Error::WouldBlock)
+        );
+        polling::block(|| producer.write(b'e')).unwrap();
+        assert_eq!(consumer.read(), Ok(b'e'));
+    }
+
+    #[cfg(all(feature = "wasm", feature = "std"))]
+    #[test]
+    fn wasm_halves_block_on_each_other() {
+        use ::alloc::vec::Vec;
+        use ::core::time::Duration;
+        use ::std::thread;
+
+        let (producer, consumer) = wasm::new(4, 1).unwrap();
+        assert!(!consumer.wait(1, Some(Duration::from_millis(1))));
+        // Posted to a worker by address.
+        let raw = producer.into_raw();
+        let worker = thread::spawn(move || {
+            // SAFETY: taken back once, in the same process.
+            let mut producer = unsafe { wasm::Producer::from_raw(raw) };
+            for chunk in b"abcdefghij".chunks(2) {
+                assert!(producer.wait(2, None));
+                producer
+                    .slices(|bufs, len| {
+                        crate::tee::copy_prefix(&[chunk], bufs, 2.min(len));
+                        Ok::<_, ()>(2)
+                    })
+                    .unwrap();
+            }
+        });
+        let mut consumer = consumer;
+        let mut got = Vec::new();
+        while got.len() < 10 {
+            assert!(consumer.wait(1, None));
+            consumer
+                .slices(|bufs, _| {
+                    got.extend_from_slice(&bufs[0][..1]);
+                    Ok::<_, ()>(1)
+                })
+                .unwrap();
+        }
+        worker.join().unwrap();
+        assert_eq!(got, b"abcdefghij");
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn stats_count_commits_wraps_and_stalls() {
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(6)).unwrap();
+        consumer.slices(|_, _| Ok::<_, ()>(4)).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(2)).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(4)).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(0)).unwrap();
+        consumer.grant().release(6).unwrap();
+        consumer.slices(|_, _| Ok::<_, ()>(2)).unwrap();
+        consumer.slice(|_| Ok::<_, ()>(0)).unwrap();
+
+        let stats = producer.stats();
+        assert_eq!(
+            stats,
+            stats::Stats {
+                bytes: 12,
+                commits: 3,
+                wraps: 1,
+                stalls: 1,
+                high_water: 8,
+            }
+        );
+        assert_eq!(
+            stats.fields(),
+            [
+                ("bytes", 12),
+                ("commits", 3),
+                ("wraps", 1),
+                ("stalls", 1),
+                ("high_water", 8),
+            ]
+        );
+        let stats = consumer.stats();
+        assert_eq!(
+            stats,
+            stats::Stats {
+                bytes: 12,
+                commits: 3,
+                wraps: 1,
+                stalls: 1,
+                high_water: 8,
+            }
+        );
+    }
+
+    #[cfg(all(feature = "metrics", feature = "std"))]
+    #[test]
+    fn metrics_are_exported_per_ring_and_half() {
+        use ::alloc::borrow::ToOwned as _;
+        use ::alloc::string::String;
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+        use ::std::sync::Mutex;
+
+        #[derive(Default)]
+        struct Log(Mutex<Vec<(String, &'static str, &'static str, f64)>>);
+        impl metrics::Recorder for Log {
+            fn counter(&self, key: &metrics::Key<'_>, value: u64) {
+                #[expect(clippy::cast_precision_loss, reason = "small test values")]
+                self.gauge(key, value as f64);
+            }
+            fn gauge(&self, key: &metrics::Key<'_>, value: f64) {
+                let entry = (key.ring.to_owned(), key.half, key.name, value);
+                self.0.lock().unwrap().push(entry);
+            }
+            fn histogram(&self, key: &metrics::Key<'_>, value: f64) {
+                self.gauge(key, value);
+            }
+        }
+
+        let (mut producer, consumer) = new(8, 1).unwrap();
+        producer.slices(|_, _| Ok::<_, ()>(6)).unwrap();
+        let log = Log::default();
+        producer.export("ingest", &log);
+        consumer.export("ingest", &log);
+
+        let log = log.0.into_inner().unwrap();
+        assert_eq!(log.len(), 16);
+        let value = |half, name| {
+            let (ring, _, _, value) = log
+                .iter()
+                .find(|&&(_, h, n, _)| h == half && n == name)
+                .unwrap();
+            assert_eq!(ring, "ingest");
+            *value
+        };
+        assert!((value("producer", metrics::BYTES) - 6.0).abs() < f64::EPSILON);
+        assert!((value("producer", metrics::FILL_RATIO) - 0.75).abs() < f64::EPSILON);
+        assert!((value("consumer", metrics::OCCUPANCY) - 6.0).abs() < f64::EPSILON);
+        assert!(value("consumer", metrics::BYTES).abs() < f64::EPSILON);
+    }
+
+    #[cfg(feature = "registry")]
+    #[test]
+    fn registry_lists_live_rings_by_name() {
+        use ::alloc::string::ToString as _;
+        use ::core::iter::{IntoIterator as _, Iterator as _};
+
+        let find = |name| {
+            registry::rings()
+                .into_iter()
+                .find(|ring| ring.name.as_deref() == Some(name))
+        };
+        let (mut producer, mut consumer) = Builder::new(16).name("registry-test").build().unwrap();
+        assert_eq!(producer.name(), Some("registry-test"));
+        producer.slices(|_, _| Ok::<_, ()>(5)).unwrap();
+        let ring = find("registry-test").unwrap();
+        assert_eq!((ring.capacity, ring.filled, ring.written), (16, 5, 5));
+        assert_eq!(
+            ring.to_string(),
+            "registry-test: 5 of 16 bytes filled, 5 written, 0 read"
+        );
+
+        resize(&mut producer, &mut consumer, 32).unwrap();
+        assert_eq!(consumer.name(), Some("registry-test"));
+        assert_eq!(find("registry-test").unwrap().capacity, 32);
+        ::core::mem::drop((producer, consumer));
+        assert_eq!(find("registry-test"), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn taps_see_every_committed_chunk() {
+        use ::alloc::vec::Vec;
+        use ::std::sync::Mutex;
+
+        type Log = Arc<Mutex<Vec<Vec<u8>>>>;
+        let tap = |log: &Log| {
+            let log = Arc::clone(log);
+            move |bufs: &[&[u8]]| log.lock().unwrap().push(bufs.concat())
+        };
+        let writes = Log::default();
+        let reads = Log::default();
+
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        producer.set_tap(tap(&writes));
+        consumer.set_tap(tap(&reads));
+        assert_eq!(producer.grant().push(b"abcdef").unwrap(), 6);
+        producer.slices(|_, _| Ok::<_, ()>(0)).unwrap();
+        consumer.slices(|_, _| Ok::<_, ()>(4)).unwrap();
+        assert_eq!(producer.grant().push(b"ghij").unwrap(), 4);
+        consumer.grant().release(6).unwrap();
+        producer.remove_tap();
+        assert_eq!(producer.grant().push(b"k").unwrap(), 1);
+        consumer.remove_tap();
+
+        let chunks = |log: Log| Arc::into_inner(log).unwrap().into_inner().unwrap();
+        assert_eq!(chunks(writes), [&b"abcdef"[..], b"ghij"]);
+        assert_eq!(chunks(reads), [&b"abcd"[..], b"efghij"]);
+        assert_eq!(consumer.grant().bufs(), [&b"k"[..], &[][..]]);
+    }
+
+    #[test]
+    fn transforms_apply_in_stream_order() {
+        use transform::{TransformReader, TransformWriter};
+
+        // A WebSocket-style mask, continued across copies and the seam.
+        let mask = |key: [u8; 4]| {
+            let mut i = 0;
+            move |buf: &mut [u8]| {
+                for b in buf {
+                    *b ^= key[i % 4];
+                    i += 1;
+                }
+            }
+        };
+        let (producer, consumer) = new(8, 1).unwrap();
+        let mut writer = TransformWriter::new(producer, mask([1, 2, 3, 4]));
+        let mut reader = TransformReader::new(consumer, mask([1, 2, 3, 4]));
+        assert_eq!(writer.write(b"hello"), 5);
+        let mut buf = [0; 3];
+        assert_eq!(reader.read(&mut buf), 3);
+        assert_eq!(&buf, b"hel");
+        assert_eq!(writer.write(b" world"), 6);
+        assert_eq!(writer.write(b"!"), 0);
+
+        let mut consumer = reader.into_inner();
+        let masked = consumer.grant().bufs().concat();
+        assert_eq!(masked.len(), 8);
+        assert!(masked != b"lo world");
+        let mut reader = TransformReader::new(consumer, {
+            let mut unmask = mask([1, 2, 3, 4]);
+            unmask(&mut [0; 3]);
+            unmask
+        });
+        let mut buf = [0; 8];
+        assert_eq!(reader.read(&mut buf), 8);
+        assert_eq!(&buf, b"lo world");
+        assert_eq!(reader.read(&mut buf), 0);
+    }
+
+    #[test]
+    fn checksum_taps_match_after_drain() {
+        assert_eq!(tap::crc32(0, b"123456789"), 0xcbf4_3926);
+        assert_eq!(tap::crc32(tap::crc32(0, b"1234"), b"56789"), 0xcbf4_3926);
+
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        let written = producer.tap_checksum();
+        let read = consumer.tap_checksum();
+        for chunk in b"123456789".chunks(3) {
+            assert_eq!(producer.grant().push(chunk).unwrap(), 3);
+            consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        }
+        assert_eq!((written.digest(), written.bytes()), (0xcbf4_3926, 9));
+        assert_eq!((read.digest(), read.bytes()), (0xcbf4_3926, 9));
+
+        // Until the ring is drained, the digests differ.
+        producer.grant().push(b"ab").unwrap();
+        consumer.grant().release(1).unwrap();
+        assert!(written.digest() != read.digest());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn rate_limited_halves_offer_the_bucket() {
+        use ::alloc::vec::Vec;
+        use ::core::time::Duration;
+        use ::std::time::Instant;
+
+        use crate::ratelimit::{RateLimited, TokenBucket};
+
+        let mut bucket = TokenBucket::new(1000, 4);
+        let start = Instant::now();
+        assert_eq!(bucket.available_at(start), 4);
+        assert_eq!(bucket.delay_at(6, start), Duration::ZERO);
+
+        let (producer, consumer) = new(8, 1).unwrap();
+        let mut producer = RateLimited::new(producer, bucket);
+        let mut consumer = RateLimited::new(consumer, TokenBucket::new(1, 3));
+        let n = producer.slices(|bufs, len| {
+            assert_eq!((bufs[0].len() + bufs[1].len(), len), (4, 4));
+            bufs[0][..4].copy_from_slice(b"abcd");
+            Ok::<_, ()>(len)
+        });
+        assert_eq!(n.unwrap(), 4);
+        let now = Instant::now();
+        assert_eq!(producer.bucket().available_at(now), 0);
+        assert!(producer.bucket().delay_at(2, now) <= Duration::from_millis(2));
+
+        let mut read = Vec::new();
+        for _ in 0..2 {
+            consumer
+                .slices(|bufs, len| {
+                    for buf in bufs {
+                        read.extend_from_slice(buf);
+                    }
+                    Ok::<_, ()>(len)
+                })
+                .unwrap();
+        }
+        // The consumer's bucket refills a byte per second.
+        assert_eq!(read, b"abc");
+        assert!(consumer.bucket().delay(1) > Duration::from_millis(500));
+
+        let later = now + Duration::from_millis(2);
+        assert_eq!(producer.bucket().available_at(later), 2);
+        assert_eq!(
+            producer
+                .bucket()
+                .available_at(later + Duration::from_secs(9)),
+            4
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn throughput_meters_count_the_window() {
+        use ::core::time::Duration;
+
+        let (mut producer, mut consumer) = new(64, 1).unwrap();
+        assert!(producer.throughput().is_none());
+        let meter = producer.meter_throughput(Duration::from_nanos(160));
+        let read = consumer.meter_throughput(Duration::from_secs(1));
+        assert_eq!(meter.window(), Duration::from_nanos(160));
+        producer.grant().push(b"abcd").unwrap();
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(read.bytes(), 4);
+        assert!(consumer.throughput().unwrap() > 0.0);
+
+        // Slots of 10ns: 10 bytes per slot for the whole window are 1e9/s.
+        let meter = producer.meter_throughput(Duration::from_nanos(160));
+        for t in 0..16 {
+            meter.record_at(10, t * 10);
+        }
+        assert_eq!(meter.bytes_at(159), 160);
+        assert!((meter.rate_at(159) - 1e9).abs() < 1.0);
+        // Moving on by half a window drops the oldest half.
+        meter.record_at(0, 239);
+        assert_eq!(meter.bytes_at(239), 80);
+        assert_eq!(meter.bytes_at(10_000), 0);
+    }
+
+    #[cfg(feature = "stats")]
+    #[test]
+    fn chunk_size_histograms_bucket_by_powers_of_two() {
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+
+        let (mut producer, mut consumer) = new(256, 1).unwrap();
+        assert!(producer.chunk_sizes().is_none());
+        producer.slices(|_, _| Ok::<_, ()>(1)).unwrap();
+        producer.track_chunk_sizes();
+        consumer.track_chunk_sizes();
+        for n in [1, 1, 3, 0, 100] {
+            producer.slices(|_, _| Ok::<_, ()>(n)).unwrap();
+        }
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+
+        let sizes = producer.chunk_sizes().unwrap();
+        assert_eq!(sizes.count(), 4);
+        assert_eq!(
+            sizes.buckets().collect::<Vec<_>>(),
+            [(1, 2), (2, 1), (64, 1)]
+        );
+        assert_eq!(sizes.percentile(50), Some(1));
+        assert_eq!(sizes.percentile(75), Some(3));
+        assert_eq!(sizes.percentile(100), Some(127));
+        let sizes = consumer.chunk_sizes().unwrap();
+        assert_eq!(sizes.buckets().collect::<Vec<_>>(), [(64, 1)]);
+
+        consumer.untrack_chunk_sizes();
+        assert!(consumer.chunk_sizes().is_none());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn ages_follow_the_oldest_unread_byte() {
+        use ::core::time::Duration;
+        use ::std::thread;
+
+        let (mut producer, mut consumer) = Builder::new(1024).track_age().build().unwrap();
+        assert_eq!(consumer.oldest_age(), None);
+        producer.grant().push(b"old").unwrap();
+        thread::sleep(Duration::from_millis(20));
+        producer.grant().push(b"new").unwrap();
+        let old = consumer.oldest_age().unwrap();
+        assert!(old >= Duration::from_millis(20));
+        consumer.grant().release(3).unwrap();
+        assert!(consumer.oldest_age().unwrap() < old);
+
+        // Commits past the stamps extend the newest one.
+        for _ in 0..age::STAMPS * 2 {
+            producer.grant().push(b"x").unwrap();
+        }
+        resize(&mut producer, &mut consumer, 2048).unwrap();
+        assert!(consumer.oldest_age().unwrap() < old);
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        assert_eq!(consumer.oldest_age(), None);
+
+        let (_, consumer) = new(16, 1).unwrap();
+        assert_eq!(consumer.oldest_age(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn inspectors_watch_without_holding_the_ring() {
+        use ::std::thread;
+
+        use crate::inspect::{Inspector, Snapshot};
+
+        assert_impl_all!(Inspector: Send, Sync, Clone);
+
+        let (mut producer, mut consumer, inspector) =
+            Builder::new(16).build_with_inspector().unwrap();
+        producer.grant().push(b"abcde").unwrap();
+        consumer.grant().release(2).unwrap();
+        let expected = Snapshot {
+            capacity: 16,
+            filled: 3,
+            written: 5,
+            read: 2,
+        };
+        let watcher = inspector.clone();
+        let seen = thread::spawn(move || watcher.snapshot()).join().unwrap();
+        assert_eq!(seen, Some(expected));
+        assert_eq!(consumer.inspector().snapshot().unwrap().free(), 13);
+
+        ::core::mem::drop(producer);
+        assert!(inspector.is_alive());
+        ::core::mem::drop(consumer);
+        assert!(!inspector.is_alive());
+        assert_eq!(inspector.snapshot(), None);
+    }
+
+    #[test]
+    fn debug_dumps_cross_the_seam() {
+        let (mut producer, mut consumer) = new(32, 1).unwrap();
+        assert_eq!(producer.debug_dump(64), "");
+        producer.slices(|_, _| Ok::<_, ()>(24)).unwrap();
+        consumer.slices(|_, _| Ok::<_, ()>(24)).unwrap();
+        producer.set_coalesce_threshold(32);
+        producer
+            .grant()
+            .push(b"GET /index.html HTTP/1.1\r\n")
+            .unwrap();
+
+        let dump = producer.debug_dump(64);
+        assert_eq!(
+            dump,
+            "00000018  47 45 54 20 2f 69 6e 64  65 78 2e 68 74 6d 6c 20  |GET /index.html |\n\
+             00000028  48 54 54 50 2f 31 2e 31  0d 0a                    |HTTP/1.1..|\n"
+        );
+        // The consumer sees the bytes once published.
+        producer.publish();
+        assert_eq!(consumer.debug_dump(64), dump);
+        assert_eq!(
+            consumer.debug_dump(4),
+            "00000018  47 45 54 20                                       |GET |\n\
+             ... 22 more bytes\n"
+        );
+        // Published bytes may be scrubbed meanwhile.
+        #[cfg(feature = "scrub")]
+        assert_eq!(producer.debug_dump(64), "");
+    }
+
+    #[test]
+    fn drop_hooks_report_unconsumed_bytes() {
+        use ::core::sync::atomic::AtomicUsize;
+
+        static DISCARDED: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        consumer.on_drop(|n| DISCARDED.store(n, Relaxed));
+        producer.set_coalesce_threshold(16);
+        producer.grant().push(b"tail data").unwrap();
+        producer.publish();
+        producer.grant().push(b"!").unwrap();
+        consumer.grant().release(4).unwrap();
+        ::core::mem::drop(consumer);
+        assert_eq!(DISCARDED.load(Relaxed), 5);
+    }
+
+    #[cfg(feature = "oplog")]
+    #[test]
+    fn op_logs_replay_the_recorded_calls() {
+        use crate::oplog::{Divergence, Op, OpLog, Side};
+
+        let log = OpLog::new();
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        producer.record_ops(&log);
+        consumer.record_ops(&log);
+        producer.slices(|_, _| Ok::<_, ()>(6)).unwrap();
+        consumer.grant().release(4).unwrap();
+        producer.grant().commit(9).unwrap_err();
+        producer.grant().push(b"abcd").unwrap();
+        consumer.slices(|_, _| Err::<usize, _>(())).unwrap_err();
+        consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap();
+        let ops = log.ops();
+        assert_eq!(
+            ops,
+            [
+                Op {
+                    side: Side::Producer,
+                    len: 8,
+                    n: 6
+                },
+                Op {
+                    side: Side::Consumer,
+                    len: 6,
+                    n: 4
+                },
+                Op {
+                    side: Side::Producer,
+                    len: 6,
+                    n: 9
+                },
+                Op {
+                    side: Side::Producer,
+                    len: 6,
+                    n: 4
+                },
+                Op {
+                    side: Side::Consumer,
+                    len: 6,
+                    n: 6
+                },
+            ]
+        );
+
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        log.replay(&mut producer, &mut consumer).unwrap();
+        assert!(consumer.is_empty());
+
+        // A run where the consumer saw less than recorded diverges.
+        let mut ops = ops;
+        ops[1].len = 5;
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        let err = OpLog::from_ops(ops).replay(&mut producer, &mut consumer);
+        assert_eq!(
+            err,
+            Err(Divergence {
+                index: 1,
+                op: Op {
+                    side: Side::Consumer,
+                    len: 5,
+                    n: 4
+                },
+                offered: 6,
+            })
+        );
+    }
+
+    #[cfg(feature = "testutil")]
+    #[test]
+    fn simulated_links_keep_the_stream_intact() {
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+        use ::core::time::Duration;
+        use ::std::io::{self, Read as _, Write as _};
+
+        use crate::testutil::{Link, Rng};
+
+        let mut rng = Rng::new(7);
+        let data = (0..4096)
+            .map(|_| rng.next_u64().to_le_bytes()[0])
+            .collect::<Vec<_>>();
+        let mut input = Link::new(io::Cursor::new(&data), 1)
+            .short_io()
+            .interrupt_one_in(3)
+            .bandwidth(1 << 20);
+        let mut output = Link::new(Vec::new(), 2)
+            .short_io()
+            .interrupt_one_in(3)
+            .latency(Duration::from_micros(1));
+
+        let (mut producer, mut consumer) = new(256, 1).unwrap();
+        let mut buf = [0; 100];
+        let mut interrupts = 0;
+        let mut done = false;
+        while !done || !consumer.is_empty() {
+            match input.read(&mut buf) {
+                Ok(0) => done = true,
+                Ok(n) => {
+                    let mut chunk = &buf[..n];
+                    while !chunk.is_empty() {
+                        let written = producer.write(chunk).unwrap();
+                        chunk = &chunk[written..];
+                        consumer
+                            .io_slices(|bufs, _| match output.write_vectored(bufs) {
+                                Err(err) if err.kind() == io::ErrorKind::Interrupted => {
+                                    interrupts += 1;
+                                    Ok(0)
+                                }
+                                result => result,
+                            })
+                            .unwrap();
+                    }
+                }
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => interrupts += 1,
+                Err(err) => panic!("{err}"),
+            }
+            if done {
+                let _ = consumer.io_slices(|bufs, _| Ok(output.write_vectored(bufs).unwrap_or(0)));
+            }
+        }
+        output.flush().unwrap();
+        assert!(interrupts > 0);
+        assert_eq!(output.into_inner(), data);
+    }
+
+    #[cfg(feature = "testutil")]
+    #[test]
+    fn pattern_endpoints_verify_the_stream() {
+        use ::std::io::{self, Write as _};
+
+        use crate::testutil::{self, PatternReader, PatternWriter, RandomReader, RandomWriter};
+
+        let (mut producer, mut consumer) = new(64, 1).unwrap();
+        let mut input = PatternReader::new(10_000, 3);
+        let mut output = PatternWriter::new(4);
+        while input.remaining() != 0 || !consumer.is_empty() {
+            producer
+                .io_slices(|bufs, _| io::Read::read_vectored(&mut input, bufs))
+                .unwrap();
+            consumer
+                .io_slices(|bufs, _| output.write_vectored(bufs))
+                .unwrap();
+        }
+        assert_eq!(output.written(), 10_000);
+
+        // A skipped byte is caught.
+        let mut output = PatternWriter::new(4);
+        let skipped = [testutil::pattern(0), testutil::pattern(2)];
+        let err = output.write_all(&skipped).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut input = RandomReader::new(1000, 5);
+        let mut output = RandomWriter::new(6);
+        while input.remaining() != 0 || !consumer.is_empty() {
+            producer
+                .io_slices(|bufs, _| io::Read::read_vectored(&mut input, bufs))
+                .unwrap();
+            consumer
+                .io_slices(|bufs, _| output.write_vectored(bufs))
+                .unwrap();
+        }
+        assert_eq!(output.written(), 1000);
+    }
+
+    #[test]
+    fn invariants_hold_across_wraps() {
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        producer.set_coalesce_threshold(4);
+        for n in [3, 7, 16, 5, 11, 9] {
+            producer.slices(|_, len| Ok::<_, ()>(n.min(len))).unwrap();
+            producer.assert_invariants();
+            consumer.slices(|_, len| Ok::<_, ()>(len.min(6))).unwrap();
+            consumer.assert_invariants();
+        }
+    }
+
+    #[test]
+    #[should_panic = "violated: read <= write <= read + size"]
+    fn invariants_report_a_read_past_write() {
+        let (producer, _consumer) = new(16, 1).unwrap();
+        producer.buffer.read.store(3, Relaxed);
+        producer.assert_invariants();
+    }
+
+    #[cfg(feature = "scrub")]
+    #[test]
+    fn scrubbed_rings_fill_released_bytes() {
+        let (mut producer, mut consumer) = new(8, 1).unwrap();
+        producer
+            .slice(|buf| {
+                assert_eq!(buf, [scrub::PATTERN; 8]);
+                buf[..6].copy_from_slice(b"abcdef");
+                Ok::<_, ()>(6)
+            })
+            .unwrap();
+        consumer.slice(|_| Ok::<_, ()>(4)).unwrap();
+        producer
+            .slices(|bufs, _| {
+                assert_eq!(bufs[0], [scrub::PATTERN; 2]);
+                assert_eq!(bufs[1], [scrub::PATTERN; 4]);
+                Ok::<_, ()>(0)
+            })
+            .unwrap();
+        resize(&mut producer, &mut consumer, 16).unwrap();
+        producer
+            .slices(|bufs, _| {
+                assert_eq!(bufs[0], [scrub::PATTERN; 10]);
+                assert_eq!(bufs[1], [scrub::PATTERN; 4]);
+                Ok::<_, ()>(0)
+            })
+            .unwrap();
+        consumer
+            .slice(|buf| {
+                assert_eq!(buf, b"ef");
+                Ok::<_, ()>(0)
+            })
+            .unwrap();
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panicking_closures_commit_nothing() {
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        producer.set_coalesce_threshold(0);
+        producer
+            .slice(|buf| {
+                buf[..4].copy_from_slice(b"abcd");
+                Ok::<_, ()>(4)
+            })
+            .unwrap();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            producer.slice(|buf| -> Result<usize, ()> {
+                buf[..8].copy_from_slice(b"garbage!");
+                panic!("producer closure");
+            })
+        }));
+        assert!(panicked.is_err());
+        producer.assert_invariants();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            consumer.slice(|_| -> Result<usize, ()> { panic!("consumer closure") })
+        }));
+        assert!(panicked.is_err());
+        consumer.assert_invariants();
+
+        consumer
+            .slice(|buf| {
+                assert_eq!(buf, b"abcd");
+                Ok::<_, ()>(4)
+            })
+            .unwrap();
+        assert!(consumer.is_empty());
+        assert_eq!(producer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 16);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn panicking_closures_poison_the_ring() {
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let (mut producer, mut consumer) = Builder::new(16).poison_on_panic().build().unwrap();
+        producer.set_coalesce_threshold(0);
+        producer.slice(|_| Ok::<_, ()>(4)).unwrap();
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            producer.slice(|_| -> Result<usize, ()> { panic!("producer closure") })
+        }));
+        assert!(panicked.is_err());
+        assert!(consumer.is_poisoned());
+        assert!(matches!(
+            consumer.slice(|_| Ok::<_, ()>(4)),
+            Err(ConsumerError::Poisoned)
+        ));
+        assert!(matches!(
+            producer.slice(|_| Ok::<_, ()>(0)),
+            Err(ProducerError::Poisoned)
+        ));
+
+        consumer.clear_poison();
+        assert!(!producer.is_poisoned());
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            consumer.slice(|_| -> Result<usize, ()> { panic!("consumer closure") })
+        }));
+        assert!(panicked.is_err());
+        assert!(matches!(
+            producer.slice(|_| Ok::<_, ()>(0)),
+            Err(ProducerError::Poisoned)
+        ));
+        producer.clear_poison();
+        assert_eq!(consumer.slice(|buf| Ok::<_, ()>(buf.len())).unwrap(), 4);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn combinators_report_poisoned_rings() {
+        use ::core::convert::Infallible;
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let poisoned = || {
+            let (mut producer, consumer) = Builder::new(16).poison_on_panic().build().unwrap();
+            let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+                producer.slice(|_| -> Result<usize, ()> { panic!("producer closure") })
+            }));
+            assert!(panicked.is_err());
+            (producer, consumer)
+        };
+        let is_poisoned = |res: Result<usize, ConsumerError<ProducerError<Infallible>>>| {
+            matches!(res, Err(ConsumerError::Callback(ProducerError::Poisoned)))
+        };
+
+        let (mut producer, mut source) = new(16, 8).unwrap();
+        assert_eq!(io::Write::write(&mut producer, b"abcd").unwrap(), 4);
+        let (mut sink, _sink_consumer) = poisoned();
+        let (mut a, _a_consumer) = new(16, 8).unwrap();
+        assert!(is_poisoned(tee(&mut source, &mut a, &mut sink)));
+        assert!(is_poisoned(tee(&mut source, &mut sink, &mut a)));
+
+        let mut distributor = Distributor::new(2).unwrap();
+        distributor.push(a);
+        distributor.push(sink);
+        // The first chunk goes out, the second one finds the sink poisoned.
+        assert_eq!(distributor.pump(&mut source).unwrap(), 2);
+        assert!(is_poisoned(distributor.pump(&mut source)));
+        let [mut a, mut sink] = <[Producer; 2]>::try_from(distributor.into_inner()).unwrap();
+
+        let mut merger = Merger::new(Fairness::RoundRobin);
+        merger.push(source);
+        assert!(is_poisoned(merger.pump(&mut sink)));
+        let (_, mut poisoned_source) = poisoned();
+        assert!(matches!(
+            tee(&mut poisoned_source, &mut sink, &mut a),
+            Err(ConsumerError::Poisoned)
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn poisoned_rings_grant_nothing() {
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let (mut producer, mut consumer) = Builder::new(16).poison_on_panic().build().unwrap();
+        producer.set_coalesce_threshold(0);
+        assert_eq!(producer.grant().push(b"abcd").unwrap(), 4);
+        let held = consumer.grant();
+        assert_eq!(held.len(), 4);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            producer.slice(|_| -> Result<usize, ()> { panic!("producer closure") })
+        }));
+        assert!(panicked.is_err());
+        assert!(matches!(held.release(4), Err(ConsumerError::Poisoned)));
+
+        let grant = producer.grant();
+        assert!(grant.is_empty());
+        assert!(matches!(grant.push(b"efgh"), Err(ProducerError::Poisoned)));
+        assert!(producer.dma_grant().is_empty());
+        let grant = consumer.grant();
+        assert_eq!(grant.bufs(), [&[][..], &[][..]]);
+        assert!(matches!(grant.release(0), Err(ConsumerError::Poisoned)));
+        assert!(consumer.dma_grant().is_empty());
+
+        consumer.clear_poison();
+        assert_eq!(consumer.grant().len(), 4);
+    }
+
+    #[cfg(feature = "testutil")]
+    #[test]
+    fn randomized_schedules_keep_the_stream_intact() {
+        use ::std::io::{Read as _, Write as _};
+        use ::std::thread;
+
+        use crate::testutil::{PatternReader, PatternWriter, Rng};
+
+        const LEN: u64 = 1 << 20;
+
+        for seed in 0..4 {
+            let mut rng = Rng::new(seed);
+            let size = 16 << rng.below(9);
+            let (mut producer, mut consumer) = new(size, 1).unwrap();
+            producer.set_coalesce_threshold(rng.below(size));
+
+            let mut schedule = Rng::new(rng.next_u64());
+            let mut input = PatternReader::new(LEN, rng.next_u64());
+            let sender = thread::spawn(move || {
+                while input.remaining() != 0 {
+                    producer
+                        .io_slices(|bufs, _| input.read_vectored(bufs))
+                        .unwrap();
+                    if schedule.below(4) == 0 {
+                        thread::yield_now();
+                    }
+                }
+            });
+
+            let mut schedule = Rng::new(rng.next_u64());
+            let mut output = PatternWriter::new(rng.next_u64());
+            while output.written() != LEN {
+                consumer
+                    .io_slices(|bufs, _| output.write_vectored(bufs))
+                    .unwrap();
+                if schedule.below(4) == 0 {
+                    thread::yield_now();
+                }
+            }
+            sender.join().unwrap();
+            assert!(consumer.is_empty());
+        }
+    }
+
+    #[test]
+    fn ranges_partition_the_buffer() {
+        use ::core::iter::Iterator as _;
+        use ::rand::rngs::SmallRng;
+        use ::rand::{RngExt as _, SeedableRng as _};
+
+        let mut rng = SmallRng::seed_from_u64(1);
+        for _ in 0..10_000 {
+            let size = 1 << rng.random_range(0..12);
+            let mask = size - 1;
+            let read = rng.random_range(0..=usize::MAX);
+            let write = read.wrapping_add(rng.random_range(0..=size));
+            let (filled, filled_len) = filled_ranges(size, mask, read, write);
+            let (empty, empty_len) = empty_ranges(size, mask, read, write);
+            assert_eq!(filled_len, write.wrapping_sub(read));
+            assert_eq!(filled_len + empty_len, size);
+            // Walking the ranges from the read position covers every byte
+            // once, filled ones first.
+            let mut pos = read & mask;
+            for range in filled.iter().chain(&empty).filter(|r| !r.is_empty()) {
+                assert_eq!(range.start, pos);
+                assert!(range.end <= size);
+                pos = range.end & mask;
+            }
+            assert_eq!(pos, read & mask);
+        }
+    }
+
+    #[test]
+    fn operation_sequences_match_a_vecdeque_model() {
+        use ::alloc::collections::VecDeque;
+        use ::alloc::vec::Vec;
+        use ::core::iter::{Extend as _, Iterator as _};
+        use ::rand::rngs::SmallRng;
+        use ::rand::{RngExt as _, SeedableRng as _};
+
+        for seed in 0..64 {
+            let mut rng = SmallRng::seed_from_u64(seed);
+            let size = 1 << rng.random_range(2..9);
+            let (mut producer, mut consumer) = new(size, 1).unwrap();
+            let mut model = VecDeque::new();
+            let mut next = 0_u8;
+            for _ in 0..500 {
+                let write = producer.position();
+                let contiguous = rng.random_bool(0.5);
+                match rng.random_range(0..6) {
+                    0 | 1 => {
+                        let free = size - model.len();
+                        let seam = size - (write & (size - 1));
+                        let expected = if contiguous { free.min(seam) } else { free };
+                        let invalid = rng.random_bool(0.1);
+                        let mut fill = |bufs: &mut [&mut [u8]], len: usize| {
+                            assert_eq!(len, expected);
+                            if invalid {
+                                return Ok::<_, ()>(len + 1);
+                            }
+                            let n = rng.random_range(0..=len);
+                            for b in bufs.iter_mut().flat_map(|buf| buf.iter_mut()).take(n) {
+                                *b = next;
+                                model.push_back(next);
+                                next = next.wrapping_add(1);
+                            }
+                            Ok(n)
+                        };
+                        let result = if contiguous {
+                            producer.slice(|buf| fill(&mut [buf], expected))
+                        } else {
+                            producer.slices(fill)
+                        };
+                        assert_eq!(result.is_err(), invalid);
+                    }
+                    2 => {
+                        let grant = producer.grant();
+                        assert_eq!(grant.len(), size - model.len());
+                        let n = rng.random_range(0..=grant.len());
+                        let src = (0..n)
+                            .map(|i| next.wrapping_add(i.to_le_bytes()[0]))
+                            .collect::<Vec<_>>();
+                        assert_eq!(grant.push(&src).unwrap(), n);
+                        model.extend(&src);
+                        next = next.wrapping_add(n.to_le_bytes()[0]);
+                    }
+                    3 | 4 => {
+                        let read = write.wrapping_sub(model.len());
+                        let seam = size - (read & (size - 1));
+                        let expected = if contiguous {
+                            model.len().min(seam)
+                        } else {
+                            model.len()
+                        };
+                        let invalid = rng.random_bool(0.1);
+                        let mut drain = |bufs: &[&[u8]], len: usize| {
+                            assert_eq!(len, expected);
+                            let seen = bufs.iter().flat_map(|buf| buf.iter());
+                            assert!(seen.eq(model.iter().take(len)));
+                            if invalid {
+                                return Ok::<_, ()>(len + 1);
+                            }
+                            let n = rng.random_range(0..=len);
+                            model.drain(..n);
+                            Ok(n)
+                        };
+                        let result = if contiguous {
+                            consumer.slice(|buf| drain(&[buf], buf.len()))
+                        } else {
+                            consumer.slices(drain)
+                        };
+                        assert_eq!(result.is_err(), invalid);
+                    }
+                    _ => {
+                        let grant = consumer.grant();
+                        assert_eq!(grant.len(), model.len());
+                        let n = rng.random_range(0..=grant.len());
+                        let [first, second] = grant.bufs();
+                        assert!(first.iter().chain(second).eq(model.iter()));
+                        grant.release(n).unwrap();
+                        model.drain(..n);
+                    }
+                }
+                producer.assert_invariants();
+                assert_eq!(
+                    producer.position().wrapping_sub(consumer.position()),
+                    model.len()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ring_traits_drive_real_and_scripted_halves() {
+        use ::alloc::vec::Vec;
+        use ::core::convert::Infallible;
+
+        use crate::ring::{RingConsumer, RingProducer};
+
+        /// Moves everything from `src` into `dst`, as application code
+        /// written against the traits would.
+        fn forward(src: &mut dyn RingConsumer, dst: &mut dyn RingProducer) -> usize {
+            let mut moved = 0;
+            while !src.is_empty() {
+                let mut chunk = [0; 4];
+                let n = src.pop(&mut chunk).unwrap();
+                let mut rest = &chunk[..n];
+                while !rest.is_empty() {
+                    rest = &rest[dst.push(rest).unwrap()..];
+                }
+                dst.publish();
+                moved += n;
+            }
+            moved
+        }
+
+        /// A producer taking one byte per call.
+        struct Trickle(Vec<u8>);
+
+        impl RingProducer for Trickle {
+            fn fill(
+                &mut self,
+                f: &mut dyn FnMut(&mut [&mut [u8]], usize) -> usize,
+            ) -> Result<usize, ProducerError<Infallible>> {
+                let mut byte = [0];
+                let n = f(&mut [&mut byte], 1);
+                self.0.extend_from_slice(&byte[..n]);
+                Ok(n)
+            }
+
+            fn free_len(&self) -> usize {
+                1
+            }
+
+            fn pending(&self) -> usize {
+                0
+            }
+
+            fn publish(&mut self) {}
+        }
+
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        assert_eq!(
+            RingProducer::push(&mut producer, b"hello, ring").unwrap(),
+            11
+        );
+        assert_eq!(RingProducer::free_len(&producer), 5);
+        assert_eq!(consumer.filled_len(), 11);
+        let mut trickle = Trickle(Vec::new());
+        assert_eq!(forward(&mut consumer, &mut trickle), 11);
+        assert_eq!(trickle.0, b"hello, ring");
+    }
+
+    #[cfg(feature = "testutil")]
+    #[test]
+    fn faulty_halves_follow_their_script() {
+        use crate::ring::{RingConsumer, RingProducer};
+        use crate::testutil::{Fault, Faulty};
+
+        let (producer, consumer) = new(16, 1).unwrap();
+        let mut producer = Faulty::new(producer)
+            .on_call(1, Fault::Stall)
+            .on_call(2, Fault::Cap(3))
+            .on_call(4, Fault::Poison);
+        let mut consumer = Faulty::new(consumer).on_call(1, Fault::Cap(2));
+
+        assert_eq!(producer.push(b"abcdef").unwrap(), 0);
+        assert_eq!(producer.push(b"abcdef").unwrap(), 3);
+        assert!(matches!(
+            producer.fill(&mut |_, len| len + 1),
+            Err(ProducerError::InvalidCount { n: 14, len: 13 })
+        ));
+        assert!(matches!(
+            producer.push(b"def"),
+            Err(ProducerError::Poisoned)
+        ));
+        assert_eq!(producer.push(b"def").unwrap(), 3);
+        assert_eq!(producer.calls(), 5);
+
+        let mut buf = [0; 8];
+        assert_eq!(consumer.pop(&mut buf).unwrap(), 2);
+        assert!(matches!(
+            consumer.drain(&mut |_, len| {
+                assert_eq!(len, 4);
+                len + 1
+            }),
+            Err(ConsumerError::InvalidCount { n: 5, len: 4 })
+        ));
+        assert_eq!(consumer.pop(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"cdef");
+        assert!(consumer.get_ref().is_empty());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn scoped_halves_reuse_one_ring() {
+        use ::std::io::{Read as _, Write as _};
+        use ::std::thread;
+
+        let mut ring = scoped::Ring::new(64, 8).unwrap();
+        for session in 0..8_u8 {
+            let (mut producer, mut consumer) = ring.split_mut();
+            let sent = [session; 1000];
+            let mut received = [0; 1000];
+            thread::scope(|s| {
+                s.spawn(move || {
+                    let mut rest = &sent[..];
+                    while !rest.is_empty() {
+                        rest = &rest[producer.write(rest).unwrap()..];
+                        thread::yield_now();
+                    }
+                });
+                let mut filled = 0;
+                while filled != received.len() {
+                    filled += consumer.read(&mut received[filled..]).unwrap();
+                    thread::yield_now();
+                }
+            });
+            assert_eq!(received, sent);
+            assert!(ring.is_empty());
+        }
+
+        let (mut producer, _) = ring.split_mut();
+        assert_eq!(producer.write(&[1; 100]).unwrap(), 64);
+        assert_eq!(ring.len(), 64);
+        ring.clear();
+        let (mut producer, mut consumer) = ring.split_mut();
+        assert_eq!(producer.write(b"abc").unwrap(), 3);
+        let mut buf = [0; 8];
+        assert_eq!(consumer.read(&mut buf).unwrap(), 3);
+        assert_eq!(&buf[..3], b"abc");
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn log_writers_emit_whole_lines_only() {
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+        use ::core::time::Duration;
+        use ::std::io::Write as _;
+        use ::std::writeln;
+
+        let (mut writer, handle) =
+            logwriter::spawn(64, Duration::from_hours(1), Vec::new()).unwrap();
+        for i in 0..100 {
+            writeln!(writer, "line {i:03}").unwrap();
+        }
+        writeln!(writer, "{}", "x".repeat(100)).unwrap();
+        write!(writer, "last").unwrap();
+        let overwritten = writer.dropped_bytes();
+        assert!(overwritten > 0);
+        ::core::mem::drop(writer);
+        let dropped = handle.dropped_lines();
+        let out = handle.join().unwrap();
+
+        let lines: Vec<&[u8]> = out.split_inclusive(|&b| b == b'\n').collect();
+        let (last, numbered) = lines.split_last().unwrap();
+        assert_eq!(*last, b"last\n");
+        assert!(!numbered.is_empty());
+        // Whole lines in order, wherever the drain thread caught up.
+        let mut next = 0;
+        for line in numbered {
+            let i = (next..100)
+                .find(|i| *line == ::std::format!("line {i:03}\n").as_bytes())
+                .unwrap();
+            next = i + 1;
+        }
+        // The line too long for the ring, the cut one is counted on exit.
+        assert!(dropped >= 1);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn proxies_relay_and_propagate_half_closes() {
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+        use ::std::io::{Read as _, Write as _};
+        use ::std::net::{Shutdown, TcpListener, TcpStream};
+        use ::std::thread;
+
+        let server = TcpListener::bind("127.0.0.1:0").unwrap();
+        let front = TcpListener::bind("127.0.0.1:0").unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let front_addr = front.local_addr().unwrap();
+
+        // Answers once the client is done sending, which it only learns
+        // from the half-close relayed by the proxy.
+        let upstream = thread::spawn(move || {
+            let (mut conn, _) = server.accept().unwrap();
+            let mut request = Vec::new();
+            conn.read_to_end(&mut request).unwrap();
+            let reply: Vec<u8> = request.iter().rev().copied().collect();
+            conn.write_all(&reply).unwrap();
+        });
+        let relay = thread::spawn(move || {
+            let (a, _) = front.accept().unwrap();
+            let b = TcpStream::connect(server_addr).unwrap();
+            proxy::Proxy::new(256)
+                .timeout(::core::time::Duration::from_secs(10))
+                .run(&a, &b)
+        });
+
+        let request: Vec<u8> = (0..10_000_u32).map(|i| i.to_le_bytes()[0]).collect();
+        let mut client = TcpStream::connect(front_addr).unwrap();
+        client.write_all(&request).unwrap();
+        client.shutdown(Shutdown::Write).unwrap();
+        let mut reply = Vec::new();
+        client.read_to_end(&mut reply).unwrap();
+        upstream.join().unwrap();
+
+        assert!(reply.iter().eq(request.iter().rev()));
+        let totals = relay.join().unwrap().unwrap();
+        assert_eq!(
+            totals,
+            proxy::Totals {
+                a_to_b: 10_000,
+                b_to_a: 10_000,
+            }
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn consumers_read_lines_and_write_to_sinks() {
+        use ::alloc::string::String;
+        use ::alloc::vec::Vec;
+        use ::std::io::{BufRead as _, Read as _, Write as _};
+
+        use crate::ring::RingConsumer as _;
+
+        let (mut producer, mut consumer) = new(16, 1).unwrap();
+        producer.write_all(&[0; 10]).unwrap();
+        consumer.read_exact(&mut [0; 10]).unwrap();
+
+        // Across the seam.
+        producer.write_all(b"abc\ndefghij\n").unwrap();
+        let mut line = String::new();
+        assert_eq!(consumer.read_line(&mut line).unwrap(), 4);
+        assert_eq!(consumer.read_line(&mut line).unwrap(), 8);
+        assert_eq!(line, "abc\ndefghij\n");
+        assert!(consumer.fill_buf().unwrap().is_empty());
+
+        producer.write_all(b"0123456789abcdef").unwrap();
+        // Up to the seam only.
+        consumer.consume(100);
+        assert_eq!(consumer.filled_len(), 6);
+        producer.write_all(b"ghij").unwrap();
+        let mut out = Vec::new();
+        assert_eq!(consumer.write_to(&mut out).unwrap(), 10);
+        assert_eq!(out, b"abcdefghij");
+        assert_eq!(consumer.write_to(&mut out).unwrap(), 0);
+
+        producer.write_all(b"xyz").unwrap();
+        let full: &mut [u8] = &mut [];
+        let err = consumer.write_to(&mut io::Cursor::new(full)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::WriteZero);
+        assert_eq!(consumer.filled_len(), 3);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn buf_read_consumes_whole_frames() {
+        use ::std::io::{BufRead as _, Write as _};
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let (mut producer, mut consumer) = Builder::new(16)
+            .frame_size(4)
+            .poison_on_panic()
+            .build()
+            .unwrap();
+        producer.write_all(b"abcdefgh").unwrap();
+        assert_eq!(consumer.fill_buf().unwrap(), b"abcdefgh");
+        consumer.consume(4);
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| consumer.consume(2)));
+        assert!(panicked.is_err());
+        assert_eq!(consumer.fill_buf().unwrap(), b"efgh");
+
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            producer.slice(|_| -> Result<usize, ()> { panic!("producer closure") })
+        }));
+        assert!(panicked.is_err());
+        assert!(consumer.fill_buf().is_err());
+        consumer.consume(4);
+        consumer.clear_poison();
+        assert_eq!(consumer.fill_buf().unwrap(), b"efgh");
+    }
+
+    #[test]
+    fn async_halves_wake_each_other() {
+        use ::core::future::Future as _;
+        use ::core::sync::atomic::AtomicUsize;
+        use ::core::task::{Context, Poll, Waker};
+
+        struct Count(AtomicUsize);
+        impl ::alloc::task::Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&count));
+        let mut cx = Context::from_waker(&waker);
+        let (mut producer, mut consumer) = asynch::new(8, 1).unwrap();
+        let mut buf = [0; 16];
+
+        assert!(consumer.poll_read(&mut cx, &mut buf).is_pending());
+        assert_eq!(producer.try_write(&[7; 16]), 8);
+        assert_eq!(count.0.load(Relaxed), 1);
+        assert!(producer.poll_write(&mut cx, b"x").is_pending());
+        assert_eq!(consumer.poll_read(&mut cx, &mut buf[..3]), Poll::Ready(3));
+        assert_eq!(count.0.load(Relaxed), 2);
+
+        let write = ::core::pin::pin!(producer.write(b"abcd")).poll(&mut cx);
+        assert_eq!(write, Poll::Ready(Ok(3)));
+        assert_eq!(consumer.try_read(&mut buf), 8);
+        assert_eq!(&buf[5..8], b"abc");
+        ::core::mem::drop(producer);
+        assert_eq!(count.0.load(Relaxed), 2);
+        assert_eq!(consumer.poll_read(&mut cx, &mut buf), Poll::Ready(0));
+
+        let (mut producer, consumer) = asynch::new(8, 1).unwrap();
+        assert!(producer.poll_write(&mut cx, &[0; 9]).is_ready());
+        assert!(producer.poll_write(&mut cx, b"x").is_pending());
+        ::core::mem::drop(consumer);
+        assert_eq!(count.0.load(Relaxed), 3);
+        assert_eq!(
+            producer.poll_write(&mut cx, b"x"),
+            Poll::Ready(Err(asynch::Closed))
+        );
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn async_consumer_awaits_interrupt_producer() {
+        use ::core::future::Future;
+        use ::core::task::{Context, Poll, Waker};
+        use ::std::thread::{self, Thread};
+
+        struct Unpark(Thread);
+        impl ::alloc::task::Wake for Unpark {
+            fn wake(self: Arc<Self>) {
+                self.0.unpark();
+            }
+        }
+
+        fn block_on<F: Future>(future: F) -> F::Output {
+            let waker = Waker::from(Arc::new(Unpark(thread::current())));
+            let mut cx = Context::from_waker(&waker);
+            let mut future = ::core::pin::pin!(future);
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+                thread::park();
+            }
+        }
+
+        let (mut producer, mut consumer) = asynch::new(16, 1).unwrap();
+        // Stands in for a receive interrupt: never waits, drops what does
+        // not fit.
+        let interrupt = thread::spawn(move || {
+            let mut dropped = 0;
+            for i in 0..=255_u8 {
+                if producer.try_write(&[i]) == 0 {
+                    dropped += 1;
+                    thread::yield_now();
+                }
+            }
+            dropped
+        });
+        let received = block_on(async {
+            let (mut received, mut buf) = (::alloc::vec::Vec::new(), [0; 16]);
+            loop {
+                match consumer.read(&mut buf).await {
+                    Ok(0) => return received,
+                    Ok(n) => received.extend_from_slice(&buf[..n]),
+                }
+            }
+        });
+        let dropped = interrupt.join().unwrap();
+        assert_eq!(received.len() + dropped, 256);
+        assert!(received.is_sorted());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn duplex_stream_wakes_waiting_tasks() {
+        use ::core::sync::atomic::AtomicUsize;
+        use ::core::task::{Context, Poll, Waker};
+
+        struct Count(AtomicUsize);
+        impl ::std::task::Wake for Count {
+            fn wake(self: Arc<Self>) {
+                self.0.fetch_add(1, Relaxed);
+            }
+        }
+
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        let waker = Waker::from(Arc::clone(&count));
+        let mut cx = Context::from_waker(&waker);
+        let (mut a, mut b) = duplex(5).unwrap();
+        let mut buf = [0; 16];
+
+        assert!(b.poll_read(&mut cx, &mut buf).is_pending());
+        assert!(matches!(
+            a.poll_write(&mut cx, &[7; 16]),
+            Poll::Ready(Ok(8))
+        ));
+        assert_eq!(count.0.load(Relaxed), 1);
+        assert!(a.poll_write(&mut cx, b"x").is_pending());
+        assert!(matches!(b.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(8))));
+        assert_eq!(count.0.load(Relaxed), 2);
+
+        assert!(matches!(a.poll_write(&mut cx, b"end"), Poll::Ready(Ok(3))));
+        assert!(a.poll_shutdown(&mut cx).is_ready());
+        assert!(matches!(b.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(3))));
+        assert!(matches!(b.poll_read(&mut cx, &mut buf), Poll::Ready(Ok(0))));
+        ::core::mem::drop(a);
+        let Poll::Ready(Err(err)) = b.poll_write(&mut cx, b"x") else {
+            ::core::panic!("write to dropped stream");
+        };
+        assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+    }
+
+    #[test]
+    fn channel_reports_full_empty_and_closed() {
+        let (mut tx, mut rx) = channel(16, 2).unwrap();
+        let mut dst = [0; 16];
+        assert_eq!(rx.try_recv(&mut dst), Err(TryRecvError::Empty));
+        assert_eq!(tx.try_send(b""), Err(TrySendError::Empty));
+        assert_eq!(rx.try_recv(&mut dst), Err(TryRecvError::Empty));
+        tx.try_send(b"hello").unwrap();
+        assert_eq!(tx.try_send(&[0; 17]), Err(TrySendError::Full));
+        tx.try_send(b"world").unwrap();
+        assert_eq!(tx.try_send(b"!"), Err(TrySendError::Full));
+        assert_eq!(rx.try_recv(&mut dst[..4]), Err(TryRecvError::TooSmall(5)));
+        assert_eq!(rx.try_recv(&mut dst), Ok(5));
+        assert_eq!(&dst[..5], b"hello");
+        ::core::mem::drop(tx);
+        assert_eq!(rx.try_recv(&mut dst), Ok(5));
+        assert_eq!(&dst[..5], b"world");
+        assert_eq!(rx.try_recv(&mut dst), Err(TryRecvError::Closed));
+
+        let (mut tx, rx) = channel(16, 2).unwrap();
+        ::core::mem::drop(rx);
+        assert_eq!(tx.try_send(b"hello"), Err(TrySendError::Closed));
+    }
+
+    #[test]
+    fn typed_channel_decodes_across_the_wrap() {
+        use ::alloc::vec::Vec;
+
+        #[derive(Debug, PartialEq)]
+        struct Word(Vec<u8>);
+
+        impl typed::Encode for Word {
+            fn encode(&self, dst: &mut Vec<u8>) {
+                dst.extend_from_slice(&self.0);
+            }
+        }
+
+        impl typed::Decode for Word {
+            fn decode(src: &[u8]) -> Option<Self> {
+                src.is_ascii().then(|| Word(src.to_vec()))
+            }
+        }
+
+        let (mut tx, mut rx) = typed::channel::<Word>(16, 4).unwrap();
+        assert_eq!(rx.try_recv(), Err(typed::TryRecvError::Empty));
+        for word in [&b"wrap"[..], b"around", b"the", b"seam"] {
+            tx.try_send(&Word(word.to_vec())).unwrap();
+            assert_eq!(
+                tx.try_send(&Word([0; 16].to_vec())),
+                Err(TrySendError::Full)
+            );
+            assert_eq!(rx.try_recv(), Ok(Word(word.to_vec())));
+        }
+        tx.try_send(&Word(::alloc::vec![0xff])).unwrap();
+        tx.try_send(&Word(b"last".to_vec())).unwrap();
+        ::core::mem::drop(tx);
+        assert_eq!(rx.try_recv(), Err(typed::TryRecvError::Malformed));
+        assert_eq!(rx.try_recv(), Ok(Word(b"last".to_vec())));
+        assert_eq!(rx.try_recv(), Err(typed::TryRecvError::Closed));
+
+        let (mut tx, mut rx) = typed::channel::<u64>(16, 2).unwrap();
+        tx.try_send(&u64::MAX).unwrap();
+        assert_eq!(rx.try_recv(), Ok(u64::MAX));
+    }
+
+    #[test]
+    fn typed_views_borrow_the_ring() {
+        struct Line(&'static str);
+
+        impl typed::Encode for Line {
+            fn encode(&self, dst: &mut ::alloc::vec::Vec<u8>) {
+                dst.extend_from_slice(self.0.as_bytes());
+            }
+        }
+
+        impl typed::View for Line {
+            type Target<'a> = &'a str;
+
+            fn view(src: &[u8]) -> Option<&str> {
+                ::core::str::from_utf8(src).ok()
+            }
+        }
+
+        let (mut tx, mut rx) = typed::channel::<Line>(16, 4).unwrap();
+        for line in ["zero", "copy", "views", "wrap"] {
+            tx.try_send(&Line(line)).unwrap();
+            let len = rx.try_view(|view| {
+                assert_eq!(view, line);
+                view.len()
+            });
+            assert_eq!(len, Ok(line.len()));
+        }
+        assert_eq!(rx.try_view(|_| ()), Err(typed::TryRecvError::Empty));
+    }
+
+    #[test]
+    fn frames_straddle_the_wrap() {
+        let (producer, consumer) = new(16, 8).unwrap();
+        let (mut writer, mut reader) = (
+            framing::FrameWriter::new(producer),
+            framing::FrameReader::new(consumer),
+        );
+        assert_eq!(reader.read_frame(<[u8]>::len), None);
+        assert!(!writer.write_frame(&[0; 13]));
+        for frame in [&b"abcdef"[..], b"ghijkl", b"", b"mnopqrstu", b"vw"] {
+            assert!(writer.write_frame(frame));
+            assert_eq!(reader.read_frame(<[u8]>::to_vec), Some(frame.to_vec()));
+        }
+        assert!(writer.write_frame(b"xyz"));
+        assert!(writer.write_frame(b"!"));
+        assert!(!writer.write_frame(b"full"));
+        assert_eq!(reader.read_frame(<[u8]>::to_vec), Some(b"xyz".to_vec()));
+        assert_eq!(reader.read_frame(<[u8]>::to_vec), Some(b"!".to_vec()));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn varint_frames_take_short_headers() {
+        use framing::{FrameReader, FrameWriter, Prefix};
+
+        let (producer, consumer) = new(256, 8).unwrap();
+        let mut writer = FrameWriter::with_prefix(producer, Prefix::Varint);
+        let mut reader = FrameReader::with_prefix(consumer, Prefix::Varint);
+        assert!(writer.write_frame(&[1; 127]));
+        assert!(writer.write_frame(&[2; 125]));
+        assert!(writer.write_frame(b""));
+        assert!(!writer.write_frame(b"x"));
+        assert_eq!(reader.read_frame(|f| (f.len(), f[0])), Some((127, 1)));
+        assert!(writer.write_frame(&[3; 126]));
+        assert_eq!(reader.read_frame(|f| (f.len(), f[0])), Some((125, 2)));
+        assert_eq!(reader.read_frame(<[u8]>::len), Some(0));
+        assert_eq!(reader.read_frame(|f| (f.len(), f[125])), Some((126, 3)));
+        assert!(writer.write_frame(&[4; 300][..200]));
+        assert_eq!(reader.read_frame(|f| (f.len(), f[199])), Some((200, 4)));
+        assert!(reader.is_empty());
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", any(unix, windows)))]
+    #[test]
+    fn shm_halves_share_one_segment() {
+        let name = ::alloc::format!("/bytering-test-{}", ::std::process::id());
+        let name = ::alloc::ffi::CString::new(name).unwrap();
+        // SAFETY: the segment is only accessed through this module.
+        let (created, opened, again) = unsafe {
+            let created = shm::Segment::create(&name, 64).unwrap();
+            let opened = shm::Segment::open(&name).unwrap();
+            (created, opened, shm::Segment::open(&name).unwrap())
+        };
+        shm::Segment::unlink(&name).unwrap();
+        assert_eq!(opened.size(), 64);
+
+        let mut producer = created.into_producer().unwrap();
+        let mut consumer = opened.into_consumer().unwrap();
+        assert!(matches!(again.into_producer(), Err(BufferError::InUse)));
+
+        let mut buf = [0; 48];
+        for i in 0..8_u8 {
+            assert_eq!(io::Write::write(&mut producer, &[i; 40]).unwrap(), 40);
+            assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 40);
+            assert_eq!(buf[..40], [i; 40]);
+        }
+        assert!(consumer.is_empty());
+        let n = producer.slices(|bufs, len| {
+            assert_eq!((bufs[0].len(), len), (64, 64));
+            Ok::<_, ()>(65)
+        });
+        assert!(matches!(
+            n,
+            Err(ProducerError::InvalidCount { n: 65, len: 64 })
+        ));
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", any(unix, windows)))]
+    #[test]
+    fn shm_consumer_follows_grown_ring() {
+        let pid = ::std::process::id();
+        let first = ::alloc::ffi::CString::new(::alloc::format!("/bytering-grow-{pid}")).unwrap();
+        let second = ::alloc::ffi::CString::new(::alloc::format!("/bytering-grown-{pid}")).unwrap();
+        // SAFETY: the segments are only accessed through this module.
+        let (created, opened) = unsafe {
+            let created = shm::Segment::create(&first, 16).unwrap();
+            (created, shm::Segment::open(&first).unwrap())
+        };
+        shm::Segment::unlink(&first).unwrap();
+        let mut producer = created.into_producer().unwrap();
+        let mut consumer = opened.into_consumer().unwrap();
+
+        assert_eq!(io::Write::write(&mut producer, b"0123456789").unwrap(), 10);
+        // SAFETY: see above.
+        unsafe { producer.grow(&second, 64).unwrap() };
+        assert_eq!(io::Write::write(&mut producer, &[7; 40]).unwrap(), 40);
+        assert!(consumer.wait(16, None));
+        assert!(!consumer.follow().unwrap());
+
+        let mut buf = [0; 64];
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 10);
+        assert_eq!(&buf[..10], b"0123456789");
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 0);
+        assert!(consumer.follow().unwrap());
+        shm::Segment::unlink(&second).unwrap();
+        assert!(!consumer.follow().unwrap());
+        assert_eq!(consumer.peer(), shm::Peer::Attached);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 40);
+        assert_eq!(buf[..40], [7; 40]);
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", any(unix, windows)))]
+    #[test]
+    fn shm_producers_append_whole_records() {
+        use ::alloc::vec::Vec;
+        use ::core::iter::Iterator as _;
+        use ::core::time::Duration;
+
+        let name = ::alloc::format!("/bytering-mpsc-{}", ::std::process::id());
+        let name = ::alloc::ffi::CString::new(name).unwrap();
+        // SAFETY: the segment is only accessed through this module.
+        let created = unsafe { shm::mpsc::Segment::create(&name, 64).unwrap() };
+        let mut consumer = created.into_consumer().unwrap();
+        let producers: Vec<_> = (0..3_u8)
+            .map(|id| {
+                // SAFETY: see above.
+                let opened = unsafe { shm::mpsc::Segment::open(&name).unwrap() };
+                let mut producer = opened.into_producer();
+                ::std::thread::spawn(move || {
+                    for seq in 0..200_u8 {
+                        let record = [id, seq, seq, seq, seq][..usize::from(seq % 4) + 2].to_vec();
+                        while !producer.send(&record) {
+                            ::std::thread::yield_now();
+                        }
+                    }
+                })
+            })
+            .collect();
+        shm::Segment::unlink(&name).unwrap();
+
+        let mut next = [0_u8; 3];
+        let mut buf = [0; 8];
+        while next != [200; 3] {
+            assert!(consumer.wait(Some(Duration::from_secs(10))));
+            let n = consumer.recv(&mut buf).unwrap();
+            let (id, seq) = (usize::from(buf[0]), buf[1]);
+            assert_eq!(seq, next[id]);
+            assert_eq!(n, usize::from(seq % 4) + 2);
+            assert!(buf[1..n].iter().all(|&b| b == seq));
+            next[id] += 1;
+        }
+        for producer in producers {
+            producer.join().unwrap();
+        }
+        assert!(consumer.is_empty());
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", any(unix, windows)))]
+    #[test]
+    fn shm_observers_see_unconsumed_bytes() {
+        use ::alloc::vec::Vec;
+
+        let name = ::alloc::format!("/bytering-observe-{}", ::std::process::id());
+        let name = ::alloc::ffi::CString::new(name).unwrap();
+        // SAFETY: the segment is only accessed through this module.
+        let (created, opened, observer) = unsafe {
+            let created = shm::Segment::create(&name, 32).unwrap();
+            let opened = shm::Segment::open(&name).unwrap();
+            (created, opened, shm::Segment::observe(&name).unwrap())
+        };
+        shm::Segment::unlink(&name).unwrap();
+        let mut producer = created.into_producer().unwrap();
+        let mut consumer = opened.into_consumer().unwrap();
+        assert_eq!(observer.size(), 32);
+        assert_eq!(observer.producer(), shm::Peer::Attached);
+
+        let mut seen = Vec::new();
+        let mut buf = [0; 32];
+        for i in 0..4_u8 {
+            assert_eq!(io::Write::write(&mut producer, &[i; 20]).unwrap(), 20);
+            assert_eq!(io::Read::read(&mut consumer, &mut buf[..12]).unwrap(), 12);
+            let at = observer.snapshot(&mut seen);
+            assert_eq!(at, u64::from(i) * 20 + 12);
+            assert_eq!(seen, [i; 8]);
+            assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 8);
+        }
+        assert_eq!(observer.counters(), (80, 80));
+        ::core::mem::drop(consumer);
+        assert_eq!(observer.consumer(), shm::Peer::Detached);
+        assert_eq!(observer.snapshot(&mut seen), 80);
+        assert!(seen.is_empty());
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn persistent_ring_survives_reopening() {
+        use ::std::io::Write as _;
+
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-persist-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        let buffer = persist::PersistentBuffer::create(&path, 64).unwrap();
+        assert!(matches!(
+            persist::PersistentBuffer::create(&path, 64),
+            Err(BufferError::MapFailed(_))
+        ));
+        let (mut producer, mut consumer) = buffer.split();
+        let mut buf = [0; 64];
+        for i in 0..3_u8 {
+            assert_eq!(io::Write::write(&mut producer, &[i; 40]).unwrap(), 40);
+            assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 40);
+        }
+        assert_eq!(io::Write::write(&mut producer, b"left behind").unwrap(), 11);
+        producer.flush().unwrap();
+        assert_eq!(io::Read::read(&mut consumer, &mut buf[..5]).unwrap(), 5);
+        consumer.sync().unwrap();
+        ::core::mem::drop((producer, consumer));
+
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.size(), buffer.len()), (64, 6));
+        let (mut producer, mut consumer) = buffer.split();
+        assert_eq!(io::Write::write(&mut producer, b"!").unwrap(), 1);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 7);
+        assert_eq!(&buf[..7], b"behind!");
+        ::core::mem::drop((producer, consumer));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn persistent_ring_invalid_count_errors() {
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-persist-count-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        let (mut producer, mut consumer) = persist::PersistentBuffer::create(&path, 64)
+            .unwrap()
+            .split();
+        assert!(matches!(
+            producer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ProducerError::InvalidCount { n: 65, len: 64 })
+        ));
+        assert_eq!(producer.slices(|_, _| Ok::<_, ()>(10)).unwrap(), 10);
+        assert!(matches!(
+            consumer.slices(|_, len| Ok::<_, ()>(len + 1)),
+            Err(ConsumerError::InvalidCount { n: 11, len: 10 })
+        ));
+        assert_eq!(consumer.slices(|_, len| Ok::<_, ()>(len)).unwrap(), 10);
+        ::core::mem::drop((producer, consumer));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn persistent_ring_recovers_last_sync() {
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-recover-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        let buffer = persist::PersistentBuffer::create(&path, 64).unwrap();
+        assert_eq!(buffer.truncated(), 0);
+        let (mut producer, mut consumer) = buffer.split();
+        let mut buf = [0; 64];
+        assert_eq!(io::Write::write(&mut producer, &[1; 10]).unwrap(), 10);
+        producer.sync().unwrap();
+        assert_eq!(io::Write::write(&mut producer, &[2; 5]).unwrap(), 5);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf[..3]).unwrap(), 3);
+        // Crashes: the file is never marked closed.
+        ::core::mem::forget((producer, consumer));
+
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.truncated(), buffer.counters()), (5, (3, 10)));
+        let (mut producer, mut consumer) = buffer.split();
+        assert_eq!(io::Write::write(&mut producer, &[3; 6]).unwrap(), 6);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf[..12]).unwrap(), 12);
+        assert_eq!(buf[..12], [[1; 7].as_slice(), &[3; 5]].concat());
+        ::core::mem::forget((producer, consumer));
+
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.truncated(), buffer.counters()), (1, (15, 15)));
+        ::core::mem::drop(buffer);
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.truncated(), buffer.counters()), (0, (15, 15)));
+        ::core::mem::drop(buffer);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn persistent_ring_salvages_checksummed_commits() {
+        use ::std::os::unix::fs::FileExt as _;
+
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-checksums-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        assert!(matches!(
+            persist::PersistentBuffer::create_checksummed(&path, 64, 3),
+            Err(BufferError::BadSize(3))
+        ));
+        let buffer = persist::PersistentBuffer::create_checksummed(&path, 64, 4).unwrap();
+        let (mut producer, consumer) = buffer.split();
+        for i in 0..5_u8 {
+            assert_eq!(io::Write::write(&mut producer, &[i; 10]).unwrap(), 10);
+        }
+        producer.sync().unwrap();
+        for i in 5..8_u8 {
+            assert_eq!(io::Write::write(&mut producer, &[i; 4]).unwrap(), 4);
+        }
+        ::core::mem::forget((producer, consumer));
+
+        // The last commit gets garbled on the way to the file.
+        let data = mmap::granularity().max(persist::HEADER_LEN);
+        let file = ::std::fs::OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .unwrap();
+        file.write_all_at(&[0xff], (data + 60) as u64).unwrap();
+
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.truncated(), buffer.counters()), (4, (0, 58)));
+        let (mut producer, consumer) = buffer.split();
+        assert_eq!(io::Write::write(&mut producer, &[9; 2]).unwrap(), 2);
+        ::core::mem::forget((producer, consumer));
+
+        let buffer = persist::PersistentBuffer::open(&path).unwrap();
+        assert_eq!((buffer.truncated(), buffer.counters()), (0, (0, 60)));
+        let (_, mut consumer) = buffer.split();
+        let mut buf = [0; 64];
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 60);
+        assert_eq!(buf[48..60], [4, 4, 5, 5, 5, 5, 6, 6, 6, 6, 9, 9]);
+        ::core::mem::drop(consumer);
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(
+        feature = "mmap",
+        feature = "std",
+        any(target_os = "linux", target_os = "android")
+    ))]
+    #[test]
+    fn persistent_ring_punches_consumed_pages() {
+        use ::alloc::vec;
+
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-compact-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        let page = mmap::granularity();
+        let buffer = persist::PersistentBuffer::create(&path, 4 * page).unwrap();
+        let (mut producer, mut consumer) = buffer.split();
+        let mut buf = vec![0; 4 * page];
+        assert_eq!(producer.compact().unwrap(), 4 * page);
+
+        let bytes = vec![0xaa; 3 * page];
+        assert_eq!(io::Write::write(&mut producer, &bytes).unwrap(), 3 * page);
+        assert_eq!(
+            io::Read::read(&mut consumer, &mut buf[..=page]).unwrap(),
+            page + 1
+        );
+        // Only the first page and the untouched last one are free.
+        assert_eq!(producer.compact().unwrap(), 2 * page);
+        assert_eq!(
+            io::Write::write(&mut producer, &bytes).unwrap(),
+            2 * page + 1
+        );
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 4 * page);
+        assert!(buf == vec![0xaa; 4 * page]);
+        ::core::mem::drop((producer, consumer));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn persistent_history_reads_consumed_bytes() {
+        let path = ::std::env::temp_dir().join(::alloc::format!(
+            "bytering-history-{}",
+            ::std::process::id()
+        ));
+        let _ = ::std::fs::remove_file(&path);
+        let buffer = persist::PersistentBuffer::create(&path, 64).unwrap();
+        let (mut producer, mut consumer) = buffer.split();
+        producer.retain(32);
+        let mut buf = [0; 64];
+        assert_eq!(io::Write::write(&mut producer, &[1; 40]).unwrap(), 32);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 32);
+
+        let mut history = persist::History::open(&path, 8).unwrap();
+        assert_eq!(history.retained(), 0..32);
+        assert_eq!(history.read(&mut buf[..16]).unwrap(), 16);
+        assert_eq!(history.read(&mut buf).unwrap(), 8);
+        assert_eq!(history.read(&mut buf).unwrap(), 0);
+        assert_eq!(io::Write::write(&mut producer, b"tail").unwrap(), 4);
+        assert_eq!(history.read(&mut buf).unwrap(), 4);
+        assert_eq!(&buf[..4], b"tail");
+        assert_eq!(history.position(), 36);
+
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 4);
+        assert_eq!(io::Write::write(&mut producer, &[2; 64]).unwrap(), 32);
+        assert_eq!(io::Read::read(&mut consumer, &mut buf).unwrap(), 32);
+        assert_eq!(io::Write::write(&mut producer, b"!").unwrap(), 1);
+        history.seek(0);
+        assert_eq!(
+            history.read(&mut buf),
+            Err(persist::Overwritten { oldest: 36 })
+        );
+        assert_eq!(history.position(), 0);
+        history.seek(36);
+        assert_eq!(history.read(&mut buf).unwrap(), 33);
+        assert_eq!(&buf[31..33], b"\x02!");
+        ::core::mem::drop((producer, consumer, history));
+        ::std::fs::remove_file(&path).unwrap();
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi_halves_acquire_and_commit() {
+        let (mut producer, mut consumer) = (ptr::null_mut(), ptr::null_mut());
+        // SAFETY: the halves are used as the bindings require.
+        unsafe {
+            assert_eq!(
+                ffi::bytering_new(12, 1, &raw mut producer, &raw mut consumer),
+                ffi::BYTERING_EBADSIZE
+            );
+            assert!(producer.is_null());
+            assert_eq!(
+                ffi::bytering_new(16, 1, &raw mut producer, &raw mut consumer),
+                ffi::BYTERING_OK
+            );
+            let mut write = ffi::WriteSlices {
+                ptr: [ptr::null_mut(); 2],
+                len: [0; 2],
+            };
+            assert_eq!(ffi::bytering_producer_acquire(producer, &raw mut write), 16);
+            assert_eq!(write.len, [16, 0]);
+            ptr::copy_nonoverlapping(b"hello".as_ptr(), write.ptr[0], 5);
+            assert_eq!(
+                ffi::bytering_producer_commit(producer, 17),
+                ffi::BYTERING_EINVALIDCOUNT
+            );
+            assert_eq!(ffi::bytering_producer_commit(producer, 5), ffi::BYTERING_OK);
+
+            let mut read = ffi::ReadSlices {
+                ptr: [ptr::null(); 2],
+                len: [0; 2],
+            };
+            assert_eq!(ffi::bytering_consumer_acquire(consumer, &raw mut read), 5);
+            assert_eq!(::core::slice::from_raw_parts(read.ptr[0], 5), b"hello");
+            assert_eq!(ffi::bytering_consumer_commit(consumer, 5), ffi::BYTERING_OK);
+            assert_eq!(ffi::bytering_producer_acquire(producer, &raw mut write), 16);
+            assert_eq!(write.len, [11, 5]);
+            ffi::bytering_producer_destroy(producer);
+            ffi::bytering_consumer_destroy(consumer);
+            ffi::bytering_consumer_destroy(ptr::null_mut());
+        }
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", any(unix, windows)))]
+    #[test]
+    fn shm_halves_block_on_each_other() {
+        use ::core::time::Duration;
+
+        let name = ::alloc::format!("/bytering-wait-{}", ::std::process::id());
+        let name = ::alloc::ffi::CString::new(name).unwrap();
+        // SAFETY: the segment is only accessed through this module.
+        let (created, opened) = unsafe {
+            let created = shm::Segment::create(&name, 64).unwrap();
+            (created, shm::Segment::open(&name).unwrap())
+        };
+        shm::Segment::unlink(&name).unwrap();
+        let mut producer = created.into_producer().unwrap();
+        let mut consumer = opened.into_consumer().unwrap();
+
+        assert!(!consumer.wait(1, Some(Duration::from_millis(1))));
+        assert!(producer.wait(1000, Some(Duration::ZERO)));
+        let reader = ::std::thread::spawn(move || {
+            let mut total = 0;
+            let mut buf = [0; 64];
+            while total < 1000 {
+                assert!(consumer.wait(1, None));
+                total += io::Read::read(&mut consumer, &mut buf).unwrap();
+            }
+            total
+        });
+        let mut written = 0;
+        while written < 1000 {
+            assert!(producer.wait(16, None));
+            written += io::Write::write(&mut producer, &[7; 16][..16.min(1000 - written)]).unwrap();
+        }
+        assert_eq!(reader.join().unwrap(), 1000);
+    }
+
+    #[cfg(all(feature = "mmap", feature = "std", unix))]
+    #[test]
+    fn shm_detects_dead_peer() {
+        let name = ::alloc::format!("/bytering-dead-{}", ::std::process::id());
+        let name = ::alloc::ffi::CString::new(name).unwrap();
+        // SAFETY: the segment is only accessed through this module.
+        let (created, opened, again) = unsafe {
+            let created = shm::Segment::create(&name, 64).unwrap();
+            let opened = shm::Segment::open(&name).unwrap();
+            (created, opened, shm::Segment::open(&name).unwrap())
+        };
+        shm::Segment::unlink(&name).unwrap();
+        let consumer = opened.into_consumer().unwrap();
+        assert_eq!(consumer.peer(), shm::Peer::Detached);
+
+        // SAFETY: the child only touches the shared mapping and exits
+        //         without unwinding or running destructors.
+        unsafe {
+            let pid = ::libc::fork();
+            assert!(pid >= 0);
+            if pid == 0 {
+                let producer = created.into_producer();
+                ::libc::_exit(i32::from(producer.is_err()));
+            }
+            let mut status = 0;
+            assert_eq!(::libc::waitpid(pid, &raw mut status, 0), pid);
+            assert_eq!(status, 0);
+        }
+        assert_eq!(consumer.peer(), shm::Peer::Dead);
+        assert!(!consumer.wait(1, None));
+
+        let mut producer = again.into_producer().unwrap();
+        assert_eq!(consumer.peer(), shm::Peer::Attached);
+        assert_eq!(producer.peer(), shm::Peer::Attached);
+        assert_eq!(io::Write::write(&mut producer, b"back").unwrap(), 4);
+        assert!(consumer.wait(4, None));
+    }
+
+    #[test]
+    fn records_stay_whole_across_wraps() {
+        let (mut producer, mut consumer) = records::new(16, 8, 4).unwrap();
+        assert_eq!(producer.record_size(), 4);
+        assert_eq!(consumer.pop_record_with(|_| ()), None);
+
+        let mut next = 0_u8;
+        let mut expected = 0_u8;
+        for _ in 0..5 {
+            while producer.push_record(&[next; 4]) {
+                next = next.wrapping_add(1);
+            }
+            for _ in 0..3 {
+                let record = consumer.pop_record_with(<[u8]>::to_vec).unwrap();
+                assert_eq!(record, [expected; 4]);
+                expected = expected.wrapping_add(1);
+            }
+        }
+        assert_eq!(producer.push_record_with(|slot| slot.fill(next)), Some(()));
+        while let Some(record) = consumer.pop_record_with(|record| record[0]) {
+            assert_eq!(record, expected);
+            expected = expected.wrapping_add(1);
+        }
+        assert_eq!(expected, next.wrapping_add(1));
+
+        assert!(matches!(
+            records::new(16, 8, 3),
+            Err(BufferError::BadGranularity(3))
+        ));
+    }
+
+    #[test]
+    fn budget_bounds_total_memory() {
+        let budget = Arc::new(Budget::new(96));
+        let builder = |size| Builder::new(size).accounting(Arc::clone(&budget) as _);
+
+        let (mut producer, mut consumer) = builder(64).build().unwrap();
+        assert_eq!(budget.used(), 64);
+        assert!(matches!(
+            builder(64).build(),
+            Err(BufferError::OverBudget(64))
+        ));
+        let small = builder(32).build().unwrap();
+        assert_eq!(budget.used(), 96);
+
+        // Resizing charges the new size before giving back the old one.
+        assert!(matches!(
+            resize(&mut producer, &mut consumer, 32),
+            Err(BufferError::OverBudget(32))
+        ));
+        ::core::mem::drop(small);
+        resize(&mut producer, &mut consumer, 32).unwrap();
+        assert_eq!(budget.used(), 32);
+
+        ::core::mem::drop(producer);
+        assert_eq!(budget.used(), 32);
+        ::core::mem::drop(consumer);
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn budget_bounds_unbounded_rings() {
+        use ::std::io::{Read as _, Write as _};
+
+        let budget = Arc::new(Budget::new(48));
+        let (mut producer, mut consumer) = Builder::new(16)
+            .accounting(Arc::clone(&budget) as _)
+            .build_unbounded()
+            .unwrap();
+        producer.write_all(&[1; 48]).unwrap();
+        assert_eq!(budget.used(), 48);
+
+        let err = producer.write_all(&[2; 16]).unwrap_err();
+        let inner = err.get_ref().and_then(|e| e.downcast_ref::<BufferError>());
+        assert!(matches!(inner, Some(BufferError::OverBudget(16))));
+        assert_eq!(budget.used(), 48);
+
+        // Drained segments are reused without charging again.
+        let mut buf = [0; 48];
+        consumer.read_exact(&mut buf).unwrap();
+        producer.write_all(&[2; 16]).unwrap();
+        assert_eq!(budget.used(), 48);
+        ::core::mem::drop((producer, consumer));
+        assert_eq!(budget.used(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn grown_unbounded_rings_keep_their_options() {
+        use ::std::io::{Read as _, Write as _};
+        use ::std::panic::{self, AssertUnwindSafe};
+
+        let (mut producer, mut consumer) = Builder::new(16)
+            .name("grown")
+            .poison_on_panic()
+            .build_unbounded()
+            .unwrap();
+        producer.write_all(&[1; 24]).unwrap();
+        assert_eq!(producer.name(), Some("grown"));
+
+        // The second segment poisons like the first would.
+        let panicked = panic::catch_unwind(AssertUnwindSafe(|| {
+            producer.slice(|_| -> Result<usize, ()> { panic!("producer closure") })
+        }));
+        assert!(panicked.is_err());
+        assert!(producer.write(&[2; 8]).is_err());
+        let mut buf = [0; 16];
+        consumer.read_exact(&mut buf).unwrap();
+        assert!(consumer.read(&mut buf).is_err());
+        assert!(consumer.is_poisoned());
+        assert_eq!(consumer.name(), Some("grown"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn global_accounting_sees_every_kind_of_ring() {
+        use ::core::sync::atomic::AtomicUsize;
+
+        /// Counts the allocations of `SIZE` bytes, which no other test
+        /// makes, and accepts all of them.
+        struct Count(AtomicUsize);
+
+        const SIZE: usize = 1 << 17;
+
+        impl Accounting for Count {
+            fn allocate(&self, bytes: usize) -> bool {
+                if bytes == SIZE {
+                    self.0.fetch_add(1, Relaxed);
+                }
+                true
+            }
+
+            fn deallocate(&self, _: usize) {}
+        }
+
+        let count = Arc::new(Count(AtomicUsize::new(0)));
+        assert!(set_global_accounting(Arc::clone(&count) as _).is_ok());
+        assert!(set_global_accounting(Arc::clone(&count) as _).is_err());
+        let counted = || count.0.load(Relaxed);
+
+        let _plain = new(SIZE, 8).unwrap();
+        assert_eq!(counted(), 1);
+        let _lossy = lossy::new(SIZE, 8).unwrap();
+        let _broadcast = broadcast::new(SIZE, 8, 2).unwrap();
+        let _typed = typed::new::<u8>(SIZE).unwrap();
+        let _arena = Arena::new(1, SIZE, 8).unwrap();
+        let _pooled = Pool::new(SIZE, 8).unwrap().pair().unwrap();
+        let _scoped = scoped::Ring::new(SIZE, 8).unwrap();
+        assert_eq!(counted(), 7);
+
+        let (mut producer, _consumer) = new_unbounded(SIZE, 8).unwrap();
+        ::std::io::Write::write_all(&mut producer, &::alloc::vec![0; SIZE + 1]).unwrap();
+        assert_eq!(counted(), 9);
+    }
+
+    #[test]
+    fn arena_carves_independent_rings() {
+        let mut arena = Arena::new(3, 32, 64).unwrap();
+        assert_eq!(arena.remaining(), 3);
+
+        let (mut p0, mut c0) = arena.pair().unwrap();
+        let (mut p1, mut c1) = arena.pair().unwrap();
+        let (mut p2, mut c2) = arena.pair().unwrap();
+        assert!(arena.pair().is_none());
+        assert_eq!(arena.remaining(), 0);
+        ::core::mem::drop(arena);
+
+        let base = p0.buffer.data.ptr.as_ptr().addr();
+        assert!(base.is_multiple_of(64));
+        assert_eq!(p1.buffer.data.ptr.as_ptr().addr(), base + 64);
+        assert_eq!(p2.buffer.data.ptr.as_ptr().addr(), base + 128);
+
+        pump_pattern(&mut p1, &mut c1, 200);
+        pump_pattern(&mut p0, &mut c0, 100);
+        pump_pattern(&mut p2, &mut c2, 300);
+        assert!(c0.is_empty() && c1.is_empty() && c2.is_empty());
+    }
+
+    #[test]
+    fn arena_rejects_bad_parameters() {
+        assert!(matches!(
+            Arena::new(4, 24, 8),
+            Err(BufferError::BadSize(24))
+        ));
+        assert!(matches!(
+            Arena::new(0, 16, 8),
+            Err(BufferError::BadSize(16))
+        ));
+        assert!(matches!(
+            Arena::new(usize::MAX, 16, 8),
+            Err(BufferError::BadSize(16))
+        ));
+        assert!(matches!(
+            Arena::new(4, 16, 12),
+            Err(BufferError::BadAlignment(12))
+        ));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pool_recycles_dropped_rings() {
+        let pool = Pool::new(64, 16).unwrap();
+        let (mut producer, mut consumer) = pool.pair().unwrap();
+        let addr = producer.buffer.data.ptr.as_ptr().addr();
+        pump_pattern(&mut producer, &mut consumer, 100);
+        assert_eq!(consumer.position(), 100);
+
+        // Memory only returns once both halves are gone.
+        ::core::mem::drop(producer);
+        assert_eq!(pool.idle(), 0);
+        ::core::mem::drop(consumer);
+        assert_eq!(pool.idle(), 1);
+
+        let (mut producer, mut consumer) = pool.pair().unwrap();
+        assert_eq!(pool.idle(), 0);
+        assert_eq!(producer.buffer.data.ptr.as_ptr().addr(), addr);
+        assert_eq!(consumer.position(), 0);
+        pump_pattern(&mut producer, &mut consumer, 100);
+
+        let second = pool.pair().unwrap();
+        ::core::mem::drop((producer, consumer, second));
+        assert_eq!(pool.idle(), 2);
+        pool.shrink();
+        assert_eq!(pool.idle(), 0);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn pool_outlived_by_rings() {
+        let pool = Pool::new(64, 16).unwrap();
+        let (mut producer, mut consumer) = pool.pair().unwrap();
+        ::core::mem::drop(pool);
+        pump_pattern(&mut producer, &mut consumer, 100);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn unbounded_chains_segments() {
+        use ::std::io::{Read as _, Write as _};
+
+        let (mut producer, mut consumer) = new_unbounded(16, 16).unwrap();
+        let mut src = [0; 100];
+        for i in 0..100 {
+            src[usize::from(i)] = i;
+        }
+        producer.write_all(&src).unwrap();
+        assert_eq!(consumer.segments(), 7);
+
+        let mut dst = [0; 100];
+        consumer.read_exact(&mut dst).unwrap();
+        assert_eq!(dst, src);
+        assert!(consumer.is_empty());
+        assert_eq!(consumer.segments(), 7);
+
+        // Drained segments are reused instead of allocating new ones.
+        producer.write_all(&src).unwrap();
+        assert_eq!(consumer.segments(), 7);
+        consumer.read_exact(&mut dst).unwrap();
+        assert_eq!(dst, src);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn unbounded_across_threads() {
+        use ::core::iter::Iterator as _;
+
+        const TOTAL: usize = 100_000;
+
+        let (mut producer, mut consumer) = new_unbounded(64, 8).unwrap();
+        let thread = ::std::thread::spawn(move || {
+            let mut written = 0;
+            while written < TOTAL {
+                written += producer
+                    .slice(|buf| {
+                        let n = buf.len().min(13).min(TOTAL - written);
+                        for (i, b) in (written..).zip(&mut buf[..n]) {
+                            *b = u8::try_from(i % 251).unwrap();
+                        }
+                        Ok::<_, ()>(n)
+                    })
+                    .unwrap();
+            }
+        });
+
+        let mut read = 0;
+        while read < TOTAL {
+            read += consumer
+                .slice(|buf| {
+                    for (i, &b) in (read..).zip(buf) {
+                        assert_eq!(usize::from(b), i % 251);
+                    }
+                    Ok::<_, ()>(buf.len())
+                })
+                .unwrap();
+        }
+        thread.join().unwrap();
+        assert!(consumer.is_empty());
+    }
+
     #[test]
     fn new_rejects_bad_parameters() {
         assert!(matches!(new(0, 16), Err(BufferError::BadSize(0))));
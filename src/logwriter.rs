@@ -0,0 +1,279 @@
+//! A non-blocking log sink on an overwriting ring, see [`spawn`].
+//!
+//! The [`LogWriter`] is an [`io::Write`] for formatting log lines into, e.g.
+//! with [`writeln!`](::std::writeln). Lines go into a [`lossy`] ring once
+//! complete, without waiting for anything: a line that does not fit is
+//! dropped and counted, and while the drain thread is not copying out, new
+//! lines overwrite the oldest unread ones instead. The drain thread spawned
+//! alongside wakes every interval, or on [`flush`](io::Write::flush), and
+//! writes whatever it finds to the sink, e.g. a file or `stderr`.
+//!
+//! The sink only ever receives whole lines. Where an overwrite cut into a
+//! line, the drain thread skips ahead to the next one, so that line is
+//! counted as dropped too. Lines overwritten entirely are counted in bytes
+//! only, see [`LogHandle::dropped_bytes`].
+
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone as _;
+use ::core::convert::Infallible;
+use ::core::iter::Iterator as _;
+use ::core::marker::Send;
+use ::core::matches;
+use ::core::ops::Drop;
+use ::core::option::Option::{None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, AtomicUsize};
+use ::core::time::Duration;
+use ::std::io;
+use ::std::thread::{self, JoinHandle, Thread};
+
+use crate::BufferError;
+use crate::lossy;
+use crate::ordering::{Acquire, Relaxed, Release};
+
+/// Spawns a thread draining a ring of `size` bytes, a power of two, into
+/// `sink` at least every `interval`, returning the writer filling it and a
+/// handle to the thread.
+///
+/// # Errors
+///
+/// Returns an error when `size` is not a power of two, or when the
+/// allocation fails.
+///
+/// # Panics
+///
+/// Panics if the thread cannot be spawned, like [`thread::spawn`].
+#[inline]
+pub fn spawn<W: io::Write + Send + 'static>(
+    size: usize,
+    interval: Duration,
+    sink: W,
+) -> Result<(LogWriter, LogHandle<W>), BufferError> {
+    let (producer, consumer) = lossy::new(size, 1)?;
+    let shared = Arc::new(Shared {
+        closed: AtomicBool::new(false),
+        refused: AtomicUsize::new(0),
+        cut: AtomicUsize::new(0),
+        overwritten: AtomicUsize::new(0),
+    });
+    let drain = Drain {
+        consumer,
+        sink,
+        shared: Arc::clone(&shared),
+        seen: 0,
+        skipping: false,
+        scratch: Vec::new(),
+    };
+    let thread = thread::spawn(move || drain.run(interval));
+    let writer = LogWriter {
+        producer,
+        line: Vec::new(),
+        shared: Arc::clone(&shared),
+        drainer: thread.thread().clone(),
+    };
+    Ok((writer, LogHandle { thread, shared }))
+}
+
+#[derive(Debug)]
+struct Shared {
+    /// Set once the writer is dropped, for the drain thread to finish.
+    closed: AtomicBool,
+    /// Lines the ring had no room for.
+    refused: AtomicUsize,
+    /// Lines an overwrite cut into.
+    cut: AtomicUsize,
+    /// Bytes overwritten, as last seen by the drain thread.
+    overwritten: AtomicUsize,
+}
+
+/// The writing end of a log sink, see [`spawn`].
+#[derive(Debug)]
+pub struct LogWriter {
+    producer: lossy::Producer,
+    /// The line being formatted.
+    line: Vec<u8>,
+    shared: Arc<Shared>,
+    drainer: Thread,
+}
+
+impl LogWriter {
+    /// Moves the complete lines formatted so far into the ring, all of
+    /// them or, if they do not fit, none.
+    #[inline]
+    fn commit(&mut self, end: usize) {
+        let lines = &self.line[..end];
+        let n = self.producer.slices(|dst, len| {
+            if len < lines.len() {
+                return Ok::<_, Infallible>(0);
+            }
+            crate::tee::copy_prefix(&[lines], dst, lines.len());
+            Ok(lines.len())
+        });
+        if !matches!(n, Ok(n) if n == end) {
+            #[expect(clippy::naive_bytecount, reason = "dropping lines is the slow path")]
+            let count = lines.iter().filter(|&&b| b == b'\n').count();
+            self.shared.refused.fetch_add(count, Relaxed);
+        }
+        self.line.drain(..end);
+    }
+
+    /// Returns the number of lines dropped so far, see
+    /// [`LogHandle::dropped_lines`].
+    #[must_use]
+    #[inline]
+    pub fn dropped_lines(&self) -> usize {
+        self.shared.refused.load(Relaxed) + self.shared.cut.load(Relaxed)
+    }
+
+    /// Returns the number of bytes overwritten before the drain thread
+    /// copied them out.
+    #[must_use]
+    #[inline]
+    pub fn dropped_bytes(&self) -> usize {
+        self.producer.dropped()
+    }
+}
+
+impl io::Write for LogWriter {
+    /// Appends `src` to the line being formatted, moving the lines it
+    /// completes into the ring. Never blocks and never fails.
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        self.line.extend_from_slice(src);
+        if let Some(last) = src.iter().rposition(|&b| b == b'\n') {
+            let end = self.line.len() - (src.len() - last - 1);
+            self.commit(end);
+        }
+        Ok(src.len())
+    }
+
+    /// Completes the line being formatted, if any, and wakes the drain
+    /// thread.
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.line.push(b'\n');
+            self.commit(self.line.len());
+        }
+        self.drainer.unpark();
+        Ok(())
+    }
+}
+
+impl Drop for LogWriter {
+    #[inline]
+    fn drop(&mut self) {
+        let _ = io::Write::flush(self);
+        self.shared.closed.store(true, Release);
+        self.drainer.unpark();
+    }
+}
+
+/// The drain thread of a log sink, see [`spawn`].
+#[derive(Debug)]
+pub struct LogHandle<W> {
+    thread: JoinHandle<io::Result<W>>,
+    shared: Arc<Shared>,
+}
+
+impl<W> LogHandle<W> {
+    /// Returns the number of lines dropped so far, because the ring had no
+    /// room for them or an overwrite cut into them.
+    #[must_use]
+    #[inline]
+    pub fn dropped_lines(&self) -> usize {
+        self.shared.refused.load(Relaxed) + self.shared.cut.load(Relaxed)
+    }
+
+    /// Returns the number of bytes overwritten before they were copied
+    /// out, as of the last wakeup of the drain thread.
+    #[must_use]
+    #[inline]
+    pub fn dropped_bytes(&self) -> usize {
+        self.shared.overwritten.load(Relaxed)
+    }
+
+    /// Waits for the drain thread to write out the last lines once the
+    /// [`LogWriter`] is dropped, returning the sink.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of the sink, after which the drain thread
+    /// stopped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sink panicked.
+    #[inline]
+    pub fn join(self) -> io::Result<W> {
+        match self.thread.join() {
+            Ok(result) => result,
+            Err(payload) => ::std::panic::resume_unwind(payload),
+        }
+    }
+}
+
+struct Drain<W> {
+    consumer: lossy::Consumer,
+    sink: W,
+    shared: Arc<Shared>,
+    /// The bytes overwritten as of the last bytes copied out.
+    seen: usize,
+    /// Whether the bytes up to the next line are the tail of a cut line.
+    skipping: bool,
+    scratch: Vec<u8>,
+}
+
+impl<W: io::Write> Drain<W> {
+    fn run(mut self, interval: Duration) -> io::Result<W> {
+        loop {
+            let closed = self.shared.closed.load(Acquire);
+            while self.drain() != 0 {
+                self.sink.write_all(&self.scratch)?;
+            }
+            self.sink.flush()?;
+            if closed {
+                return Ok(self.sink);
+            }
+            thread::park_timeout(interval);
+        }
+    }
+
+    /// Copies the filled bytes out into `scratch`, leaving out the tail of
+    /// a cut line, and returns the number of bytes released.
+    fn drain(&mut self) -> usize {
+        let Drain {
+            consumer,
+            shared,
+            seen,
+            skipping,
+            scratch,
+            ..
+        } = self;
+        scratch.clear();
+        let n = consumer.slices_dropped(|src, len, dropped| {
+            if dropped != *seen {
+                *seen = dropped;
+                *skipping = true;
+                shared.overwritten.store(dropped, Relaxed);
+                shared.cut.fetch_add(1, Relaxed);
+            }
+            for &buf in src {
+                let mut buf = buf;
+                if *skipping {
+                    match buf.iter().position(|&b| b == b'\n') {
+                        None => continue,
+                        Some(end) => {
+                            *skipping = false;
+                            buf = &buf[end + 1..];
+                        }
+                    }
+                }
+                scratch.extend_from_slice(buf);
+            }
+            Ok::<_, Infallible>(len)
+        });
+        n.unwrap_or(0)
+    }
+}
@@ -0,0 +1,368 @@
+//! Rings that overwrite the oldest bytes instead of filling up.
+//!
+//! The [`Producer`] never finds the ring full: when it writes past the empty
+//! space, the oldest unread bytes are dropped and counted, see
+//! [`Producer::dropped`]. This is what flight-recorder style logging and
+//! telemetry capture need, keeping the most recent history.
+//!
+//! Overwriting is ruled out while the consumer's callback runs; the producer
+//! is then handed the empty space only, like a plain producer. Likewise a
+//! consumer call overlapping an overwriting producer call finds the ring
+//! empty. Neither half ever waits for the other.
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+#[cfg(feature = "std")]
+use ::core::cmp::Ord as _;
+use ::core::hint;
+use ::core::marker::PhantomData;
+use ::core::ops::FnMut;
+use ::core::option::Option::None;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, AtomicUsize};
+use ::crossbeam_utils::CachePadded;
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::ordering::{Acquire, Relaxed, Release, SeqCst};
+use crate::{
+    AlignedData, BufferError, ConsumerError, ProducerError, SendNotSyncZst, empty_ranges,
+    filled_ranges,
+};
+
+/// Creates a producer-consumer pair sharing an overwriting ring buffer of
+/// `size` bytes, aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    if !size.is_power_of_two() {
+        return Err(BufferError::BadSize(size));
+    }
+    if !align.is_power_of_two() {
+        return Err(BufferError::BadAlignment(align));
+    }
+
+    let buffer = Arc::new(Buffer {
+        read: CachePadded::new(AtomicUsize::new(0)),
+        write: CachePadded::new(AtomicUsize::new(0)),
+        reading: CachePadded::new(AtomicBool::new(false)),
+        overwriting: CachePadded::new(AtomicBool::new(false)),
+        dropped: AtomicUsize::new(0),
+        mask: size - 1,
+        data: AlignedData::new(size, align, None)?,
+    });
+    let producer = Producer {
+        buffer: Arc::clone(&buffer),
+        _notsync: PhantomData,
+    };
+    let consumer = Consumer {
+        buffer,
+        _notsync: PhantomData,
+    };
+    Ok((producer, consumer))
+}
+
+/// The state shared by the halves. The slices handed out over `data` are
+/// never aliased for the same reasons as for the plain buffer, with one
+/// addition: the producer is handed filled bytes only after winning the
+/// exchange of `overwriting` and `reading`. Both flags are stored before
+/// the other one is loaded, sequentially consistent, so at most one half
+/// sees the other's flag clear, and only then does it touch the filled
+/// region or `read`.
+#[derive(Debug)]
+struct Buffer {
+    read: CachePadded<AtomicUsize>,
+    write: CachePadded<AtomicUsize>,
+    /// Set by the consumer for the duration of its callback.
+    reading: CachePadded<AtomicBool>,
+    /// Set by the producer for the duration of a callback that may
+    /// overwrite filled bytes.
+    overwriting: CachePadded<AtomicBool>,
+    /// Bytes overwritten before they were read.
+    dropped: AtomicUsize,
+    mask: usize,
+    data: AlignedData,
+}
+
+/// The writing half of an overwriting ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    buffer: Arc<Buffer>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Producer {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn produce_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let buffer = &*self.buffer;
+        let size = buffer.data.len();
+        let w = buffer.write.load(Relaxed);
+
+        buffer.overwriting.store(true, SeqCst);
+        let overwrite = !buffer.reading.load(SeqCst);
+        if !overwrite {
+            buffer.overwriting.store(false, SeqCst);
+        }
+        let r = buffer.read.load(Acquire);
+
+        // With the consumer shut out, all of the ring is up for the taking.
+        let (mut ranges, mut len) =
+            empty_ranges(size, buffer.mask, if overwrite { w } else { r }, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map the empty region only, which the consumer does
+        //         not read at the same time, or the whole ring while the
+        //         consumer is shut out.
+        let bufs = unsafe { buffer.data.slices_mut(ranges) };
+
+        let result = match f(bufs, len) {
+            Ok(n) if n <= len => {
+                let end = w.wrapping_add(n);
+                let lost = end.wrapping_sub(r).saturating_sub(size);
+                if lost != 0 {
+                    buffer.read.store(r.wrapping_add(lost), Relaxed);
+                    buffer.dropped.fetch_add(lost, Relaxed);
+                }
+                if n != 0 {
+                    buffer.write.store(end, Release);
+                }
+                Ok(n)
+            }
+            Ok(n) => {
+                hint::cold_path();
+                Err(ProducerError::InvalidCount { n, len })
+            }
+            Err(e) => Err(ProducerError::Callback(e)),
+        };
+        if overwrite {
+            buffer.overwriting.store(false, SeqCst);
+        }
+        result
+    }
+
+    /// Fills the ring: calls the passed closure with a pair of `&mut [u8]`
+    /// mapping the empty space, or all of the ring if the consumer is not
+    /// reading at the moment, and their total length. The closure must
+    /// return the number of bytes written; bytes written past the empty
+    /// space replace the oldest unread ones.
+    ///
+    /// Past the empty space, the closure must only write the bytes it
+    /// commits, the others remain unread data.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given. Nothing is
+    /// committed on error.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(false, |mut bufs, len| f(&mut bufs, len))
+    }
+
+    /// Fills the ring: calls the passed closure with a single `&mut [u8]`
+    /// mapping the contiguous part of what [`Producer::slices`] offers.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given. Nothing is
+    /// committed on error.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(true, |[buf, _], _| f(buf))
+    }
+
+    /// Returns the number of bytes overwritten before they were read.
+    #[must_use]
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.buffer.dropped.load(Relaxed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Write for Producer {
+    /// Writes as much of `src` as fits into the ring, overwriting the oldest
+    /// bytes unless the consumer is reading.
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        let n = self.slices(|dst, len| {
+            let n = src.len().min(len);
+            let mut copied = 0;
+            for buf in dst {
+                let k = buf.len().min(n - copied);
+                buf[..k].copy_from_slice(&src[copied..copied + k]);
+                copied += k;
+            }
+            Ok::<_, io::Error>(n)
+        });
+        n.map_err(|e| match e {
+            ProducerError::Callback(e) => e,
+            e @ (ProducerError::InvalidCount { .. }
+            | ProducerError::TornFrame { .. }
+            | ProducerError::Poisoned) => io::Error::other(e),
+        })
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reading half of an overwriting ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    buffer: Arc<Buffer>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Consumer {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn consume_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&[u8]; 2], usize, usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+
+        buffer.reading.store(true, SeqCst);
+        if buffer.overwriting.load(SeqCst) {
+            buffer.reading.store(false, SeqCst);
+            let dropped = buffer.dropped.load(Relaxed);
+            return f([&[], &[]], 0, dropped).map_err(ConsumerError::Callback);
+        }
+        let r = buffer.read.load(Relaxed);
+        // Read after winning the exchange like `read`, so it counts exactly
+        // the bytes lost before `r`.
+        let dropped = buffer.dropped.load(Relaxed);
+        let w = buffer.write.load(Acquire);
+
+        let (mut ranges, mut len) = filled_ranges(buffer.data.len(), buffer.mask, r, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map the filled region only, which the producer
+        //         neither writes to nor frees while `reading` is set.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+
+        let result = match f(bufs, len, dropped) {
+            Ok(n) if n <= len => {
+                if n != 0 {
+                    buffer.read.store(r.wrapping_add(n), Release);
+                }
+                Ok(n)
+            }
+            Ok(n) => {
+                hint::cold_path();
+                Err(ConsumerError::InvalidCount { n, len })
+            }
+            Err(e) => Err(ConsumerError::Callback(e)),
+        };
+        buffer.reading.store(false, SeqCst);
+        result
+    }
+
+    /// Drains the ring: calls the passed closure with a pair of `&[u8]`
+    /// mapping the filled space and their total length. The closure must
+    /// return the number of bytes read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len, _| f(&bufs, len))
+    }
+
+    /// Drains the ring like [`Consumer::slices`], also passing the closure
+    /// the count of [`Consumer::dropped`] as of the first byte handed out.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub(crate) fn slices_dropped<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize, usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len, dropped| f(&bufs, len, dropped))
+    }
+
+    /// Drains the ring: calls the passed closure with a single `&[u8]`
+    /// mapping the contiguous part of the filled space.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _, _| f(buf))
+    }
+
+    /// Returns the number of bytes overwritten before they were read.
+    #[must_use]
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.buffer.dropped.load(Relaxed)
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let n = self.slices(|src, len| {
+            let n = dst.len().min(len);
+            let mut copied = 0;
+            for buf in src {
+                let k = buf.len().min(n - copied);
+                dst[copied..copied + k].copy_from_slice(&buf[..k]);
+                copied += k;
+            }
+            Ok::<_, io::Error>(n)
+        });
+        n.map_err(|e| match e {
+            ConsumerError::Callback(e) => e,
+            e @ (ConsumerError::InvalidCount { .. }
+            | ConsumerError::TornFrame { .. }
+            | ConsumerError::Poisoned) => io::Error::other(e),
+        })
+    }
+}
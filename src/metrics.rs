@@ -0,0 +1,126 @@
+//! Exporting the [statistics](crate::stats) of the halves as metrics, see
+//! [`Producer::export`] and [`Consumer::export`], enabled by the `metrics`
+//! feature.
+//!
+//! Exporting passes every metric of a half to a [`Recorder`], keyed by its
+//! name, a ring label chosen by the caller and the half's role, so each
+//! ring gets its own series on a dashboard. Counters are cumulative and
+//! passed as absolute values. Call it periodically, e.g. from the scrape
+//! handler of a Prometheus endpoint; nothing is recorded in between, so
+//! the halves' hot paths stay free of calls into the recorder.
+//!
+//! The recorder's methods match the handles of the `metrics` crate, so
+//! forwarding to its global recorder is short:
+//!
+//! ```text
+//! struct Global;
+//!
+//! impl bytering::metrics::Recorder for Global {
+//!     fn counter(&self, key: &Key<'_>, value: u64) {
+//!         metrics::counter!(key.name, "ring" => key.ring.to_owned(), "half" => key.half)
+//!             .absolute(value);
+//!     }
+//!     fn gauge(&self, key: &Key<'_>, value: f64) {
+//!         metrics::gauge!(key.name, "ring" => key.ring.to_owned(), "half" => key.half).set(value);
+//!     }
+//!     fn histogram(&self, key: &Key<'_>, value: f64) {
+//!         metrics::histogram!(key.name, "ring" => key.ring.to_owned(), "half" => key.half)
+//!             .record(value);
+//!     }
+//! }
+//! ```
+
+use ::core::clone::Clone;
+use ::core::marker::{Copy, Send, Sync};
+
+use crate::ordering::Relaxed;
+use crate::stats::Stats;
+use crate::{Consumer, Producer};
+
+/// Bytes committed or released, a counter.
+pub const BYTES: &str = "bytering_bytes_total";
+/// Commits or releases of at least one byte, a counter.
+pub const COMMITS: &str = "bytering_commits_total";
+/// Commits or releases reaching the end of the buffer, a counter.
+pub const WRAPS: &str = "bytering_wraps_total";
+/// Commits or releases of nothing, a counter.
+pub const STALLS: &str = "bytering_stalls_total";
+/// The bytes filled when exporting, a gauge.
+pub const OCCUPANCY: &str = "bytering_occupancy_bytes";
+/// The most bytes the half saw filled, a gauge.
+pub const HIGH_WATER: &str = "bytering_high_water_bytes";
+/// The size of the buffer, a gauge.
+pub const CAPACITY: &str = "bytering_capacity_bytes";
+/// The filled fraction of the buffer, sampled at every export, a
+/// histogram.
+pub const FILL_RATIO: &str = "bytering_fill_ratio";
+
+/// The key of a metric.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Key<'a> {
+    /// The metric's name, one of the constants of this module.
+    pub name: &'static str,
+    /// The label of the ring, as passed to the export.
+    pub ring: &'a str,
+    /// The half exporting, `"producer"` or `"consumer"`.
+    pub half: &'static str,
+}
+
+/// Receives the metrics of a half, see the [module docs](self).
+pub trait Recorder: Send + Sync {
+    /// Sets the counter `key` to `value`.
+    fn counter(&self, key: &Key<'_>, value: u64);
+
+    /// Sets the gauge `key` to `value`.
+    fn gauge(&self, key: &Key<'_>, value: f64);
+
+    /// Records `value` into the histogram `key`.
+    fn histogram(&self, key: &Key<'_>, value: f64);
+}
+
+#[expect(
+    clippy::cast_precision_loss,
+    reason = "gauges are approximate beyond 2⁵³ bytes"
+)]
+#[inline]
+fn export(
+    recorder: &dyn Recorder,
+    ring: &str,
+    half: &'static str,
+    stats: Stats,
+    size: usize,
+    filled: usize,
+) {
+    let key = |name| Key { name, ring, half };
+    recorder.counter(&key(BYTES), stats.bytes);
+    recorder.counter(&key(COMMITS), stats.commits);
+    recorder.counter(&key(WRAPS), stats.wraps);
+    recorder.counter(&key(STALLS), stats.stalls);
+    recorder.gauge(&key(OCCUPANCY), filled as f64);
+    recorder.gauge(&key(HIGH_WATER), stats.high_water as f64);
+    recorder.gauge(&key(CAPACITY), size as f64);
+    recorder.histogram(&key(FILL_RATIO), filled as f64 / size as f64);
+}
+
+impl Producer {
+    /// Passes the producer's metrics to `recorder`, labeled `ring`, see the
+    /// [module docs](crate::metrics).
+    #[inline]
+    pub fn export(&self, ring: &str, recorder: &dyn Recorder) {
+        let size = self.buffer.data.len();
+        let filled = size - self.free_len();
+        export(recorder, ring, "producer", self.stats(), size, filled);
+    }
+}
+
+impl Consumer {
+    /// Passes the consumer's metrics to `recorder`, labeled `ring`, see the
+    /// [module docs](crate::metrics).
+    #[inline]
+    pub fn export(&self, ring: &str, recorder: &dyn Recorder) {
+        let size = self.buffer.data.len();
+        let r = self.buffer.read.load(Relaxed);
+        let filled = self.buffer.write.load(Relaxed).wrapping_sub(r);
+        export(recorder, ring, "consumer", self.stats(), size, filled);
+    }
+}
@@ -0,0 +1,228 @@
+//! Memory mappings backing the buffer in place of the global allocator.
+//!
+//! Cross-process blocking, see [`wait`] and [`wake`], is only implemented
+//! on Linux and Android, with futexes. Other systems have no blocking wait
+//! on a shared word here: waiters sleep for a millisecond and check again,
+//! and waking does nothing. Named semaphores (`sem_open`) or events
+//! (`CreateEventW`) would close this gap; they are not implemented.
+
+use ::core::clone::Clone;
+#[cfg(unix)]
+use ::core::cmp::Ord as _;
+use ::core::cmp::{Eq, PartialEq};
+use ::core::ffi::CStr;
+use ::core::marker::{Copy, Send};
+use ::core::ops::Drop;
+use ::core::option::Option::{self, None};
+use ::core::ptr::NonNull;
+use ::core::result::Result::{self, Ok};
+use ::core::sync::atomic::AtomicU32;
+use ::core::time::Duration;
+
+use crate::BufferError;
+
+#[cfg(unix)]
+mod unix;
+#[cfg(unix)]
+use unix as sys;
+
+#[cfg(windows)]
+mod windows;
+#[cfg(windows)]
+use windows as sys;
+
+/// An owned memory mapping, unmapped on drop.
+#[derive(Debug)]
+pub struct Mapping {
+    ptr: NonNull<u8>,
+    len: usize,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Mirrored,
+    Aligned,
+    Shared,
+}
+
+// SAFETY: Send is safe because the mapping is exclusively owned and the
+//         pointer cannot be accessed directly.
+unsafe impl Send for Mapping {}
+
+impl Mapping {
+    /// Maps `size` bytes twice, back-to-back, so that the byte at offset
+    /// `i + size` aliases the byte at offset `i`. `size` must be a multiple
+    /// of [`granularity`].
+    #[inline]
+    pub fn mirrored(size: usize) -> Result<Self, BufferError> {
+        let (ptr, len) = sys::map_mirrored(size)?;
+        Ok(Mapping {
+            ptr,
+            len,
+            kind: Kind::Mirrored,
+        })
+    }
+
+    /// Maps at least `size` zeroed bytes aligned to `align`, which must be a
+    /// power of two larger than [`granularity`].
+    #[inline]
+    pub fn aligned(size: usize, align: usize) -> Result<Self, BufferError> {
+        let (ptr, len) = sys::map_aligned(size, align)?;
+        Ok(Mapping {
+            ptr,
+            len,
+            kind: Kind::Aligned,
+        })
+    }
+
+    /// Maps the named shared memory object, creating it with `size` bytes
+    /// if passed, or mapping all of an existing one otherwise.
+    #[inline]
+    pub fn shared(name: &CStr, size: Option<usize>) -> Result<Self, BufferError> {
+        let (ptr, len) = sys::map_shared(name, size, true)?;
+        Ok(Mapping {
+            ptr,
+            len,
+            kind: Kind::Shared,
+        })
+    }
+
+    /// Maps all of the existing named shared memory object read-only.
+    /// Writing to the mapping faults.
+    #[inline]
+    pub fn shared_read_only(name: &CStr) -> Result<Self, BufferError> {
+        let (ptr, len) = sys::map_shared(name, None, false)?;
+        Ok(Mapping {
+            ptr,
+            len,
+            kind: Kind::Shared,
+        })
+    }
+
+    /// Maps the first `len` bytes of the open file `fd`, shared with the
+    /// file, for reading and, if `writable`, writing.
+    #[cfg(unix)]
+    #[inline]
+    pub fn file(fd: i32, len: usize, writable: bool) -> Result<Self, BufferError> {
+        let (ptr, len) = sys::map_file(fd, len, writable)?;
+        Ok(Mapping {
+            ptr,
+            len,
+            kind: Kind::Shared,
+        })
+    }
+
+    /// Writes the modified pages among the `len` bytes at `offset` back to
+    /// the file and waits for the write to complete.
+    #[cfg(unix)]
+    #[inline]
+    pub fn sync(&self, offset: usize, len: usize) -> Result<(), BufferError> {
+        let start = offset - offset % granularity();
+        let len = (offset + len).min(self.len) - start;
+        if len == 0 {
+            return Ok(());
+        }
+        // SAFETY: the range lies within the mapping and starts on a page.
+        unsafe { sys::sync(self.ptr.add(start), len) }
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn ptr(&self) -> NonNull<u8> {
+        self.ptr
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    #[must_use]
+    #[inline]
+    pub fn is_mirrored(&self) -> bool {
+        self.kind == Kind::Mirrored
+    }
+}
+
+impl Drop for Mapping {
+    #[inline]
+    fn drop(&mut self) {
+        // SAFETY: ptr and len describe a live mapping created by `sys` and
+        //         owned by self.
+        unsafe { sys::unmap(self.ptr, self.len, self.kind) }
+    }
+}
+
+/// Hints the operating system that the contents of `len` bytes at `ptr` are
+/// no longer needed and their pages can be reclaimed. `shared` selects the
+/// variant for mirrored mappings, whose pages are shared between the views.
+///
+/// # Safety
+/// `ptr` and `len` must describe whole pages of memory owned by the caller
+/// that nothing references while the contents change.
+#[inline]
+pub unsafe fn discard(ptr: NonNull<u8>, len: usize, shared: bool) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { sys::discard(ptr, len, shared) }
+}
+
+/// Frees the disk space of `len` bytes at `offset` of the open file `fd`,
+/// which read back as zeros then, mapped or not. Only Linux and Android
+/// can punch holes into files.
+#[cfg(unix)]
+#[inline]
+pub fn punch_hole(fd: i32, offset: usize, len: usize) -> Result<(), BufferError> {
+    sys::punch_hole(fd, offset, len)
+}
+
+/// Removes the name of a shared memory object, see [`Mapping::shared`].
+/// Existing mappings stay valid. Does nothing on Windows, where objects
+/// live as long as they are mapped.
+#[inline]
+pub fn unlink_shared(name: &CStr) -> Result<(), BufferError> {
+    sys::unlink_shared(name)
+}
+
+/// Blocks until `word`, which may be shared with other processes, no
+/// longer holds `expected` and another process or thread calls [`wake`],
+/// or until `timeout` passes. May return early for no reason.
+///
+/// Only Linux and Android can block on a word; elsewhere this sleeps for a
+/// millisecond at most and callers poll.
+#[inline]
+pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    sys::wait(word, expected, timeout);
+}
+
+/// Wakes all processes and threads blocked in [`wait`] on `word`.
+///
+/// Does nothing outside Linux and Android, where waiters poll.
+#[inline]
+pub fn wake(word: &AtomicU32) {
+    sys::wake(word);
+}
+
+/// Returns the id of the calling process.
+#[must_use]
+#[inline]
+pub fn process_id() -> u32 {
+    sys::process_id()
+}
+
+/// Returns whether the process `pid` is running. Ids are reused, so a
+/// process started since `pid` exited deceives this.
+#[must_use]
+#[inline]
+pub fn process_alive(pid: u32) -> bool {
+    sys::process_alive(pid)
+}
+
+/// The size mirrored mappings must be a multiple of. Alignments up to this
+/// are provided by the global allocator.
+#[must_use]
+#[inline]
+pub fn granularity() -> usize {
+    sys::granularity()
+}
@@ -0,0 +1,458 @@
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use ::core::cmp::Ord as _;
+use ::core::convert::TryFrom as _;
+use ::core::ffi::CStr;
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+use ::core::matches;
+use ::core::mem::MaybeUninit;
+use ::core::option::Option::{self, None, Some};
+use ::core::ptr::{self, NonNull};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::AtomicU32;
+use ::core::time::Duration;
+
+use super::Kind;
+use crate::BufferError;
+
+#[must_use]
+#[inline]
+pub fn granularity() -> usize {
+    // SAFETY: sysconf has no preconditions.
+    let size = unsafe { ::libc::sysconf(::libc::_SC_PAGESIZE) };
+    usize::try_from(size).unwrap_or(4096)
+}
+
+pub fn map_mirrored(size: usize) -> Result<(NonNull<u8>, usize), BufferError> {
+    let Some(len) = size.checked_mul(2) else {
+        return Err(BufferError::BadSize(size));
+    };
+    let Ok(off) = ::libc::off_t::try_from(size) else {
+        return Err(BufferError::BadSize(size));
+    };
+
+    let fd = anonymous_fd()?;
+
+    // SAFETY: fd is a valid, owned file descriptor.
+    if unsafe { ::libc::ftruncate(fd, off) } != 0 {
+        return Err(close_with(fd, last_error()));
+    }
+
+    // Reserve the address range for both views first so that nobody else
+    // can map into the gap between them.
+    // SAFETY: an anonymous PROT_NONE mapping at an address of the
+    //         kernel's choosing has no preconditions.
+    let base = unsafe {
+        ::libc::mmap(
+            ptr::null_mut(),
+            len,
+            ::libc::PROT_NONE,
+            ::libc::MAP_PRIVATE | ::libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if base == ::libc::MAP_FAILED {
+        return Err(close_with(fd, last_error()));
+    }
+
+    for offset in [0, size] {
+        // SAFETY: both views replace parts of the reservation made above
+        //         with MAP_FIXED, which is owned by this function.
+        let view = unsafe {
+            ::libc::mmap(
+                base.cast::<u8>().add(offset).cast(),
+                size,
+                ::libc::PROT_READ | ::libc::PROT_WRITE,
+                ::libc::MAP_SHARED | ::libc::MAP_FIXED,
+                fd,
+                0,
+            )
+        };
+        if view == ::libc::MAP_FAILED {
+            let err = last_error();
+            // SAFETY: base and len describe the reservation made above.
+            unsafe { ::libc::munmap(base, len) };
+            return Err(close_with(fd, err));
+        }
+    }
+
+    // The views keep the memory alive.
+    // SAFETY: fd is a valid, owned file descriptor.
+    unsafe { ::libc::close(fd) };
+
+    NonNull::new(base.cast::<u8>())
+        .map(|ptr| (ptr, len))
+        .ok_or(BufferError::AllocFailed)
+}
+
+pub fn map_aligned(size: usize, align: usize) -> Result<(NonNull<u8>, usize), BufferError> {
+    let page = granularity();
+    // Page-rounded, so that the trimmed tail starts at a page boundary.
+    let Some(size) = size.checked_next_multiple_of(page) else {
+        return Err(BufferError::BadSize(size));
+    };
+    let Some(len) = size.checked_add(align) else {
+        return Err(BufferError::BadSize(size));
+    };
+
+    // Over-allocate by `align` and trim the unaligned head and the tail.
+    // SAFETY: an anonymous mapping at an address of the kernel's choosing
+    //         has no preconditions.
+    let base = unsafe {
+        ::libc::mmap(
+            ptr::null_mut(),
+            len,
+            ::libc::PROT_READ | ::libc::PROT_WRITE,
+            ::libc::MAP_PRIVATE | ::libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if base == ::libc::MAP_FAILED {
+        return Err(last_error());
+    }
+
+    let head = base.addr().wrapping_neg() & (align - 1);
+    let tail = len - head - size;
+    // SAFETY: head and tail lie within the mapping made above and are page
+    //         aligned, because both `align` and `size` are multiples of the
+    //         page size.
+    let aligned = unsafe {
+        let aligned = base.cast::<u8>().add(head);
+        if head != 0 {
+            ::libc::munmap(base, head);
+        }
+        if tail != 0 {
+            ::libc::munmap(aligned.add(size).cast(), tail);
+        }
+        aligned
+    };
+
+    NonNull::new(aligned)
+        .map(|ptr| (ptr, size))
+        .ok_or(BufferError::AllocFailed)
+}
+
+pub fn map_shared(
+    name: &CStr,
+    size: Option<usize>,
+    writable: bool,
+) -> Result<(NonNull<u8>, usize), BufferError> {
+    let (flags, prot) = match size {
+        Some(_) => (
+            ::libc::O_RDWR | ::libc::O_CREAT | ::libc::O_EXCL,
+            ::libc::PROT_READ | ::libc::PROT_WRITE,
+        ),
+        None if writable => (::libc::O_RDWR, ::libc::PROT_READ | ::libc::PROT_WRITE),
+        None => (::libc::O_RDONLY, ::libc::PROT_READ),
+    };
+    // SAFETY: the name is a NUL-terminated string.
+    let fd = unsafe { ::libc::shm_open(name.as_ptr(), flags, 0o600 as ::libc::c_uint) };
+    if fd < 0 {
+        return Err(last_error());
+    }
+
+    let len = if let Some(size) = size {
+        let Ok(off) = ::libc::off_t::try_from(size) else {
+            return Err(unlink_with(
+                name,
+                close_with(fd, BufferError::BadSize(size)),
+            ));
+        };
+        // SAFETY: fd is a valid, owned file descriptor.
+        if unsafe { ::libc::ftruncate(fd, off) } != 0 {
+            return Err(unlink_with(name, close_with(fd, last_error())));
+        }
+        size
+    } else {
+        let mut stat = MaybeUninit::<::libc::stat>::uninit();
+        // SAFETY: fd is a valid, owned file descriptor, stat is writable.
+        if unsafe { ::libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+            return Err(close_with(fd, last_error()));
+        }
+        // SAFETY: fstat succeeded and initialized stat.
+        let size = unsafe { stat.assume_init() }.st_size;
+        match usize::try_from(size) {
+            Ok(size) if size != 0 => size,
+            _ => return Err(close_with(fd, BufferError::BadSize(0))),
+        }
+    };
+
+    // SAFETY: a shared mapping of an owned file descriptor at an address of
+    //         the kernel's choosing has no preconditions.
+    let base = unsafe { ::libc::mmap(ptr::null_mut(), len, prot, ::libc::MAP_SHARED, fd, 0) };
+    if base == ::libc::MAP_FAILED {
+        let err = close_with(fd, last_error());
+        return Err(if size.is_some() {
+            unlink_with(name, err)
+        } else {
+            err
+        });
+    }
+
+    // The mapping keeps the memory alive.
+    // SAFETY: fd is a valid, owned file descriptor.
+    unsafe { ::libc::close(fd) };
+
+    NonNull::new(base.cast::<u8>())
+        .map(|ptr| (ptr, len))
+        .ok_or(BufferError::AllocFailed)
+}
+
+pub fn map_file(
+    fd: ::libc::c_int,
+    len: usize,
+    writable: bool,
+) -> Result<(NonNull<u8>, usize), BufferError> {
+    let prot = if writable {
+        ::libc::PROT_READ | ::libc::PROT_WRITE
+    } else {
+        ::libc::PROT_READ
+    };
+    // SAFETY: a shared mapping of a file descriptor at an address of the
+    //         kernel's choosing has no preconditions.
+    let base = unsafe { ::libc::mmap(ptr::null_mut(), len, prot, ::libc::MAP_SHARED, fd, 0) };
+    if base == ::libc::MAP_FAILED {
+        return Err(last_error());
+    }
+    NonNull::new(base.cast::<u8>())
+        .map(|ptr| (ptr, len))
+        .ok_or(BufferError::AllocFailed)
+}
+
+/// # Safety
+/// `ptr` and `len` must lie within a live mapping, `ptr` page aligned.
+pub unsafe fn sync(ptr: NonNull<u8>, len: usize) -> Result<(), BufferError> {
+    // SAFETY: guaranteed by the caller.
+    if unsafe { ::libc::msync(ptr.as_ptr().cast(), len, ::libc::MS_SYNC) } != 0 {
+        return Err(last_error());
+    }
+    Ok(())
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn punch_hole(fd: ::libc::c_int, offset: usize, len: usize) -> Result<(), BufferError> {
+    let (Ok(off), Ok(len)) = (
+        ::libc::off_t::try_from(offset),
+        ::libc::off_t::try_from(len),
+    ) else {
+        return Err(BufferError::BadSize(len));
+    };
+    let mode = ::libc::FALLOC_FL_PUNCH_HOLE | ::libc::FALLOC_FL_KEEP_SIZE;
+    // SAFETY: fallocate has no memory safety preconditions.
+    if unsafe { ::libc::fallocate(fd, mode, off, len) } != 0 {
+        return Err(last_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn punch_hole(_fd: ::libc::c_int, _offset: usize, _len: usize) -> Result<(), BufferError> {
+    Err(BufferError::MapFailed(::libc::EOPNOTSUPP))
+}
+
+pub fn unlink_shared(name: &CStr) -> Result<(), BufferError> {
+    // SAFETY: the name is a NUL-terminated string.
+    if unsafe { ::libc::shm_unlink(name.as_ptr()) } != 0 {
+        return Err(last_error());
+    }
+    Ok(())
+}
+
+/// # Safety
+/// `ptr` and `len` must describe a live mapping returned by this module.
+pub unsafe fn unmap(ptr: NonNull<u8>, len: usize, _kind: Kind) {
+    // SAFETY: guaranteed by the caller.
+    unsafe { ::libc::munmap(ptr.as_ptr().cast(), len) };
+}
+
+/// # Safety
+/// `ptr` and `len` must describe whole pages of memory owned by the caller.
+pub unsafe fn discard(ptr: NonNull<u8>, len: usize, shared: bool) {
+    // DONTNEED only drops this process' view of shared memory; freeing the
+    // pages of the backing file takes REMOVE.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    let advice = if shared {
+        ::libc::MADV_REMOVE
+    } else {
+        ::libc::MADV_DONTNEED
+    };
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    let advice = {
+        let _ = shared;
+        ::libc::MADV_DONTNEED
+    };
+
+    // This is only a hint, failure is of no consequence.
+    // SAFETY: guaranteed by the caller.
+    unsafe { ::libc::madvise(ptr.as_ptr().cast(), len, advice) };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn anonymous_fd() -> Result<::libc::c_int, BufferError> {
+    // SAFETY: the name is a NUL-terminated string.
+    let fd = unsafe { ::libc::memfd_create(c"bytering".as_ptr(), ::libc::MFD_CLOEXEC) };
+    if fd < 0 {
+        return Err(last_error());
+    }
+    Ok(fd)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn anonymous_fd() -> Result<::libc::c_int, BufferError> {
+    use crate::ordering::Relaxed;
+    use ::core::sync::atomic::AtomicUsize;
+
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+    // SAFETY: getpid has no preconditions.
+    let pid = unsafe { ::libc::getpid() };
+    loop {
+        let n = COUNTER.fetch_add(1, Relaxed);
+        let name = ::alloc::format!("/bytering.{pid}.{n}\0");
+        // SAFETY: the name is a NUL-terminated string.
+        let fd = unsafe {
+            ::libc::shm_open(
+                name.as_ptr().cast(),
+                ::libc::O_RDWR | ::libc::O_CREAT | ::libc::O_EXCL,
+                0o600 as ::libc::c_uint,
+            )
+        };
+        if fd < 0 {
+            let err = last_error();
+            if matches!(err, BufferError::MapFailed(code) if code == ::libc::EEXIST) {
+                continue;
+            }
+            return Err(err);
+        }
+        // The object stays alive as long as it is open or mapped.
+        // SAFETY: the name is a NUL-terminated string.
+        unsafe { ::libc::shm_unlink(name.as_ptr().cast()) };
+        return Ok(fd);
+    }
+}
+
+fn close_with(fd: ::libc::c_int, err: BufferError) -> BufferError {
+    // SAFETY: fd is a valid, owned file descriptor.
+    unsafe { ::libc::close(fd) };
+    err
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    #[allow(
+        clippy::unnecessary_fallible_conversions,
+        reason = "`c_long` is 32 bits wide on some targets"
+    )]
+    let timeout = timeout.map(|timeout| ::libc::timespec {
+        tv_sec: ::libc::time_t::try_from(timeout.as_secs()).unwrap_or(::libc::time_t::MAX),
+        tv_nsec: ::libc::c_long::try_from(timeout.subsec_nanos()).unwrap_or(0),
+    });
+    let timeout = timeout.as_ref().map_or(ptr::null(), ptr::from_ref);
+    // Without FUTEX_PRIVATE_FLAG the word may be shared between processes.
+    // Errors are spurious wake ups to the caller.
+    // SAFETY: word is a valid, aligned 32-bit atomic for the duration of
+    //         the call, timeout null or a valid timespec.
+    unsafe {
+        ::libc::syscall(
+            ::libc::SYS_futex,
+            word.as_ptr(),
+            ::libc::FUTEX_WAIT,
+            expected,
+            timeout,
+        )
+    };
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn wake(word: &AtomicU32) {
+    // SAFETY: word is a valid, aligned 32-bit atomic for the duration of
+    //         the call.
+    unsafe {
+        ::libc::syscall(
+            ::libc::SYS_futex,
+            word.as_ptr(),
+            ::libc::FUTEX_WAKE,
+            ::libc::c_int::MAX,
+        )
+    };
+}
+
+/// Not a blocking wait: sleeps for a millisecond at most, see the
+/// [module docs](super).
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    const POLL: Duration = Duration::from_millis(1);
+
+    if word.load(crate::ordering::Relaxed) != expected {
+        return;
+    }
+    let nap = timeout.map_or(POLL, |timeout| timeout.min(POLL));
+    #[allow(
+        clippy::unnecessary_fallible_conversions,
+        reason = "`c_long` is 32 bits wide on some targets"
+    )]
+    let nap = ::libc::timespec {
+        tv_sec: 0,
+        tv_nsec: ::libc::c_long::try_from(nap.subsec_nanos()).unwrap_or(0),
+    };
+    // Interruptions are spurious wake ups to the caller.
+    // SAFETY: nap is a valid timespec, the remainder is not asked for.
+    unsafe { ::libc::nanosleep(&nap, ptr::null_mut()) };
+}
+
+/// Waiters poll, see [`wait`].
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn wake(_word: &AtomicU32) {}
+
+pub fn process_id() -> u32 {
+    // SAFETY: getpid has no preconditions.
+    let pid = unsafe { ::libc::getpid() };
+    u32::try_from(pid).unwrap_or(0)
+}
+
+pub fn process_alive(pid: u32) -> bool {
+    let Ok(pid) = ::libc::pid_t::try_from(pid) else {
+        return false;
+    };
+    // Signal 0 only checks whether the process exists; it may exist but
+    // belong to another user.
+    // SAFETY: kill with signal 0 has no effect beyond its result.
+    let found = unsafe { ::libc::kill(pid, 0) } == 0;
+    found || ::core::matches!(last_error(), BufferError::MapFailed(code) if code == ::libc::EPERM)
+}
+
+fn unlink_with(name: &CStr, err: BufferError) -> BufferError {
+    // SAFETY: the name is a NUL-terminated string.
+    unsafe { ::libc::shm_unlink(name.as_ptr()) };
+    err
+}
+
+fn last_error() -> BufferError {
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "emscripten"))]
+    // SAFETY: errno is thread-local and always readable.
+    let code = unsafe { *::libc::__errno_location() };
+    #[cfg(any(
+        target_vendor = "apple",
+        target_os = "freebsd",
+        target_os = "dragonfly"
+    ))]
+    // SAFETY: errno is thread-local and always readable.
+    let code = unsafe { *::libc::__error() };
+    #[cfg(any(target_os = "openbsd", target_os = "netbsd"))]
+    // SAFETY: errno is thread-local and always readable.
+    let code = unsafe { *::libc::__errno() };
+    #[cfg(not(any(
+        target_os = "linux",
+        target_os = "android",
+        target_os = "emscripten",
+        target_vendor = "apple",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd",
+    )))]
+    let code = 0;
+
+    BufferError::MapFailed(code)
+}
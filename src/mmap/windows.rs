@@ -0,0 +1,397 @@
+use ::alloc::vec::Vec;
+use ::core::ffi::{CStr, c_void};
+use ::core::iter::Iterator as _;
+use ::core::option::Option::{self, None, Some};
+use ::core::ptr::{self, NonNull};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::AtomicU32;
+use ::core::time::Duration;
+
+use super::Kind;
+use crate::BufferError;
+use crate::ordering::Relaxed;
+
+type Handle = *mut c_void;
+
+const PAGE_NOACCESS: u32 = 0x01;
+const PAGE_READWRITE: u32 = 0x04;
+const MEM_COMMIT: u32 = 0x1000;
+const MEM_RESERVE: u32 = 0x2000;
+const MEM_RELEASE: u32 = 0x8000;
+const MEM_RESET: u32 = 0x0008_0000;
+const FILE_MAP_WRITE: u32 = 0x0002;
+const FILE_MAP_READ: u32 = 0x0004;
+const ERROR_ACCESS_DENIED: u32 = 5;
+const ERROR_INVALID_NAME: u32 = 123;
+const ERROR_ALREADY_EXISTS: u32 = 183;
+const PROCESS_QUERY_LIMITED_INFORMATION: u32 = 0x1000;
+const STILL_ACTIVE: u32 = 259;
+
+/// Another thread may map into the address range between releasing the
+/// reservation and mapping the views. Retry a few times before giving up.
+const MAP_ATTEMPTS: usize = 8;
+
+#[repr(C)]
+struct SystemInfo {
+    oem_id: u32,
+    page_size: u32,
+    minimum_application_address: *mut c_void,
+    maximum_application_address: *mut c_void,
+    active_processor_mask: usize,
+    number_of_processors: u32,
+    processor_type: u32,
+    allocation_granularity: u32,
+    processor_level: u16,
+    processor_revision: u16,
+}
+
+#[repr(C)]
+struct MemoryBasicInformation {
+    base_address: *mut c_void,
+    allocation_base: *mut c_void,
+    allocation_protect: u32,
+    #[cfg(target_pointer_width = "64")]
+    partition_id: u16,
+    region_size: usize,
+    state: u32,
+    protect: u32,
+    kind: u32,
+}
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn GetSystemInfo(info: *mut SystemInfo);
+    fn GetLastError() -> u32;
+    fn CloseHandle(handle: Handle) -> i32;
+    fn CreateFileMappingW(
+        file: Handle,
+        attributes: *const c_void,
+        protect: u32,
+        maximum_size_high: u32,
+        maximum_size_low: u32,
+        name: *const u16,
+    ) -> Handle;
+    fn MapViewOfFileEx(
+        mapping: Handle,
+        desired_access: u32,
+        file_offset_high: u32,
+        file_offset_low: u32,
+        number_of_bytes: usize,
+        base_address: *mut c_void,
+    ) -> *mut c_void;
+    fn OpenFileMappingW(desired_access: u32, inherit_handle: i32, name: *const u16) -> Handle;
+    fn UnmapViewOfFile(base_address: *const c_void) -> i32;
+    fn VirtualQuery(
+        address: *const c_void,
+        buffer: *mut MemoryBasicInformation,
+        length: usize,
+    ) -> usize;
+    fn VirtualAlloc(
+        address: *mut c_void,
+        size: usize,
+        allocation_type: u32,
+        protect: u32,
+    ) -> *mut c_void;
+    fn VirtualFree(address: *mut c_void, size: usize, free_type: u32) -> i32;
+    fn Sleep(milliseconds: u32);
+    fn GetCurrentProcessId() -> u32;
+    fn OpenProcess(desired_access: u32, inherit_handle: i32, process_id: u32) -> Handle;
+    fn GetExitCodeProcess(process: Handle, exit_code: *mut u32) -> i32;
+}
+
+#[must_use]
+#[inline]
+pub fn granularity() -> usize {
+    let mut info = ::core::mem::MaybeUninit::<SystemInfo>::uninit();
+    // SAFETY: GetSystemInfo always fills the passed struct.
+    let info = unsafe {
+        GetSystemInfo(info.as_mut_ptr());
+        info.assume_init()
+    };
+    info.allocation_granularity as usize
+}
+
+pub fn map_mirrored(size: usize) -> Result<(NonNull<u8>, usize), BufferError> {
+    let Some(len) = size.checked_mul(2) else {
+        return Err(BufferError::BadSize(size));
+    };
+    #[expect(
+        clippy::cast_possible_truncation,
+        reason = "splits the size into its high and low halves"
+    )]
+    let (size_high, size_low) = ((size as u64 >> 32) as u32, size as u32);
+
+    // SAFETY: a pagefile-backed section without a name has no further
+    //         preconditions.
+    let section = unsafe {
+        CreateFileMappingW(
+            -1_isize as Handle,
+            ptr::null(),
+            PAGE_READWRITE,
+            size_high,
+            size_low,
+            ptr::null(),
+        )
+    };
+    if section.is_null() {
+        return Err(last_error());
+    }
+
+    let mut result = Err(BufferError::AllocFailed);
+    for _ in 0..MAP_ATTEMPTS {
+        // Find a free address range large enough for both views.
+        // SAFETY: reserving at an address of the system's choosing has no
+        //         preconditions.
+        let base = unsafe { VirtualAlloc(ptr::null_mut(), len, MEM_RESERVE, PAGE_NOACCESS) };
+        if base.is_null() {
+            result = Err(last_error());
+            break;
+        }
+        // SAFETY: base is the reservation made right above.
+        unsafe { VirtualFree(base, 0, MEM_RELEASE) };
+
+        match map_views(section, base.cast(), size) {
+            Some(ptr) => {
+                result = Ok((ptr, len));
+                break;
+            }
+            None => result = Err(last_error()),
+        }
+    }
+
+    // The views keep the section alive.
+    // SAFETY: section is a valid, owned handle.
+    unsafe { CloseHandle(section) };
+
+    result
+}
+
+fn map_views(section: Handle, base: *mut u8, size: usize) -> Option<NonNull<u8>> {
+    let access = FILE_MAP_READ | FILE_MAP_WRITE;
+
+    // SAFETY: mapping into an address range that is not reserved fails
+    //         instead of clobbering other mappings.
+    let first = unsafe { MapViewOfFileEx(section, access, 0, 0, size, base.cast()) };
+    if first.is_null() {
+        return None;
+    }
+    // SAFETY: see above; base + size stays within the released reservation.
+    let second = unsafe { MapViewOfFileEx(section, access, 0, 0, size, base.add(size).cast()) };
+    if second.is_null() {
+        // SAFETY: first is a view mapped right above.
+        unsafe { UnmapViewOfFile(first) };
+        return None;
+    }
+
+    NonNull::new(first.cast())
+}
+
+pub fn map_aligned(size: usize, align: usize) -> Result<(NonNull<u8>, usize), BufferError> {
+    let Some(len) = size.checked_add(align) else {
+        return Err(BufferError::BadSize(size));
+    };
+
+    for _ in 0..MAP_ATTEMPTS {
+        // Find a free address range large enough to contain an aligned
+        // region of `size` bytes.
+        // SAFETY: reserving at an address of the system's choosing has no
+        //         preconditions.
+        let base = unsafe { VirtualAlloc(ptr::null_mut(), len, MEM_RESERVE, PAGE_NOACCESS) };
+        if base.is_null() {
+            return Err(last_error());
+        }
+        let head = base.addr().wrapping_neg() & (align - 1);
+        // SAFETY: base is the reservation made right above and the aligned
+        //         pointer stays within it.
+        let aligned = unsafe {
+            VirtualFree(base, 0, MEM_RELEASE);
+            base.cast::<u8>().add(head)
+        };
+
+        // SAFETY: allocating in an address range that is not free fails
+        //         instead of clobbering other allocations.
+        let ptr = unsafe {
+            VirtualAlloc(
+                aligned.cast(),
+                size,
+                MEM_RESERVE | MEM_COMMIT,
+                PAGE_READWRITE,
+            )
+        };
+        if let Some(ptr) = NonNull::new(ptr.cast::<u8>()) {
+            return Ok((ptr, size));
+        }
+    }
+
+    Err(last_error())
+}
+
+pub fn map_shared(
+    name: &CStr,
+    size: Option<usize>,
+    writable: bool,
+) -> Result<(NonNull<u8>, usize), BufferError> {
+    let Ok(name) = ::core::str::from_utf8(name.to_bytes()) else {
+        return Err(map_error(ERROR_INVALID_NAME));
+    };
+    let name: Vec<u16> = name.encode_utf16().chain([0]).collect();
+    let access = if writable || size.is_some() {
+        FILE_MAP_READ | FILE_MAP_WRITE
+    } else {
+        FILE_MAP_READ
+    };
+
+    let section = if let Some(size) = size {
+        #[expect(
+            clippy::cast_possible_truncation,
+            reason = "splits the size into its high and low halves"
+        )]
+        let (size_high, size_low) = ((size as u64 >> 32) as u32, size as u32);
+        // SAFETY: a pagefile-backed section with a NUL-terminated name has
+        //         no further preconditions.
+        let section = unsafe {
+            CreateFileMappingW(
+                -1_isize as Handle,
+                ptr::null(),
+                PAGE_READWRITE,
+                size_high,
+                size_low,
+                name.as_ptr(),
+            )
+        };
+        // SAFETY: GetLastError has no preconditions.
+        if !section.is_null() && unsafe { GetLastError() } == ERROR_ALREADY_EXISTS {
+            // SAFETY: section is a valid, owned handle.
+            unsafe { CloseHandle(section) };
+            return Err(map_error(ERROR_ALREADY_EXISTS));
+        }
+        section
+    } else {
+        // SAFETY: the name is NUL-terminated.
+        unsafe { OpenFileMappingW(access, 0, name.as_ptr()) }
+    };
+    if section.is_null() {
+        return Err(last_error());
+    }
+
+    // SAFETY: section is a valid handle, mapped whole at an address of the
+    //         system's choosing.
+    let view = unsafe { MapViewOfFileEx(section, access, 0, 0, 0, ptr::null_mut()) };
+    let err = last_error();
+    // The view keeps the section alive.
+    // SAFETY: section is a valid, owned handle.
+    unsafe { CloseHandle(section) };
+    let Some(view) = NonNull::new(view.cast::<u8>()) else {
+        return Err(err);
+    };
+
+    let len = if let Some(size) = size {
+        size
+    } else {
+        let mut info = ::core::mem::MaybeUninit::<MemoryBasicInformation>::uninit();
+        // SAFETY: view is a live view, info is writable.
+        let written = unsafe {
+            VirtualQuery(
+                view.as_ptr().cast(),
+                info.as_mut_ptr(),
+                ::core::mem::size_of::<MemoryBasicInformation>(),
+            )
+        };
+        if written == 0 {
+            let err = last_error();
+            // SAFETY: view is the view mapped right above.
+            unsafe { UnmapViewOfFile(view.as_ptr().cast()) };
+            return Err(err);
+        }
+        // SAFETY: VirtualQuery succeeded and filled info. The region size
+        //         is the size of the section rounded up to whole pages.
+        unsafe { info.assume_init() }.region_size
+    };
+    Ok((view, len))
+}
+
+/// Named sections live as long as they are mapped, there is no name to
+/// remove.
+pub fn unlink_shared(_name: &CStr) -> Result<(), BufferError> {
+    Ok(())
+}
+
+/// `WaitOnAddress` does not work across processes, and named events are
+/// not implemented: sleeps for a millisecond, callers poll.
+pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) {
+    if word.load(Relaxed) != expected || timeout.is_some_and(|timeout| timeout.is_zero()) {
+        return;
+    }
+    // SAFETY: Sleep has no preconditions.
+    unsafe { Sleep(1) };
+}
+
+/// Waiters poll, see [`wait`].
+pub fn wake(_word: &AtomicU32) {}
+
+pub fn process_id() -> u32 {
+    // SAFETY: GetCurrentProcessId has no preconditions.
+    unsafe { GetCurrentProcessId() }
+}
+
+pub fn process_alive(pid: u32) -> bool {
+    // SAFETY: opening a process by id has no preconditions.
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+    if process.is_null() {
+        // Denied access means the process exists.
+        return last_error_code() == ERROR_ACCESS_DENIED;
+    }
+    let mut code = 0;
+    // SAFETY: process is a valid, owned handle, code is writable.
+    let alive = unsafe { GetExitCodeProcess(process, &raw mut code) } != 0 && code == STILL_ACTIVE;
+    // SAFETY: process is a valid, owned handle.
+    unsafe { CloseHandle(process) };
+    alive
+}
+
+/// # Safety
+/// `ptr` and `len` must describe a live mapping returned by this module.
+pub unsafe fn unmap(ptr: NonNull<u8>, len: usize, kind: Kind) {
+    let base = ptr.as_ptr();
+    // SAFETY: guaranteed by the caller; mirrored views start at the base of
+    //         the mapping and at its middle.
+    unsafe {
+        match kind {
+            Kind::Mirrored => {
+                UnmapViewOfFile(base.add(len / 2).cast());
+                UnmapViewOfFile(base.cast());
+            }
+            Kind::Aligned => {
+                VirtualFree(base.cast(), 0, MEM_RELEASE);
+            }
+            Kind::Shared => {
+                UnmapViewOfFile(base.cast());
+            }
+        }
+    }
+}
+
+/// # Safety
+/// `ptr` and `len` must describe whole pages of memory owned by the caller.
+pub unsafe fn discard(ptr: NonNull<u8>, len: usize, _shared: bool) {
+    // This is only a hint, failure is of no consequence.
+    // SAFETY: guaranteed by the caller. MEM_RESET keeps the pages committed.
+    unsafe { VirtualAlloc(ptr.as_ptr().cast(), len, MEM_RESET, PAGE_READWRITE) };
+}
+
+fn last_error() -> BufferError {
+    map_error(last_error_code())
+}
+
+fn last_error_code() -> u32 {
+    // SAFETY: GetLastError has no preconditions.
+    unsafe { GetLastError() }
+}
+
+fn map_error(code: u32) -> BufferError {
+    #[expect(
+        clippy::cast_possible_wrap,
+        reason = "matches io::Error::from_raw_os_error"
+    )]
+    BufferError::MapFailed(code as i32)
+}
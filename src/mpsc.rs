@@ -0,0 +1,244 @@
+//! Many producers feeding one consumer.
+//!
+//! Every [`Producer`] writes into a ring of its own, created by
+//! [`Producer::try_clone`], which keeps the producers from contending with
+//! each other. The [`Consumer`] drains the rings round-robin, one ring per
+//! call. Bytes of one producer stay in order and the bytes of one commit
+//! are never interleaved with other producers' bytes, which suits
+//! log-aggregation style workloads writing whole records per commit; there
+//! is no order across producers.
+
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::mem;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Ok};
+use ::core::sync::atomic::{AtomicBool, fence};
+use ::std::io;
+use ::std::sync::{Mutex, PoisonError};
+
+use crate::ordering::{Acquire, Release};
+use crate::{BufferError, ConsumerError, ProducerError};
+
+/// Creates a first producer and the consumer. Every producer gets a ring of
+/// `size` bytes, aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    let shared = Arc::new(Shared {
+        size,
+        align,
+        registered: AtomicBool::new(false),
+        rings: Mutex::new(Vec::new()),
+    });
+
+    let producer = Producer {
+        inner: producer,
+        shared: Arc::clone(&shared),
+    };
+    let consumer = Consumer {
+        rings: ::alloc::vec![consumer],
+        next: 0,
+        shared,
+    };
+    Ok((producer, consumer))
+}
+
+#[derive(Debug)]
+struct Shared {
+    size: usize,
+    align: usize,
+    /// Set when `rings` is not empty, so the consumer can skip the lock.
+    registered: AtomicBool,
+    /// Rings of new producers, not yet picked up by the consumer.
+    rings: Mutex<Vec<crate::Consumer>>,
+}
+
+/// One of the writing halves of a multi-producer ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    inner: crate::Producer,
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// Creates another producer, with a ring of its own.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when the allocation fails.
+    #[inline]
+    pub fn try_clone(&self) -> Result<Self, BufferError> {
+        let (producer, consumer) = crate::new(self.shared.size, self.shared.align)?;
+
+        self.shared
+            .rings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(consumer);
+        self.shared.registered.store(true, Release);
+
+        Ok(Producer {
+            inner: producer,
+            shared: Arc::clone(&self.shared),
+        })
+    }
+
+    /// Fills this producer's ring, see [`crate::Producer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.inner.slices(f)
+    }
+
+    /// Fills this producer's ring, see [`crate::Producer::slice`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Producer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.inner.slice(f)
+    }
+}
+
+impl io::Write for Producer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut self.inner, src)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.inner)
+    }
+}
+
+/// The reading half of a multi-producer ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    rings: Vec<crate::Consumer>,
+    /// Index of the ring to be drained next.
+    next: usize,
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// Picks up the rings of new producers and drops the drained rings of
+    /// dropped ones, then returns the index of the next ring with bytes in
+    /// it, if any.
+    #[inline]
+    fn select(&mut self) -> Option<usize> {
+        // Cleared before taking the lock, so a ring registered meanwhile
+        // is either picked up now or sets the flag again.
+        if self.shared.registered.swap(false, Acquire) {
+            self.rings.append(
+                &mut self
+                    .shared
+                    .rings
+                    .lock()
+                    .unwrap_or_else(PoisonError::into_inner),
+            );
+        }
+
+        let mut i = 0;
+        while i < self.rings.len() {
+            let index = (self.next + i) % self.rings.len();
+            let ring = &self.rings[index];
+            if !ring.is_empty() {
+                self.next = index + 1;
+                return Some(index);
+            }
+
+            // A ring left only to us belongs to a dropped producer, which
+            // published its last bytes before letting go of the ring.
+            if Arc::strong_count(&ring.buffer) == 1 {
+                fence(Acquire);
+                if ring.is_empty() {
+                    mem::drop(self.rings.swap_remove(index));
+                    continue;
+                }
+            }
+            i += 1;
+        }
+        None
+    }
+
+    /// Drains the next ring with bytes in it, see
+    /// [`crate::Consumer::slices`]. The closure is called with empty slices
+    /// if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        match self.select() {
+            Some(index) => self.rings[index].slices(f),
+            None => f(&[], 0).map_err(ConsumerError::Callback),
+        }
+    }
+
+    /// Drains the next ring with bytes in it, see
+    /// [`crate::Consumer::slice`]. The closure is called with an empty
+    /// slice if there is none.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Consumer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        match self.select() {
+            Some(index) => self.rings[index].slice(f),
+            None => f(&[]).map_err(ConsumerError::Callback),
+        }
+    }
+
+    /// Returns the number of producers whose rings are still drained,
+    /// including dropped producers with bytes left.
+    #[must_use]
+    #[inline]
+    pub fn producers(&mut self) -> usize {
+        let _ = self.select();
+        self.rings.len()
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&mut self) -> bool {
+        self.select().is_none()
+    }
+}
+
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        match self.select() {
+            Some(index) => io::Read::read(&mut self.rings[index], dst),
+            None => Ok(0),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Many logical streams sharing one ring.
+//!
+//! The [`Mux`] frames every write with a header of stream id and length,
+//! see [`HEADER`]. The [`Demux`] routes the frames into a ring per stream,
+//! whose plain [`Consumer`] halves hand out each stream's bytes in order.
+//! A frame for a stream whose ring is full holds back all frames behind it,
+//! so streams should be drained independently of each other. Frames that
+//! could never fit into their stream's ring are dropped instead.
+
+use ::alloc::collections::BTreeMap;
+use ::core::cmp::Ord as _;
+use ::core::convert::{From as _, Infallible, TryFrom as _};
+use ::core::option::Option::{None, Some};
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::{BufferError, Consumer, Producer, ProducerError};
+
+/// The length of a frame header: the stream id and the payload length, both
+/// little-endian `u32`.
+pub const HEADER: usize = 8;
+
+/// Creates a multiplexer and a demultiplexer sharing a ring buffer of
+/// `size` bytes, aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Mux, Demux), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    let demux = Demux {
+        consumer,
+        streams: BTreeMap::new(),
+        dropped: 0,
+    };
+    Ok((Mux { producer }, demux))
+}
+
+/// The writing half of a multiplexed ring, see [`new`].
+#[derive(Debug)]
+pub struct Mux {
+    producer: Producer,
+}
+
+impl Mux {
+    /// Writes `payload` as one frame of `stream`. Returns `false`,
+    /// committing nothing, if the frame does not fit into the empty space.
+    #[inline]
+    pub fn send(&mut self, stream: u32, payload: &[u8]) -> bool {
+        let Ok(len) = u32::try_from(payload.len()) else {
+            return false;
+        };
+        let mut header = [0; HEADER];
+        header[..4].copy_from_slice(&stream.to_le_bytes());
+        header[4..].copy_from_slice(&len.to_le_bytes());
+
+        let n = self.producer.slices(|bufs, free| {
+            let n = HEADER + payload.len();
+            if n > free {
+                return Ok::<_, ()>(0);
+            }
+            put(bufs, 0, &header);
+            put(bufs, HEADER, payload);
+            Ok(n)
+        });
+        ::core::matches!(n, Ok(n) if n != 0)
+    }
+}
+
+/// The reading half of a multiplexed ring, routing frames into per-stream
+/// rings, see [`new`].
+#[derive(Debug)]
+pub struct Demux {
+    consumer: Consumer,
+    streams: BTreeMap<u32, Producer>,
+    dropped: usize,
+}
+
+impl Demux {
+    /// Opens `stream` with a ring of `size` bytes and returns its reading
+    /// half. Frames of a stream not open are dropped, see
+    /// [`Demux::dropped`]. Reopening a stream replaces its ring; the old
+    /// consumer keeps the bytes routed so far.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `size` is not a power of two, or when the
+    /// allocation fails.
+    #[inline]
+    pub fn open(&mut self, stream: u32, size: usize) -> Result<Consumer, BufferError> {
+        let (producer, consumer) = crate::new(size, ::core::mem::align_of::<usize>())?;
+        let _ = self.streams.insert(stream, producer);
+        Ok(consumer)
+    }
+
+    /// Closes `stream`; its frames are dropped from now on.
+    #[inline]
+    pub fn close(&mut self, stream: u32) {
+        let _ = self.streams.remove(&stream);
+    }
+
+    /// Routes frames into their streams' rings until the ring is empty or
+    /// the next frame's stream ring has no room for it. Returns the number
+    /// of frames routed or dropped.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of the next frame's stream ring, e.g.
+    /// [`ProducerError::Poisoned`]. The frame is held back, the frames
+    /// before it stay routed.
+    #[inline]
+    pub fn poll(&mut self) -> Result<usize, ProducerError<Infallible>> {
+        let mut frames = 0;
+        loop {
+            let streams = &mut self.streams;
+            let mut dropped = false;
+            let mut failed = None;
+            let n = self.consumer.slices(|bufs, len| {
+                if len < HEADER {
+                    return Ok::<_, ()>(0);
+                }
+                let mut header = [0; HEADER];
+                get(bufs, 0, &mut header);
+                let [s0, s1, s2, s3, l0, l1, l2, l3] = header;
+                let stream = u32::from_le_bytes([s0, s1, s2, s3]);
+                let Ok(payload) = usize::try_from(u32::from_le_bytes([l0, l1, l2, l3])) else {
+                    return Ok(0);
+                };
+                let n = HEADER + payload;
+                let producer = match streams.get_mut(&stream) {
+                    Some(producer) if payload <= producer.buffer.data.len() => producer,
+                    // Not open, or never fitting into the stream's ring.
+                    _ => {
+                        dropped = true;
+                        return Ok(n);
+                    }
+                };
+                let routed = producer.slices(|dst, free| {
+                    if payload > free {
+                        return Ok(0);
+                    }
+                    copy_at(bufs, HEADER, dst, payload);
+                    Ok(payload)
+                });
+                // A full stream ring holds back the frame, an empty payload
+                // is routed nonetheless.
+                Ok(match routed {
+                    Ok(0) if payload != 0 => 0,
+                    Ok(_) => n,
+                    Err(e) => {
+                        failed = Some(e);
+                        0
+                    }
+                })
+            });
+            if let Some(e) = failed {
+                return Err(e);
+            }
+            match n {
+                Ok(0) | Err(_) => return Ok(frames),
+                Ok(_) => {
+                    frames += 1;
+                    self.dropped += usize::from(dropped);
+                }
+            }
+        }
+    }
+
+    /// Returns the number of frames dropped because their stream was not
+    /// open, or their payload larger than the stream's ring.
+    #[must_use]
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+}
+
+/// Copies `src` into the pair of slices `dst`, starting `at` bytes in.
+#[inline]
+pub(crate) fn put(dst: &mut [&mut [u8]], mut at: usize, mut src: &[u8]) {
+    for buf in dst {
+        if at >= buf.len() {
+            at -= buf.len();
+            continue;
+        }
+        let n = (buf.len() - at).min(src.len());
+        buf[at..at + n].copy_from_slice(&src[..n]);
+        src = &src[n..];
+        at = 0;
+    }
+}
+
+/// Fills `dst` from the pair of slices `src`, starting `at` bytes in.
+#[inline]
+pub(crate) fn get(src: &[&[u8]], mut at: usize, mut dst: &mut [u8]) {
+    for buf in src {
+        if at >= buf.len() {
+            at -= buf.len();
+            continue;
+        }
+        let n = (buf.len() - at).min(dst.len());
+        let (head, tail) = ::core::mem::take(&mut dst).split_at_mut(n);
+        head.copy_from_slice(&buf[at..at + n]);
+        dst = tail;
+        at = 0;
+    }
+}
+
+/// Copies `n` bytes from the pair of slices `src`, starting `at` bytes in,
+/// to the start of the pair of slices `dst`.
+#[inline]
+fn copy_at(src: &[&[u8]], at: usize, dst: &mut [&mut [u8]], n: usize) {
+    let mut copied = 0;
+    for buf in dst {
+        let k = buf.len().min(n - copied);
+        get(src, at + copied, &mut buf[..k]);
+        copied += k;
+    }
+}
@@ -0,0 +1,178 @@
+//! Recording the operations of the halves to replay them, see [`OpLog`],
+//! enabled by the `oplog` feature.
+//!
+//! A half recording into a log appends an [`Op`] for every call of its
+//! slice-vending methods and grants whose callback returned a count: the
+//! length it offered and the count returned, valid or not. Both halves may
+//! share a log, which then holds their calls in the order they completed.
+//! [`OpLog::replay`] applies such a sequence to fresh halves in a single
+//! thread, checking that each call is offered what was recorded, so a
+//! heisenbug in the commit accounting of a concurrent run can be turned
+//! into a deterministic test, e.g. by printing the recorded ops:
+//!
+//! ```
+//! use bytering::oplog::{Op, OpLog, Side};
+//!
+//! let ops = [
+//!     Op { side: Side::Producer, len: 8, n: 5 },
+//!     Op { side: Side::Consumer, len: 5, n: 5 },
+//!     Op { side: Side::Producer, len: 8, n: 8 },
+//! ];
+//! let (mut producer, mut consumer) = bytering::new(8, 1).unwrap();
+//! OpLog::from_ops(ops).replay(&mut producer, &mut consumer).unwrap();
+//! ```
+//!
+//! The log is locked for every call, so only record while debugging.
+
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::cmp::{Eq, PartialEq};
+use ::core::convert::Infallible;
+use ::core::default::Default;
+use ::core::fmt;
+use ::core::iter::{IntoIterator, Iterator as _};
+use ::core::marker::Copy;
+use ::core::option::Option::Some;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::write;
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::{Consumer, Producer};
+
+/// The half calling, see [`Op`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    /// The [`Producer`].
+    Producer,
+    /// The [`Consumer`].
+    Consumer,
+}
+
+/// A call of a half, see the [module docs](self).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Op {
+    /// The half calling.
+    pub side: Side,
+    /// The length offered to the callback, or granted.
+    pub len: usize,
+    /// The count the callback returned, or committed or released.
+    pub n: usize,
+}
+
+/// A log of [`Op`]s, shared by its clones, see the [module docs](self).
+#[derive(Debug, Clone, Default)]
+pub struct OpLog(Arc<Mutex<Vec<Op>>>);
+
+impl OpLog {
+    /// Creates an empty log.
+    #[must_use]
+    #[inline]
+    pub fn new() -> Self {
+        OpLog::default()
+    }
+
+    /// Creates a log of `ops`, e.g. to replay them.
+    #[must_use]
+    #[inline]
+    pub fn from_ops(ops: impl IntoIterator<Item = Op>) -> Self {
+        OpLog(Arc::new(Mutex::new(ops.into_iter().collect())))
+    }
+
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Vec<Op>> {
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Returns the ops recorded so far.
+    #[must_use]
+    #[inline]
+    pub fn ops(&self) -> Vec<Op> {
+        self.lock().clone()
+    }
+
+    /// Drops the ops recorded so far.
+    #[inline]
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    #[inline]
+    pub(crate) fn push(&self, side: Side, len: usize, n: usize) {
+        self.lock().push(Op { side, len, n });
+    }
+
+    /// Applies the ops to `producer` and `consumer`, built as the halves
+    /// recorded were, e.g. with the same size and publish threshold, and
+    /// not yet used.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first op offered a different length than recorded.
+    #[inline]
+    pub fn replay(
+        &self,
+        producer: &mut Producer,
+        consumer: &mut Consumer,
+    ) -> Result<(), Divergence> {
+        for (index, &op) in self.ops().iter().enumerate() {
+            let mut offered = 0;
+            let mut count = |len| {
+                offered = len;
+                Ok::<_, Infallible>(op.n)
+            };
+            // Invalid counts were recorded too: their errors replay as well.
+            let _ = match op.side {
+                Side::Producer => producer.slices(|_, len| count(len)).map_err(|_| ()),
+                Side::Consumer => consumer.slices(|_, len| count(len)).map_err(|_| ()),
+            };
+            if offered != op.len {
+                return Err(Divergence { index, op, offered });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The error type of [`OpLog::replay`]: an op was offered a different
+/// length than recorded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// The index of the op in the log.
+    pub index: usize,
+    /// The op recorded.
+    pub op: Op,
+    /// The length offered when replaying.
+    pub offered: usize,
+}
+
+impl fmt::Display for Divergence {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "op {} of the {:?} was offered {} bytes, {} when recorded",
+            self.index, self.op.side, self.offered, self.op.len
+        )
+    }
+}
+
+impl ::core::error::Error for Divergence {}
+
+impl Producer {
+    /// Records the calls of the producer into `log`, replacing any log
+    /// recorded into before, see the [module docs](crate::oplog).
+    #[inline]
+    pub fn record_ops(&mut self, log: &OpLog) {
+        self.oplog = Some(log.clone());
+    }
+}
+
+impl Consumer {
+    /// Records the calls of the consumer into `log`, replacing any log
+    /// recorded into before, see the [module docs](crate::oplog).
+    #[inline]
+    pub fn record_ops(&mut self, log: &OpLog) {
+        self.oplog = Some(log.clone());
+    }
+}
@@ -0,0 +1,26 @@
+//! The atomic orderings used throughout the crate.
+//!
+//! With the `seqcst` feature enabled, every one of them is `SeqCst`, to
+//! rule the crate's choice of orderings in or out when chasing a suspected
+//! memory-ordering bug, e.g. on a weakly ordered platform: if the bug
+//! persists, it is not down to a missing `Acquire` or `Release` here. Only
+//! enable it while debugging, sequentially consistent stores cost a full
+//! fence on most platforms.
+
+#[cfg(feature = "seqcst")]
+pub use ::core::sync::atomic::Ordering::SeqCst;
+#[cfg(not(feature = "seqcst"))]
+pub use ::core::sync::atomic::Ordering::{AcqRel, Acquire, Relaxed, Release, SeqCst};
+#[cfg(feature = "seqcst")]
+pub use seqcst::{AcqRel, Acquire, Relaxed, Release};
+
+#[cfg(feature = "seqcst")]
+#[expect(non_upper_case_globals, reason = "stand-ins for the variants")]
+mod seqcst {
+    use ::core::sync::atomic::Ordering::{self, SeqCst};
+
+    pub const AcqRel: Ordering = SeqCst;
+    pub const Acquire: Ordering = SeqCst;
+    pub const Relaxed: Ordering = SeqCst;
+    pub const Release: Ordering = SeqCst;
+}
@@ -0,0 +1,1067 @@
+//! Rings persisted in a file, like a tiny write-ahead log.
+//!
+//! A [`PersistentBuffer`] maps a file holding a control block and the
+//! ring's bytes, so what is in the ring survives restarts of the process.
+//! Its [`Producer`] and [`Consumer`] work like the crate's, and make the
+//! bytes they committed or released durable on [`Producer::sync`] and
+//! [`Consumer::sync`], which also back `flush` of [`io::Write`].
+//! [`Producer::compact`] keeps a long-running ring from holding on to
+//! disk space for bytes long consumed, and a [`History`] reads the ring
+//! from an earlier position, alongside the halves.
+//!
+//! # Layout
+//!
+//! The control block takes the first [`HEADER_LEN`] bytes of the file. All
+//! fields are unsigned integers in the byte order of the machine, at these
+//! byte offsets:
+//!
+//! | Offset | Width | Field                                              |
+//! |-------:|------:|----------------------------------------------------|
+//! |      0 |     8 | magic, [`MAGIC`]                                   |
+//! |      8 |     4 | version, [`VERSION`]                               |
+//! |     12 |     4 | length of the control block, [`HEADER_LEN`]        |
+//! |     16 |     8 | capacity of the ring in bytes, a power of two      |
+//! |     24 |     8 | offset of the ring's bytes from the file start     |
+//! |     32 |     4 | state, 1 while mapped, 0 once closed cleanly       |
+//! |     36 |     4 | number of commit records, a power of two, or 0     |
+//! |     40 |     8 | offset of the commit records from the file start   |
+//! |     64 |     8 | write counter                                      |
+//! |     72 |     8 | write counter as of the last sync                  |
+//! |     80 |     8 | commit counter                                     |
+//! |     88 |     8 | commit counter as of the last sync                 |
+//! |     96 |     8 | write counter the producer may write bytes up to   |
+//! |    128 |     8 | read counter                                       |
+//!
+//! Bytes up to [`HEADER_LEN`] not listed are reserved and zero. The
+//! counters count bytes like those of [shared rings](crate::shm).
+//!
+//! Rings created by [`PersistentBuffer::create_checksummed`] keep a record
+//! of every non-empty commit, [`RECORD_LEN`] bytes each, in a ring of
+//! records following the ring's bytes. The record of the `n`th commit sits
+//! at index `n` modulo the number of records:
+//!
+//! | Offset | Width | Field                                              |
+//! |-------:|------:|----------------------------------------------------|
+//! |      0 |     8 | `n`, the value of the commit counter before        |
+//! |      8 |     8 | write counter before the commit                    |
+//! |     16 |     8 | write counter after the commit                     |
+//! |     24 |     4 | CRC-32 (IEEE) of the committed bytes               |
+//!
+//! Syncing writes the ring's bytes back to the file before the control
+//! block. The kernel writes modified pages back on its own too, in any
+//! order, so after a crash the write counter may cover bytes that never
+//! made it to the file.
+//!
+//! # Recovery
+//!
+//! Opening a file that was not closed cleanly, see
+//! [`PersistentBuffer::open`], truncates the ring to the last consistent
+//! commit: the write counter as of the last sync, or the read counter if
+//! the consumer read further. Bytes committed but not synced before a crash
+//! are lost, even if the crash only took down the process.
+//!
+//! With commit records, recovery goes on past the last sync, commit by
+//! commit as long as the records are intact and the checksums match the
+//! bytes, so nothing that made it to the file is lost. Records are
+//! overwritten after as many commits as there are, so a sync at least that
+//! often keeps all of them.
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::{AsRef, TryFrom as _};
+use ::core::fmt;
+use ::core::hint;
+use ::core::iter::Iterator as _;
+use ::core::marker::{Copy, PhantomData, Sync};
+use ::core::mem;
+use ::core::ops::{Drop, FnMut, Range};
+use ::core::option::Option::Some;
+use ::core::ptr;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicU8, AtomicU32, AtomicU64, fence};
+use ::core::write;
+use ::std::fs::{File, OpenOptions};
+use ::std::io;
+use ::std::os::fd::AsRawFd as _;
+use ::std::path::Path;
+
+use crate::mmap::{self, Mapping};
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::tap::crc32;
+use crate::{
+    BufferError, ConsumerError, ProducerError, SendNotSyncZst, empty_ranges, filled_ranges,
+};
+
+/// Marks an initialized control block: the bytes `byterwal` read as a
+/// little-endian integer.
+pub const MAGIC: u64 = u64::from_le_bytes(*b"byterwal");
+
+/// The version of the file layout, see the [module docs](self).
+pub const VERSION: u32 = 1;
+
+/// The length of the control block.
+pub const HEADER_LEN: usize = 192;
+
+/// The length of a commit record, see the [module docs](self).
+pub const RECORD_LEN: usize = 32;
+
+/// The state of a file closed cleanly.
+const CLEAN: u32 = 0;
+/// The state of a file mapped, or not closed cleanly.
+const OPEN: u32 = 1;
+
+/// The control block at the start of the file, see the
+/// [module docs](self).
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    version: u32,
+    control_len: u32,
+    capacity: u64,
+    data_offset: u64,
+    state: AtomicU32,
+    records: u32,
+    records_offset: u64,
+    _reserved0: [u8; 16],
+    write: AtomicU64,
+    synced: AtomicU64,
+    commits: AtomicU64,
+    synced_commits: AtomicU64,
+    claimed: AtomicU64,
+    _reserved1: [u8; 24],
+    read: AtomicU64,
+    _reserved2: [u8; 56],
+}
+
+const _: () = {
+    ::core::assert!(mem::size_of::<Header>() == HEADER_LEN);
+    ::core::assert!(mem::offset_of!(Header, capacity) == 16);
+    ::core::assert!(mem::offset_of!(Header, data_offset) == 24);
+    ::core::assert!(mem::offset_of!(Header, state) == 32);
+    ::core::assert!(mem::offset_of!(Header, write) == 64);
+    ::core::assert!(mem::offset_of!(Header, records_offset) == 40);
+    ::core::assert!(mem::offset_of!(Header, synced) == 72);
+    ::core::assert!(mem::offset_of!(Header, synced_commits) == 88);
+    ::core::assert!(mem::offset_of!(Header, claimed) == 96);
+    ::core::assert!(mem::size_of::<Record>() == RECORD_LEN);
+    ::core::assert!(mem::offset_of!(Header, read) == 128);
+};
+
+/// The record of a commit, see the [module docs](self).
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct Record {
+    seq: u64,
+    start: u64,
+    end: u64,
+    crc: u32,
+    _reserved: u32,
+}
+
+/// A ring persisted in a file, see [`PersistentBuffer::create`] and
+/// [`PersistentBuffer::open`].
+#[derive(Debug)]
+pub struct PersistentBuffer {
+    mapping: Mapping,
+    /// Kept open for the lifetime of the mapping.
+    file: File,
+    /// The offset of the ring's bytes.
+    data: usize,
+    /// 0 until the control block was validated.
+    size: usize,
+    /// The offset of the commit records.
+    table: usize,
+    /// The number of commit records, 0 without.
+    records: usize,
+    /// The bytes recovery dropped from the tail.
+    truncated: u64,
+}
+
+// SAFETY: Sync is safe because the halves access disjoint parts of the
+//         ring, the counters are atomics and all else is immutable once
+//         split.
+unsafe impl Sync for PersistentBuffer {}
+
+impl PersistentBuffer {
+    /// Creates the file `path` holding an empty ring of `size` bytes, a
+    /// power of two, and maps it. The control block is synced right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadSize`] if `size` is not a power of two, or
+    /// [`BufferError::MapFailed`] if the file exists already or cannot be
+    /// created or mapped.
+    #[inline]
+    pub fn create(path: impl AsRef<Path>, size: usize) -> Result<Self, BufferError> {
+        Self::create_with(path.as_ref(), size, 0)
+    }
+
+    /// Like [`PersistentBuffer::create`], but keeps checksummed records of
+    /// the last `records` commits, a power of two, which let
+    /// [recovery](self#recovery) keep intact bytes committed since the last
+    /// sync.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadSize`] if `records` is not a power of
+    /// two, or the errors of [`PersistentBuffer::create`].
+    #[inline]
+    pub fn create_checksummed(
+        path: impl AsRef<Path>,
+        size: usize,
+        records: usize,
+    ) -> Result<Self, BufferError> {
+        if !records.is_power_of_two() || u32::try_from(records).is_err() {
+            return Err(BufferError::BadSize(records));
+        }
+        Self::create_with(path.as_ref(), size, records)
+    }
+
+    #[inline]
+    fn create_with(path: &Path, size: usize, records: usize) -> Result<Self, BufferError> {
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        let data = mmap::granularity().max(HEADER_LEN);
+        let table = data.checked_add(size);
+        let len = table.and_then(|table| table.checked_add(records.checked_mul(RECORD_LEN)?));
+        let (Some(table), Some(len)) = (table, len) else {
+            return Err(BufferError::BadSize(size));
+        };
+        let Ok(file_len) = u64::try_from(len) else {
+            return Err(BufferError::BadSize(size));
+        };
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .map_err(os_error)?;
+        file.set_len(file_len).map_err(os_error)?;
+        let buffer = PersistentBuffer {
+            mapping: Mapping::file(file.as_raw_fd(), len, true)?,
+            file,
+            data,
+            size,
+            table,
+            records,
+            truncated: 0,
+        };
+        // SAFETY: the file was just created and zeroed, and is mapped by
+        //         this process only.
+        unsafe {
+            let header = buffer.header_ptr();
+            (*header).version = VERSION;
+            #[expect(clippy::cast_possible_truncation, reason = "a small constant")]
+            let control_len = HEADER_LEN as u32;
+            (*header).control_len = control_len;
+            (*header).capacity = size as u64;
+            (*header).data_offset = data as u64;
+            #[expect(clippy::cast_possible_truncation, reason = "checked by the caller")]
+            let records = records as u32;
+            (*header).records = records;
+            (*header).records_offset = table as u64;
+        }
+        buffer.header().state.store(OPEN, Relaxed);
+        buffer.header().magic.store(MAGIC, Release);
+        buffer.mapping.sync(0, HEADER_LEN)?;
+        buffer.file.sync_all().map_err(os_error)?;
+        Ok(buffer)
+    }
+
+    /// Maps the file `path`, created by [`PersistentBuffer::create`], with
+    /// the bytes left in the ring when it was last mapped. If the file was
+    /// not closed cleanly, the ring is truncated to the last consistent
+    /// commit first, see [recovery](self#recovery) and
+    /// [`PersistentBuffer::truncated`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if the file does not exist or
+    /// cannot be mapped, or [`BufferError::Incompatible`] if it holds no
+    /// ring.
+    #[inline]
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, BufferError> {
+        let (file, mapping) = map(path.as_ref(), true)?;
+        let (size, data) = validate(&mapping)?;
+        let len = mapping.len();
+        let mut buffer = PersistentBuffer {
+            mapping,
+            file,
+            data: 0,
+            size: 0,
+            table: 0,
+            records: 0,
+            truncated: 0,
+        };
+        let header = buffer.header();
+        let (Ok(records), Ok(table)) = (
+            usize::try_from(header.records),
+            usize::try_from(header.records_offset),
+        ) else {
+            return Err(BufferError::Incompatible);
+        };
+        let table_len = records.saturating_mul(RECORD_LEN);
+        if records != 0
+            && (!records.is_power_of_two()
+                || table != data + size
+                || table > len
+                || table_len > len - table)
+        {
+            return Err(BufferError::Incompatible);
+        }
+        let (r, w) = (header.read.load(Relaxed), header.write.load(Relaxed));
+        let synced = header.synced.load(Relaxed);
+        let distance = |pos: u64| w.wrapping_sub(pos);
+        if distance(r) > size as u64 || distance(synced) > size as u64 {
+            return Err(BufferError::Incompatible);
+        }
+        let clean = header.state.load(Relaxed) == CLEAN;
+        buffer.size = size;
+        buffer.data = data;
+        buffer.table = table;
+        buffer.records = records;
+        if !clean {
+            let (salvaged, commits) = buffer.salvage(w, synced);
+            // Whichever counter is closer to the write counter marks the
+            // tail: nothing past it is known to be intact.
+            let tail = if distance(salvaged) < distance(r) {
+                salvaged
+            } else {
+                r
+            };
+            let header = buffer.header();
+            header.write.store(tail, Relaxed);
+            header.synced.store(tail, Relaxed);
+            header.commits.store(commits, Relaxed);
+            header.synced_commits.store(commits, Relaxed);
+            buffer.truncated = distance(tail);
+        }
+        buffer.header().state.store(OPEN, Relaxed);
+        buffer.mapping.sync(0, HEADER_LEN)?;
+        Ok(buffer)
+    }
+
+    /// Follows the commit records from the last sync as long as they are
+    /// intact, up to write counter `w`. Returns the write and commit
+    /// counters after the last intact commit.
+    #[inline]
+    fn salvage(&self, w: u64, mut pos: u64) -> (u64, u64) {
+        let mut seq = self.header().synced_commits.load(Relaxed);
+        for _ in 0..self.records {
+            let record = self.record(seq);
+            let len = record.end.wrapping_sub(record.start);
+            if record.seq != seq
+                || record.start != pos
+                || len == 0
+                || len > w.wrapping_sub(pos)
+                || self.crc(pos, record.end) != record.crc
+            {
+                break;
+            }
+            pos = record.end;
+            seq = seq.wrapping_add(1);
+        }
+        (pos, seq)
+    }
+
+    /// Returns the CRC-32 of the bytes between counter positions `from` and
+    /// `to`, at most the size apart.
+    #[inline]
+    fn crc(&self, from: u64, to: u64) -> u32 {
+        let len = usize::try_from(to.wrapping_sub(from)).unwrap_or(usize::MAX);
+        let start = self.offset(from);
+        let (ranges, _) = filled_ranges(self.size, self.size - 1, start, start + len);
+        // SAFETY: only called before the halves are split.
+        let bufs = unsafe { self.slices(ranges) };
+        bufs.iter().fold(0, |crc, buf| crc32(crc, buf))
+    }
+
+    #[inline]
+    #[expect(clippy::cast_ptr_alignment, reason = "only accessed unaligned")]
+    fn record_ptr(&self, seq: u64) -> *mut Record {
+        // Truncation intended: only the bits within the mask matter.
+        #[expect(clippy::cast_possible_truncation, reason = "masked right away")]
+        let index = seq as usize & (self.records - 1);
+        // SAFETY: the records lie within the mapping, see `open`.
+        unsafe {
+            let ptr = self
+                .mapping
+                .ptr()
+                .as_ptr()
+                .add(self.table + index * RECORD_LEN);
+            ptr.cast::<Record>()
+        }
+    }
+
+    /// Returns the record of commit `seq`, or of the commit sharing its
+    /// index. Requires records.
+    #[inline]
+    fn record(&self, seq: u64) -> Record {
+        // SAFETY: the record lies within the mapping, and is all integers,
+        //         so any bytes are a valid value. Only the producer writes
+        //         records.
+        unsafe { self.record_ptr(seq).read_unaligned() }
+    }
+
+    /// Returns the number of bytes committed but not synced before a crash,
+    /// which were dropped when opening the file.
+    #[must_use]
+    #[inline]
+    pub fn truncated(&self) -> u64 {
+        self.truncated
+    }
+
+    /// Returns the read and write counters, positions in the stream of
+    /// bytes ever committed.
+    #[must_use]
+    #[inline]
+    pub fn counters(&self) -> (u64, u64) {
+        let header = self.header();
+        (header.read.load(Acquire), header.write.load(Acquire))
+    }
+
+    /// Returns the size of the ring.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the number of bytes in the ring.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        let header = self.header();
+        let filled = header
+            .write
+            .load(Relaxed)
+            .wrapping_sub(header.read.load(Relaxed));
+        usize::try_from(filled).unwrap_or(usize::MAX)
+    }
+
+    /// Returns whether the ring is empty.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Splits the ring into its producer and consumer halves.
+    #[must_use]
+    #[inline]
+    pub fn split(self) -> (Producer, Consumer) {
+        let header = self.header();
+        let (write, read) = (header.write.load(Relaxed), header.read.load(Relaxed));
+        let claimed = header.claimed.load(Relaxed);
+        let buffer = Arc::new(self);
+        let producer = Producer {
+            buffer: Arc::clone(&buffer),
+            write,
+            synced: write,
+            retain: 0,
+            claimed,
+            _notsync: PhantomData,
+        };
+        let consumer = Consumer {
+            buffer,
+            read,
+            synced: read,
+            _notsync: PhantomData,
+        };
+        (producer, consumer)
+    }
+
+    #[inline]
+    fn header_ptr(&self) -> *mut Header {
+        header_ptr(&self.mapping)
+    }
+
+    #[inline]
+    fn header(&self) -> &Header {
+        // SAFETY: see `header`. Only the atomics change once the halves are
+        //         split.
+        unsafe { header(&self.mapping) }
+    }
+
+    /// Maps counter position `pos` onto the ring.
+    #[inline]
+    fn offset(&self, pos: u64) -> usize {
+        offset(self.size, pos)
+    }
+
+    /// Writes the bytes between counter positions `from` and `to` back to
+    /// the file.
+    #[inline]
+    fn sync_data(&self, from: u64, to: u64) -> Result<(), BufferError> {
+        let len = usize::try_from(to.wrapping_sub(from)).unwrap_or(usize::MAX);
+        let start = self.offset(from);
+        let (ranges, _) =
+            filled_ranges(self.size, self.size - 1, start, start + len.min(self.size));
+        for range in ranges {
+            self.mapping
+                .sync(self.data + range.start, range.end - range.start)?;
+        }
+        Ok(())
+    }
+
+    /// # Safety
+    /// The ranges must lie within the ring and not overlap any live slices.
+    #[inline]
+    #[expect(
+        clippy::mut_from_ref,
+        reason = "aliasing is ruled out by the # Safety contract"
+    )]
+    unsafe fn slices_mut(&self, ranges: [Range<usize>; 2]) -> [&mut [u8]; 2] {
+        // SAFETY: guaranteed by the caller; the ring's bytes start at `data`
+        //         and are `size` long.
+        unsafe {
+            let base = self.mapping.ptr().as_ptr().add(self.data);
+            ranges.map(|r| &mut *ptr::slice_from_raw_parts_mut(base.add(r.start), r.end - r.start))
+        }
+    }
+
+    /// # Safety
+    /// See [`PersistentBuffer::slices_mut`].
+    #[inline]
+    unsafe fn slices(&self, ranges: [Range<usize>; 2]) -> [&[u8]; 2] {
+        // SAFETY: guaranteed by the caller.
+        unsafe { self.slices_mut(ranges).map(|s| &*s) }
+    }
+}
+
+impl Drop for PersistentBuffer {
+    /// Syncs the ring and marks the file closed cleanly. If syncing fails,
+    /// the file stays marked open and is recovered when opened next.
+    #[inline]
+    fn drop(&mut self) {
+        if self.size == 0 || self.mapping.sync(0, self.mapping.len()).is_err() {
+            return;
+        }
+        let header = self.header();
+        header.synced.store(header.write.load(Relaxed), Relaxed);
+        header
+            .synced_commits
+            .store(header.commits.load(Relaxed), Relaxed);
+        header.state.store(CLEAN, Relaxed);
+        let _ = self.mapping.sync(0, HEADER_LEN);
+    }
+}
+
+/// Opens the file `path` and maps all of it, for writing too if
+/// `writable`.
+#[inline]
+fn map(path: &Path, writable: bool) -> Result<(File, Mapping), BufferError> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(writable)
+        .open(path)
+        .map_err(os_error)?;
+    let len = file.metadata().map_err(os_error)?.len();
+    let Ok(len) = usize::try_from(len) else {
+        return Err(BufferError::Incompatible);
+    };
+    if len < HEADER_LEN {
+        return Err(BufferError::Incompatible);
+    }
+    let mapping = Mapping::file(file.as_raw_fd(), len, writable)?;
+    Ok((file, mapping))
+}
+
+#[inline]
+#[expect(clippy::cast_ptr_alignment, reason = "mappings are page aligned")]
+fn header_ptr(mapping: &Mapping) -> *mut Header {
+    mapping.ptr().as_ptr().cast::<Header>()
+}
+
+/// # Safety
+/// The mapping must be at least [`HEADER_LEN`] long.
+#[inline]
+unsafe fn header(mapping: &Mapping) -> &Header {
+    // SAFETY: the mapping starts page aligned with the header, which is all
+    //         integers, so any bytes are a valid value.
+    unsafe { &*header_ptr(mapping) }
+}
+
+/// Checks the control block at the start of `mapping`, which is at least
+/// [`HEADER_LEN`] long. Returns the size of the ring and the offset of its
+/// bytes.
+#[inline]
+fn validate(mapping: &Mapping) -> Result<(usize, usize), BufferError> {
+    // SAFETY: guaranteed by the caller.
+    let header = unsafe { header(mapping) };
+    if header.magic.load(Acquire) != MAGIC
+        || header.version != VERSION
+        || usize::try_from(header.control_len) != Ok(HEADER_LEN)
+    {
+        return Err(BufferError::Incompatible);
+    }
+    let (Ok(size), Ok(data)) = (
+        usize::try_from(header.capacity),
+        usize::try_from(header.data_offset),
+    ) else {
+        return Err(BufferError::Incompatible);
+    };
+    let len = mapping.len();
+    if !size.is_power_of_two() || data < HEADER_LEN || data > len || size > len - data {
+        return Err(BufferError::Incompatible);
+    }
+    Ok((size, data))
+}
+
+/// Maps counter position `pos` onto a ring of `size` bytes.
+#[inline]
+const fn offset(size: usize, pos: u64) -> usize {
+    // Truncation intended: only the bits within the mask matter.
+    #[expect(clippy::cast_possible_truncation, reason = "masked right away")]
+    let pos = pos as usize;
+    pos & (size - 1)
+}
+
+/// Converts an I/O error of the file into a [`BufferError`].
+#[inline]
+#[expect(clippy::needless_pass_by_value, reason = "passed to map_err")]
+fn os_error(err: io::Error) -> BufferError {
+    BufferError::MapFailed(err.raw_os_error().unwrap_or(0))
+}
+
+/// The writing half of a persistent ring, see [`PersistentBuffer::split`].
+#[derive(Debug)]
+pub struct Producer {
+    buffer: Arc<PersistentBuffer>,
+    write: u64,
+    /// The write counter as of the last sync.
+    synced: u64,
+    /// The number of consumed bytes left alone, see [`Producer::retain`].
+    retain: usize,
+    /// The value last stored to the control block's claimed counter.
+    claimed: u64,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Producer {
+    /// Returns the offsets of the empty space within the ring, less the
+    /// retained bytes.
+    #[inline]
+    fn empty(&self) -> (usize, usize) {
+        let buffer = &*self.buffer;
+        let r = buffer.header().read.load(Acquire);
+        let filled = usize::try_from(self.write.wrapping_sub(r)).unwrap_or(usize::MAX);
+        let kept = filled.saturating_add(self.retain).min(buffer.size);
+        let w = buffer.offset(self.write);
+        (w.wrapping_sub(kept), w)
+    }
+
+    /// Leaves the last `len` bytes consumed alone, at most the size of the
+    /// ring, so [`History`] readers find them until more are consumed. The
+    /// producer gets as much less room.
+    #[inline]
+    pub fn retain(&mut self, len: usize) {
+        self.retain = len.min(self.buffer.size);
+    }
+
+    /// Fills the ring, see [`crate::Producer::slices`]. Committed bytes are
+    /// published to the consumer right away, and durable once synced.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let (r, w) = self.empty();
+        let buffer = &*self.buffer;
+        let size = buffer.size;
+        let (ranges, len) = empty_ranges(size, size - 1, r, w);
+        let claimed = self.write.wrapping_add(len as u64);
+        if claimed != self.claimed {
+            // Like a seqlock writer: a history reader seeing any byte
+            // written below sees that they may be overwritten.
+            buffer.header().claimed.store(claimed, Relaxed);
+            fence(Release);
+            self.claimed = claimed;
+        }
+        // SAFETY: the ranges map the empty space, which the consumer does
+        //         not touch until it is published, and history readers
+        //         only load from atomically.
+        let mut bufs = unsafe { buffer.slices_mut(ranges) };
+        let n = f(&mut bufs, len).map_err(ProducerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ProducerError::InvalidCount { n, len });
+        }
+        let end = self.write.wrapping_add(n as u64);
+        if n != 0 && buffer.records != 0 {
+            let (first, second) = (n.min(bufs[0].len()), n.saturating_sub(bufs[0].len()));
+            let crc = crc32(crc32(0, &bufs[0][..first]), &bufs[1][..second]);
+            let seq = buffer.header().commits.load(Relaxed);
+            let record = Record {
+                seq,
+                start: self.write,
+                end,
+                crc,
+                _reserved: 0,
+            };
+            // SAFETY: only the producer accesses records once split.
+            unsafe { buffer.record_ptr(seq).write_unaligned(record) };
+            buffer.header().commits.store(seq.wrapping_add(1), Relaxed);
+        }
+        self.write = end;
+        buffer.header().write.store(self.write, Release);
+        Ok(n)
+    }
+
+    /// Like [`Producer::slices`], but only offers the contiguous part of the
+    /// empty space.
+    ///
+    /// # Errors
+    ///
+    /// See [`Producer::slices`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.slices(|bufs, _| {
+            let [buf, ..] = bufs else {
+                return Ok(0);
+            };
+            f(buf)
+        })
+    }
+
+    /// Frees the disk space taken by the empty part of the ring: punches
+    /// holes into the file wherever whole pages of the ring hold no bytes,
+    /// read or retained. The pages read back as zeros, and take disk space
+    /// again once written to. Returns the number of bytes freed, including
+    /// pages that were holes already.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if the file system cannot punch
+    /// holes, always on other systems than Linux and Android.
+    #[inline]
+    pub fn compact(&mut self) -> Result<usize, BufferError> {
+        let (r, w) = self.empty();
+        let buffer = &*self.buffer;
+        let (ranges, _) = empty_ranges(buffer.size, buffer.size - 1, r, w);
+        let page = mmap::granularity();
+        let mut freed = 0;
+        for range in ranges {
+            // The ring's bytes start on a page.
+            let start = (buffer.data + range.start).next_multiple_of(page);
+            let end = (buffer.data + range.end) / page * page;
+            if start < end {
+                mmap::punch_hole(buffer.file.as_raw_fd(), start, end - start)?;
+                freed += end - start;
+            }
+        }
+        Ok(freed)
+    }
+
+    /// Makes the bytes committed so far durable: writes them back to the
+    /// file, then the write counter, which marks the last consistent
+    /// commit for [recovery](self#recovery).
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if writing back fails.
+    #[inline]
+    pub fn sync(&mut self) -> Result<(), BufferError> {
+        if self.synced == self.write {
+            return Ok(());
+        }
+        let buffer = &*self.buffer;
+        buffer.sync_data(self.synced, self.write)?;
+        buffer
+            .mapping
+            .sync(buffer.table, buffer.records * RECORD_LEN)?;
+        let header = buffer.header();
+        header.synced.store(self.write, Relaxed);
+        header
+            .synced_commits
+            .store(header.commits.load(Relaxed), Relaxed);
+        buffer.mapping.sync(0, HEADER_LEN)?;
+        self.synced = self.write;
+        Ok(())
+    }
+}
+
+impl io::Write for Producer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        let n = self.slices(|bufs, len| {
+            let n = src.len().min(len);
+            crate::tee::copy_prefix(&[src], bufs, n);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+
+    /// Syncs, see [`Producer::sync`].
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.sync().map_err(io::Error::other)
+    }
+}
+
+/// The reading half of a persistent ring, see [`PersistentBuffer::split`].
+#[derive(Debug)]
+pub struct Consumer {
+    buffer: Arc<PersistentBuffer>,
+    read: u64,
+    /// The read counter as of the last sync.
+    synced: u64,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Consumer {
+    /// Drains the ring, see [`crate::Consumer::slices`]. Released bytes are
+    /// handed to the producer right away, and gone for good once synced.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`crate::Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+        let w = buffer.header().write.load(Acquire);
+        let filled = usize::try_from(w.wrapping_sub(self.read)).unwrap_or(usize::MAX);
+        let (size, r) = (buffer.size, buffer.offset(self.read));
+        let (ranges, len) = filled_ranges(size, size - 1, r, r.wrapping_add(filled.min(size)));
+        // SAFETY: the ranges map the filled space, which the producer does
+        //         not touch until it is released.
+        let bufs = unsafe { buffer.slices(ranges) };
+        let n = f(&bufs, len).map_err(ConsumerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+        self.read = self.read.wrapping_add(n as u64);
+        buffer.header().read.store(self.read, Release);
+        Ok(n)
+    }
+
+    /// Like [`Consumer::slices`], but only offers the contiguous part of the
+    /// filled space.
+    ///
+    /// # Errors
+    ///
+    /// See [`Consumer::slices`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.slices(|bufs, _| {
+            let [buf, ..] = bufs else {
+                return Ok(0);
+            };
+            f(buf)
+        })
+    }
+
+    /// Makes the release of the bytes read so far durable: writes the read
+    /// counter back to the file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if writing back fails.
+    #[inline]
+    pub fn sync(&mut self) -> Result<(), BufferError> {
+        if self.synced == self.read {
+            return Ok(());
+        }
+        self.buffer.mapping.sync(0, HEADER_LEN)?;
+        self.synced = self.read;
+        Ok(())
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.header().write.load(Relaxed) == self.read
+    }
+}
+
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let n = self.slices(|bufs, len| {
+            let n = dst.len().min(len);
+            crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+}
+
+/// The error type returned by [`History::read`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overwritten {
+    /// The oldest position still retained.
+    pub oldest: u64,
+}
+
+impl fmt::Display for Overwritten {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "history overwritten up to position {}", self.oldest)
+    }
+}
+
+impl ::core::error::Error for Overwritten {}
+
+/// A reader of a persistent ring's history, see [`History::open`].
+///
+/// It maps the file read-only and takes no part in the protocol: it reads
+/// bytes anywhere between the oldest byte the producer has not been handed
+/// to write over and the write counter, consumed or not, and follows the
+/// producer as it commits more. How much consumed history is retained is
+/// up to the producer, see [`Producer::retain`]; without, bytes may be
+/// overwritten as soon as they are consumed.
+#[derive(Debug)]
+pub struct History {
+    /// Mapped read-only: only ever loaded from.
+    mapping: Mapping,
+    _file: File,
+    data: usize,
+    size: usize,
+    pos: u64,
+}
+
+impl History {
+    /// Maps the ring persisted in the file `path` read-only, to read from
+    /// the position `from` of the write counter on. The file may be used
+    /// by a live [`PersistentBuffer`] meanwhile.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`PersistentBuffer::open`], but opening a
+    /// file not closed cleanly does not recover it.
+    #[inline]
+    pub fn open(path: impl AsRef<Path>, from: u64) -> Result<Self, BufferError> {
+        let (file, mapping) = map(path.as_ref(), false)?;
+        let (size, data) = validate(&mapping)?;
+        Ok(History {
+            mapping,
+            _file: file,
+            data,
+            size,
+            pos: from,
+        })
+    }
+
+    #[inline]
+    fn header(&self) -> &Header {
+        // SAFETY: see `header`. Fields are only ever loaded from.
+        unsafe { header(&self.mapping) }
+    }
+
+    /// Returns the size of the ring.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Returns the position the next read starts at.
+    #[must_use]
+    #[inline]
+    pub fn position(&self) -> u64 {
+        self.pos
+    }
+
+    /// Moves to the position `pos`.
+    #[inline]
+    pub fn seek(&mut self, pos: u64) {
+        self.pos = pos;
+    }
+
+    /// Returns the positions retained right now: from the oldest byte not
+    /// yet handed to the producer to write over, up to the write counter.
+    #[must_use]
+    #[inline]
+    pub fn retained(&self) -> Range<u64> {
+        let header = self.header();
+        let w = header.write.load(Acquire);
+        let oldest = header
+            .claimed
+            .load(Relaxed)
+            .saturating_sub(self.size as u64);
+        oldest.max(w.saturating_sub(self.size as u64)).min(w)..w
+    }
+
+    /// Copies bytes from the position on into `dst` and moves past them.
+    /// Returns the number of bytes copied, 0 if the producer did not commit
+    /// any past the position yet, so following the producer, like
+    /// `tail -f`, means polling.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Overwritten`] if the bytes at the position are no longer
+    /// retained, or were overwritten while copying. The position is left
+    /// unchanged then.
+    #[inline]
+    pub fn read(&mut self, dst: &mut [u8]) -> Result<usize, Overwritten> {
+        let retained = self.retained();
+        if self.pos < retained.start {
+            return Err(Overwritten {
+                oldest: retained.start,
+            });
+        }
+        let available = usize::try_from(retained.end.saturating_sub(self.pos));
+        let n = available.unwrap_or(usize::MAX).min(dst.len());
+        // SAFETY: the ring's bytes start at `data` and are `size` long.
+        let base = unsafe { self.mapping.ptr().as_ptr().add(self.data) };
+        for (i, dst) in dst[..n].iter_mut().enumerate() {
+            let offset = offset(self.size, self.pos.wrapping_add(i as u64));
+            // SAFETY: the offset lies within the ring. The producer may
+            //         write the byte concurrently, so it is loaded
+            //         atomically; atomic loads from read-only memory are
+            //         fine.
+            let byte = unsafe { AtomicU8::from_ptr(base.add(offset)) };
+            *dst = byte.load(Relaxed);
+        }
+        // Like a seqlock reader: bytes the producer may write over now were
+        // not written while copying, see `Producer::slices`.
+        fence(Acquire);
+        let claimed = self.header().claimed.load(Relaxed);
+        let oldest = claimed.saturating_sub(self.size as u64);
+        if self.pos < oldest {
+            return Err(Overwritten { oldest });
+        }
+        self.pos = self.pos.wrapping_add(n as u64);
+        Ok(n)
+    }
+}
+
+impl io::Read for History {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        History::read(self, dst).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,304 @@
+//! Blocking in-memory pipes, see [`pipe`].
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::From as _;
+use ::core::iter::Iterator;
+use ::core::ops::{Drop, Fn, FnMut};
+use ::core::option::Option::{None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, fence};
+use ::std::io;
+use ::std::sync::{Condvar, Mutex, PoisonError};
+
+use crate::ordering::{Acquire, Relaxed, Release, SeqCst};
+use crate::{BufferError, Consumer, ConsumerError, Producer, ProducerError};
+
+/// Creates a pipe buffering up to `capacity` bytes, a power of two, for
+/// connecting in-process components without a dependency or a system call.
+///
+/// Like an OS pipe, reads block until bytes are buffered and return 0 once
+/// the writer is dropped and all bytes are read; writes block until there is
+/// room and fail with [`io::ErrorKind::BrokenPipe`] once the reader is
+/// dropped.
+///
+/// # Errors
+///
+/// Returns an error when `capacity` is not a power of two, or when the
+/// allocation fails.
+#[inline]
+pub fn pipe(capacity: usize) -> Result<(PipeWriter, PipeReader), BufferError> {
+    let (producer, consumer) = crate::new(capacity, ::core::mem::align_of::<usize>())?;
+    let shared = Arc::new(Shared {
+        lock: Mutex::new(()),
+        cond: Condvar::new(),
+        reader_waiting: AtomicBool::new(false),
+        writer_waiting: AtomicBool::new(false),
+        reader_closed: AtomicBool::new(false),
+        writer_closed: AtomicBool::new(false),
+    });
+    let writer = PipeWriter {
+        inner: producer,
+        shared: Arc::clone(&shared),
+    };
+    let reader = PipeReader {
+        inner: consumer,
+        shared,
+    };
+    Ok((writer, reader))
+}
+
+#[derive(Debug)]
+struct Shared {
+    lock: Mutex<()>,
+    cond: Condvar,
+    reader_waiting: AtomicBool,
+    writer_waiting: AtomicBool,
+    reader_closed: AtomicBool,
+    writer_closed: AtomicBool,
+}
+
+impl Shared {
+    /// Blocks until `ready` returns true, flagging `waiting` meanwhile so
+    /// the other end knows to wake us.
+    #[inline]
+    fn wait(&self, waiting: &AtomicBool, ready: impl Fn() -> bool) {
+        let guard = self.lock.lock().unwrap_or_else(PoisonError::into_inner);
+        waiting.store(true, Relaxed);
+        // Pairs with the fence in `wake`: either this end sees the other's
+        // progress, or the other end sees the flag.
+        fence(SeqCst);
+        ::core::mem::drop(
+            self.cond
+                .wait_while(guard, |()| !ready())
+                .unwrap_or_else(PoisonError::into_inner),
+        );
+        waiting.store(false, Relaxed);
+    }
+
+    /// Wakes the other end if it is flagged `waiting`, after this end made
+    /// progress.
+    #[inline]
+    fn wake(&self, waiting: &AtomicBool) {
+        fence(SeqCst);
+        if waiting.load(Relaxed) {
+            // Passing the lock rules out the waiter being between its check
+            // and its wait.
+            ::core::mem::drop(self.lock.lock().unwrap_or_else(PoisonError::into_inner));
+            self.cond.notify_all();
+        }
+    }
+}
+
+/// The writing end of a [`pipe`].
+#[derive(Debug)]
+pub struct PipeWriter {
+    inner: Producer,
+    shared: Arc<Shared>,
+}
+
+impl PipeWriter {
+    /// Blocks until there is room or the reader is dropped, then calls `f`
+    /// with the empty space and wakes the reader if it wrote anything.
+    #[inline]
+    fn write_fn(
+        &mut self,
+        mut f: impl FnMut(&mut [io::IoSliceMut<'_>]) -> io::Result<usize>,
+    ) -> io::Result<usize> {
+        loop {
+            if self.shared.reader_closed.load(Acquire) {
+                return Err(io::Error::from(io::ErrorKind::BrokenPipe));
+            }
+            if self.inner.free_len() == 0 {
+                let (inner, shared) = (&self.inner, &*self.shared);
+                shared.wait(&shared.writer_waiting, || {
+                    inner.free_len() != 0 || shared.reader_closed.load(Acquire)
+                });
+                continue;
+            }
+            let n = match self.inner.io_slices(|bufs, _| f(bufs)) {
+                Ok(n) => n,
+                Err(ProducerError::Callback(e)) => return Err(e),
+                Err(
+                    err @ (ProducerError::InvalidCount { .. }
+                    | ProducerError::TornFrame { .. }
+                    | ProducerError::Poisoned),
+                ) => {
+                    return Err(io::Error::other(err));
+                }
+            };
+            if n != 0 {
+                self.shared.wake(&self.shared.reader_waiting);
+            }
+            return Ok(n);
+        }
+    }
+
+    /// Blocks until there is room or the reader is dropped, then reads from
+    /// `src` straight into the empty space. Returns 0 once `src` does.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `src`, or [`io::ErrorKind::BrokenPipe`] once
+    /// the reader is dropped.
+    #[inline]
+    pub fn fill_from(&mut self, src: &mut impl io::Read) -> io::Result<usize> {
+        self.write_fn(|dsts| src.read_vectored(dsts))
+    }
+}
+
+impl io::Write for PipeWriter {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[io::IoSlice::new(src)])
+    }
+
+    #[inline]
+    fn write_vectored(&mut self, srcs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        if srcs.iter().all(|src| src.is_empty()) {
+            return Ok(0);
+        }
+        self.write_fn(|dsts| {
+            Ok(copy(
+                srcs.iter().map(|src| &**src),
+                dsts.iter_mut().map(|dst| &mut **dst),
+            ))
+        })
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.publish();
+        Ok(())
+    }
+}
+
+impl Drop for PipeWriter {
+    #[inline]
+    fn drop(&mut self) {
+        self.inner.publish();
+        self.shared.writer_closed.store(true, Release);
+        self.shared.wake(&self.shared.reader_waiting);
+    }
+}
+
+/// The reading end of a [`pipe`].
+#[derive(Debug)]
+pub struct PipeReader {
+    inner: Consumer,
+    shared: Arc<Shared>,
+}
+
+impl PipeReader {
+    /// Blocks until bytes are buffered or the writer is dropped, then calls
+    /// `f` with the filled space and wakes the writer if it read anything.
+    /// Returns 0 once the writer is dropped and all bytes are read.
+    #[inline]
+    fn read_fn(
+        &mut self,
+        mut f: impl FnMut(&[io::IoSlice<'_>]) -> io::Result<usize>,
+    ) -> io::Result<usize> {
+        loop {
+            if self.inner.is_empty() {
+                // The writer published its last bytes before closing.
+                if self.shared.writer_closed.load(Acquire) && self.inner.is_empty() {
+                    return Ok(0);
+                }
+                let (inner, shared) = (&self.inner, &*self.shared);
+                shared.wait(&shared.reader_waiting, || {
+                    !inner.is_empty() || shared.writer_closed.load(Acquire)
+                });
+                continue;
+            }
+            let n = match self.inner.io_slices(|srcs, _| f(srcs)) {
+                Ok(n) => n,
+                Err(ConsumerError::Callback(e)) => return Err(e),
+                Err(
+                    err @ (ConsumerError::InvalidCount { .. }
+                    | ConsumerError::TornFrame { .. }
+                    | ConsumerError::Poisoned),
+                ) => {
+                    return Err(io::Error::other(err));
+                }
+            };
+            if n != 0 {
+                self.shared.wake(&self.shared.writer_waiting);
+            }
+            return Ok(n);
+        }
+    }
+
+    /// Blocks until bytes are buffered or the writer is dropped, then
+    /// writes them straight out to `dst`. Returns 0 once the writer is
+    /// dropped and all bytes are written.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of `dst`, or [`io::ErrorKind::WriteZero`] if it
+    /// accepts no bytes.
+    #[inline]
+    pub fn drain_into(&mut self, dst: &mut impl io::Write) -> io::Result<usize> {
+        self.read_fn(|srcs| match dst.write_vectored(srcs)? {
+            0 => Err(io::Error::from(io::ErrorKind::WriteZero)),
+            n => Ok(n),
+        })
+    }
+}
+
+impl io::Read for PipeReader {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        self.read_vectored(&mut [io::IoSliceMut::new(dst)])
+    }
+
+    #[inline]
+    fn read_vectored(&mut self, dsts: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        if dsts.iter().all(|dst| dst.is_empty()) {
+            return Ok(0);
+        }
+        self.read_fn(|srcs| {
+            Ok(copy(
+                srcs.iter().map(|src| &**src),
+                dsts.iter_mut().map(|dst| &mut **dst),
+            ))
+        })
+    }
+}
+
+impl Drop for PipeReader {
+    #[inline]
+    fn drop(&mut self) {
+        self.shared.reader_closed.store(true, Release);
+        self.shared.wake(&self.shared.writer_waiting);
+    }
+}
+
+/// Copies from a sequence of slices into another until either runs out.
+/// Returns the number of bytes copied.
+#[inline]
+fn copy<'a, 'b>(
+    srcs: impl Iterator<Item = &'a [u8]>,
+    mut dsts: impl Iterator<Item = &'b mut [u8]>,
+) -> usize {
+    let mut n = 0;
+    let mut dst: &mut [u8] = &mut [];
+    for mut src in srcs {
+        while !src.is_empty() {
+            if dst.is_empty() {
+                match dsts.next() {
+                    Some(next) => dst = next,
+                    None => return n,
+                }
+                continue;
+            }
+            let k = src.len().min(dst.len());
+            let (head, tail) = ::core::mem::take(&mut dst).split_at_mut(k);
+            head.copy_from_slice(&src[..k]);
+            dst = tail;
+            src = &src[k..];
+            n += k;
+        }
+    }
+    n
+}
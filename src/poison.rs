@@ -0,0 +1,89 @@
+//! Poisoning a ring when a callback panics, see
+//! [`Builder::poison_on_panic`].
+//!
+//! A callback panicking commits nothing, so the ring itself stays
+//! consistent, but the application state the callback was updating may not
+//! be, e.g. a frame half parsed. With poisoning enabled, the panic marks the
+//! ring poisoned and the slice-vending methods of both halves return
+//! [`ProducerError::Poisoned`] or [`ConsumerError::Poisoned`] from then on,
+//! so the peer learns of the panic on its next call instead of carrying on,
+//! as with a poisoned [`Mutex`](https://doc.rust-lang.org/std/sync/struct.Mutex.html).
+
+use ::core::ops::Drop;
+use ::core::option::Option::Some;
+use ::core::sync::atomic::AtomicBool;
+
+use crate::ordering::{Acquire, Release};
+use crate::{Buffer, Builder, Consumer, Producer};
+#[cfg(doc)]
+use crate::{ConsumerError, ProducerError};
+
+/// Poisons the ring when dropped, unless forgotten once the callback
+/// returned.
+pub(crate) struct Guard<'a>(pub(crate) &'a AtomicBool);
+
+impl Drop for Guard<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.0.store(true, Release);
+    }
+}
+
+impl Buffer {
+    #[inline]
+    pub(crate) fn is_poisoned(&self) -> bool {
+        self.poison
+            .as_ref()
+            .is_some_and(|poison| poison.load(Acquire))
+    }
+
+    #[inline]
+    fn clear_poison(&self) {
+        if let Some(poison) = &self.poison {
+            poison.store(false, Release);
+        }
+    }
+}
+
+impl Builder {
+    /// Poisons the ring when a callback passed to a half panics, so both
+    /// halves refuse further calls, see the [module docs](crate::poison).
+    #[inline]
+    pub const fn poison_on_panic(mut self) -> Self {
+        self.poison = true;
+        self
+    }
+}
+
+impl Producer {
+    /// Returns whether a callback panicked, see
+    /// [`Builder::poison_on_panic`].
+    #[must_use]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.buffer.is_poisoned()
+    }
+
+    /// Lets both halves accept calls again, e.g. once the application
+    /// recovered from the panic.
+    #[inline]
+    pub fn clear_poison(&mut self) {
+        self.buffer.clear_poison();
+    }
+}
+
+impl Consumer {
+    /// Returns whether a callback panicked, see
+    /// [`Builder::poison_on_panic`].
+    #[must_use]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.buffer.is_poisoned()
+    }
+
+    /// Lets both halves accept calls again, see [`Producer::clear_poison`].
+    #[inline]
+    pub fn clear_poison(&mut self) {
+        self.buffer.clear_poison();
+    }
+}
@@ -0,0 +1,177 @@
+//! Polling halves of a ring, see [`new`], for classic embedded code that
+//! spins on or interleaves non-blocking calls.
+//!
+//! Instead of waiting, the methods return [`Error::WouldBlock`] while the
+//! ring is full or empty, for the caller to retry, e.g. in a main loop or
+//! with [`block`]. [`Error`] and [`Result`] mirror those of the `nb`
+//! crate, and the word methods [`Producer::write`], [`Producer::flush`] and
+//! [`Consumer::read`] have the signatures of the `embedded_hal_nb::serial`
+//! traits, so implementing those is a matter of forwarding and mapping
+//! `WouldBlock`.
+
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::fmt;
+use ::core::hint;
+use ::core::marker::Copy;
+use ::core::ops::FnMut;
+use ::core::result::Result::{Err, Ok};
+use ::core::write;
+
+use crate::BufferError;
+
+/// A non-blocking result, see [`Error`].
+pub type Result<T, E> = ::core::result::Result<T, Error<E>>;
+
+/// The error type of non-blocking operations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error<E> {
+    /// The operation failed.
+    Other(E),
+    /// The operation cannot complete yet, the ring is full or empty.
+    WouldBlock,
+}
+
+impl<E: fmt::Display> fmt::Display for Error<E> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Other(err) => err.fmt(f),
+            Error::WouldBlock => write!(f, "operation would block"),
+        }
+    }
+}
+
+impl<E: ::core::error::Error> ::core::error::Error for Error<E> {}
+
+/// Retries `f` until it no longer returns [`Error::WouldBlock`], spinning
+/// meanwhile, as the `nb::block!` macro does.
+///
+/// # Errors
+///
+/// Returns the error of `f`, if any.
+#[inline]
+pub fn block<T, E>(mut f: impl FnMut() -> Result<T, E>) -> ::core::result::Result<T, E> {
+    loop {
+        match f() {
+            Ok(value) => return Ok(value),
+            Err(Error::Other(err)) => return Err(err),
+            Err(Error::WouldBlock) => hint::spin_loop(),
+        }
+    }
+}
+
+/// Creates a pair of polling halves sharing a ring buffer of `size` bytes,
+/// aligned to `align`.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> ::core::result::Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    Ok((Producer { inner: producer }, Consumer { inner: consumer }))
+}
+
+/// The writing half of a polling ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    inner: crate::Producer,
+}
+
+impl Producer {
+    /// Writes a single byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WouldBlock`] if the ring is full.
+    #[inline]
+    pub fn write(&mut self, word: u8) -> Result<(), Infallible> {
+        self.write_slice(&[word]).map(|_| ())
+    }
+
+    /// Copies as much of `src` into the ring as fits and returns the number
+    /// of bytes copied, only 0 if `src` is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WouldBlock`] if the ring is full.
+    #[inline]
+    pub fn write_slice(&mut self, src: &[u8]) -> Result<usize, Infallible> {
+        if src.is_empty() {
+            return Ok(0);
+        }
+        let n = self
+            .inner
+            .slices(|bufs, len| {
+                let n = src.len().min(len);
+                crate::tee::copy_prefix(&[src], bufs, n);
+                Ok::<_, Infallible>(n)
+            })
+            .unwrap_or(0);
+        if n == 0 {
+            hint::cold_path();
+            return Err(Error::WouldBlock);
+        }
+        Ok(n)
+    }
+
+    /// Publishes the bytes written. Commits are published right away, so
+    /// this never blocks.
+    ///
+    /// # Errors
+    ///
+    /// Never fails.
+    #[inline]
+    pub fn flush(&mut self) -> Result<(), Infallible> {
+        self.inner.publish();
+        Ok(())
+    }
+}
+
+/// The reading half of a polling ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    inner: crate::Consumer,
+}
+
+impl Consumer {
+    /// Reads a single byte.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WouldBlock`] if the ring is empty.
+    #[inline]
+    pub fn read(&mut self) -> Result<u8, Infallible> {
+        let mut word = [0];
+        self.read_slice(&mut word).map(|_| word[0])
+    }
+
+    /// Copies bytes from the ring into `dst`, consumes them and returns
+    /// their number, only 0 if `dst` is empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::WouldBlock`] if the ring is empty.
+    #[inline]
+    pub fn read_slice(&mut self, dst: &mut [u8]) -> Result<usize, Infallible> {
+        if dst.is_empty() {
+            return Ok(0);
+        }
+        let n = self
+            .inner
+            .slices(|bufs, len| {
+                let n = dst.len().min(len);
+                crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+                Ok::<_, Infallible>(n)
+            })
+            .unwrap_or(0);
+        if n == 0 {
+            hint::cold_path();
+            return Err(Error::WouldBlock);
+        }
+        Ok(n)
+    }
+}
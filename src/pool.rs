@@ -0,0 +1,109 @@
+//! Recycling of ring memory, see [`Pool`].
+
+use ::alloc::sync::{Arc, Weak};
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::default::Default as _;
+use ::core::option::Option::{None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::{AlignedData, Backing, Buffer, BufferError, Consumer, Producer};
+
+/// Memory of dropped rings, ready to be handed out again.
+#[derive(Debug, Default)]
+pub struct Idle(Mutex<Vec<AlignedData>>);
+
+impl Idle {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Vec<AlignedData>> {
+        // Pushing and popping can't leave the list broken.
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Takes back the memory of a ring whose halves were both dropped.
+    #[inline]
+    pub fn put(&self, data: AlignedData) {
+        self.lock().push(data);
+    }
+}
+
+/// Hands out producer-consumer pairs of one size and takes their memory back
+/// once both halves are dropped, so servers don't pay for an allocation of
+/// the buffer per request.
+///
+/// Recycled rings start over at position 0, their contents are left as they
+/// were. Clones share the idle memory. Alignments served by mmap, see
+/// [`Builder::align`], are not recycled.
+///
+/// [`Builder::align`]: crate::Builder::align
+#[derive(Debug, Clone)]
+pub struct Pool {
+    size: usize,
+    align: usize,
+    idle: Arc<Idle>,
+}
+
+impl Pool {
+    /// Creates an empty pool of rings of `size` bytes, aligned to `align`.
+    /// Both must be powers of two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `size` or `align` is not a power of two.
+    #[inline]
+    pub fn new(size: usize, align: usize) -> Result<Self, BufferError> {
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        if !align.is_power_of_two() {
+            return Err(BufferError::BadAlignment(align));
+        }
+        Ok(Pool {
+            size,
+            align,
+            idle: Arc::default(),
+        })
+    }
+
+    /// Hands out a producer-consumer pair, reusing idle memory if there is
+    /// any.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when no memory is idle and the allocation fails.
+    #[inline]
+    pub fn pair(&self) -> Result<(Producer, Consumer), BufferError> {
+        let reused = self.idle.lock().pop();
+        let mut data = match reused {
+            Some(data) => data,
+            None => AlignedData::new(self.size, self.align, None)?,
+        };
+
+        if let Backing::Heap(layout) = data.backing {
+            data.backing = Backing::Pooled(layout, Arc::downgrade(&self.idle));
+        }
+        Ok(crate::pair(Buffer::new(data, 1, 0, 0)))
+    }
+
+    /// Returns the number of idle rings.
+    #[must_use]
+    #[inline]
+    pub fn idle(&self) -> usize {
+        self.idle.lock().len()
+    }
+
+    /// Frees the memory of all idle rings.
+    #[inline]
+    pub fn shrink(&self) {
+        self.idle.lock().clear();
+    }
+}
+
+/// Returns pooled memory to its pool, if that is still around.
+#[inline]
+pub fn recycle(data: AlignedData, pool: &Weak<Idle>) {
+    if let Some(idle) = pool.upgrade() {
+        idle.put(data);
+    }
+}
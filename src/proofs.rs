@@ -0,0 +1,87 @@
+//! Kani proof harnesses for the range math behind the slices handed out,
+//! run with `cargo kani`.
+//!
+//! For every size, counter pair and granularity within the invariants of
+//! [`Buffer`](crate::Buffer), the ranges lie within the data, are disjoint
+//! and add up to the length returned, and the filled and empty ranges of
+//! the same counters never overlap: what the `slices_mut` callers rely on.
+
+use ::core::clone::Clone as _;
+use ::core::cmp::Ord as _;
+use ::core::iter::Iterator as _;
+use ::core::ops::Range;
+
+use crate::{empty_ranges, filled_ranges, mirrored_ranges, range_len, restrict_ranges};
+
+/// Picks a size, 2 to the power of at most `max_shift`, and counters at
+/// most the size apart.
+fn any_ring(max_shift: u32) -> (usize, usize, usize, usize) {
+    let shift: u32 = ::kani::any();
+    ::kani::assume(shift <= max_shift);
+    let size = 1_usize << shift;
+    let read: usize = ::kani::any();
+    let len: usize = ::kani::any();
+    ::kani::assume(len <= size);
+    (size, size - 1, read, read.wrapping_add(len))
+}
+
+/// Asserts that `ranges` lie within `0..bound`, are disjoint and add up to
+/// `len`.
+fn assert_sound(ranges: &[Range<usize>; 2], len: usize, bound: usize) {
+    let [first, second] = ranges;
+    ::core::assert!(first.start <= first.end && first.end <= bound);
+    ::core::assert!(second.start <= second.end && second.end <= bound);
+    ::core::assert!(second.is_empty() || second.end <= first.start);
+    ::core::assert!(range_len(first) + range_len(second) == len);
+}
+
+/// Returns whether the ranges of `a` and `b` overlap.
+fn overlap(a: &[Range<usize>; 2], b: &[Range<usize>; 2]) -> bool {
+    let meet = |x: &Range<usize>, y: &Range<usize>| x.start.max(y.start) < x.end.min(y.end);
+    meet(&a[0], &b[0]) || meet(&a[0], &b[1]) || meet(&a[1], &b[0]) || meet(&a[1], &b[1])
+}
+
+#[::kani::proof]
+fn filled_and_empty_ranges_partition_the_data() {
+    let (size, mask, read, write) = any_ring(usize::BITS - 1);
+    let (filled, filled_len) = filled_ranges(size, mask, read, write);
+    let (empty, empty_len) = empty_ranges(size, mask, read, write);
+    assert_sound(&filled, filled_len, size);
+    assert_sound(&empty, empty_len, size);
+    ::core::assert!(filled_len == write.wrapping_sub(read));
+    ::core::assert!(filled_len + empty_len == size);
+    ::core::assert!(!overlap(&filled, &empty));
+}
+
+#[::kani::proof]
+fn mirrored_ranges_stay_within_both_views() {
+    let (size, mask, read, write) = any_ring(usize::BITS - 2);
+    let len = write.wrapping_sub(read);
+    let (filled, filled_len) = mirrored_ranges(mask, read, len);
+    let (empty, empty_len) = mirrored_ranges(mask, write, size - len);
+    assert_sound(&filled, filled_len, 2 * size);
+    assert_sound(&empty, empty_len, 2 * size);
+    // Both views map the same pages, so the ranges must not overlap modulo
+    // the size: they are adjacent and add up to it.
+    ::core::assert!(filled[0].start.wrapping_add(filled_len) & mask == empty[0].start);
+    ::core::assert!(filled_len + empty_len == size);
+}
+
+#[::kani::proof]
+#[::kani::unwind(3)]
+fn restricted_ranges_stay_within_the_offered_ones() {
+    let (size, mask, read, write) = any_ring(usize::BITS - 1);
+    let shift: u32 = ::kani::any();
+    ::kani::assume(shift < usize::BITS);
+    let granularity = 1_usize << shift;
+    let contiguous: bool = ::kani::any();
+    let (ranges, _) = filled_ranges(size, mask, read, write);
+    let (restricted, len) = restrict_ranges(ranges.clone(), contiguous, granularity);
+    assert_sound(&restricted, len, size);
+    ::core::assert!(len & (granularity - 1) == 0);
+    for (part, offered) in restricted.iter().zip(&ranges) {
+        ::core::assert!(
+            part.is_empty() || (offered.start <= part.start && part.end <= offered.end)
+        );
+    }
+}
@@ -0,0 +1,138 @@
+//! Relaying between two TCP connections, see [`Proxy`].
+//!
+//! Each direction runs through a [`pipe`](crate::pipe) with two threads:
+//! one reads from its source socket straight into the ring, the other
+//! writes from the ring straight to the destination socket, so a slow
+//! destination holds back reading only once the ring is full. When a
+//! source reaches end of file, the destination is shut down for writing
+//! once everything is relayed, and the other direction carries on until it
+//! ends as well. Any error shuts both connections down, ending the relay.
+
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{Err, Ok};
+use ::core::time::Duration;
+use ::std::io;
+use ::std::net::{Shutdown, TcpStream};
+use ::std::sync::{Mutex, PoisonError};
+use ::std::thread;
+
+use crate::{PipeReader, PipeWriter};
+
+/// The bytes a [`Proxy`] relayed in each direction.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Totals {
+    /// Bytes read from the first connection and written to the second.
+    pub a_to_b: u64,
+    /// Bytes read from the second connection and written to the first.
+    pub b_to_a: u64,
+}
+
+/// Configures and runs a relay between two connections, see the
+/// [module docs](self).
+#[derive(Debug, Clone)]
+#[must_use]
+pub struct Proxy {
+    capacity: usize,
+    timeout: Option<Duration>,
+}
+
+impl Proxy {
+    /// Starts configuring a relay buffering up to `capacity` bytes, a power
+    /// of two, in each direction.
+    #[inline]
+    pub const fn new(capacity: usize) -> Self {
+        Proxy {
+            capacity,
+            timeout: None,
+        }
+    }
+
+    /// Fails the relay once a read or write waits longer than `timeout`,
+    /// e.g. to drop idle connections. Defaults to waiting forever.
+    #[inline]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Relays between `a` and `b` until both directions reached end of
+    /// file, returning the bytes relayed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the first error of either connection, including the
+    /// [`io::ErrorKind::WouldBlock`] or [`io::ErrorKind::TimedOut`] of a
+    /// [timeout](Proxy::timeout), or an error if `capacity` is not a power
+    /// of two or an allocation fails.
+    #[inline]
+    pub fn run(&self, a: &TcpStream, b: &TcpStream) -> io::Result<Totals> {
+        for stream in [a, b] {
+            stream.set_read_timeout(self.timeout)?;
+            stream.set_write_timeout(self.timeout)?;
+        }
+        let (a_writer, a_reader) = crate::pipe(self.capacity).map_err(io::Error::other)?;
+        let (b_writer, b_reader) = crate::pipe(self.capacity).map_err(io::Error::other)?;
+
+        let first = Mutex::new(None);
+        let fail = |e: io::Error| {
+            first
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .get_or_insert(e);
+            // Unblocks the other threads; errors from here on are echoes.
+            let _ = a.shutdown(Shutdown::Both);
+            let _ = b.shutdown(Shutdown::Both);
+        };
+        let totals = thread::scope(|s| {
+            s.spawn(|| fill(a_writer, a).unwrap_or_else(fail));
+            s.spawn(|| fill(b_writer, b).unwrap_or_else(fail));
+            let b_to_a = s.spawn(|| {
+                drain(b_reader, a).unwrap_or_else(|e| {
+                    fail(e);
+                    0
+                })
+            });
+            let a_to_b = drain(a_reader, b).unwrap_or_else(|e| {
+                fail(e);
+                0
+            });
+            Totals {
+                a_to_b,
+                // Only a panic fails the join, which the scope propagates.
+                b_to_a: b_to_a.join().unwrap_or(0),
+            }
+        });
+        first
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .map_or(Ok(totals), Err)
+    }
+}
+
+/// Reads from `src` into the pipe until end of file, then closes it.
+fn fill(mut writer: PipeWriter, mut src: &TcpStream) -> io::Result<()> {
+    loop {
+        match writer.fill_from(&mut src) {
+            Ok(0) => return Ok(()),
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Writes from the pipe to `dst` until it is closed and empty, then shuts
+/// `dst` down for writing.
+fn drain(mut reader: PipeReader, mut dst: &TcpStream) -> io::Result<u64> {
+    let mut total = 0;
+    loop {
+        match reader.drain_into(&mut dst) {
+            Ok(0) => break,
+            Ok(n) => total += n as u64,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    dst.shutdown(Shutdown::Write)?;
+    Ok(total)
+}
@@ -0,0 +1,276 @@
+//! Capping the bytes per second a half hands out, see [`RateLimited`].
+//!
+//! A [`TokenBucket`] refills at a fixed rate up to its burst size, and the
+//! wrapped half's `slices` and `io_slices` offer at most as many bytes as
+//! it holds, taking the bytes committed or released out of it. With the
+//! bucket empty, they offer nothing, as if the ring were full or empty, so
+//! proxy pipelines shaping their traffic keep their loops unchanged.
+//!
+//! To wait for tokens, block with [`RateLimited::wait`], or sleep for
+//! [`TokenBucket::delay`] with the timer of an async runtime, e.g. before
+//! awaiting the other half.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::{From as _, TryFrom as _};
+use ::core::ops::FnMut;
+use ::core::result::Result::{self, Ok};
+use ::core::time::Duration;
+use ::std::io;
+use ::std::thread;
+use ::std::time::Instant;
+
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+const NANOS: u128 = 1_000_000_000;
+
+/// A token bucket of bytes, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct TokenBucket {
+    rate: u64,
+    burst: u64,
+    /// The tokens held, in bytes times nanoseconds so refilling is exact.
+    credit: u128,
+    last: Instant,
+}
+
+impl TokenBucket {
+    /// Creates a full bucket refilling at `rate` bytes per second, holding
+    /// at most `burst` bytes.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `rate` is zero.
+    #[must_use]
+    #[inline]
+    pub fn new(rate: u64, burst: u64) -> Self {
+        assert!(rate != 0, "zero rate");
+        TokenBucket {
+            rate,
+            burst,
+            credit: u128::from(burst) * NANOS,
+            last: Instant::now(),
+        }
+    }
+
+    /// Returns the refill rate in bytes per second.
+    #[must_use]
+    #[inline]
+    pub const fn rate(&self) -> u64 {
+        self.rate
+    }
+
+    /// Returns the most bytes the bucket holds.
+    #[must_use]
+    #[inline]
+    pub const fn burst(&self) -> u64 {
+        self.burst
+    }
+
+    /// Returns the bytes the bucket holds now.
+    #[must_use]
+    #[inline]
+    pub fn available(&mut self) -> u64 {
+        self.available_at(Instant::now())
+    }
+
+    /// Returns how long until the bucket holds `bytes` bytes, at most its
+    /// burst size, zero if it does already.
+    #[must_use]
+    #[inline]
+    pub fn delay(&mut self, bytes: u64) -> Duration {
+        self.delay_at(bytes, Instant::now())
+    }
+
+    #[inline]
+    pub(crate) fn available_at(&mut self, now: Instant) -> u64 {
+        self.refill(now);
+        u64::try_from(self.credit / NANOS).unwrap_or(u64::MAX)
+    }
+
+    #[inline]
+    pub(crate) fn delay_at(&mut self, bytes: u64, now: Instant) -> Duration {
+        self.refill(now);
+        let needed = u128::from(bytes.min(self.burst)) * NANOS;
+        let nanos = needed
+            .saturating_sub(self.credit)
+            .div_ceil(u128::from(self.rate));
+        Duration::from_nanos(u64::try_from(nanos).unwrap_or(u64::MAX))
+    }
+
+    #[inline]
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last).as_nanos();
+        let full = u128::from(self.burst) * NANOS;
+        self.credit = self
+            .credit
+            .saturating_add(elapsed.saturating_mul(u128::from(self.rate)))
+            .min(full);
+        self.last = self.last.max(now);
+    }
+
+    #[inline]
+    pub(crate) fn take(&mut self, bytes: usize) {
+        let bytes = u128::try_from(bytes).unwrap_or(u128::MAX);
+        self.credit = self.credit.saturating_sub(bytes.saturating_mul(NANOS));
+    }
+
+    /// Returns `len` capped to the bytes held, rounded down to a multiple
+    /// of `granularity`, a power of two.
+    #[inline]
+    fn limit(&mut self, len: usize, granularity: usize) -> usize {
+        let available = usize::try_from(self.available()).unwrap_or(usize::MAX);
+        len.min(available & !granularity.wrapping_sub(1))
+    }
+}
+
+/// A half handing out at most as many bytes as its [`TokenBucket`] holds,
+/// see the [module docs](self).
+#[derive(Debug)]
+pub struct RateLimited<H> {
+    inner: H,
+    bucket: TokenBucket,
+}
+
+impl<H> RateLimited<H> {
+    /// Wraps `half`, limiting it by `bucket`.
+    #[must_use]
+    #[inline]
+    pub const fn new(half: H, bucket: TokenBucket) -> Self {
+        RateLimited {
+            inner: half,
+            bucket,
+        }
+    }
+
+    /// Returns the bucket, e.g. to ask for the [delay](TokenBucket::delay).
+    #[inline]
+    pub const fn bucket(&mut self) -> &mut TokenBucket {
+        &mut self.bucket
+    }
+
+    /// Blocks until the bucket holds `bytes` bytes, at most its burst size.
+    #[inline]
+    pub fn wait(&mut self, bytes: u64) {
+        loop {
+            let delay = self.bucket.delay(bytes);
+            if delay.is_zero() {
+                return;
+            }
+            thread::sleep(delay);
+        }
+    }
+
+    /// Returns the wrapped half.
+    #[must_use]
+    #[inline]
+    pub const fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Unwraps the half.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+}
+
+impl RateLimited<Producer> {
+    /// Fills the buffer as [`Producer::io_slices`] does, offering at most
+    /// the bytes the bucket holds.
+    ///
+    /// # Errors
+    ///
+    /// See [`Producer::io_slices`].
+    #[inline]
+    pub fn io_slices(
+        &mut self,
+        mut f: impl FnMut(&mut [io::IoSliceMut<'_>], usize) -> io::Result<usize>,
+    ) -> Result<usize, ProducerError<io::Error>> {
+        self.produce_fn(|[first, second], len| {
+            let count = 1 + usize::from(!second.is_empty());
+            let mut bufs = [io::IoSliceMut::new(first), io::IoSliceMut::new(second)];
+            f(&mut bufs[..count], len)
+        })
+    }
+
+    /// Fills the buffer as [`Producer::slices`] does, offering at most the
+    /// bytes the bucket holds.
+    ///
+    /// # Errors
+    ///
+    /// See [`Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(|mut bufs, len| f(&mut bufs, len))
+    }
+
+    #[inline]
+    fn produce_fn<E>(
+        &mut self,
+        mut f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let bucket = &mut self.bucket;
+        let granularity = self.inner.limits.granularity();
+        let n = self.inner.produce_fn(false, |[first, second], len| {
+            let limit = bucket.limit(len, granularity);
+            let head = first.len().min(limit);
+            f([&mut first[..head], &mut second[..limit - head]], limit)
+        })?;
+        self.bucket.take(n);
+        Ok(n)
+    }
+}
+
+impl RateLimited<Consumer> {
+    /// Drains the buffer as [`Consumer::io_slices`] does, offering at most
+    /// the bytes the bucket holds.
+    ///
+    /// # Errors
+    ///
+    /// See [`Consumer::io_slices`].
+    #[inline]
+    pub fn io_slices(
+        &mut self,
+        mut f: impl FnMut(&[io::IoSlice<'_>], usize) -> io::Result<usize>,
+    ) -> Result<usize, ConsumerError<io::Error>> {
+        self.consume_fn(|[first, second], len| {
+            let count = 1 + usize::from(!second.is_empty());
+            let bufs = [io::IoSlice::new(first), io::IoSlice::new(second)];
+            f(&bufs[..count], len)
+        })
+    }
+
+    /// Drains the buffer as [`Consumer::slices`] does, offering at most the
+    /// bytes the bucket holds.
+    ///
+    /// # Errors
+    ///
+    /// See [`Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(|bufs, len| f(&bufs, len))
+    }
+
+    #[inline]
+    fn consume_fn<E>(
+        &mut self,
+        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let bucket = &mut self.bucket;
+        let granularity = self.inner.limits.granularity();
+        let n = self.inner.consume_fn(false, |[first, second], len| {
+            let limit = bucket.limit(len, granularity);
+            let head = first.len().min(limit);
+            f([&first[..head], &second[..limit - head]], limit)
+        })?;
+        self.bucket.take(n);
+        Ok(n)
+    }
+}
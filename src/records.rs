@@ -0,0 +1,126 @@
+//! Rings of fixed-size records.
+//!
+//! The ring is divided into slots of one record each and the halves move
+//! whole records only; the record size divides the ring size, so no record
+//! ever straddles the wrap seam.
+
+use ::core::assert_eq;
+use ::core::debug_assert;
+use ::core::ops::FnOnce;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Ok};
+
+use crate::{BufferError, Builder};
+
+/// Creates a producer-consumer pair sharing a ring buffer of `size` bytes,
+/// aligned to `align`, divided into slots of `record` bytes.
+///
+/// # Errors
+///
+/// Returns an error when a parameter is not a power of two, the record is
+/// larger than the buffer, or the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize, record: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = Builder::new(size).align(align).frame_size(record).build()?;
+    let producer = Producer {
+        inner: producer,
+        record,
+    };
+    let consumer = Consumer {
+        inner: consumer,
+        record,
+    };
+    Ok((producer, consumer))
+}
+
+/// The writing half of a record ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    inner: crate::Producer,
+    record: usize,
+}
+
+impl Producer {
+    /// Returns the size of a record.
+    #[must_use]
+    #[inline]
+    pub fn record_size(&self) -> usize {
+        self.record
+    }
+
+    /// Calls the passed closure with the next empty slot to fill it in place
+    /// and commits the record once it returns. Returns `None`, without
+    /// calling the closure, if the ring is full.
+    #[inline]
+    pub fn push_record_with<R>(&mut self, f: impl FnOnce(&mut [u8]) -> R) -> Option<R> {
+        let record = self.record;
+        let mut f = Some(f);
+        let mut result = None;
+        let n = self.inner.slice(|buf| {
+            if buf.len() < record {
+                return Ok::<_, ()>(0);
+            }
+            result = f.take().map(|f| f(&mut buf[..record]));
+            Ok(record)
+        });
+        // A record is a frame; the slot offered is within the slice.
+        debug_assert!(n.is_ok(), "record count refused");
+        result
+    }
+
+    /// Copies `record` into the next empty slot and commits it. Returns
+    /// `false` if the ring is full.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the length of `record` is not the record size.
+    #[inline]
+    pub fn push_record(&mut self, record: &[u8]) -> bool {
+        assert_eq!(record.len(), self.record, "record size mismatch");
+        self.push_record_with(|slot| slot.copy_from_slice(record))
+            .is_some()
+    }
+}
+
+/// The reading half of a record ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    inner: crate::Consumer,
+    record: usize,
+}
+
+impl Consumer {
+    /// Returns the size of a record.
+    #[must_use]
+    #[inline]
+    pub fn record_size(&self) -> usize {
+        self.record
+    }
+
+    /// Calls the passed closure with the oldest record and frees its slot
+    /// once it returns. Returns `None`, without calling the closure, if the
+    /// ring is empty.
+    #[inline]
+    pub fn pop_record_with<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> Option<R> {
+        let record = self.record;
+        let mut f = Some(f);
+        let mut result = None;
+        let n = self.inner.slice(|buf| {
+            if buf.len() < record {
+                return Ok::<_, ()>(0);
+            }
+            result = f.take().map(|f| f(&buf[..record]));
+            Ok(record)
+        });
+        // A record is a frame; the slot offered is within the slice.
+        debug_assert!(n.is_ok(), "record count refused");
+        result
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+}
@@ -0,0 +1,88 @@
+//! A process-wide registry of live rings, see [`rings`], enabled by the
+//! `registry` feature.
+//!
+//! Every ring created with [`Builder`](crate::Builder) or one of the
+//! functions based on it registers when created or [resized](crate::resize)
+//! and drops out once both halves are gone, e.g. for a diagnostics endpoint
+//! of a service to dump the state of all its buffers. The registry only
+//! holds weak references and is only locked when a ring is created and
+//! when enumerating, never on the halves' hot paths.
+
+use ::alloc::borrow::ToOwned as _;
+use ::alloc::string::String;
+use ::alloc::sync::{Arc, Weak};
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::cmp::{Eq, Ord as _, PartialEq};
+use ::core::fmt;
+use ::core::iter::Iterator as _;
+use ::core::option::Option;
+use ::core::write;
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::Buffer;
+use crate::ordering::Acquire;
+
+static RINGS: Mutex<Vec<Weak<Buffer>>> = Mutex::new(Vec::new());
+
+#[inline]
+fn lock() -> MutexGuard<'static, Vec<Weak<Buffer>>> {
+    RINGS.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Adds `buffer`, dropping the rings gone since the last time.
+#[inline]
+pub(crate) fn register(buffer: &Arc<Buffer>) {
+    let mut rings = lock();
+    rings.retain(|ring| ring.strong_count() != 0);
+    rings.push(Arc::downgrade(buffer));
+}
+
+/// The state of a live ring at the time of [`rings`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ring {
+    /// The name of the ring, see [`Builder::name`](crate::Builder::name).
+    pub name: Option<String>,
+    /// The size of the ring in bytes.
+    pub capacity: usize,
+    /// The bytes published by the producer and not yet consumed.
+    pub filled: usize,
+    /// The write counter: the bytes ever published, wrapping around.
+    pub written: usize,
+    /// The read counter: the bytes ever consumed, wrapping around.
+    pub read: usize,
+}
+
+impl fmt::Display for Ring {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.name.as_deref().unwrap_or("(unnamed)");
+        write!(
+            f,
+            "{name}: {} of {} bytes filled, {} written, {} read",
+            self.filled, self.capacity, self.written, self.read
+        )
+    }
+}
+
+/// Returns the state of every live ring, oldest first.
+#[must_use]
+#[inline]
+pub fn rings() -> Vec<Ring> {
+    let rings = lock();
+    rings
+        .iter()
+        .filter_map(Weak::upgrade)
+        .map(|buffer| {
+            let read = buffer.read.load(Acquire);
+            let written = buffer.write.load(Acquire);
+            Ring {
+                name: buffer.name.as_deref().map(str::to_owned),
+                capacity: buffer.data.len(),
+                filled: written.wrapping_sub(read).min(buffer.data.len()),
+                written,
+                read,
+            }
+        })
+        .collect()
+}
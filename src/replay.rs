@@ -0,0 +1,182 @@
+//! Rings keeping consumed bytes until they are acknowledged.
+//!
+//! The [`Consumer`] reads ahead of the space it frees: consumed bytes stay
+//! in the ring until [`Consumer::ack`] hands their space back to the
+//! producer, so they can be read again after [`Consumer::rewind`], e.g. to
+//! retry a failed downstream write. Unacknowledged bytes reduce the space
+//! left to the producer.
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::convert::Infallible;
+use ::core::hint;
+use ::core::ops::FnMut;
+use ::core::result::Result::{self, Err, Ok};
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{Buffer, BufferError, ConsumerError, Producer, filled_ranges};
+
+/// Creates a producer-consumer pair sharing a ring buffer of `size` bytes,
+/// aligned to `align`, whose consumer frees space by acknowledging it.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    let buffer = Arc::clone(&consumer.buffer);
+    let consumer = Consumer {
+        cursor: buffer.read.load(Relaxed),
+        buffer,
+    };
+    Ok((producer, consumer))
+}
+
+/// The reading half of a replay ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    buffer: Arc<Buffer>,
+    /// Read position, ahead of the shared read counter by the unacknowledged
+    /// bytes.
+    cursor: usize,
+}
+
+impl Consumer {
+    #[inline]
+    fn consume_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+        let w = buffer.write.load(Acquire);
+
+        let (mut ranges, mut len) = filled_ranges(buffer.data.len(), buffer.mask, self.cursor, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map part of the filled region, which the producer
+        //         does not write to before it is acknowledged, and `&mut self`
+        //         rules out other slices being live.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+
+        let n = f(bufs, len).map_err(ConsumerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+        self.cursor = self.cursor.wrapping_add(n);
+        Ok(n)
+    }
+
+    /// Drains the ring: calls the passed closure with a pair of `&[u8]`
+    /// mapping the bytes not consumed yet and their total length. The
+    /// closure must return the number of bytes consumed, whose space stays
+    /// taken until acknowledged.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len| f(&bufs, len))
+    }
+
+    /// Drains the ring: calls the passed closure with a single `&[u8]`
+    /// mapping the contiguous part of the bytes not consumed yet.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _| f(buf))
+    }
+
+    /// Returns the number of bytes consumed but not acknowledged.
+    #[must_use]
+    #[inline]
+    pub fn unacked(&self) -> usize {
+        self.cursor.wrapping_sub(self.buffer.read.load(Relaxed))
+    }
+
+    /// Acknowledges the oldest `bytes` consumed bytes, handing their space
+    /// back to the producer.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::InvalidCount`] if `bytes` exceeds the
+    /// unacknowledged bytes, see [`Consumer::unacked`]. Nothing is
+    /// acknowledged then.
+    #[inline]
+    pub fn ack(&mut self, bytes: usize) -> Result<(), ConsumerError<Infallible>> {
+        let len = self.unacked();
+        if bytes > len {
+            return Err(ConsumerError::InvalidCount { n: bytes, len });
+        }
+        let r = self.buffer.read.load(Relaxed);
+        #[cfg(feature = "scrub")]
+        crate::scrub::release(&self.buffer, r, bytes);
+        self.buffer.read.store(r.wrapping_add(bytes), Release);
+        Ok(())
+    }
+
+    /// Moves the read position back to the oldest unacknowledged byte, so
+    /// the next calls hand out the unacknowledged bytes again.
+    #[inline]
+    pub fn rewind(&mut self) {
+        self.cursor = self.buffer.read.load(Relaxed);
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.cursor == self.buffer.write.load(Relaxed)
+    }
+}
+
+/// Reads without acknowledging.
+#[cfg(feature = "std")]
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        use io::Write;
+        let mut dst = io::Cursor::new(dst);
+        match self.slices(|srcs, _| {
+            let mut n = 0;
+            for src in srcs {
+                let k = dst.write(src)?;
+                n += k;
+                if k != src.len() {
+                    break;
+                }
+            }
+            Ok(n)
+        }) {
+            Ok(n) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+}
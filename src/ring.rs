@@ -0,0 +1,145 @@
+//! Object-safe traits over the halves of a ring, see [`RingProducer`] and
+//! [`RingConsumer`].
+//!
+//! The slice-vending methods of the halves are generic over the closure
+//! and its error, so code taking them cannot be handed anything else. The
+//! traits capture the same operations with `dyn` closures that return the
+//! count, so application code can be written against `&mut dyn
+//! RingProducer` or a generic parameter and be driven by scripted mocks in
+//! its unit tests, e.g. a producer offering one byte at a time or a
+//! consumer refusing counts. The real halves implement them by forwarding,
+//! the dynamic dispatch of the closure aside.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::ops::FnMut;
+use ::core::result::Result::{self, Ok};
+
+use crate::ordering::{Acquire, Relaxed};
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+/// The writing half of a ring, see the [module docs](self).
+pub trait RingProducer {
+    /// Calls `f` with the empty space and its total length, committing the
+    /// count `f` returns, see [`Producer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::InvalidCount`] or
+    /// [`ProducerError::TornFrame`] for counts the ring refuses, or
+    /// [`ProducerError::Poisoned`] if the ring is poisoned, see
+    /// [`crate::Builder::poison_on_panic`]. The callback cannot fail, so
+    /// there is no [`ProducerError::Callback`].
+    fn fill(
+        &mut self,
+        f: &mut dyn FnMut(&mut [&mut [u8]], usize) -> usize,
+    ) -> Result<usize, ProducerError<Infallible>>;
+
+    /// Returns the bytes of empty space, what [`RingProducer::fill`] offers
+    /// unless restricted, e.g. by a granularity.
+    fn free_len(&self) -> usize;
+
+    /// Returns the bytes committed but not yet published, see
+    /// [`Producer::pending`].
+    fn pending(&self) -> usize;
+
+    /// Publishes the pending bytes, see [`Producer::publish`].
+    fn publish(&mut self);
+
+    /// Copies as much of `src` as fits and commits it, returning the count.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`RingProducer::fill`].
+    #[inline]
+    fn push(&mut self, src: &[u8]) -> Result<usize, ProducerError<Infallible>> {
+        self.fill(&mut |bufs, len| {
+            let n = src.len().min(len);
+            crate::tee::copy_prefix(&[src], bufs, n);
+            n
+        })
+    }
+}
+
+/// The reading half of a ring, see the [module docs](self).
+pub trait RingConsumer {
+    /// Calls `f` with the filled space and its total length, releasing the
+    /// count `f` returns, see [`Consumer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::InvalidCount`] or
+    /// [`ConsumerError::TornFrame`] for counts the ring refuses, or
+    /// [`ConsumerError::Poisoned`] if the ring is poisoned, see
+    /// [`crate::Builder::poison_on_panic`]. The callback cannot fail, so
+    /// there is no [`ConsumerError::Callback`].
+    fn drain(
+        &mut self,
+        f: &mut dyn FnMut(&[&[u8]], usize) -> usize,
+    ) -> Result<usize, ConsumerError<Infallible>>;
+
+    /// Returns the bytes published and not yet consumed.
+    fn filled_len(&self) -> usize;
+
+    /// Returns whether no bytes are published and not yet consumed.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.filled_len() == 0
+    }
+
+    /// Copies as many bytes as fit into `dst` and releases them, returning
+    /// the count.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`RingConsumer::drain`].
+    #[inline]
+    fn pop(&mut self, dst: &mut [u8]) -> Result<usize, ConsumerError<Infallible>> {
+        self.drain(&mut |bufs, len| {
+            let n = dst.len().min(len);
+            crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+            n
+        })
+    }
+}
+
+impl RingProducer for Producer {
+    #[inline]
+    fn fill(
+        &mut self,
+        f: &mut dyn FnMut(&mut [&mut [u8]], usize) -> usize,
+    ) -> Result<usize, ProducerError<Infallible>> {
+        self.slices(|bufs, len| Ok(f(bufs, len)))
+    }
+
+    #[inline]
+    fn free_len(&self) -> usize {
+        Producer::free_len(self)
+    }
+
+    #[inline]
+    fn pending(&self) -> usize {
+        Producer::pending(self)
+    }
+
+    #[inline]
+    fn publish(&mut self) {
+        Producer::publish(self);
+    }
+}
+
+impl RingConsumer for Consumer {
+    #[inline]
+    fn drain(
+        &mut self,
+        f: &mut dyn FnMut(&[&[u8]], usize) -> usize,
+    ) -> Result<usize, ConsumerError<Infallible>> {
+        self.slices(|bufs, len| Ok(f(bufs, len)))
+    }
+
+    #[inline]
+    fn filled_len(&self) -> usize {
+        let r = self.buffer.read.load(Relaxed);
+        self.buffer.write.load(Acquire).wrapping_sub(r)
+    }
+}
@@ -0,0 +1,112 @@
+//! Routing frames into rings by their content, see [`Router`].
+
+use ::alloc::vec::Vec;
+use ::core::convert::Infallible;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, Some};
+use ::core::result::Result::{self, Err, Ok};
+
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+/// Forwards every frame of a source ring into one of several target rings,
+/// picked by a classify closure, for in-process pub/sub style topologies.
+///
+/// Frames are those of the source ring, see [`crate::Builder::frame_size`],
+/// or its start alignment if larger, see
+/// [`Consumer::set_start_alignment`]; frames never straddle the wrap seam,
+/// so the closure is passed each one as a single slice. A target whose
+/// frame size or start alignment does not divide the source's frames
+/// refuses them, see [`Router::route`].
+#[derive(Debug)]
+pub struct Router<F> {
+    targets: Vec<Producer>,
+    classify: F,
+    dropped: usize,
+}
+
+impl<F: FnMut(&[u8]) -> Option<usize>> Router<F> {
+    /// Creates a router without targets. `classify` returns the index of the
+    /// target a frame is forwarded to, `None` or an index without a target
+    /// drops the frame.
+    #[must_use]
+    #[inline]
+    pub const fn new(classify: F) -> Self {
+        Router {
+            targets: Vec::new(),
+            classify,
+            dropped: 0,
+        }
+    }
+
+    /// Adds `target`, at the next index.
+    #[inline]
+    pub fn push(&mut self, target: Producer) {
+        self.targets.push(target);
+    }
+
+    /// Forwards frames out of `source` until it is empty or the target of
+    /// the next frame has no room for it. Returns the number of frames
+    /// forwarded or dropped. A held back frame is classified again by the
+    /// next call.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `source`, or as [`ConsumerError::Callback`] the
+    /// error of the first target refusing a frame, e.g. because it is
+    /// poisoned. Frames forwarded before that target are consumed and
+    /// counted, and the error is held back until the next call then.
+    #[inline]
+    pub fn route(
+        &mut self,
+        source: &mut Consumer,
+    ) -> Result<usize, ConsumerError<ProducerError<Infallible>>> {
+        let frame = source.limits.frame;
+        let targets = &mut self.targets;
+        let classify = &mut self.classify;
+        let dropped = &mut self.dropped;
+
+        let mut frames = 0;
+        source.slices(|src, _| {
+            let mut moved = 0;
+            'frames: for &buf in src {
+                for message in buf.chunks_exact(frame) {
+                    if let Some(target) = classify(message).and_then(|i| targets.get_mut(i)) {
+                        let sent = target.slices(|dst, free| {
+                            if free < frame {
+                                return Ok::<_, Infallible>(0);
+                            }
+                            crate::tee::copy_prefix(&[message], dst, frame);
+                            Ok(frame)
+                        });
+                        match sent {
+                            Ok(0) => break 'frames,
+                            Ok(_) => {}
+                            Err(err) if moved == 0 => return Err(err),
+                            Err(_) => break 'frames,
+                        }
+                    } else {
+                        *dropped += 1;
+                    }
+                    moved += frame;
+                    frames += 1;
+                }
+            }
+            Ok(moved)
+        })?;
+        Ok(frames)
+    }
+
+    /// Returns the number of frames dropped by the classify closure.
+    #[must_use]
+    #[inline]
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Splits the targets back off.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Vec<Producer> {
+        self.targets
+    }
+}
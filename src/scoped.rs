@@ -0,0 +1,287 @@
+//! Rings split into halves borrowing them, see [`Ring::split_mut`].
+//!
+//! The halves of [`new`](crate::new) share their buffer through an `Arc`,
+//! so they can live anywhere but a ring lives as long as both. A [`Ring`]
+//! owns its buffer instead and [`Ring::split_mut`] lends it to a
+//! [`ProducerRef`] and a [`ConsumerRef`] for as long as the ring is
+//! borrowed, e.g. for the threads of a [`std::thread::scope`]. Once both
+//! are dropped the ring can be split again, keeping its bytes, so one
+//! allocation serves any number of sessions:
+//!
+//! ```
+//! use std::io::{Read as _, Write as _};
+//!
+//! let mut ring = bytering::scoped::Ring::new(4096, 8).unwrap();
+//! for session in 0..3_u8 {
+//!     let (mut producer, mut consumer) = ring.split_mut();
+//!     std::thread::scope(|s| {
+//!         s.spawn(move || producer.write_all(&[session; 100]).unwrap());
+//!     });
+//!     let mut buf = [0; 100];
+//!     consumer.read_exact(&mut buf).unwrap();
+//!     assert_eq!(buf, [session; 100]);
+//! }
+//! ```
+//!
+//! The borrowed halves publish every commit and offer the slice-vending
+//! methods only, none of the tuning and instrumentation of the owned ones.
+
+use ::core::convert::Infallible;
+use ::core::marker::PhantomData;
+use ::core::ops::FnMut;
+use ::core::option::Option::None;
+use ::core::result::Result::{self, Err, Ok};
+#[cfg(feature = "std")]
+use ::std::io;
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::ring::{RingConsumer, RingProducer};
+use crate::{
+    AlignedData, Buffer, BufferError, ConsumerError, Limits, ProducerError, SendNotSyncZst,
+};
+
+/// A ring owning its buffer, split into borrowed halves, see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct Ring {
+    buffer: Buffer,
+}
+
+impl Ring {
+    /// Creates a ring of `size` bytes, aligned to `align`, both powers of
+    /// two.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error when `size` or `align` is not a power of two, or
+    /// when the allocation fails.
+    #[inline]
+    pub fn new(size: usize, align: usize) -> Result<Self, BufferError> {
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        if !align.is_power_of_two() {
+            return Err(BufferError::BadAlignment(align));
+        }
+        let buffer = Buffer::new(AlignedData::new(size, align, None)?, 1, 0, 0);
+        #[cfg(feature = "scrub")]
+        let buffer = crate::scrub::filled(buffer);
+        Ok(Ring { buffer })
+    }
+
+    /// Returns the size of the ring in bytes.
+    #[must_use]
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.buffer.data.len()
+    }
+
+    /// Returns the bytes committed and not yet consumed.
+    #[must_use]
+    #[inline]
+    pub fn len(&self) -> usize {
+        let r = self.buffer.read.load(Relaxed);
+        self.buffer.write.load(Relaxed).wrapping_sub(r)
+    }
+
+    /// Returns whether no bytes are committed and not yet consumed.
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Drops the bytes committed and not yet consumed.
+    #[inline]
+    pub fn clear(&mut self) {
+        let w = self.buffer.write.load(Relaxed);
+        self.buffer.read.store(w, Relaxed);
+    }
+
+    /// Splits the ring into halves borrowing it.
+    #[must_use]
+    #[inline]
+    pub fn split_mut(&mut self) -> (ProducerRef<'_>, ConsumerRef<'_>) {
+        let buffer = &self.buffer;
+        let limits = Limits::new(buffer);
+        let producer = ProducerRef {
+            buffer,
+            write: buffer.write.load(Relaxed),
+            limits,
+            _notsync: PhantomData,
+        };
+        let consumer = ConsumerRef {
+            buffer,
+            limits,
+            _notsync: PhantomData,
+        };
+        (producer, consumer)
+    }
+}
+
+/// The writing half of a [`Ring`], see [`Ring::split_mut`].
+#[derive(Debug)]
+pub struct ProducerRef<'a> {
+    buffer: &'a Buffer,
+    write: usize,
+    limits: Limits,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl ProducerRef<'_> {
+    #[inline]
+    fn produce_fn<E>(
+        &mut self,
+        contiguous: bool,
+        f: impl FnMut([&mut [u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let n = self
+            .buffer
+            .produce_fn(self.write, contiguous, self.limits, f)?;
+        if n != 0 {
+            self.write = self.write.wrapping_add(n);
+            self.buffer.write.store(self.write, Release);
+        }
+        Ok(n)
+    }
+
+    /// Fills the ring, see [`Producer::slices`](crate::Producer::slices).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slices`](crate::Producer::slices).
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(false, |mut bufs, len| f(&mut bufs, len))
+    }
+
+    /// Fills the contiguous part of the ring, see
+    /// [`Producer::slice`](crate::Producer::slice).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slice`](crate::Producer::slice).
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(true, |[buf, _], _| f(buf))
+    }
+}
+
+impl RingProducer for ProducerRef<'_> {
+    #[inline]
+    fn fill(
+        &mut self,
+        f: &mut dyn FnMut(&mut [&mut [u8]], usize) -> usize,
+    ) -> Result<usize, ProducerError<Infallible>> {
+        self.slices(|bufs, len| Ok(f(bufs, len)))
+    }
+
+    #[inline]
+    fn free_len(&self) -> usize {
+        let r = self.buffer.read.load(Relaxed);
+        self.buffer.data.len() - self.write.wrapping_sub(r)
+    }
+
+    #[inline]
+    fn pending(&self) -> usize {
+        0
+    }
+
+    #[inline]
+    fn publish(&mut self) {}
+}
+
+#[cfg(feature = "std")]
+impl io::Write for ProducerRef<'_> {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        RingProducer::push(self, src).map_err(io::Error::other)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The reading half of a [`Ring`], see [`Ring::split_mut`].
+#[derive(Debug)]
+pub struct ConsumerRef<'a> {
+    buffer: &'a Buffer,
+    limits: Limits,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl ConsumerRef<'_> {
+    #[inline]
+    fn consume_fn<E>(
+        &self,
+        contiguous: bool,
+        f: impl FnMut([&[u8]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let (r, n) = self.buffer.consume_fn(contiguous, self.limits, f)?;
+        if n != 0 {
+            #[cfg(feature = "scrub")]
+            crate::scrub::release(self.buffer, r, n);
+            self.buffer.read.store(r.wrapping_add(n), Release);
+        }
+        Ok(n)
+    }
+
+    /// Drains the ring, see [`Consumer::slices`](crate::Consumer::slices).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slices`](crate::Consumer::slices).
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len| f(&bufs, len))
+    }
+
+    /// Drains the contiguous part of the ring, see
+    /// [`Consumer::slice`](crate::Consumer::slice).
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slice`](crate::Consumer::slice).
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _| f(buf))
+    }
+}
+
+impl RingConsumer for ConsumerRef<'_> {
+    #[inline]
+    fn drain(
+        &mut self,
+        f: &mut dyn FnMut(&[&[u8]], usize) -> usize,
+    ) -> Result<usize, ConsumerError<Infallible>> {
+        self.slices(|bufs, len| Ok(f(bufs, len)))
+    }
+
+    #[inline]
+    fn filled_len(&self) -> usize {
+        let r = self.buffer.read.load(Relaxed);
+        self.buffer.write.load(Acquire).wrapping_sub(r)
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Read for ConsumerRef<'_> {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        RingConsumer::pop(self, dst).map_err(io::Error::other)
+    }
+}
@@ -0,0 +1,46 @@
+//! Filling the empty space of a ring with [`PATTERN`], enabled by the
+//! `scrub` feature.
+//!
+//! Buffers are zeroed when allocated, so a consumer reading past what was
+//! committed, or keeping a slice past the call it was passed to, silently
+//! sees zeros or stale bytes. With the feature enabled, the whole buffer is
+//! filled with the pattern when built and the bytes the consumer releases
+//! are filled again before the producer may reuse them, so such reads show
+//! up as runs of `0xa5`. Filling costs a write of every byte consumed, so
+//! only enable it while debugging.
+
+use crate::Buffer;
+
+/// The byte the empty space is filled with.
+pub const PATTERN: u8 = 0xA5;
+
+/// Fills the whole data of `buffer`, which no half owns yet.
+#[must_use]
+#[inline]
+pub(crate) fn filled(buffer: Buffer) -> Buffer {
+    let len = buffer.data.len();
+    // SAFETY: the buffer is owned, so no slices of it exist.
+    let [data, _] = unsafe { buffer.data.slices_mut([0..len, len..len]) };
+    data.fill(PATTERN);
+    buffer
+}
+
+/// Fills the `n` bytes of `buffer` past counter `r`, released by the
+/// consumer calling.
+#[inline]
+pub(crate) fn release(buffer: &Buffer, r: usize, n: usize) {
+    let (ranges, _) = if buffer.data.is_mirrored() {
+        crate::mirrored_ranges(buffer.mask, r, n)
+    } else {
+        crate::filled_ranges(buffer.data.len(), buffer.mask, r, r.wrapping_add(n))
+    };
+    // SAFETY: the bytes are filled and the read counter is not yet past
+    //         them, so the producer does not write them, nor read them, as
+    //         `Producer::debug_dump` skips published bytes with scrubbing.
+    //         The consumer's slices of them ended with the call releasing
+    //         them.
+    let data = unsafe { buffer.data.slices_mut(ranges) };
+    for part in data {
+        part.fill(PATTERN);
+    }
+}
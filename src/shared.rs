@@ -0,0 +1,170 @@
+//! Mutex-guarded halves, see [`SharedProducer`] and [`SharedConsumer`].
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::convert::From;
+use ::core::ops::FnMut;
+use ::core::result::Result;
+use ::std::io;
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+/// Locks `mutex`, ignoring poisoning: a callback panicking leaves its
+/// commit undone, so the half stays consistent.
+#[inline]
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// A [`Producer`] behind a mutex, `Sync` and cloneable, for use from a
+/// thread pool without restructuring code. Slower than a plain producer,
+/// each call takes the lock for the duration of the closure.
+///
+/// The closure must not call back into the producer through a clone: that
+/// deadlocks or panics, as locking a held mutex does.
+#[derive(Debug, Clone)]
+pub struct SharedProducer(Arc<Mutex<Producer>>);
+
+impl SharedProducer {
+    /// Wraps `producer`.
+    #[must_use]
+    #[inline]
+    pub fn new(producer: Producer) -> Self {
+        SharedProducer(Arc::new(Mutex::new(producer)))
+    }
+
+    /// Locks the producer for a sequence of calls.
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, Producer> {
+        lock(&self.0)
+    }
+
+    /// Fills the buffer under the lock, see [`Producer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &self,
+        f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.lock().slices(f)
+    }
+
+    /// Fills the buffer under the lock, see [`Producer::slice`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &self,
+        f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.lock().slice(f)
+    }
+}
+
+impl From<Producer> for SharedProducer {
+    #[inline]
+    fn from(producer: Producer) -> Self {
+        SharedProducer::new(producer)
+    }
+}
+
+impl io::Write for &SharedProducer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut *self.lock(), src)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut *self.lock())
+    }
+}
+
+impl io::Write for SharedProducer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        io::Write::write(&mut &*self, src)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut &*self)
+    }
+}
+
+/// A [`Consumer`] behind a mutex, `Sync` and cloneable, for use from a
+/// thread pool without restructuring code. Slower than a plain consumer,
+/// each call takes the lock for the duration of the closure.
+///
+/// The closure must not call back into the consumer through a clone, see
+/// [`SharedProducer`].
+#[derive(Debug, Clone)]
+pub struct SharedConsumer(Arc<Mutex<Consumer>>);
+
+impl SharedConsumer {
+    /// Wraps `consumer`.
+    #[must_use]
+    #[inline]
+    pub fn new(consumer: Consumer) -> Self {
+        SharedConsumer(Arc::new(Mutex::new(consumer)))
+    }
+
+    /// Locks the consumer for a sequence of calls.
+    #[inline]
+    pub fn lock(&self) -> MutexGuard<'_, Consumer> {
+        lock(&self.0)
+    }
+
+    /// Drains the buffer under the lock, see [`Consumer::slices`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &self,
+        f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.lock().slices(f)
+    }
+
+    /// Drains the buffer under the lock, see [`Consumer::slice`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &self,
+        f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.lock().slice(f)
+    }
+}
+
+impl From<Consumer> for SharedConsumer {
+    #[inline]
+    fn from(consumer: Consumer) -> Self {
+        SharedConsumer::new(consumer)
+    }
+}
+
+impl io::Read for &SharedConsumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut *self.lock(), dst)
+    }
+}
+
+impl io::Read for SharedConsumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        io::Read::read(&mut &*self, dst)
+    }
+}
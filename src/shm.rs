@@ -0,0 +1,955 @@
+//! Rings shared between processes.
+//!
+//! A [`Segment`] is a named shared memory object, POSIX shared memory or a
+//! Windows named file mapping, holding a control block and the ring's
+//! bytes. The control block only holds offsets and counters, never
+//! pointers, so every process can map the segment at a different address.
+//! One process takes the [`Producer`] half, another the [`Consumer`] half;
+//! both offer the `slices` and commit API of [`crate::Producer`] and
+//! [`crate::Consumer`].
+//!
+//! # Layout
+//!
+//! The control block is stable within a [`VERSION`], so peers written in
+//! other languages can implement either half. All fields are unsigned
+//! integers in the byte order of the machine, at these byte offsets:
+//!
+//! | Offset | Width | Field                                              |
+//! |-------:|------:|----------------------------------------------------|
+//! |      0 |     8 | magic, [`MAGIC`]                                   |
+//! |      8 |     4 | version, [`VERSION`]                               |
+//! |     12 |     4 | length of the control block, [`HEADER_LEN`]        |
+//! |     16 |     8 | capacity of the ring in bytes, a power of two      |
+//! |     24 |     8 | offset of the ring's bytes from the segment start  |
+//! |     32 |     4 | process id of the producer, 0 if not taken         |
+//! |     36 |     4 | process id of the consumer, 0 if not taken         |
+//! |     40 |     4 | write event                                        |
+//! |     44 |     4 | read event                                         |
+//! |     48 |     4 | consumer waiting, 0 or 1                           |
+//! |     52 |     4 | producer waiting, 0 or 1                           |
+//! |     56 |     4 | epoch, one more than the segment's predecessor's   |
+//! |     60 |     4 | moved, 1 once the producer went to a successor     |
+//! |     64 |     8 | write counter                                      |
+//! |     72 |    56 | name of the successor, NUL-terminated              |
+//! |    128 |     8 | read counter                                       |
+//!
+//! Bytes up to [`HEADER_LEN`] not listed are reserved and zero. In C:
+//!
+//! ```c
+//! struct bytering_header {
+//!     _Atomic uint64_t magic;
+//!     uint32_t version;
+//!     uint32_t control_len;
+//!     uint64_t capacity;
+//!     uint64_t data_offset;
+//!     _Atomic uint32_t producer;
+//!     _Atomic uint32_t consumer;
+//!     _Atomic uint32_t write_event;
+//!     _Atomic uint32_t read_event;
+//!     _Atomic uint32_t consumer_waiting;
+//!     _Atomic uint32_t producer_waiting;
+//!     uint32_t epoch;
+//!     _Atomic uint32_t moved;
+//!     _Atomic uint64_t write;
+//!     _Atomic uint8_t successor[56];
+//!     _Atomic uint64_t read;
+//!     uint8_t reserved2[56];
+//! };
+//! ```
+//!
+//! The creator zeroes the segment, fills in the fields and stores the magic
+//! last, with release ordering; openers load it with acquire ordering and
+//! check magic and version before reading anything else. A half is taken
+//! by a compare-and-swap of its field from 0, or from the id of a process
+//! that died, to the id of the taking process, with acquire ordering, and
+//! given up by storing 0 with release ordering.
+//!
+//! The counters count bytes and wrap around at 2⁶⁴; a counter masked with
+//! capacity − 1 is an offset into the ring. The producer writes bytes in
+//! the `capacity - (write - read)` bytes past the write counter, then
+//! advances it with a release store. The consumer reads the `write - read`
+//! bytes past the read counter, loading the write counter with acquire
+//! ordering, then advances the read counter with a release store. Each half
+//! loads the other's counter with acquire ordering.
+//!
+//! A half waiting for the other sets its waiting field, issues a
+//! sequentially consistent fence, loads the other half's event, checks the
+//! counters once more and then blocks until the event changes; on Linux
+//! with a futex on the event. After advancing its counter, a half issues a
+//! sequentially consistent fence, and if the other half is waiting, bumps
+//! its own event and wakes the event's futex.
+//!
+//! Other systems than Linux and Android have no such blocking: a waiting
+//! half sleeps for a millisecond at a time and checks the event again, and
+//! waking is a no-op, so a waiter notices progress up to a millisecond
+//! late. Named semaphores or events are not implemented.
+//!
+//! Observers map a ring read-only, see [`Segment::observe`]. They inspect
+//! the bytes in it without taking part in the protocol: they only load
+//! the counters, and copy bytes past the read counter, dropping those
+//! released meanwhile.
+//!
+//! Segments of [`mpsc`] rings, which many producers append to, share this
+//! layout with a different magic, see there.
+//!
+//! # Peer death
+//!
+//! Commits are single stores, so a process dying leaves the counters
+//! consistent: bytes are either committed or not. Bytes a dead consumer
+//! read but did not release are read again by its successor. Waits check
+//! for a dead peer every [`LIVENESS_INTERVAL`], and another process may
+//! take the half of a dead one, see [`Segment::into_producer`].
+//!
+//! # Growing
+//!
+//! A segment cannot change size while mapped, so the producer grows the
+//! ring by moving on to a successor segment, see [`Producer::grow`], and
+//! the consumer follows once it read everything left behind, see
+//! [`Consumer::follow`]:
+//!
+//! 1. The producer creates the successor with an epoch one more than the
+//!    current segment's, both counters set to its write counter, and takes
+//!    its producer half.
+//! 2. It stores the successor's name into the current segment, then 1 into
+//!    moved with release ordering, wakes a waiting consumer, and gives up
+//!    its half of the current segment. From then on it writes to the
+//!    successor only.
+//! 3. The consumer, finding moved set with acquire ordering and the ring
+//!    empty, maps the successor, checks its epoch and read counter, takes
+//!    its consumer half and gives up its half of the current segment.
+//!
+//! Bytes keep their counter positions across the move, so none are copied.
+//! The names of both segments are the producer's to choose; the
+//! successor's must stay linked until the consumer followed.
+
+use ::alloc::vec::Vec;
+use ::core::cmp::Ord as _;
+use ::core::convert::TryFrom as _;
+use ::core::ffi::CStr;
+use ::core::hint;
+use ::core::iter::Iterator as _;
+use ::core::marker::PhantomData;
+use ::core::mem;
+#[cfg(feature = "std")]
+use ::core::ops::Fn;
+use ::core::ops::{Drop, FnMut, Range};
+use ::core::option::Option::{self, None, Some};
+use ::core::ptr;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicU8, AtomicU32, AtomicU64, fence};
+#[cfg(feature = "std")]
+use ::core::time::Duration;
+#[cfg(feature = "std")]
+use ::std::io;
+#[cfg(feature = "std")]
+use ::std::time::Instant;
+
+use crate::mmap::{self, Mapping};
+use crate::ordering::{Acquire, Relaxed, Release, SeqCst};
+use crate::{
+    BufferError, ConsumerError, ProducerError, SendNotSyncZst, empty_ranges, filled_ranges,
+};
+
+pub mod mpsc;
+
+/// Marks an initialized control block: the bytes `bytering` read as a
+/// little-endian integer.
+pub const MAGIC: u64 = u64::from_le_bytes(*b"bytering");
+
+/// The version of the control block layout, see the [module docs](self).
+pub const VERSION: u32 = 2;
+
+/// The length of the control block.
+pub const HEADER_LEN: usize = 192;
+
+/// The longest name of a successor segment, see [`Producer::grow`].
+pub const MAX_NAME: usize = 55;
+
+/// How often blocked halves check whether their peer died.
+#[cfg(feature = "std")]
+pub const LIVENESS_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The state of the other half of a shared ring.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Peer {
+    /// A running process holds the other half.
+    Attached,
+    /// No process holds the other half.
+    Detached,
+    /// The process holding the other half died without giving it up.
+    Dead,
+}
+
+impl Peer {
+    /// Judges the half field `half` of the control block.
+    #[inline]
+    fn of(half: &AtomicU32) -> Self {
+        match half.load(Acquire) {
+            0 => Peer::Detached,
+            pid if mmap::process_alive(pid) => Peer::Attached,
+            _ => Peer::Dead,
+        }
+    }
+}
+
+/// The control block at the start of a segment, see the
+/// [module docs](self). The counters live on cache lines of their own.
+#[repr(C)]
+struct Header {
+    magic: AtomicU64,
+    version: u32,
+    control_len: u32,
+    capacity: u64,
+    data_offset: u64,
+    producer: AtomicU32,
+    consumer: AtomicU32,
+    write_event: AtomicU32,
+    read_event: AtomicU32,
+    consumer_waiting: AtomicU32,
+    producer_waiting: AtomicU32,
+    epoch: u32,
+    moved: AtomicU32,
+    write: AtomicU64,
+    successor: [AtomicU8; MAX_NAME + 1],
+    read: AtomicU64,
+    _reserved2: [u8; 56],
+}
+
+const _: () = {
+    ::core::assert!(mem::size_of::<Header>() == HEADER_LEN);
+    ::core::assert!(mem::offset_of!(Header, version) == 8);
+    ::core::assert!(mem::offset_of!(Header, capacity) == 16);
+    ::core::assert!(mem::offset_of!(Header, data_offset) == 24);
+    ::core::assert!(mem::offset_of!(Header, producer) == 32);
+    ::core::assert!(mem::offset_of!(Header, write_event) == 40);
+    ::core::assert!(mem::offset_of!(Header, producer_waiting) == 52);
+    ::core::assert!(mem::offset_of!(Header, epoch) == 56);
+    ::core::assert!(mem::offset_of!(Header, write) == 64);
+    ::core::assert!(mem::offset_of!(Header, successor) == 72);
+    ::core::assert!(mem::offset_of!(Header, read) == 128);
+};
+
+/// A mapping of a shared ring, see [`Segment::create`] and
+/// [`Segment::open`].
+#[derive(Debug)]
+pub struct Segment {
+    mapping: Mapping,
+    /// The offset of the ring's bytes.
+    data: usize,
+    size: usize,
+}
+
+impl Segment {
+    /// Creates the shared memory object `name`, e.g. `c"/my-ring"`, holding
+    /// a ring of `size` bytes, a power of two, and maps it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::BadSize`] if `size` is not a power of two, or
+    /// [`BufferError::MapFailed`] if the object exists already or cannot be
+    /// created or mapped.
+    ///
+    /// # Safety
+    ///
+    /// Every process mapping the object must access it through this module
+    /// only, or follow the same protocol.
+    #[inline]
+    pub unsafe fn create(name: &CStr, size: usize) -> Result<Self, BufferError> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { Self::create_at(name, size, MAGIC, 0, 0) }
+    }
+
+    /// Like [`Segment::create`], marked with `magic`, with the given epoch
+    /// and both counters at `pos`.
+    ///
+    /// # Safety
+    /// See [`Segment::create`].
+    #[inline]
+    unsafe fn create_at(
+        name: &CStr,
+        size: usize,
+        magic: u64,
+        epoch: u32,
+        pos: u64,
+    ) -> Result<Self, BufferError> {
+        if !size.is_power_of_two() {
+            return Err(BufferError::BadSize(size));
+        }
+        let data = data_offset();
+        let Some(len) = data.checked_add(size) else {
+            return Err(BufferError::BadSize(size));
+        };
+        let segment = Segment {
+            mapping: Mapping::shared(name, Some(len))?,
+            data,
+            size,
+        };
+        // SAFETY: the object was just created and zeroed, no other process
+        //         reads past the still zero magic yet.
+        unsafe {
+            let header = segment.header_ptr();
+            (*header).version = VERSION;
+            #[expect(clippy::cast_possible_truncation, reason = "a small constant")]
+            let control_len = HEADER_LEN as u32;
+            (*header).control_len = control_len;
+            (*header).capacity = size as u64;
+            (*header).data_offset = data as u64;
+            (*header).epoch = epoch;
+        }
+        segment.header().write.store(pos, Relaxed);
+        segment.header().read.store(pos, Relaxed);
+        segment.header().magic.store(magic, Release);
+        Ok(segment)
+    }
+
+    /// Maps the existing shared memory object `name`, created by
+    /// [`Segment::create`], possibly in another process.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if the object does not exist or
+    /// cannot be mapped, or [`BufferError::Incompatible`] if it holds no
+    /// ring, or its creator has not initialized it yet.
+    ///
+    /// # Safety
+    ///
+    /// See [`Segment::create`].
+    #[inline]
+    pub unsafe fn open(name: &CStr) -> Result<Self, BufferError> {
+        // SAFETY: guaranteed by the caller.
+        unsafe { Self::open_as(name, MAGIC) }
+    }
+
+    /// Like [`Segment::open`], for a segment marked with `magic`.
+    ///
+    /// # Safety
+    /// See [`Segment::create`].
+    #[inline]
+    unsafe fn open_as(name: &CStr, magic: u64) -> Result<Self, BufferError> {
+        Self::validate(Mapping::shared(name, None)?, magic)
+    }
+
+    /// Maps the existing ring `name` read-only, to inspect the bytes in it
+    /// without consuming them, e.g. from a monitoring process. Any number
+    /// of observers may map a ring.
+    ///
+    /// # Errors
+    ///
+    /// See [`Segment::open`].
+    ///
+    /// # Safety
+    ///
+    /// See [`Segment::create`].
+    #[inline]
+    pub unsafe fn observe(name: &CStr) -> Result<Observer, BufferError> {
+        let segment = Self::validate(Mapping::shared_read_only(name)?, MAGIC)?;
+        Ok(Observer { segment })
+    }
+
+    /// Checks that `mapping` holds a control block marked with `magic`.
+    #[inline]
+    fn validate(mapping: Mapping, magic: u64) -> Result<Self, BufferError> {
+        if mapping.len() < HEADER_LEN {
+            return Err(BufferError::Incompatible);
+        }
+        let mut segment = Segment {
+            mapping,
+            data: 0,
+            size: 0,
+        };
+        let header = segment.header();
+        if header.magic.load(Acquire) != magic
+            || header.version != VERSION
+            || usize::try_from(header.control_len) != Ok(HEADER_LEN)
+        {
+            return Err(BufferError::Incompatible);
+        }
+        let (Ok(size), Ok(data)) = (
+            usize::try_from(header.capacity),
+            usize::try_from(header.data_offset),
+        ) else {
+            return Err(BufferError::Incompatible);
+        };
+        let len = segment.mapping.len();
+        if !size.is_power_of_two() || data < HEADER_LEN || data > len || size > len - data {
+            return Err(BufferError::Incompatible);
+        }
+        segment.size = size;
+        segment.data = data;
+        Ok(segment)
+    }
+
+    /// Removes the name of the shared memory object, so it is freed once
+    /// all processes unmapped it. Existing mappings stay valid. Does nothing
+    /// on Windows, where the object is freed once unmapped regardless.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::MapFailed`] if the object does not exist.
+    #[inline]
+    pub fn unlink(name: &CStr) -> Result<(), BufferError> {
+        mmap::unlink_shared(name)
+    }
+
+    /// Returns the size of the ring.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Takes the producer half of the ring, also from a process that died
+    /// holding it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::InUse`] if a running process holds it.
+    #[inline]
+    pub fn into_producer(self) -> Result<Producer, BufferError> {
+        Self::attach(&self.header().producer)?;
+        let write = self.header().write.load(Relaxed);
+        Ok(Producer {
+            segment: self,
+            write,
+            _notsync: PhantomData,
+        })
+    }
+
+    /// Takes the consumer half of the ring, also from a process that died
+    /// holding it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::InUse`] if a running process holds it.
+    #[inline]
+    pub fn into_consumer(self) -> Result<Consumer, BufferError> {
+        Self::attach(&self.header().consumer)?;
+        let read = self.header().read.load(Relaxed);
+        Ok(Consumer {
+            segment: self,
+            read,
+            _notsync: PhantomData,
+        })
+    }
+
+    #[inline]
+    fn attach(half: &AtomicU32) -> Result<(), BufferError> {
+        let pid = mmap::process_id();
+        let mut holder = 0;
+        loop {
+            match half.compare_exchange(holder, pid, Acquire, Relaxed) {
+                Ok(_) => return Ok(()),
+                Err(other) if other == 0 || !mmap::process_alive(other) => holder = other,
+                Err(_) => return Err(BufferError::InUse),
+            }
+        }
+    }
+
+    #[inline]
+    #[expect(clippy::cast_ptr_alignment, reason = "mappings are page aligned")]
+    fn header_ptr(&self) -> *mut Header {
+        self.mapping.ptr().as_ptr().cast::<Header>()
+    }
+
+    #[inline]
+    fn header(&self) -> &Header {
+        // SAFETY: the mapping starts page aligned with the header, which is
+        //         all integers, so any bytes are a valid value. Only the
+        //         atomics change once the segment is shared.
+        unsafe { &*self.header_ptr() }
+    }
+
+    /// Maps counter position `pos` onto the ring.
+    #[inline]
+    fn offset(&self, pos: u64) -> usize {
+        // Truncation intended: only the bits within the mask matter.
+        #[expect(clippy::cast_possible_truncation, reason = "masked right away")]
+        let pos = pos as usize;
+        pos & (self.size - 1)
+    }
+
+    /// # Safety
+    /// The ranges must lie within the ring and not overlap any slices live
+    /// in this or the peer process.
+    #[inline]
+    #[expect(
+        clippy::mut_from_ref,
+        reason = "aliasing is ruled out by the # Safety contract"
+    )]
+    unsafe fn slices_mut(&self, ranges: [Range<usize>; 2]) -> [&mut [u8]; 2] {
+        // SAFETY: guaranteed by the caller; the ring's bytes start at `data`
+        //         and are `size` long.
+        unsafe {
+            let base = self.mapping.ptr().as_ptr().add(self.data);
+            ranges.map(|r| &mut *ptr::slice_from_raw_parts_mut(base.add(r.start), r.end - r.start))
+        }
+    }
+
+    /// # Safety
+    /// See [`Segment::slices_mut`].
+    #[inline]
+    unsafe fn slices(&self, ranges: [Range<usize>; 2]) -> [&[u8]; 2] {
+        // SAFETY: guaranteed by the caller.
+        unsafe { self.slices_mut(ranges).map(|s| &*s) }
+    }
+}
+
+/// The offset of the ring's bytes in segments created here: the first page
+/// boundary past the control block.
+#[inline]
+fn data_offset() -> usize {
+    mmap::granularity().max(HEADER_LEN)
+}
+
+/// The writing half of a shared ring, see [`Segment::into_producer`].
+#[derive(Debug)]
+pub struct Producer {
+    segment: Segment,
+    write: u64,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Producer {
+    /// Fills the ring: calls the passed closure with the empty space, two
+    /// slices whose second one continues at the start of the ring, and
+    /// their total length. The closure returns the number of bytes it wrote,
+    /// which are committed and published to the consumer right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let header = self.segment.header();
+        let r = header.read.load(Acquire);
+        // Clamped: a misbehaving peer must not push the ranges out of the
+        // ring.
+        let filled = usize::try_from(self.write.wrapping_sub(r)).unwrap_or(usize::MAX);
+        let (size, w) = (self.segment.size, self.segment.offset(self.write));
+        let (ranges, len) = empty_ranges(size, size - 1, w.wrapping_sub(filled.min(size)), w);
+        // SAFETY: the ranges map the empty space, which the consumer does
+        //         not touch until it is published.
+        let mut bufs = unsafe { self.segment.slices_mut(ranges) };
+        let n = f(&mut bufs, len).map_err(ProducerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ProducerError::InvalidCount { n, len });
+        }
+        self.write = self.write.wrapping_add(n as u64);
+        header.write.store(self.write, Release);
+        notify(&header.consumer_waiting, &header.write_event);
+        Ok(n)
+    }
+
+    /// Like [`Producer::slices`], but only offers the contiguous part of the
+    /// empty space.
+    ///
+    /// # Errors
+    ///
+    /// See [`Producer::slices`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.slices(|bufs, _| {
+            let [buf, ..] = bufs else {
+                return Ok(0);
+            };
+            f(buf)
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl Producer {
+    /// Blocks until at least `bytes` bytes are free, at most the capacity,
+    /// until `timeout` passes or the consumer's process died. Returns
+    /// whether they are.
+    #[must_use]
+    #[inline]
+    pub fn wait(&self, bytes: usize, timeout: Option<Duration>) -> bool {
+        let header = self.segment.header();
+        let size = self.segment.size as u64;
+        let bytes = (bytes as u64).min(size);
+        let ready = || size - self.write.wrapping_sub(header.read.load(Acquire)).min(size) >= bytes;
+        block(
+            &header.producer_waiting,
+            &header.read_event,
+            &header.consumer,
+            timeout,
+            ready,
+        )
+    }
+}
+
+impl Producer {
+    /// Moves on to a new segment `name` holding a ring of `size` bytes, a
+    /// power of two, which the consumer follows once it read everything
+    /// written so far, see [`Consumer::follow`] and the
+    /// [module docs](self#growing). Despite the name, the new ring may also
+    /// be smaller.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::Incompatible`] if `name` is longer than
+    /// [`MAX_NAME`] bytes, or an error of [`Segment::create`]. The producer
+    /// stays on its segment then.
+    ///
+    /// # Safety
+    ///
+    /// See [`Segment::create`].
+    #[inline]
+    pub unsafe fn grow(&mut self, name: &CStr, size: usize) -> Result<(), BufferError> {
+        let bytes = name.to_bytes_with_nul();
+        if bytes.len() > MAX_NAME + 1 {
+            return Err(BufferError::Incompatible);
+        }
+        let header = self.segment.header();
+        let epoch = header.epoch.wrapping_add(1);
+        // SAFETY: guaranteed by the caller.
+        let next = unsafe { Segment::create_at(name, size, MAGIC, epoch, self.write)? };
+        Segment::attach(&next.header().producer)?;
+        for (dst, &src) in header.successor.iter().zip(bytes) {
+            dst.store(src, Relaxed);
+        }
+        header.moved.store(1, Release);
+        notify(&header.consumer_waiting, &header.write_event);
+        let old = mem::replace(&mut self.segment, next);
+        old.header().producer.store(0, Release);
+        Ok(())
+    }
+
+    /// Returns the state of the consumer.
+    #[must_use]
+    #[inline]
+    pub fn peer(&self) -> Peer {
+        Peer::of(&self.segment.header().consumer)
+    }
+}
+
+impl Drop for Producer {
+    #[inline]
+    fn drop(&mut self) {
+        self.segment.header().producer.store(0, Release);
+    }
+}
+
+/// The reading half of a shared ring, see [`Segment::into_consumer`].
+#[derive(Debug)]
+pub struct Consumer {
+    segment: Segment,
+    read: u64,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl Consumer {
+    /// Drains the ring: calls the passed closure with the filled space, two
+    /// slices whose second one continues at the start of the ring, and
+    /// their total length. The closure returns the number of bytes it read,
+    /// which are released to the producer right away.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let header = self.segment.header();
+        let w = header.write.load(Acquire);
+        // Clamped, see `Producer::slices`.
+        let filled = usize::try_from(w.wrapping_sub(self.read)).unwrap_or(usize::MAX);
+        let (size, r) = (self.segment.size, self.segment.offset(self.read));
+        let (ranges, len) = filled_ranges(size, size - 1, r, r.wrapping_add(filled.min(size)));
+        // SAFETY: the ranges map the filled space, which the producer does
+        //         not touch until it is released.
+        let bufs = unsafe { self.segment.slices(ranges) };
+        let n = f(&bufs, len).map_err(ConsumerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+        self.read = self.read.wrapping_add(n as u64);
+        header.read.store(self.read, Release);
+        notify(&header.producer_waiting, &header.read_event);
+        Ok(n)
+    }
+
+    /// Like [`Consumer::slices`], but only offers the contiguous part of the
+    /// filled space.
+    ///
+    /// # Errors
+    ///
+    /// See [`Consumer::slices`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.slices(|bufs, _| {
+            let [buf, ..] = bufs else {
+                return Ok(0);
+            };
+            f(buf)
+        })
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.segment.header().write.load(Relaxed) == self.read
+    }
+}
+
+#[cfg(feature = "std")]
+impl Consumer {
+    /// Blocks until at least `bytes` bytes are filled, at most the
+    /// capacity, or the producer moved on to a successor segment, until
+    /// `timeout` passes or the producer's process died. Returns whether
+    /// either happened.
+    #[must_use]
+    #[inline]
+    pub fn wait(&self, bytes: usize, timeout: Option<Duration>) -> bool {
+        let header = self.segment.header();
+        let size = self.segment.size as u64;
+        let bytes = (bytes as u64).min(size);
+        let ready = || {
+            header.write.load(Acquire).wrapping_sub(self.read) >= bytes
+                || header.moved.load(Acquire) != 0
+        };
+        block(
+            &header.consumer_waiting,
+            &header.write_event,
+            &header.producer,
+            timeout,
+            ready,
+        )
+    }
+}
+
+impl Consumer {
+    /// Switches to the segment the producer moved on to, see
+    /// [`Producer::grow`], once everything left in this one was read.
+    /// Returns whether it switched.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error of [`Segment::open`], [`BufferError::Incompatible`]
+    /// if the successor is not the one the producer created, or
+    /// [`BufferError::InUse`] if another consumer took it. The consumer
+    /// stays on its segment then.
+    #[inline]
+    pub fn follow(&mut self) -> Result<bool, BufferError> {
+        let header = self.segment.header();
+        if header.moved.load(Acquire) == 0 || header.write.load(Relaxed) != self.read {
+            return Ok(false);
+        }
+        let mut name = [0; MAX_NAME + 1];
+        for (dst, src) in name.iter_mut().zip(&header.successor) {
+            *dst = src.load(Relaxed);
+        }
+        let name = CStr::from_bytes_until_nul(&name).map_err(|_| BufferError::Incompatible)?;
+        let epoch = header.epoch.wrapping_add(1);
+        // SAFETY: the producer created the successor under the contract
+        //         this consumer's segment was mapped with.
+        let next = unsafe { Segment::open(name)? };
+        if next.header().epoch != epoch || next.header().read.load(Relaxed) != self.read {
+            return Err(BufferError::Incompatible);
+        }
+        Segment::attach(&next.header().consumer)?;
+        let old = mem::replace(&mut self.segment, next);
+        old.header().consumer.store(0, Release);
+        Ok(true)
+    }
+
+    /// Returns the state of the producer.
+    #[must_use]
+    #[inline]
+    pub fn peer(&self) -> Peer {
+        Peer::of(&self.segment.header().producer)
+    }
+}
+
+impl Drop for Consumer {
+    #[inline]
+    fn drop(&mut self) {
+        self.segment.header().consumer.store(0, Release);
+    }
+}
+
+/// A read-only view of a shared ring, see [`Segment::observe`].
+#[derive(Debug)]
+pub struct Observer {
+    /// Mapped read-only: only ever loaded from.
+    segment: Segment,
+}
+
+impl Observer {
+    /// Returns the size of the ring.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.segment.size
+    }
+
+    /// Returns the read and write counters.
+    #[must_use]
+    #[inline]
+    pub fn counters(&self) -> (u64, u64) {
+        let header = self.segment.header();
+        (header.read.load(Acquire), header.write.load(Acquire))
+    }
+
+    /// Returns the state of the producer.
+    #[must_use]
+    #[inline]
+    pub fn producer(&self) -> Peer {
+        Peer::of(&self.segment.header().producer)
+    }
+
+    /// Returns the state of the consumer.
+    #[must_use]
+    #[inline]
+    pub fn consumer(&self) -> Peer {
+        Peer::of(&self.segment.header().consumer)
+    }
+
+    /// Replaces the contents of `dst` with a copy of the bytes in the ring,
+    /// and returns the counter position of the first one. Bytes the
+    /// consumer releases during the copy may be overwritten by the producer
+    /// right away, so they are left out.
+    #[inline]
+    pub fn snapshot(&self, dst: &mut Vec<u8>) -> u64 {
+        let header = self.segment.header();
+        let r = header.read.load(Acquire);
+        let w = header.write.load(Acquire);
+        // Clamped, see `Producer::slices`.
+        let filled = usize::try_from(w.wrapping_sub(r)).unwrap_or(usize::MAX);
+        let filled = filled.min(self.segment.size);
+        dst.clear();
+        dst.reserve(filled);
+        // SAFETY: the ring's bytes start at `data` and are `size` long.
+        let base = unsafe { self.segment.mapping.ptr().as_ptr().add(self.segment.data) };
+        for i in 0..filled {
+            let offset = self.segment.offset(r.wrapping_add(i as u64));
+            // SAFETY: the offset lies within the ring. The producer may
+            //         write the byte concurrently, so it is loaded
+            //         atomically; atomic loads from read-only memory are
+            //         fine.
+            let byte = unsafe { AtomicU8::from_ptr(base.add(offset)) };
+            dst.push(byte.load(Relaxed));
+        }
+        // Like a seqlock reader: bytes past the read counter seen now were
+        // not overwritten while copying.
+        fence(Acquire);
+        let released = header.read.load(Relaxed).wrapping_sub(r);
+        let stale = usize::try_from(released).unwrap_or(usize::MAX).min(filled);
+        let _ = dst.drain(..stale);
+        r.wrapping_add(stale as u64)
+    }
+}
+
+/// Wakes the other half if it is `waiting` on `event`, after a counter
+/// advanced.
+#[inline]
+fn notify(waiting: &AtomicU32, event: &AtomicU32) {
+    // Pairs with the fence in `block`: either the waiter sees the counter
+    // advanced, or this half sees the waiter.
+    fence(SeqCst);
+    if waiting.load(Relaxed) != 0 {
+        let _ = event.fetch_add(1, Relaxed);
+        mmap::wake(event);
+    }
+}
+
+/// Blocks until `ready` returns true, flagging `waiting` meanwhile so the
+/// other half bumps `event`, until `timeout` passes or the process holding
+/// the `peer` half died. Returns `ready`.
+#[cfg(feature = "std")]
+#[inline]
+fn block(
+    waiting: &AtomicU32,
+    event: &AtomicU32,
+    peer: &AtomicU32,
+    timeout: Option<Duration>,
+    ready: impl Fn() -> bool,
+) -> bool {
+    let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+    loop {
+        if ready() {
+            return true;
+        }
+        waiting.store(1, Relaxed);
+        fence(SeqCst);
+        let seen = event.load(Relaxed);
+        if ready() {
+            waiting.store(0, Relaxed);
+            return true;
+        }
+        let left = deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()));
+        if left.is_some_and(|left| left.is_zero()) || Peer::of(peer) == Peer::Dead {
+            waiting.store(0, Relaxed);
+            return false;
+        }
+        let nap = left.map_or(LIVENESS_INTERVAL, |left| left.min(LIVENESS_INTERVAL));
+        mmap::wait(event, seen, Some(nap));
+        waiting.store(0, Relaxed);
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Write for Producer {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        let n = self.slices(|bufs, len| {
+            let n = src.len().min(len);
+            crate::tee::copy_prefix(&[src], bufs, n);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(feature = "std")]
+impl io::Read for Consumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        let n = self.slices(|bufs, len| {
+            let n = dst.len().min(len);
+            crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+            Ok::<_, io::Error>(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ConsumerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ConsumerError::InvalidCount { .. }
+                | ConsumerError::TornFrame { .. }
+                | ConsumerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+}
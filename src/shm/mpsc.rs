@@ -0,0 +1,320 @@
+//! Rings shared between processes that many producers append records to,
+//! e.g. log lines shipped from worker processes to one collector.
+//!
+//! The segment has the [control block](super) of a single-producer ring,
+//! marked with [`MAGIC`] instead, with the producer fields unused. The
+//! write counter counts bytes claimed rather than committed: a producer
+//! claims room for a record by a compare-and-swap of the write counter,
+//! checked against the read counter loaded with acquire ordering, then
+//! writes the record and commits it on its own, so producers never wait
+//! for each other.
+//!
+//! Every record starts on a multiple of [`RECORD_HEADER`] bytes with a
+//! header word, a 32-bit unsigned integer holding the record's length with
+//! the [`COMMITTED`] bit set, stored with release ordering once the record
+//! was written. The record's bytes follow the header's [`RECORD_HEADER`]
+//! bytes, possibly wrapping around to the start of the ring.
+//!
+//! The consumer loads the header word at its read counter with acquire
+//! ordering, and receives records in the order they were claimed, waiting
+//! on a record claimed but not committed yet. It zeroes a record's bytes
+//! before advancing the read counter past them with a release store, so no
+//! stale header word is ever taken for a committed one.
+//!
+//! A producer dying between claim and commit stalls the ring for good.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::TryFrom as _;
+use ::core::ffi::CStr;
+use ::core::ops::{Drop, FnMut};
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::AtomicU32;
+#[cfg(feature = "std")]
+use ::core::time::Duration;
+
+#[cfg(feature = "std")]
+use super::block;
+use super::notify;
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{BufferError, ConsumerError, filled_ranges};
+
+/// Marks an initialized control block of a multi-producer ring: the bytes
+/// `bytermpx` read as a little-endian integer.
+pub const MAGIC: u64 = u64::from_le_bytes(*b"bytermpx");
+
+/// The length of a record's header, which records are aligned to.
+pub const RECORD_HEADER: usize = 8;
+
+/// Set in the header word of a committed record.
+pub const COMMITTED: u32 = 1 << 31;
+
+/// A mapping of a multi-producer ring, see [`Segment::create`] and
+/// [`Segment::open`].
+#[derive(Debug)]
+pub struct Segment {
+    inner: super::Segment,
+}
+
+impl Segment {
+    /// Creates the shared memory object `name` holding a ring of `size`
+    /// bytes, a power of two of at least [`RECORD_HEADER`], and maps it.
+    ///
+    /// # Errors
+    ///
+    /// See [`super::Segment::create`].
+    ///
+    /// # Safety
+    ///
+    /// See [`super::Segment::create`].
+    #[inline]
+    pub unsafe fn create(name: &CStr, size: usize) -> Result<Self, BufferError> {
+        if size < RECORD_HEADER {
+            return Err(BufferError::BadSize(size));
+        }
+        // SAFETY: guaranteed by the caller.
+        let inner = unsafe { super::Segment::create_at(name, size, MAGIC, 0, 0)? };
+        Ok(Segment { inner })
+    }
+
+    /// Maps the existing shared memory object `name`, created by
+    /// [`Segment::create`], possibly in another process.
+    ///
+    /// # Errors
+    ///
+    /// See [`super::Segment::open`].
+    ///
+    /// # Safety
+    ///
+    /// See [`super::Segment::create`].
+    #[inline]
+    pub unsafe fn open(name: &CStr) -> Result<Self, BufferError> {
+        // SAFETY: guaranteed by the caller.
+        let inner = unsafe { super::Segment::open_as(name, MAGIC)? };
+        if inner.size < RECORD_HEADER {
+            return Err(BufferError::Incompatible);
+        }
+        Ok(Segment { inner })
+    }
+
+    /// Returns the size of the ring.
+    #[must_use]
+    #[inline]
+    pub fn size(&self) -> usize {
+        self.inner.size
+    }
+
+    /// Makes a producer of the ring. Any number of them may append to it,
+    /// in this and other processes.
+    #[must_use]
+    #[inline]
+    pub fn into_producer(self) -> Producer {
+        Producer {
+            segment: self.inner,
+        }
+    }
+
+    /// Takes the consumer half of the ring, also from a process that died
+    /// holding it.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferError::InUse`] if a running process holds it.
+    #[inline]
+    pub fn into_consumer(self) -> Result<Consumer, BufferError> {
+        super::Segment::attach(&self.inner.header().consumer)?;
+        let read = self.inner.header().read.load(Relaxed);
+        Ok(Consumer {
+            segment: self.inner,
+            read,
+        })
+    }
+}
+
+/// Returns the header word of the record at counter position `pos`.
+#[inline]
+#[expect(clippy::cast_ptr_alignment, reason = "records are aligned")]
+fn word(segment: &super::Segment, pos: u64) -> &AtomicU32 {
+    let offset = segment.offset(pos);
+    // SAFETY: `pos` is a multiple of RECORD_HEADER, so the word lies
+    //         aligned within the page aligned ring. Header words are only
+    //         accessed atomically while shared.
+    unsafe {
+        let ptr = segment.mapping.ptr().as_ptr().add(segment.data + offset);
+        &*ptr.cast::<AtomicU32>()
+    }
+}
+
+/// The bytes a record of `len` bytes takes up, header and padding included.
+#[inline]
+const fn claim(len: usize) -> Option<usize> {
+    match len.checked_add(2 * RECORD_HEADER - 1) {
+        Some(n) => Some(n & !(RECORD_HEADER - 1)),
+        None => None,
+    }
+}
+
+/// One of the writing halves of a multi-producer ring, see
+/// [`Segment::into_producer`].
+#[derive(Debug)]
+pub struct Producer {
+    segment: super::Segment,
+}
+
+impl Producer {
+    /// Copies `record` into the ring as one record. Returns `false`,
+    /// committing nothing, if it is empty or does not fit.
+    #[inline]
+    pub fn send(&mut self, record: &[u8]) -> bool {
+        let segment = &self.segment;
+        let size = segment.size as u64;
+        let Some(claim) = claim(record.len()) else {
+            return false;
+        };
+        let (Ok(len), Ok(claim)) = (u32::try_from(record.len()), u64::try_from(claim)) else {
+            return false;
+        };
+        if record.is_empty() || len & COMMITTED != 0 || claim > size {
+            return false;
+        }
+
+        let header = segment.header();
+        let mut w = header.write.load(Relaxed);
+        loop {
+            let r = header.read.load(Acquire);
+            if claim > size - w.wrapping_sub(r).min(size) {
+                return false;
+            }
+            match header
+                .write
+                .compare_exchange_weak(w, w.wrapping_add(claim), Relaxed, Relaxed)
+            {
+                Ok(_) => break,
+                Err(now) => w = now,
+            }
+        }
+
+        let start = segment.offset(w.wrapping_add(RECORD_HEADER as u64));
+        let (ranges, _) =
+            filled_ranges(segment.size, segment.size - 1, start, start + record.len());
+        // SAFETY: the ranges map the claimed space past the header word,
+        //         which no other producer claims and the consumer does not
+        //         read before the record is committed.
+        let mut bufs = unsafe { segment.slices_mut(ranges) };
+        crate::tee::copy_prefix(&[record], &mut bufs, record.len());
+        word(segment, w).store(len | COMMITTED, Release);
+        notify(&header.consumer_waiting, &header.write_event);
+        true
+    }
+}
+
+/// The reading half of a multi-producer ring, see
+/// [`Segment::into_consumer`].
+#[derive(Debug)]
+pub struct Consumer {
+    segment: super::Segment,
+    read: u64,
+}
+
+impl Consumer {
+    /// Returns the length of the oldest record if it was committed.
+    #[inline]
+    fn next(&self) -> Option<usize> {
+        let word = word(&self.segment, self.read).load(Acquire);
+        if word & COMMITTED == 0 {
+            return None;
+        }
+        // Clamped: a misbehaving producer must not push the record out of
+        // the ring.
+        let len = usize::try_from(word & !COMMITTED).unwrap_or(usize::MAX);
+        Some(len.min(self.segment.size - RECORD_HEADER))
+    }
+
+    /// Receives a record: calls the passed closure with a pair of `&[u8]`
+    /// mapping the oldest record and its length. The record is consumed
+    /// once the closure succeeds. Returns the length of the record, 0
+    /// without calling the closure if there was none committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged. The record is left in the ring then.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[u8]], usize) -> Result<(), E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let Some(len) = self.next() else {
+            return Ok(0);
+        };
+        let segment = &self.segment;
+        let start = segment.offset(self.read.wrapping_add(RECORD_HEADER as u64));
+        let (ranges, _) = filled_ranges(segment.size, segment.size - 1, start, start + len);
+        // SAFETY: the ranges map the committed record, which no producer
+        //         touches until it is released.
+        let bufs = unsafe { segment.slices(ranges) };
+        f(&bufs, len).map_err(ConsumerError::Callback)?;
+
+        // Cannot fail, `next` clamped the length.
+        let claim = claim(len).unwrap_or(segment.size).min(segment.size);
+        let start = segment.offset(self.read);
+        let (ranges, _) = filled_ranges(segment.size, segment.size - 1, start, start + claim);
+        // SAFETY: the ranges map the record, header included, which is
+        //         still only ours.
+        for buf in unsafe { segment.slices_mut(ranges) } {
+            buf.fill(0);
+        }
+        self.read = self.read.wrapping_add(claim as u64);
+        let header = segment.header();
+        header.read.store(self.read, Release);
+        Ok(len)
+    }
+
+    /// Copies the oldest record into `dst` and consumes it. A record longer
+    /// than `dst` is truncated, the rest of it is discarded. Returns the
+    /// length of the record, `None` if there was none committed.
+    #[inline]
+    pub fn recv(&mut self, dst: &mut [u8]) -> Option<usize> {
+        let n = self.slices(|record, len| {
+            let n = len.min(dst.len());
+            crate::tee::copy_prefix(record, &mut [&mut *dst], n);
+            Ok::<_, ()>(())
+        });
+        match n {
+            Ok(0) | Err(_) => None,
+            Ok(n) => Some(n),
+        }
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.next().is_none()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Consumer {
+    /// Blocks until the oldest record is committed, or until `timeout`
+    /// passes. Returns whether it is.
+    #[must_use]
+    #[inline]
+    pub fn wait(&self, timeout: Option<Duration>) -> bool {
+        let header = self.segment.header();
+        block(
+            &header.consumer_waiting,
+            &header.write_event,
+            &header.producer,
+            timeout,
+            || self.next().is_some(),
+        )
+    }
+}
+
+impl Drop for Consumer {
+    #[inline]
+    fn drop(&mut self) {
+        self.segment.header().consumer.store(0, Release);
+    }
+}
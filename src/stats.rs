@@ -0,0 +1,201 @@
+//! Counters of what a half did, see [`Producer::stats`] and
+//! [`Consumer::stats`], enabled by the `stats` feature.
+//!
+//! Each half counts in plain fields of its own, never shared, so counting
+//! costs a few additions per commit and no synchronization. Without the
+//! feature the fields and the counting are compiled out.
+//!
+//! A [`Histogram`] of the commit sizes is kept once enabled with
+//! [`Producer::track_chunk_sizes`] or [`Consumer::track_chunk_sizes`].
+//! Many commits of a few bytes each point to readers or writers woken too
+//! early, to be tuned with the publish threshold or the watermarks.
+
+use ::alloc::boxed::Box;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::default::Default;
+use ::core::iter::Iterator;
+use ::core::marker::Copy;
+use ::core::option::Option::{self, None, Some};
+
+use crate::{Consumer, Producer};
+
+/// What a half did since it was created, see the [module docs](self).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Bytes committed by the producer, or released by the consumer.
+    pub bytes: u64,
+    /// Commits or releases of at least one byte.
+    pub commits: u64,
+    /// Commits or releases reaching the end of the buffer, continuing at
+    /// its start.
+    pub wraps: u64,
+    /// Commits or releases of nothing, e.g. because the ring was full or
+    /// empty.
+    pub stalls: u64,
+    /// The most bytes the half saw filled.
+    pub high_water: usize,
+}
+
+impl Stats {
+    /// Returns the counters by field name, in declaration order, e.g. to
+    /// embed them in a health check's JSON without copying field by field:
+    /// `serde_json::Map::from_iter(stats.fields().map(|(k, v)| (k.into(), v.into())))`.
+    #[must_use]
+    #[inline]
+    pub fn fields(&self) -> [(&'static str, u64); 5] {
+        [
+            ("bytes", self.bytes),
+            ("commits", self.commits),
+            ("wraps", self.wraps),
+            ("stalls", self.stalls),
+            ("high_water", self.high_water as u64),
+        ]
+    }
+
+    /// Records the commit or release of `n` bytes at position `pos` of a
+    /// buffer of `size` bytes, with `filled` bytes filled.
+    #[inline]
+    pub(crate) fn record(&mut self, pos: usize, n: usize, size: usize, filled: usize) {
+        self.high_water = self.high_water.max(filled);
+        if n == 0 {
+            self.stalls = self.stalls.wrapping_add(1);
+            return;
+        }
+        self.bytes = self.bytes.wrapping_add(n as u64);
+        self.commits = self.commits.wrapping_add(1);
+        if (pos & size.wrapping_sub(1)) + n >= size {
+            self.wraps = self.wraps.wrapping_add(1);
+        }
+    }
+}
+
+/// The number of buckets of a [`Histogram`].
+const BUCKETS: usize = usize::BITS as usize;
+
+/// Counts of commit or release sizes by powers of two, see the
+/// [module docs](self).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Histogram {
+    /// Bucket `i` counts the sizes from `2^i` to `2^(i + 1) - 1`.
+    counts: [u64; BUCKETS],
+}
+
+impl Histogram {
+    #[inline]
+    const fn new() -> Self {
+        Histogram {
+            counts: [0; BUCKETS],
+        }
+    }
+
+    /// Returns the number of sizes counted.
+    #[must_use]
+    #[inline]
+    pub fn count(&self) -> u64 {
+        self.counts.iter().sum()
+    }
+
+    /// Returns the non-empty buckets, smallest first, as the smallest size
+    /// of the bucket and its count.
+    #[inline]
+    pub fn buckets(&self) -> impl Iterator<Item = (usize, u64)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|&(_, &count)| count != 0)
+            .map(|(i, &count)| (1 << i, count))
+    }
+
+    /// Returns an upper bound of the `p`th percentile of the sizes, the
+    /// largest size of its bucket, or `None` if none were counted.
+    #[must_use]
+    #[inline]
+    pub fn percentile(&self, p: u64) -> Option<usize> {
+        let rank = (self.count() * p.min(100)).div_ceil(100).max(1);
+        let mut seen = 0;
+        for (i, &count) in self.counts.iter().enumerate() {
+            seen += count;
+            if seen >= rank {
+                return Some(usize::MAX >> (BUCKETS - 1 - i));
+            }
+        }
+        None
+    }
+
+    #[inline]
+    pub(crate) fn record(&mut self, n: usize) {
+        if n != 0 {
+            let bucket = &mut self.counts[n.ilog2() as usize];
+            *bucket = bucket.wrapping_add(1);
+        }
+    }
+}
+
+impl Producer {
+    /// Starts counting the sizes of the commits, see the
+    /// [module docs](crate::stats). Does nothing if counting already.
+    #[inline]
+    pub fn track_chunk_sizes(&mut self) {
+        if self.sizes.is_none() {
+            self.sizes = Some(Box::new(Histogram::new()));
+        }
+    }
+
+    /// Stops counting the sizes of the commits, dropping the counts.
+    #[inline]
+    pub fn untrack_chunk_sizes(&mut self) {
+        self.sizes = None;
+    }
+
+    /// Returns the sizes of the commits since counting started, if it did.
+    #[must_use]
+    #[inline]
+    pub fn chunk_sizes(&self) -> Option<&Histogram> {
+        self.sizes.as_deref()
+    }
+}
+
+impl Consumer {
+    /// Starts counting the sizes of the releases, see the
+    /// [module docs](crate::stats). Does nothing if counting already.
+    #[inline]
+    pub fn track_chunk_sizes(&mut self) {
+        if self.sizes.is_none() {
+            self.sizes = Some(Box::new(Histogram::new()));
+        }
+    }
+
+    /// Stops counting the sizes of the releases, dropping the counts.
+    #[inline]
+    pub fn untrack_chunk_sizes(&mut self) {
+        self.sizes = None;
+    }
+
+    /// Returns the sizes of the releases since counting started, if it did.
+    #[must_use]
+    #[inline]
+    pub fn chunk_sizes(&self) -> Option<&Histogram> {
+        self.sizes.as_deref()
+    }
+}
+
+impl Producer {
+    /// Returns the counters of the producer, see the
+    /// [module docs](crate::stats).
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
+
+impl Consumer {
+    /// Returns the counters of the consumer, see the
+    /// [module docs](crate::stats).
+    #[must_use]
+    #[inline]
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+}
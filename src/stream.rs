@@ -0,0 +1,204 @@
+//! Async in-memory duplex streams, see [`duplex`].
+//!
+//! Modeled on `tokio::io::duplex`, without its internal mutex around the
+//! buffer: each direction is a ring, locks are only taken to register the
+//! waker of an end that found nothing to do.
+
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::From as _;
+use ::core::default::Default as _;
+use ::core::ops::Drop;
+use ::core::option::Option::{self, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::{AtomicBool, fence};
+use ::core::task::{Context, Poll, Waker};
+use ::std::io;
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::ordering::{Acquire, Relaxed, Release, SeqCst};
+use crate::{BufferError, Consumer, Producer};
+
+/// Creates a pair of connected streams, each direction buffering up to
+/// `max_buf_size` bytes, rounded up to a power of two.
+///
+/// Reads wait for bytes and return 0 once the other stream is shut down or
+/// dropped; writes wait for room and fail with
+/// [`io::ErrorKind::BrokenPipe`] once the other stream is dropped.
+///
+/// # Errors
+///
+/// Returns an error when the size overflows, or when an allocation fails.
+#[inline]
+pub fn duplex(max_buf_size: usize) -> Result<(DuplexStream, DuplexStream), BufferError> {
+    let Some(size) = max_buf_size.max(1).checked_next_power_of_two() else {
+        return Err(BufferError::BadSize(max_buf_size));
+    };
+    let align = ::core::mem::align_of::<usize>();
+    let (tx_a, rx_b) = crate::new(size, align)?;
+    let (tx_b, rx_a) = crate::new(size, align)?;
+    let a_to_b = Arc::new(Direction::default());
+    let b_to_a = Arc::new(Direction::default());
+    let a = DuplexStream {
+        tx: tx_a,
+        rx: rx_a,
+        outgoing: Arc::clone(&a_to_b),
+        incoming: Arc::clone(&b_to_a),
+    };
+    let b = DuplexStream {
+        tx: tx_b,
+        rx: rx_b,
+        outgoing: b_to_a,
+        incoming: a_to_b,
+    };
+    Ok((a, b))
+}
+
+/// The waker of an end waiting for the other.
+#[derive(Debug, Default)]
+struct Slot {
+    waiting: AtomicBool,
+    waker: Mutex<Option<Waker>>,
+}
+
+impl Slot {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Option<Waker>> {
+        self.waker.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Registers `waker`; the caller must check for progress once more
+    /// afterwards.
+    #[inline]
+    fn register(&self, waker: &Waker) {
+        let _old = self.lock().replace(waker.clone());
+        self.waiting.store(true, Relaxed);
+        // Pairs with the fence in `wake`: either the caller's check sees
+        // the other end's progress, or the other end sees the flag.
+        fence(SeqCst);
+    }
+
+    /// Wakes the registered waker, after this end made progress.
+    #[inline]
+    fn wake(&self) {
+        fence(SeqCst);
+        if self.waiting.swap(false, Relaxed) {
+            let waker = self.lock().take();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+    }
+}
+
+/// One direction of a duplex: its reader's and writer's wakers, and whether
+/// either end is gone.
+#[derive(Debug, Default)]
+struct Direction {
+    reader: Slot,
+    writer: Slot,
+    /// The writing end shut down or was dropped.
+    shutdown: AtomicBool,
+    /// The reading end was dropped.
+    closed: AtomicBool,
+}
+
+/// One end of an async in-memory duplex, see [`duplex`].
+///
+/// The `poll_*` methods follow the contracts of the async I/O traits of the
+/// ecosystem, so thin adapters can implement them.
+#[derive(Debug)]
+pub struct DuplexStream {
+    tx: Producer,
+    rx: Consumer,
+    outgoing: Arc<Direction>,
+    incoming: Arc<Direction>,
+}
+
+impl DuplexStream {
+    /// Attempts to read bytes into `dst`, registering the task to be woken
+    /// if none are buffered.
+    #[inline]
+    pub fn poll_read(&mut self, cx: &mut Context<'_>, dst: &mut [u8]) -> Poll<io::Result<usize>> {
+        if dst.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut registered = false;
+        loop {
+            let n = io::Read::read(&mut self.rx, dst)?;
+            if n != 0 {
+                self.incoming.writer.wake();
+                return Poll::Ready(Ok(n));
+            }
+            // The writer published its last bytes before shutting down.
+            if self.incoming.shutdown.load(Acquire) && self.rx.is_empty() {
+                return Poll::Ready(Ok(0));
+            }
+            if registered {
+                return Poll::Pending;
+            }
+            self.incoming.reader.register(cx.waker());
+            registered = true;
+        }
+    }
+
+    /// Attempts to write bytes from `src`, registering the task to be woken
+    /// if there is no room.
+    #[inline]
+    pub fn poll_write(&mut self, cx: &mut Context<'_>, src: &[u8]) -> Poll<io::Result<usize>> {
+        if src.is_empty() {
+            return Poll::Ready(Ok(0));
+        }
+        let mut registered = false;
+        loop {
+            if self.outgoing.closed.load(Acquire) {
+                return Poll::Ready(Err(io::Error::from(io::ErrorKind::BrokenPipe)));
+            }
+            if self.outgoing.shutdown.load(Relaxed) {
+                return Poll::Ready(Err(io::Error::other("write after shutdown")));
+            }
+            let n = io::Write::write(&mut self.tx, src)?;
+            if n != 0 {
+                self.outgoing.reader.wake();
+                return Poll::Ready(Ok(n));
+            }
+            if registered {
+                return Poll::Pending;
+            }
+            self.outgoing.writer.register(cx.waker());
+            registered = true;
+        }
+    }
+
+    /// Writes are published right away, there is nothing to flush.
+    #[inline]
+    pub fn poll_flush(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.tx.publish();
+        Poll::Ready(Ok(()))
+    }
+
+    /// Shuts down the writing direction: the other stream reads 0 once it
+    /// has read all bytes written before.
+    #[inline]
+    pub fn poll_shutdown(&mut self, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.shutdown();
+        Poll::Ready(Ok(()))
+    }
+
+    #[inline]
+    fn shutdown(&mut self) {
+        self.tx.publish();
+        self.outgoing.shutdown.store(true, Release);
+        self.outgoing.reader.wake();
+    }
+}
+
+impl Drop for DuplexStream {
+    #[inline]
+    fn drop(&mut self) {
+        self.shutdown();
+        self.incoming.closed.store(true, Release);
+        self.incoming.writer.wake();
+    }
+}
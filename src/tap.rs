@@ -0,0 +1,188 @@
+//! Taps on the bytes flowing through a half, see [`Producer::set_tap`] and
+//! [`Consumer::set_tap`].
+//!
+//! A tap sees every chunk its half commits or releases, one or two slices,
+//! e.g. to count, hash or mirror the bytes, while the half's API and the
+//! other half stay unchanged. The producer's tap runs once the chunk is
+//! committed, the consumer's before it is released, so the bytes are
+//! still in place. Both run on the half's thread, inside the commit: a
+//! slow tap slows the half down. Without a tap installed, a commit costs a
+//! single branch more.
+//!
+//! # Checksums
+//!
+//! [`Producer::tap_checksum`] and [`Consumer::tap_checksum`] install a tap
+//! computing the CRC-32 of all bytes passing, e.g. to assert end-to-end
+//! integrity in tests or canaries: once the ring is drained, the digests
+//! of both halves match unless bytes were lost, duplicated or reordered.
+
+use ::alloc::boxed::Box;
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::convert::From as _;
+use ::core::default::Default;
+use ::core::fmt;
+use ::core::iter::Iterator as _;
+use ::core::marker::Send;
+use ::core::ops::FnMut;
+use ::core::option::Option::{None, Some};
+use ::core::sync::atomic::{AtomicU32, AtomicU64};
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{Buffer, Consumer, Producer};
+
+/// A tap's callback.
+type Callback = dyn FnMut(&[&[u8]]) + Send;
+
+/// An installed tap.
+pub(crate) struct Tap(Box<Callback>);
+
+impl fmt::Debug for Tap {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Tap")
+    }
+}
+
+impl Tap {
+    /// Passes the `n` bytes past the counter `pos` to the callback.
+    #[inline]
+    pub(crate) fn call(&mut self, buffer: &Buffer, pos: usize, n: usize) {
+        let (ranges, _) = if buffer.data.is_mirrored() {
+            crate::mirrored_ranges(buffer.mask, pos, n)
+        } else {
+            crate::filled_ranges(buffer.data.len(), buffer.mask, pos, pos.wrapping_add(n))
+        };
+        // SAFETY: the bytes are filled, past the producer's write counter
+        //         and not yet past the read counter, so neither half writes
+        //         them while the half calling is borrowed mutably.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+        (self.0)(&bufs);
+    }
+}
+
+impl Producer {
+    /// Installs `tap`, replacing any installed before, to be called with
+    /// every chunk committed, see the [module docs](crate::tap).
+    #[inline]
+    pub fn set_tap(&mut self, tap: impl FnMut(&[&[u8]]) + Send + 'static) {
+        self.tap = Some(Tap(Box::new(tap)));
+    }
+
+    /// Removes the tap, if any.
+    #[inline]
+    pub fn remove_tap(&mut self) {
+        self.tap = None;
+    }
+}
+
+impl Consumer {
+    /// Installs `tap`, replacing any installed before, to be called with
+    /// every chunk consumed, see the [module docs](crate::tap).
+    #[inline]
+    pub fn set_tap(&mut self, tap: impl FnMut(&[&[u8]]) + Send + 'static) {
+        self.tap = Some(Tap(Box::new(tap)));
+    }
+
+    /// Removes the tap, if any.
+    #[inline]
+    pub fn remove_tap(&mut self) {
+        self.tap = None;
+    }
+}
+
+impl Producer {
+    /// Installs a tap computing the CRC-32 of every byte committed, see the
+    /// [module docs](crate::tap#checksums), replacing any tap installed.
+    #[inline]
+    pub fn tap_checksum(&mut self) -> Checksum {
+        let checksum = Checksum::default();
+        self.set_tap(checksum.tap());
+        checksum
+    }
+}
+
+impl Consumer {
+    /// Installs a tap computing the CRC-32 of every byte consumed, see the
+    /// [module docs](crate::tap#checksums), replacing any tap installed.
+    #[inline]
+    pub fn tap_checksum(&mut self) -> Checksum {
+        let checksum = Checksum::default();
+        self.set_tap(checksum.tap());
+        checksum
+    }
+}
+
+/// The running CRC-32 of the bytes passing a half, see
+/// [`Producer::tap_checksum`].
+#[derive(Debug, Clone, Default)]
+pub struct Checksum(Arc<Digest>);
+
+#[derive(Debug, Default)]
+struct Digest {
+    crc: AtomicU32,
+    bytes: AtomicU64,
+}
+
+impl Checksum {
+    /// Returns the CRC-32 of the bytes passed so far.
+    #[must_use]
+    #[inline]
+    pub fn digest(&self) -> u32 {
+        self.0.crc.load(Acquire)
+    }
+
+    /// Returns the number of bytes passed so far.
+    #[must_use]
+    #[inline]
+    pub fn bytes(&self) -> u64 {
+        self.0.bytes.load(Acquire)
+    }
+
+    #[inline]
+    fn tap(&self) -> impl FnMut(&[&[u8]]) + Send + 'static {
+        let digest = Arc::clone(&self.0);
+        move |bufs| {
+            // Only the tap stores, the loads just read back its own value.
+            let mut crc = digest.crc.load(Relaxed);
+            let mut bytes = digest.bytes.load(Relaxed);
+            for buf in bufs {
+                crc = crc32(crc, buf);
+                bytes = bytes.wrapping_add(buf.len() as u64);
+            }
+            digest.crc.store(crc, Release);
+            digest.bytes.store(bytes, Release);
+        }
+    }
+}
+
+/// The table of the reflected CRC-32 polynomial of IEEE 802.3.
+const CRC32: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 == 0 {
+                crc >> 1
+            } else {
+                (crc >> 1) ^ 0xedb8_8320
+            };
+            bit += 1;
+        }
+        table[i as usize] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Continues the CRC-32 (IEEE 802.3, as of zlib) `crc` of some bytes over
+/// `data`. The CRC-32 of no bytes is 0.
+#[must_use]
+#[inline]
+pub(crate) fn crc32(crc: u32, data: &[u8]) -> u32 {
+    !data.iter().fold(!crc, |crc, &b| {
+        CRC32[((crc ^ u32::from(b)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
@@ -0,0 +1,111 @@
+//! Copying one ring into two, see [`tee`].
+
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::result::Result::{self, Ok};
+
+use crate::{Consumer, ConsumerError, Producer, ProducerError};
+
+/// Moves as many bytes as possible out of `source` into both `a` and `b`.
+///
+/// Meant e.g. to mirror traffic to a recorder. Bytes are only consumed once
+/// both destinations have room for them, so the fuller one holds back the
+/// source. Returns the number of bytes moved.
+///
+/// The count is a multiple of the frame size or start alignment of each of
+/// the three halves.
+///
+/// # Errors
+///
+/// Returns the error of `source`, or as [`ConsumerError::Callback`] the
+/// error of `a` or `b`, e.g. [`ProducerError::Poisoned`]. No half commits
+/// anything then.
+#[inline]
+pub fn tee(
+    source: &mut Consumer,
+    a: &mut Producer,
+    b: &mut Producer,
+) -> Result<usize, ConsumerError<ProducerError<Infallible>>> {
+    let frame = source.limits.frame.max(a.limits.frame).max(b.limits.frame);
+
+    source.slices(|src, len| {
+        a.slices(|dst_a, len_a| {
+            b.slices(|dst_b, len_b| {
+                let n = len.min(len_a).min(len_b) & !(frame - 1);
+                copy_prefix(src, dst_a, n);
+                copy_prefix(src, dst_b, n);
+                Ok::<_, Infallible>(n)
+            })
+        })
+        .map_err(flatten)
+    })
+}
+
+/// Unnests the error of a producer whose callback failed with that of
+/// another.
+#[inline]
+fn flatten(err: ProducerError<ProducerError<Infallible>>) -> ProducerError<Infallible> {
+    match err {
+        ProducerError::Callback(err) => err,
+        ProducerError::InvalidCount { n, len } => ProducerError::InvalidCount { n, len },
+        ProducerError::TornFrame { n, frame } => ProducerError::TornFrame { n, frame },
+        ProducerError::Poisoned => ProducerError::Poisoned,
+    }
+}
+
+/// Copies the first `n` bytes of `src` into `dst`.
+#[inline]
+pub fn copy_prefix(src: &[&[u8]], dst: &mut [&mut [u8]], mut n: usize) {
+    let mut d = 0;
+    let mut offset = 0;
+    for &s in src {
+        let mut s = s;
+        while n != 0 && !s.is_empty() {
+            if offset == dst[d].len() {
+                d += 1;
+                offset = 0;
+                continue;
+            }
+            let buf = &mut dst[d][offset..];
+            let len = s.len().min(buf.len()).min(n);
+            buf[..len].copy_from_slice(&s[..len]);
+            s = &s[len..];
+            n -= len;
+            offset += len;
+        }
+    }
+}
+
+/// Owns a source and two destinations for [`tee`].
+#[derive(Debug)]
+pub struct Tee {
+    source: Consumer,
+    a: Producer,
+    b: Producer,
+}
+
+impl Tee {
+    /// Combines the halves, see [`tee`].
+    #[must_use]
+    #[inline]
+    pub const fn new(source: Consumer, a: Producer, b: Producer) -> Self {
+        Tee { source, a, b }
+    }
+
+    /// Moves as many bytes as possible, see [`tee`].
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`tee`].
+    #[inline]
+    pub fn pump(&mut self) -> Result<usize, ConsumerError<ProducerError<Infallible>>> {
+        tee(&mut self.source, &mut self.a, &mut self.b)
+    }
+
+    /// Splits the adapter back into its halves.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> (Consumer, Producer, Producer) {
+        (self.source, self.a, self.b)
+    }
+}
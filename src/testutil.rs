@@ -0,0 +1,626 @@
+//! Endpoints for exercising the loops driving a ring in tests, enabled by
+//! the `testutil` feature.
+//!
+//! [`Link`] wraps an `io::Read` or `io::Write` endpoint, e.g. a socket or
+//! a `Cursor`, with the conditions of a real network: latency before every
+//! call, a bandwidth cap, short reads and writes, and `Interrupted` errors.
+//! All randomness comes from a seeded [`Rng`], so a failing run replays
+//! with the same seed.
+//!
+//! [`RandomReader`] and [`RandomWriter`] make random progress, reading or
+//! writing a random part of the buffers passed on every call, without
+//! touching the bytes, to stress a ring's accounting at full speed.
+//! [`PatternReader`] and [`PatternWriter`] do the same while producing and
+//! verifying the stream given by [`pattern`], to catch lost, duplicated or
+//! reordered bytes.
+//!
+//! [`Faulty`] wraps a half, or anything implementing the [`ring`](crate::ring) traits,
+//! and injects a scripted [`Fault`] into chosen calls: a ring that looks
+//! full or empty, progress capped to a few bytes, or an error, so the retry
+//! and backpressure logic driving it can be tested deterministically.
+
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::{Infallible, TryFrom as _};
+use ::core::iter::Iterator as _;
+use ::core::marker::Copy;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, None, Some};
+use ::core::result::Result::{self, Err, Ok};
+use ::core::time::Duration;
+use ::std::io;
+use ::std::thread;
+
+use crate::ratelimit::TokenBucket;
+use crate::ring::{RingConsumer, RingProducer};
+use crate::{ConsumerError, ProducerError};
+
+/// A small, seedable pseudo-random number generator, xorshift64*. Not for
+/// cryptography.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    /// Creates a generator from `seed`; generators of the same seed return
+    /// the same numbers.
+    #[must_use]
+    #[inline]
+    pub const fn new(seed: u64) -> Self {
+        // Spread the seed, and keep the state non-zero as xorshift needs.
+        Rng((seed ^ 0x9e37_79b9_7f4a_7c15) | 1)
+    }
+
+    /// Returns the next number.
+    #[inline]
+    pub const fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Returns a number in `0..n`, or 0 if `n` is 0.
+    #[expect(clippy::cast_possible_truncation, reason = "less than `n`")]
+    #[must_use]
+    #[inline]
+    pub fn below(&mut self, n: usize) -> usize {
+        if n == 0 {
+            return 0;
+        }
+        let n = n as u64;
+        // The modulo bias is negligible for test sizes.
+        (self.next_u64() % n) as usize
+    }
+}
+
+/// An endpoint behind simulated network conditions, see the
+/// [module docs](self).
+#[derive(Debug)]
+pub struct Link<T> {
+    inner: T,
+    rng: Rng,
+    latency: Duration,
+    bandwidth: Option<TokenBucket>,
+    short: bool,
+    interrupt_one_in: usize,
+}
+
+impl<T> Link<T> {
+    /// Wraps `inner` without any conditions yet, drawing randomness from
+    /// `seed`.
+    #[must_use]
+    #[inline]
+    pub const fn new(inner: T, seed: u64) -> Self {
+        Link {
+            inner,
+            rng: Rng::new(seed),
+            latency: Duration::ZERO,
+            bandwidth: None,
+            short: false,
+            interrupt_one_in: 0,
+        }
+    }
+
+    /// Sleeps for `latency` before every call.
+    #[must_use]
+    #[inline]
+    pub const fn latency(mut self, latency: Duration) -> Self {
+        self.latency = latency;
+        self
+    }
+
+    /// Caps the bytes moved to `bytes_per_sec`, in bursts of at most a
+    /// hundredth of a second's worth, sleeping while the cap is reached.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bytes_per_sec` is zero.
+    #[must_use]
+    #[inline]
+    pub fn bandwidth(mut self, bytes_per_sec: u64) -> Self {
+        let burst = (bytes_per_sec / 100).max(1);
+        self.bandwidth = Some(TokenBucket::new(bytes_per_sec, burst));
+        self
+    }
+
+    /// Moves a random number of bytes, at least one, on every call.
+    #[must_use]
+    #[inline]
+    pub const fn short_io(mut self) -> Self {
+        self.short = true;
+        self
+    }
+
+    /// Fails one in `n` calls at random with [`io::ErrorKind::Interrupted`],
+    /// never if `n` is 0.
+    #[must_use]
+    #[inline]
+    pub const fn interrupt_one_in(mut self, n: usize) -> Self {
+        self.interrupt_one_in = n;
+        self
+    }
+
+    /// Returns the wrapped endpoint.
+    #[must_use]
+    #[inline]
+    pub const fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    /// Unwraps the endpoint.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+
+    /// Applies the conditions to a call of `len` bytes, returning how many
+    /// it may move.
+    #[inline]
+    fn admit(&mut self, len: usize) -> io::Result<usize> {
+        if self.interrupt_one_in != 0 && self.rng.below(self.interrupt_one_in) == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                "simulated interrupt",
+            ));
+        }
+        if !self.latency.is_zero() {
+            thread::sleep(self.latency);
+        }
+        if len == 0 {
+            return Ok(0);
+        }
+        let mut n = if self.short {
+            1 + self.rng.below(len)
+        } else {
+            len
+        };
+        if let Some(bucket) = &mut self.bandwidth {
+            let available = loop {
+                let available = bucket.available();
+                if available != 0 {
+                    break available;
+                }
+                thread::sleep(bucket.delay(1));
+            };
+            n = n.min(usize::try_from(available).unwrap_or(usize::MAX));
+        }
+        Ok(n)
+    }
+
+    #[inline]
+    fn moved(&mut self, n: usize) {
+        if let Some(bucket) = &mut self.bandwidth {
+            bucket.take(n);
+        }
+    }
+}
+
+impl<T: io::Read> io::Read for Link<T> {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let limit = self.admit(buf.len())?;
+        let n = self.inner.read(&mut buf[..limit])?;
+        self.moved(n);
+        Ok(n)
+    }
+}
+
+impl<T: io::Write> io::Write for Link<T> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let limit = self.admit(buf.len())?;
+        let n = self.inner.write(&buf[..limit])?;
+        self.moved(n);
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Returns the byte at position `pos` of the stream [`PatternReader`]
+/// produces; it repeats only every 2²⁴ bytes.
+#[must_use]
+#[inline]
+pub const fn pattern(pos: u64) -> u8 {
+    let [a, b, c, ..] = pos.to_le_bytes();
+    a ^ b.rotate_left(3) ^ c.rotate_left(5)
+}
+
+/// Returns how many of `len` bytes a call makes progress on: all of a few,
+/// otherwise a random number of at least `min`.
+#[inline]
+fn progress(rng: &mut Rng, len: usize, min: usize) -> usize {
+    if len <= 10 {
+        len
+    } else {
+        min + rng.below(len - min)
+    }
+}
+
+/// A reader of `len` bytes, returning a random number of them, at least
+/// one, on every call, see the [module docs](self). The bytes read into
+/// are left as they are.
+#[derive(Debug, Clone)]
+pub struct RandomReader {
+    rng: Rng,
+    remaining: u64,
+}
+
+impl RandomReader {
+    /// Creates a reader of `len` bytes drawing randomness from `seed`.
+    #[must_use]
+    #[inline]
+    pub const fn new(len: u64, seed: u64) -> Self {
+        RandomReader {
+            rng: Rng::new(seed),
+            remaining: len,
+        }
+    }
+
+    /// Returns the bytes left to read.
+    #[must_use]
+    #[inline]
+    pub const fn remaining(&self) -> u64 {
+        self.remaining
+    }
+
+    #[inline]
+    fn advance(&mut self, len: usize) -> usize {
+        let len = len.min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+        let n = progress(&mut self.rng, len, 1);
+        self.remaining -= n as u64;
+        n
+    }
+}
+
+impl io::Read for RandomReader {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        Ok(self.advance(buf.len()))
+    }
+
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        Ok(self.advance(bufs.iter().map(|buf| buf.len()).sum()))
+    }
+}
+
+/// A writer taking a random number of the bytes passed, possibly none, on
+/// every call, see the [module docs](self). The bytes are not looked at.
+#[derive(Debug, Clone)]
+pub struct RandomWriter {
+    rng: Rng,
+    written: u64,
+}
+
+impl RandomWriter {
+    /// Creates a writer drawing randomness from `seed`.
+    #[must_use]
+    #[inline]
+    pub const fn new(seed: u64) -> Self {
+        RandomWriter {
+            rng: Rng::new(seed),
+            written: 0,
+        }
+    }
+
+    /// Returns the bytes written so far.
+    #[must_use]
+    #[inline]
+    pub const fn written(&self) -> u64 {
+        self.written
+    }
+
+    #[inline]
+    fn advance(&mut self, len: usize) -> usize {
+        let n = progress(&mut self.rng, len, 0);
+        self.written += n as u64;
+        n
+    }
+}
+
+impl io::Write for RandomWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Ok(self.advance(buf.len()))
+    }
+
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        Ok(self.advance(bufs.iter().map(|buf| buf.len()).sum()))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A [`RandomReader`] filling the bytes read with the [`pattern`].
+#[derive(Debug, Clone)]
+pub struct PatternReader {
+    inner: RandomReader,
+    pos: u64,
+}
+
+impl PatternReader {
+    /// Creates a reader of `len` bytes drawing randomness from `seed`.
+    #[must_use]
+    #[inline]
+    pub const fn new(len: u64, seed: u64) -> Self {
+        PatternReader {
+            inner: RandomReader::new(len, seed),
+            pos: 0,
+        }
+    }
+
+    /// Returns the bytes left to read.
+    #[must_use]
+    #[inline]
+    pub const fn remaining(&self) -> u64 {
+        self.inner.remaining
+    }
+}
+
+impl io::Read for PatternReader {
+    #[inline]
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_vectored(&mut [io::IoSliceMut::new(buf)])
+    }
+
+    #[inline]
+    fn read_vectored(&mut self, bufs: &mut [io::IoSliceMut<'_>]) -> io::Result<usize> {
+        let n = self.inner.advance(bufs.iter().map(|buf| buf.len()).sum());
+        let bytes = bufs.iter_mut().flat_map(|buf| buf.iter_mut()).take(n);
+        for b in bytes {
+            *b = pattern(self.pos);
+            self.pos += 1;
+        }
+        Ok(n)
+    }
+}
+
+/// A [`RandomWriter`] verifying the bytes written against the [`pattern`].
+#[derive(Debug, Clone)]
+pub struct PatternWriter {
+    inner: RandomWriter,
+}
+
+impl PatternWriter {
+    /// Creates a writer drawing randomness from `seed`.
+    #[must_use]
+    #[inline]
+    pub const fn new(seed: u64) -> Self {
+        PatternWriter {
+            inner: RandomWriter::new(seed),
+        }
+    }
+
+    /// Returns the bytes written and verified so far.
+    #[must_use]
+    #[inline]
+    pub const fn written(&self) -> u64 {
+        self.inner.written
+    }
+}
+
+impl io::Write for PatternWriter {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_vectored(&[io::IoSlice::new(buf)])
+    }
+
+    /// Fails with [`io::ErrorKind::InvalidData`] at the first byte not
+    /// matching the pattern, taking none of the bytes.
+    #[inline]
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        let start = self.inner.written;
+        let n = progress(
+            &mut self.inner.rng,
+            bufs.iter().map(|buf| buf.len()).sum(),
+            0,
+        );
+        let bytes = bufs.iter().flat_map(|buf| buf.iter()).take(n);
+        for (pos, &b) in (start..).zip(bytes) {
+            if b != pattern(pos) {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    ::std::format!("byte {pos} is {b:#04x}, expected {:#04x}", pattern(pos)),
+                ));
+            }
+        }
+        self.inner.written += n as u64;
+        Ok(n)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A fault injected into a call of a [`Faulty`] half.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Offers no bytes, so the ring looks full to a producer and empty to a
+    /// consumer.
+    Stall,
+    /// Offers at most this many bytes.
+    Cap(usize),
+    /// Fails with [`ProducerError::Poisoned`] or
+    /// [`ConsumerError::Poisoned`]. The half is not called.
+    Poison,
+}
+
+/// A half injecting [`Fault`]s into chosen calls of
+/// [`RingProducer::fill`] or [`RingConsumer::drain`], see the
+/// [module docs](self). Other calls are forwarded unchanged.
+#[derive(Debug)]
+pub struct Faulty<H> {
+    inner: H,
+    calls: u64,
+    script: Vec<(u64, Fault)>,
+}
+
+impl<H> Faulty<H> {
+    /// Wraps `inner`, injecting no faults yet.
+    #[must_use]
+    #[inline]
+    pub const fn new(inner: H) -> Self {
+        Faulty {
+            inner,
+            calls: 0,
+            script: Vec::new(),
+        }
+    }
+
+    /// Injects `fault` into the `call`th call, counting from 1. A later
+    /// fault for the same call replaces the earlier one.
+    #[must_use]
+    #[inline]
+    pub fn on_call(mut self, call: u64, fault: Fault) -> Self {
+        self.script.retain(|&(c, _)| c != call);
+        self.script.push((call, fault));
+        self
+    }
+
+    /// Returns the number of calls so far.
+    #[must_use]
+    #[inline]
+    pub const fn calls(&self) -> u64 {
+        self.calls
+    }
+
+    /// Returns the wrapped half.
+    #[must_use]
+    #[inline]
+    pub const fn get_ref(&self) -> &H {
+        &self.inner
+    }
+
+    /// Returns the wrapped half mutably.
+    #[must_use]
+    #[inline]
+    pub const fn get_mut(&mut self) -> &mut H {
+        &mut self.inner
+    }
+
+    /// Unwraps the half.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> H {
+        self.inner
+    }
+
+    /// Counts a call and returns its fault, if any.
+    #[inline]
+    fn next_fault(&mut self) -> Option<Fault> {
+        self.calls += 1;
+        let call = self.calls;
+        self.script
+            .iter()
+            .find(|&&(c, _)| c == call)
+            .map(|&(_, fault)| fault)
+    }
+}
+
+/// Returns the first `n` bytes of `bufs`.
+#[inline]
+fn cap<'a>(bufs: &[&'a [u8]], mut n: usize) -> Vec<&'a [u8]> {
+    let mut capped = Vec::new();
+    for &buf in bufs {
+        let len = buf.len().min(n);
+        capped.push(&buf[..len]);
+        n -= len;
+    }
+    capped
+}
+
+/// Returns the first `n` bytes of `bufs` mutably.
+#[inline]
+fn cap_mut<'a>(bufs: &'a mut [&mut [u8]], mut n: usize) -> Vec<&'a mut [u8]> {
+    let mut capped = Vec::new();
+    for buf in bufs {
+        let len = buf.len().min(n);
+        capped.push(&mut buf[..len]);
+        n -= len;
+    }
+    capped
+}
+
+impl<H: RingProducer> RingProducer for Faulty<H> {
+    #[inline]
+    fn fill(
+        &mut self,
+        f: &mut dyn FnMut(&mut [&mut [u8]], usize) -> usize,
+    ) -> Result<usize, ProducerError<Infallible>> {
+        let limit = match self.next_fault() {
+            None => return self.inner.fill(f),
+            Some(Fault::Poison) => return Err(ProducerError::Poisoned),
+            Some(Fault::Stall) => 0,
+            Some(Fault::Cap(limit)) => limit,
+        };
+        let mut refused = None;
+        let n = self.inner.fill(&mut |bufs, len| {
+            let len = len.min(limit);
+            let n = f(&mut cap_mut(bufs, len), len);
+            if n > len {
+                refused = Some(ProducerError::InvalidCount { n, len });
+                return 0;
+            }
+            n
+        })?;
+        refused.map_or(Ok(n), Err)
+    }
+
+    #[inline]
+    fn free_len(&self) -> usize {
+        self.inner.free_len()
+    }
+
+    #[inline]
+    fn pending(&self) -> usize {
+        self.inner.pending()
+    }
+
+    #[inline]
+    fn publish(&mut self) {
+        self.inner.publish();
+    }
+}
+
+impl<H: RingConsumer> RingConsumer for Faulty<H> {
+    #[inline]
+    fn drain(
+        &mut self,
+        f: &mut dyn FnMut(&[&[u8]], usize) -> usize,
+    ) -> Result<usize, ConsumerError<Infallible>> {
+        let limit = match self.next_fault() {
+            None => return self.inner.drain(f),
+            Some(Fault::Poison) => return Err(ConsumerError::Poisoned),
+            Some(Fault::Stall) => 0,
+            Some(Fault::Cap(limit)) => limit,
+        };
+        let mut refused = None;
+        let n = self.inner.drain(&mut |bufs, len| {
+            let len = len.min(limit);
+            let n = f(&cap(bufs, len), len);
+            if n > len {
+                refused = Some(ConsumerError::InvalidCount { n, len });
+                return 0;
+            }
+            n
+        })?;
+        refused.map_or(Ok(n), Err)
+    }
+
+    #[inline]
+    fn filled_len(&self) -> usize {
+        self.inner.filled_len()
+    }
+}
@@ -0,0 +1,187 @@
+//! Estimating the bytes per second a half moves, see
+//! [`Producer::meter_throughput`] and [`Consumer::meter_throughput`].
+//!
+//! A meter splits its window into [`SLOTS`] slots of equal length and adds
+//! the bytes of every commit or release to the current one, clearing the
+//! slots the window moved past. The [rate](Throughput::rate) is the sum of
+//! the slots over the time they span, so it trails changes by at most one
+//! window. The half records into atomics the [`Throughput`] handles share,
+//! so the rate may be queried from any thread, e.g. a dashboard's, while
+//! the half keeps going. Without a meter installed, a commit costs a single
+//! branch more.
+
+use ::alloc::sync::Arc;
+use ::core::array;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::convert::TryFrom as _;
+use ::core::iter::Iterator as _;
+use ::core::option::Option::{self, Some};
+use ::core::sync::atomic::AtomicU64;
+use ::core::time::Duration;
+use ::std::time::Instant;
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{Consumer, Producer};
+
+/// The number of slots a meter's window is split into.
+pub const SLOTS: usize = 16;
+
+/// Returns the index of the slot of `tick`.
+#[expect(clippy::cast_possible_truncation, reason = "less than `SLOTS`")]
+#[inline]
+const fn index(tick: u64) -> usize {
+    (tick % SLOTS as u64) as usize
+}
+
+/// A handle to the meter of a half, see the [module docs](self).
+#[derive(Debug, Clone)]
+pub struct Throughput(Arc<Meter>);
+
+#[derive(Debug)]
+struct Meter {
+    start: Instant,
+    /// The length of a slot in nanoseconds, at least 1.
+    slot: u64,
+    /// The index of the slot last recorded into, counted from `start`.
+    tick: AtomicU64,
+    slots: [AtomicU64; SLOTS],
+}
+
+impl Throughput {
+    #[inline]
+    fn new(window: Duration) -> Self {
+        let slot = window.as_nanos() / SLOTS as u128;
+        Throughput(Arc::new(Meter {
+            start: Instant::now(),
+            slot: u64::try_from(slot).unwrap_or(u64::MAX).max(1),
+            tick: AtomicU64::new(0),
+            slots: array::from_fn(|_| AtomicU64::new(0)),
+        }))
+    }
+
+    /// Returns the length of the window.
+    #[must_use]
+    #[inline]
+    pub fn window(&self) -> Duration {
+        Duration::from_nanos(self.0.slot.saturating_mul(SLOTS as u64))
+    }
+
+    /// Returns the bytes moved per second over the last window.
+    #[must_use]
+    #[inline]
+    pub fn rate(&self) -> f64 {
+        self.rate_at(self.now())
+    }
+
+    /// Returns the bytes moved over the last window.
+    #[must_use]
+    #[inline]
+    pub fn bytes(&self) -> u64 {
+        self.bytes_at(self.now())
+    }
+
+    #[inline]
+    fn now(&self) -> u64 {
+        u64::try_from(self.0.start.elapsed().as_nanos()).unwrap_or(u64::MAX)
+    }
+
+    /// Adds `n` bytes at `now` nanoseconds past the meter's start. Only the
+    /// half calls this, so the slots have a single writer.
+    #[inline]
+    pub(crate) fn record_at(&self, n: usize, now: u64) {
+        let meter = &*self.0;
+        let tick = now / meter.slot;
+        let last = meter.tick.load(Relaxed);
+        if tick > last {
+            for t in (last + 1)..=tick.min(last + SLOTS as u64) {
+                meter.slots[index(t)].store(0, Relaxed);
+            }
+            meter.tick.store(tick, Release);
+        }
+        let slot = &meter.slots[index(tick)];
+        slot.store(slot.load(Relaxed).saturating_add(n as u64), Relaxed);
+    }
+
+    #[inline]
+    pub(crate) fn bytes_at(&self, now: u64) -> u64 {
+        let meter = &*self.0;
+        let tick = now / meter.slot;
+        let last = meter.tick.load(Acquire);
+        let first = (tick + 1).saturating_sub(SLOTS as u64);
+        (first..=last.min(tick))
+            .map(|t| meter.slots[index(t)].load(Relaxed))
+            .sum()
+    }
+
+    #[expect(
+        clippy::cast_precision_loss,
+        reason = "rates are approximate beyond 2⁵³ bytes"
+    )]
+    #[inline]
+    pub(crate) fn rate_at(&self, now: u64) -> f64 {
+        let slot = self.0.slot;
+        // The window's older slots in full, the current one so far.
+        let span = ((SLOTS as u64 - 1) * slot + now % slot + 1).min(now + 1);
+        self.bytes_at(now) as f64 * 1e9 / span as f64
+    }
+
+    #[inline]
+    fn record(&self, n: usize) {
+        self.record_at(n, self.now());
+    }
+}
+
+impl Producer {
+    /// Installs a meter of the bytes committed over `window`, replacing any
+    /// installed before, and returns a handle to it, see the
+    /// [module docs](crate::throughput).
+    #[inline]
+    pub fn meter_throughput(&mut self, window: Duration) -> Throughput {
+        let meter = Throughput::new(window);
+        self.meter = Some(meter.clone());
+        meter
+    }
+
+    /// Returns the bytes committed per second over the meter's window, if
+    /// a meter is installed.
+    #[must_use]
+    #[inline]
+    pub fn throughput(&self) -> Option<f64> {
+        self.meter.as_ref().map(Throughput::rate)
+    }
+
+    #[inline]
+    pub(crate) fn record_throughput(&self, n: usize) {
+        if let Some(meter) = &self.meter {
+            meter.record(n);
+        }
+    }
+}
+
+impl Consumer {
+    /// Installs a meter of the bytes consumed over `window`, replacing any
+    /// installed before, and returns a handle to it, see the
+    /// [module docs](crate::throughput).
+    #[inline]
+    pub fn meter_throughput(&mut self, window: Duration) -> Throughput {
+        let meter = Throughput::new(window);
+        self.meter = Some(meter.clone());
+        meter
+    }
+
+    /// Returns the bytes consumed per second over the meter's window, if
+    /// a meter is installed.
+    #[must_use]
+    #[inline]
+    pub fn throughput(&self) -> Option<f64> {
+        self.meter.as_ref().map(Throughput::rate)
+    }
+
+    #[inline]
+    pub(crate) fn record_throughput(&self, n: usize) {
+        if let Some(meter) = &self.meter {
+            meter.record(n);
+        }
+    }
+}
@@ -0,0 +1,186 @@
+//! Transforming bytes while they are copied into or out of a ring, see
+//! [`TransformWriter`] and [`TransformReader`].
+//!
+//! The closure transforms the bytes in place where they are copied to, the
+//! producer's empty space or the caller's buffer, e.g. XOR masking a
+//! WebSocket payload or swapping the byte order of samples, so no second
+//! staging buffer is needed. It is called with the copied bytes in stream
+//! order, one or two slices per copy, so a closure keeping state, e.g. the
+//! offset into a mask, sees one continuous stream.
+
+use ::core::cmp::Ord as _;
+use ::core::convert::Infallible;
+use ::core::fmt;
+use ::core::iter::{IntoIterator, Iterator as _};
+use ::core::ops::FnMut;
+#[cfg(feature = "std")]
+use ::core::result::Result::Err;
+use ::core::result::Result::Ok;
+#[cfg(feature = "std")]
+use ::std::io;
+
+#[cfg(feature = "std")]
+use crate::ProducerError;
+use crate::{Consumer, Producer};
+
+/// Passes the first `n` bytes of `bufs` to `f`, in order.
+#[inline]
+fn apply<'a>(
+    f: &mut impl FnMut(&mut [u8]),
+    bufs: impl IntoIterator<Item = &'a mut [u8]>,
+    mut n: usize,
+) {
+    for buf in bufs {
+        if n == 0 {
+            break;
+        }
+        let len = buf.len().min(n);
+        f(&mut buf[..len]);
+        n -= len;
+    }
+}
+
+/// Writes into a ring, transforming the bytes in the empty space before
+/// committing them, see the [module docs](self).
+pub struct TransformWriter<F> {
+    inner: Producer,
+    f: F,
+}
+
+impl<F> fmt::Debug for TransformWriter<F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransformWriter")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut(&mut [u8])> TransformWriter<F> {
+    /// Wraps `producer`, transforming the bytes written with `f`.
+    #[must_use]
+    #[inline]
+    pub const fn new(producer: Producer, f: F) -> Self {
+        TransformWriter { inner: producer, f }
+    }
+
+    /// Copies as much of `src` into the ring as fits, transforms and
+    /// commits it. Returns the number of bytes committed, 0 if the ring is
+    /// full.
+    #[inline]
+    pub fn write(&mut self, src: &[u8]) -> usize {
+        let f = &mut self.f;
+        let n = self.inner.slices(|bufs, len| {
+            let n = src.len().min(len);
+            crate::tee::copy_prefix(&[src], bufs, n);
+            apply(f, bufs.iter_mut().map(|buf| &mut **buf), n);
+            Ok::<_, Infallible>(n)
+        });
+        n.unwrap_or(0)
+    }
+
+    /// Reads from `reader` straight into the empty space with one vectored
+    /// read, transforms and commits what was read. Returns the number of
+    /// bytes committed.
+    ///
+    /// # Errors
+    ///
+    /// Returns the error of `reader`.
+    #[cfg(feature = "std")]
+    #[inline]
+    pub fn read_from(&mut self, reader: &mut impl io::Read) -> io::Result<usize> {
+        let f = &mut self.f;
+        let n = self.inner.io_slices(|bufs, _| {
+            let n = reader.read_vectored(bufs)?;
+            apply(f, bufs.iter_mut().map(|buf| &mut **buf), n);
+            Ok(n)
+        });
+        match n {
+            Ok(n) => Ok(n),
+            Err(ProducerError::Callback(e)) => Err(e),
+            Err(
+                err @ (ProducerError::InvalidCount { .. }
+                | ProducerError::TornFrame { .. }
+                | ProducerError::Poisoned),
+            ) => Err(io::Error::other(err)),
+        }
+    }
+
+    /// Unwraps the producer.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Producer {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(&mut [u8])> io::Write for TransformWriter<F> {
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        Ok(TransformWriter::write(self, src))
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.publish();
+        Ok(())
+    }
+}
+
+/// Reads out of a ring, transforming the bytes in the caller's buffer, see
+/// the [module docs](self).
+pub struct TransformReader<F> {
+    inner: Consumer,
+    f: F,
+}
+
+impl<F> fmt::Debug for TransformReader<F> {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TransformReader")
+            .field("inner", &self.inner)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<F: FnMut(&mut [u8])> TransformReader<F> {
+    /// Wraps `consumer`, transforming the bytes read with `f`.
+    #[must_use]
+    #[inline]
+    pub const fn new(consumer: Consumer, f: F) -> Self {
+        TransformReader { inner: consumer, f }
+    }
+
+    /// Copies bytes from the ring into `dst`, transforms them there and
+    /// consumes them. Returns the number of bytes copied, 0 if the ring is
+    /// empty.
+    #[inline]
+    pub fn read(&mut self, dst: &mut [u8]) -> usize {
+        let n = self.inner.slices(|bufs, len| {
+            let n = dst.len().min(len);
+            crate::tee::copy_prefix(bufs, &mut [&mut *dst], n);
+            Ok::<_, Infallible>(n)
+        });
+        let n = n.unwrap_or(0);
+        if n != 0 {
+            (self.f)(&mut dst[..n]);
+        }
+        n
+    }
+
+    /// Unwraps the consumer.
+    #[must_use]
+    #[inline]
+    pub fn into_inner(self) -> Consumer {
+        self.inner
+    }
+}
+
+#[cfg(feature = "std")]
+impl<F: FnMut(&mut [u8])> io::Read for TransformReader<F> {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        Ok(TransformReader::read(self, dst))
+    }
+}
@@ -0,0 +1,501 @@
+//! Rings of typed elements, e.g. `f32` audio samples or telemetry structs.
+//!
+//! The halves mirror [`crate::Producer`] and [`crate::Consumer`], but hand
+//! out `&mut [T]` and `&[T]`, and count elements instead of bytes.
+//!
+//! Values that are not plain old data travel encoded, over a [`channel`].
+
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::convert::TryInto as _;
+use ::core::debug_assert;
+use ::core::default::Default;
+use ::core::fmt;
+use ::core::hint;
+use ::core::marker::{Copy, PhantomData, Send, Sized, Sync};
+use ::core::mem;
+use ::core::ops::{FnMut, FnOnce, Range};
+use ::core::option::Option::{self, None, Some};
+use ::core::ptr;
+use ::core::result::Result::{self, Err, Ok};
+use ::core::sync::atomic::AtomicUsize;
+use ::core::write;
+use ::crossbeam_utils::CachePadded;
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{
+    AlignedData, BufferError, ConsumerError, ProducerError, SendNotSyncZst, TrySendError,
+    empty_ranges, filled_ranges,
+};
+
+/// Creates a producer-consumer pair sharing a ring of `capacity` elements,
+/// which must be a power of two. Every element starts out as
+/// `T::default()`.
+///
+/// # Errors
+///
+/// Returns an error when `capacity` is not a power of two, `T` is zero
+/// sized, or the allocation fails.
+#[inline]
+pub fn new<T: Copy + Default>(capacity: usize) -> Result<(Producer<T>, Consumer<T>), BufferError> {
+    let Some(size) = capacity.checked_mul(mem::size_of::<T>()) else {
+        return Err(BufferError::BadSize(capacity));
+    };
+    if !capacity.is_power_of_two() || size == 0 {
+        return Err(BufferError::BadSize(capacity));
+    }
+
+    let data = AlignedData::new(size, mem::align_of::<T>(), None)?;
+    let base = data.ptr.as_ptr().cast::<T>();
+    for i in 0..capacity {
+        // SAFETY: the allocation holds `capacity` elements and is aligned
+        //         for `T`; nothing references it yet.
+        unsafe { ptr::write(base.add(i), T::default()) };
+    }
+
+    let buffer = Arc::new(Buffer {
+        read: CachePadded::default(),
+        write: CachePadded::default(),
+        mask: capacity - 1,
+        data,
+        _elements: PhantomData,
+    });
+    let producer = Producer {
+        buffer: Arc::clone(&buffer),
+        _notsync: PhantomData,
+    };
+    let consumer = Consumer {
+        buffer,
+        _notsync: PhantomData,
+    };
+    Ok((producer, consumer))
+}
+
+#[derive(Debug)]
+struct Buffer<T> {
+    read: CachePadded<AtomicUsize>,
+    write: CachePadded<AtomicUsize>,
+    /// Capacity in elements minus one.
+    mask: usize,
+    data: AlignedData,
+    _elements: PhantomData<T>,
+}
+
+// SAFETY: Sync is safe for the same reasons as for the byte buffer: each
+//         counter is advanced only by its uniquely owned half, and the
+//         halves' ranges never overlap. Elements cross threads, hence `T`
+//         must be `Send`.
+unsafe impl<T: Send> Sync for Buffer<T> {}
+
+impl<T: Copy> Buffer<T> {
+    #[inline]
+    fn capacity(&self) -> usize {
+        self.mask + 1
+    }
+
+    /// Scales element ranges to the byte ranges of `data`.
+    #[inline]
+    fn bytes(ranges: [Range<usize>; 2]) -> [Range<usize>; 2] {
+        let size = mem::size_of::<T>();
+        ranges.map(|r| r.start * size..r.end * size)
+    }
+
+    /// # Safety
+    /// See [`AlignedData::slices`], with element ranges.
+    #[inline]
+    unsafe fn slices(&self, ranges: [Range<usize>; 2]) -> [&[T]; 2] {
+        // SAFETY: forwarded to the caller. The data is aligned for `T` and
+        //         holds initialized elements, which byte ranges scaled from
+        //         element ranges cover whole.
+        unsafe {
+            self.data.slices(Self::bytes(ranges)).map(|s| {
+                &*ptr::slice_from_raw_parts(s.as_ptr().cast::<T>(), s.len() / mem::size_of::<T>())
+            })
+        }
+    }
+
+    /// # Safety
+    /// See [`AlignedData::slices_mut`], with element ranges.
+    #[inline]
+    #[expect(
+        clippy::mut_from_ref,
+        reason = "aliasing is ruled out by the # Safety contract"
+    )]
+    unsafe fn slices_mut(&self, ranges: [Range<usize>; 2]) -> [&mut [T]; 2] {
+        // SAFETY: as for `slices`; `T` is `Copy`, so overwriting elements
+        //         drops nothing.
+        unsafe {
+            self.data.slices_mut(Self::bytes(ranges)).map(|s| {
+                &mut *ptr::slice_from_raw_parts_mut(
+                    s.as_mut_ptr().cast::<T>(),
+                    s.len() / mem::size_of::<T>(),
+                )
+            })
+        }
+    }
+}
+
+/// The writing half of a typed ring, see [`new`].
+#[derive(Debug)]
+pub struct Producer<T> {
+    buffer: Arc<Buffer<T>>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl<T: Copy> Producer<T> {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn produce_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&mut [T]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let buffer = &*self.buffer;
+        let w = buffer.write.load(Relaxed);
+        let r = buffer.read.load(Acquire);
+
+        let (mut ranges, mut len) = empty_ranges(buffer.capacity(), buffer.mask, r, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map the empty region only, which the consumer does
+        //         not read at the same time.
+        let bufs = unsafe { buffer.slices_mut(ranges) };
+
+        let n = f(bufs, len).map_err(ProducerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ProducerError::InvalidCount { n, len });
+        }
+
+        if n != 0 {
+            buffer.write.store(w.wrapping_add(n), Release);
+        }
+        Ok(n)
+    }
+
+    /// Fills the ring: calls the passed closure with a pair of `&mut [T]`
+    /// mapping the empty slots and their total length. The closure must
+    /// return the number of elements written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [&mut [T]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(false, |mut bufs, len| f(&mut bufs, len))
+    }
+
+    /// Fills the ring: calls the passed closure with a single `&mut [T]`
+    /// mapping the contiguous part of the empty slots.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ProducerError::Callback`] with the closure's error
+    /// unchanged, or [`ProducerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&mut [T]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        self.produce_fn(true, |[buf, _], _| f(buf))
+    }
+}
+
+/// The reading half of a typed ring, see [`new`].
+#[derive(Debug)]
+pub struct Consumer<T> {
+    buffer: Arc<Buffer<T>>,
+    _notsync: PhantomData<SendNotSyncZst>,
+}
+
+impl<T: Copy> Consumer<T> {
+    #[inline]
+    #[expect(
+        clippy::needless_pass_by_ref_mut,
+        reason = "rules out re-entering while slices are live"
+    )]
+    fn consume_fn<E>(
+        &mut self,
+        contiguous: bool,
+        mut f: impl FnMut([&[T]; 2], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+        let r = buffer.read.load(Relaxed);
+        let w = buffer.write.load(Acquire);
+
+        let (mut ranges, mut len) = filled_ranges(buffer.capacity(), buffer.mask, r, w);
+        if contiguous {
+            len = crate::range_len(&ranges[0]);
+            ranges[1] = 0..0;
+        }
+
+        // SAFETY: ranges map the filled region only, which the producer does
+        //         not write at the same time.
+        let bufs = unsafe { buffer.slices(ranges) };
+
+        let n = f(bufs, len).map_err(ConsumerError::Callback)?;
+        if n > len {
+            hint::cold_path();
+            return Err(ConsumerError::InvalidCount { n, len });
+        }
+
+        if n != 0 {
+            buffer.read.store(r.wrapping_add(n), Release);
+        }
+        Ok(n)
+    }
+
+    /// Drains the ring: calls the passed closure with a pair of `&[T]`
+    /// mapping the filled slots and their total length. The closure must
+    /// return the number of elements read.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the total length it was given.
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        mut f: impl FnMut(&[&[T]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(false, |bufs, len| f(&bufs, len))
+    }
+
+    /// Drains the ring: calls the passed closure with a single `&[T]`
+    /// mapping the contiguous part of the filled slots.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged, or [`ConsumerError::InvalidCount`] if the closure returned
+    /// a count greater than the length of the slice it was given.
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        mut f: impl FnMut(&[T]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.consume_fn(true, |[buf, _], _| f(buf))
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.buffer.read.load(Relaxed) == self.buffer.write.load(Relaxed)
+    }
+}
+
+/// Types sent over a typed [`channel`] as encoded messages.
+///
+/// Implement this and [`Decode`] with the codec of your choice, e.g. by
+/// forwarding to `postcard` or `bincode` for serde types. Encodings must not
+/// be empty.
+pub trait Encode {
+    /// Appends the encoding of `self` to `dst`.
+    fn encode(&self, dst: &mut Vec<u8>);
+}
+
+/// Types received over a typed [`channel`], see [`Encode`].
+pub trait Decode: Sized {
+    /// Decodes a value from a whole message, or returns `None` if it is
+    /// malformed.
+    fn decode(src: &[u8]) -> Option<Self>;
+}
+
+/// Types received over a typed [`channel`] as views of the ring's bytes,
+/// see [`Receiver::try_view`].
+///
+/// Meant for zero-copy formats, e.g. `rkyv`: `Target` is the archived type
+/// and `view` validates the bytes in place. Messages are not aligned in the
+/// ring, formats that need aligned bytes must check for it.
+pub trait View {
+    /// The view of a message.
+    type Target<'a>;
+
+    /// Views a whole message, or returns `None` if it is malformed.
+    fn view(src: &[u8]) -> Option<Self::Target<'_>>;
+}
+
+macro_rules! impl_codec {
+    ($($t:ty),*) => {$(
+        impl Encode for $t {
+            #[inline]
+            fn encode(&self, dst: &mut Vec<u8>) {
+                dst.extend_from_slice(&self.to_le_bytes());
+            }
+        }
+
+        impl Decode for $t {
+            #[inline]
+            fn decode(src: &[u8]) -> Option<Self> {
+                src.try_into().ok().map(<$t>::from_le_bytes)
+            }
+        }
+    )*};
+}
+
+impl_codec!(u8, u16, u32, u64, u128, i8, i16, i32, i64, i128, f32, f64);
+
+/// Creates a bounded channel of encoded values, sharing a ring of `size`
+/// bytes that holds up to `messages` messages. Both must be powers of two.
+///
+/// Values are encoded into a buffer kept by the sender, and decoded right
+/// out of the ring unless a message wraps around its end.
+///
+/// # Errors
+///
+/// Returns an error when a parameter is not a power of two, or when an
+/// allocation fails.
+#[inline]
+pub fn channel<T>(size: usize, messages: usize) -> Result<(Sender<T>, Receiver<T>), BufferError> {
+    let (sender, receiver) = crate::channel(size, messages)?;
+    let sender = Sender {
+        inner: sender,
+        encoded: Vec::new(),
+        _values: PhantomData,
+    };
+    let receiver = Receiver {
+        inner: receiver,
+        wrapped: Vec::new(),
+        _values: PhantomData,
+    };
+    Ok((sender, receiver))
+}
+
+/// The error type returned by [`Receiver::try_recv`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError {
+    /// There is no message right now.
+    Empty,
+    /// The sender was dropped and all messages were received.
+    Closed,
+    /// The next message did not decode, it was discarded.
+    Malformed,
+}
+
+impl fmt::Display for TryRecvError {
+    #[inline]
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TryRecvError::Empty => write!(f, "receiving on an empty channel"),
+            TryRecvError::Closed => write!(f, "receiving on a closed channel"),
+            TryRecvError::Malformed => write!(f, "received a malformed message"),
+        }
+    }
+}
+
+impl ::core::error::Error for TryRecvError {}
+
+/// The sending half of a typed [`channel`].
+#[derive(Debug)]
+pub struct Sender<T> {
+    inner: crate::Sender,
+    encoded: Vec<u8>,
+    _values: PhantomData<fn(&T)>,
+}
+
+impl<T: Encode> Sender<T> {
+    /// Encodes and sends `value` without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TrySendError::Closed`] if the receiver was dropped, or
+    /// [`TrySendError::Full`] if there is no room for the message.
+    #[inline]
+    pub fn try_send(&mut self, value: &T) -> Result<(), TrySendError> {
+        self.encoded.clear();
+        value.encode(&mut self.encoded);
+        debug_assert!(!self.encoded.is_empty(), "empty encoding");
+        self.inner.try_send(&self.encoded)
+    }
+}
+
+/// The receiving half of a typed [`channel`].
+#[derive(Debug)]
+pub struct Receiver<T> {
+    inner: crate::Receiver,
+    /// Reassembles messages wrapping around the end of the ring.
+    wrapped: Vec<u8>,
+    _values: PhantomData<fn() -> T>,
+}
+
+impl<T> Receiver<T> {
+    /// Passes the next message to `f` without blocking, consuming it when
+    /// `f` returns.
+    #[inline]
+    fn try_recv_with<R>(&mut self, f: impl FnOnce(&[u8]) -> R) -> Result<R, TryRecvError> {
+        let wrapped = &mut self.wrapped;
+        let mut f = Some(f);
+        let mut result = None;
+        let n = self.inner.try_recv_with(|message, _| {
+            let Some(f) = f.take() else {
+                return Ok(());
+            };
+            result = Some(if let [buf] = message {
+                f(buf)
+            } else {
+                wrapped.clear();
+                for buf in message {
+                    wrapped.extend_from_slice(buf);
+                }
+                f(wrapped)
+            });
+            Ok(())
+        });
+        match (n, result) {
+            (Ok(_), Some(result)) => Ok(result),
+            (Ok(_), None) => ::core::unreachable!("message consumed unseen"),
+            (Err(crate::TryRecvError::Empty), _) => Err(TryRecvError::Empty),
+            (Err(crate::TryRecvError::Closed), _) => Err(TryRecvError::Closed),
+            (Err(crate::TryRecvError::TooSmall(_)), _) => {
+                ::core::unreachable!("closure takes any length")
+            }
+        }
+    }
+}
+
+impl<T: View> Receiver<T> {
+    /// Views the next message in place and passes the view to `f` without
+    /// blocking. The message is consumed when `f` returns. Only a message
+    /// wrapping around the end of the ring is copied first.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryRecvError::Empty`] if there is no message,
+    /// [`TryRecvError::Closed`] if there is none and the sender was dropped,
+    /// or [`TryRecvError::Malformed`] if the message did not validate.
+    #[inline]
+    pub fn try_view<R>(&mut self, f: impl FnOnce(T::Target<'_>) -> R) -> Result<R, TryRecvError> {
+        self.try_recv_with(|message| T::view(message).map(f))?
+            .ok_or(TryRecvError::Malformed)
+    }
+}
+
+impl<T: Decode> Receiver<T> {
+    /// Receives and decodes the next value without blocking.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TryRecvError::Empty`] if there is no message,
+    /// [`TryRecvError::Closed`] if there is none and the sender was dropped,
+    /// or [`TryRecvError::Malformed`] if the message did not decode.
+    #[inline]
+    pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+        self.try_recv_with(T::decode)?
+            .ok_or(TryRecvError::Malformed)
+    }
+}
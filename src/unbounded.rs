@@ -0,0 +1,274 @@
+//! Unbounded ring chaining fixed-size segments, see [`new_unbounded`].
+//!
+//! [`new_unbounded`]: crate::new_unbounded
+
+use ::alloc::collections::VecDeque;
+use ::alloc::sync::Arc;
+use ::alloc::vec::Vec;
+use ::core::clone::Clone;
+use ::core::debug_assert_eq;
+use ::core::default::Default as _;
+use ::core::mem;
+use ::core::ops::FnMut;
+use ::core::option::Option::{self, Some};
+use ::core::result::Result::{self, Ok};
+use ::std::io;
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::{Buffer, BufferError, Builder, Consumer, ConsumerError, Producer, ProducerError};
+
+/// State shared by both halves, rarely touched: only when the producer
+/// moves on to a new segment and when the consumer follows it.
+#[derive(Debug)]
+struct Shared {
+    /// Builds every new segment, with the options of the first.
+    builder: Builder,
+    state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Segments after the consumer's current one, oldest first.
+    queue: VecDeque<Consumer>,
+    /// Drained segments, kept for reuse by the producer.
+    free: Vec<Arc<Buffer>>,
+}
+
+impl Shared {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, State> {
+        // The state is consistent between statements, a panic while holding
+        // the lock can't leave it broken.
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Builds the first segment and chains further ones alike with `builder`.
+#[inline]
+pub fn new(builder: Builder) -> Result<(UnboundedProducer, UnboundedConsumer), BufferError> {
+    let (producer, consumer) = builder.clone().build()?;
+    let shared = Arc::new(Shared {
+        builder,
+        state: Mutex::default(),
+    });
+    Ok((
+        UnboundedProducer {
+            current: producer,
+            shared: Arc::clone(&shared),
+        },
+        UnboundedConsumer {
+            current: consumer,
+            shared,
+        },
+    ))
+}
+
+/// The writing half of an unbounded ring, see [`new_unbounded`].
+///
+/// [`new_unbounded`]: crate::new_unbounded
+#[derive(Debug)]
+pub struct UnboundedProducer {
+    current: Producer,
+    shared: Arc<Shared>,
+}
+
+impl UnboundedProducer {
+    /// Returns the name of the ring, see [`Builder::name`].
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.current.name()
+    }
+
+    /// Returns whether a callback panicked on the current segment, see
+    /// [`Builder::poison_on_panic`].
+    #[must_use]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.current.is_poisoned()
+    }
+
+    /// Moves on to a new segment once the current one is full, reusing a
+    /// drained one if available. Stays on the full segment if the
+    /// allocation fails or is refused by the accounting hook, or if the
+    /// segment is poisoned, so the poisoning is not left behind.
+    #[inline]
+    fn advance(&mut self) -> Result<(), BufferError> {
+        if self.current.free_len() != 0 || self.current.buffer.is_poisoned() {
+            return Ok(());
+        }
+
+        let reused = self.shared.lock().free.pop();
+        let (producer, consumer) = if let Some(buffer) = reused {
+            (Producer::new(Arc::clone(&buffer)), Consumer::new(buffer))
+        } else {
+            self.shared.builder.clone().build()?
+        };
+
+        // Publish everything to the old segment before the consumer can see
+        // the new one, it drains the old segment for good once it does.
+        mem::drop(mem::replace(&mut self.current, producer));
+        self.shared.lock().queue.push_back(consumer);
+        Ok(())
+    }
+
+    /// Fills the ring: calls the passed closure with a pair of `&mut [u8]`
+    /// mapping the empty space of the current segment, see
+    /// [`Producer::slices`]. A new segment is appended first if the current
+    /// one is full, so the slices are only empty if that fails, see
+    /// [`io::Write`] for the error.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let _ = self.advance();
+        self.current.slices(f)
+    }
+
+    /// Fills the ring: calls the passed closure with a single `&mut [u8]`
+    /// mapping the contiguous part of the empty space of the current
+    /// segment, see [`Producer::slice`]. A new segment is appended first if
+    /// the current one is full.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Producer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        f: impl FnMut(&mut [u8]) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let _ = self.advance();
+        self.current.slice(f)
+    }
+}
+
+impl io::Write for UnboundedProducer {
+    /// Writes as much of `src` as fits into the current segment, appending
+    /// a new one first if it is full. Fails with the [`BufferError`] as the
+    /// inner error if that fails, e.g. [`BufferError::OverBudget`].
+    #[inline]
+    fn write(&mut self, src: &[u8]) -> io::Result<usize> {
+        self.advance().map_err(io::Error::other)?;
+        io::Write::write(&mut self.current, src)
+    }
+
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.current)
+    }
+}
+
+/// The reading half of an unbounded ring, see [`new_unbounded`].
+///
+/// [`new_unbounded`]: crate::new_unbounded
+#[derive(Debug)]
+pub struct UnboundedConsumer {
+    current: Consumer,
+    shared: Arc<Shared>,
+}
+
+impl UnboundedConsumer {
+    /// Returns the name of the ring, see [`Builder::name`].
+    #[must_use]
+    #[inline]
+    pub fn name(&self) -> Option<&str> {
+        self.current.name()
+    }
+
+    /// Returns whether a callback panicked on the current segment, see
+    /// [`Builder::poison_on_panic`].
+    #[must_use]
+    #[inline]
+    pub fn is_poisoned(&self) -> bool {
+        self.current.is_poisoned()
+    }
+
+    /// Follows the producer to the next segment once the current one is
+    /// drained, releasing it to the free list.
+    #[inline]
+    fn advance(&mut self) {
+        while self.current.is_empty() {
+            let mut state = self.shared.lock();
+            // The producer published its last bytes to the current segment
+            // before queuing the next one: check again under the lock.
+            if !self.current.is_empty() {
+                return;
+            }
+            let Some(next) = state.queue.pop_front() else {
+                return;
+            };
+
+            let drained = mem::replace(&mut self.current, next);
+            let buffer = Arc::clone(&drained.buffer);
+            mem::drop(drained);
+            // The producer dropped its half before queuing the next segment.
+            debug_assert_eq!(Arc::strong_count(&buffer), 1);
+            state.free.push(buffer);
+        }
+    }
+
+    /// Drains the ring: calls the passed closure with a pair of `&[u8]`
+    /// mapping the filled space of the current segment, see
+    /// [`Consumer::slices`]. Drained segments are left behind first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.advance();
+        self.current.slices(f)
+    }
+
+    /// Drains the ring: calls the passed closure with a single `&[u8]`
+    /// mapping the contiguous part of the filled space of the current
+    /// segment, see [`Consumer::slice`]. Drained segments are left behind
+    /// first.
+    ///
+    /// # Errors
+    ///
+    /// Returns the errors of [`Consumer::slice`].
+    #[inline]
+    pub fn slice<E>(
+        &mut self,
+        f: impl FnMut(&[u8]) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        self.advance();
+        self.current.slice(f)
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&mut self) -> bool {
+        self.advance();
+        self.current.is_empty()
+    }
+
+    /// Returns the number of segments in use, including the free ones kept
+    /// for reuse.
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn segments(&self) -> usize {
+        let state = self.shared.lock();
+        1 + state.queue.len() + state.free.len()
+    }
+}
+
+impl io::Read for UnboundedConsumer {
+    #[inline]
+    fn read(&mut self, dst: &mut [u8]) -> io::Result<usize> {
+        self.advance();
+        io::Read::read(&mut self.current, dst)
+    }
+}
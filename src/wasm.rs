@@ -0,0 +1,296 @@
+//! Rings between web workers and the main thread, see [`new`].
+//!
+//! Built for `wasm32` with `-C target-feature=+atomics,+bulk-memory` and a
+//! standard library rebuilt for it, a module's linear memory is a
+//! `SharedArrayBuffer` every worker instantiating the module shares, and
+//! the ring's atomics compile to `memory.atomic` instructions. A ring is
+//! thus shared as it is between threads: create it on one side, hand a
+//! half to a worker with [`Producer::into_raw`] through `postMessage` and
+//! take it with [`Producer::from_raw`] there.
+//!
+//! [`Producer::wait`] and [`Consumer::wait`] block with
+//! `memory.atomic.wait32`, `Atomics.wait` in JavaScript, until the other
+//! half notifies with `memory.atomic.notify`. Browsers do not allow the
+//! main thread to block, `Atomics.wait` traps there: only wait in workers,
+//! and poll the main thread's half from its event loop. The intrinsics are
+//! unstable, so on `wasm32` the feature needs a nightly toolchain, as
+//! rebuilding the standard library does anyway. On other targets, waiting
+//! polls, e.g. for tests.
+
+use ::alloc::boxed::Box;
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::ops::{Fn, FnMut};
+use ::core::option::Option;
+use ::core::ptr;
+use ::core::result::Result::{self, Ok};
+use ::core::sync::atomic::{AtomicU32, fence};
+use ::core::time::Duration;
+
+use crate::ordering::{Acquire, Relaxed, SeqCst};
+use crate::{BufferError, ConsumerError, ProducerError};
+
+/// Creates a pair of halves sharing a ring buffer of `size` bytes, aligned
+/// to `align`, which may block on each other in workers.
+///
+/// # Errors
+///
+/// Returns an error when `size` or `align` is not a power of two, or when
+/// the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize) -> Result<(Producer, Consumer), BufferError> {
+    let (producer, consumer) = crate::new(size, align)?;
+    let shared = Arc::new(Shared {
+        write_event: AtomicU32::new(0),
+        read_event: AtomicU32::new(0),
+        consumer_waiting: AtomicU32::new(0),
+        producer_waiting: AtomicU32::new(0),
+    });
+    let producer = Producer {
+        inner: producer,
+        shared: Arc::clone(&shared),
+    };
+    let consumer = Consumer {
+        inner: consumer,
+        shared,
+    };
+    Ok((producer, consumer))
+}
+
+/// The events both halves wait on, and whether they do.
+#[derive(Debug)]
+struct Shared {
+    write_event: AtomicU32,
+    read_event: AtomicU32,
+    consumer_waiting: AtomicU32,
+    producer_waiting: AtomicU32,
+}
+
+/// The writing half of a ring shared with workers, see [`new`].
+#[derive(Debug)]
+pub struct Producer {
+    inner: crate::Producer,
+    shared: Arc<Shared>,
+}
+
+impl Producer {
+    /// Passes the empty space to `f` as [`crate::Producer::slices`] does,
+    /// and notifies the consumer if it waits.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::Producer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        f: impl FnMut(&mut [&mut [u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ProducerError<E>> {
+        let n = self.inner.slices(f)?;
+        if n != 0 {
+            notify(&self.shared.consumer_waiting, &self.shared.write_event);
+        }
+        Ok(n)
+    }
+
+    /// Blocks until at least `bytes` bytes are free, at most the capacity,
+    /// or until `timeout` passes without the consumer releasing any.
+    /// Returns whether they are. Must not be called on the main thread of
+    /// a browser, see the [module docs](self).
+    #[must_use]
+    #[inline]
+    pub fn wait(&self, bytes: usize, timeout: Option<Duration>) -> bool {
+        let bytes = bytes.min(self.inner.buffer.data.len());
+        block(
+            &self.shared.producer_waiting,
+            &self.shared.read_event,
+            timeout,
+            || self.inner.free_len() >= bytes,
+        )
+    }
+
+    /// Turns the producer into an address, e.g. to post it to a worker.
+    #[must_use]
+    #[inline]
+    pub fn into_raw(self) -> usize {
+        Box::into_raw(Box::new(self)).expose_provenance()
+    }
+
+    /// Takes back a producer turned into an address.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must come from [`Producer::into_raw`] in the same module's
+    /// memory and must not be taken back more than once.
+    #[must_use]
+    #[inline]
+    pub unsafe fn from_raw(raw: usize) -> Self {
+        // SAFETY: `raw` is the address of a boxed producer, see above.
+        *unsafe { Box::from_raw(ptr::with_exposed_provenance_mut(raw)) }
+    }
+}
+
+/// The reading half of a ring shared with workers, see [`new`].
+#[derive(Debug)]
+pub struct Consumer {
+    inner: crate::Consumer,
+    shared: Arc<Shared>,
+}
+
+impl Consumer {
+    /// Passes the filled space to `f` as [`crate::Consumer::slices`] does,
+    /// and notifies the producer if it waits.
+    ///
+    /// # Errors
+    ///
+    /// See [`crate::Consumer::slices`].
+    #[inline]
+    pub fn slices<E>(
+        &mut self,
+        f: impl FnMut(&[&[u8]], usize) -> Result<usize, E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let n = self.inner.slices(f)?;
+        if n != 0 {
+            notify(&self.shared.producer_waiting, &self.shared.read_event);
+        }
+        Ok(n)
+    }
+
+    /// Blocks until at least `bytes` bytes are filled, at most the
+    /// capacity, or until `timeout` passes without the producer committing
+    /// any. Returns whether they are. Must not be called on the main thread
+    /// of a browser, see the [module docs](self).
+    #[must_use]
+    #[inline]
+    pub fn wait(&self, bytes: usize, timeout: Option<Duration>) -> bool {
+        let buffer = &self.inner.buffer;
+        let bytes = bytes.min(buffer.data.len());
+        block(
+            &self.shared.consumer_waiting,
+            &self.shared.write_event,
+            timeout,
+            || {
+                let r = buffer.read.load(Relaxed);
+                buffer.write.load(Acquire).wrapping_sub(r) >= bytes
+            },
+        )
+    }
+
+    /// Turns the consumer into an address, e.g. to post it to a worker.
+    #[must_use]
+    #[inline]
+    pub fn into_raw(self) -> usize {
+        Box::into_raw(Box::new(self)).expose_provenance()
+    }
+
+    /// Takes back a consumer turned into an address.
+    ///
+    /// # Safety
+    ///
+    /// `raw` must come from [`Consumer::into_raw`] in the same module's
+    /// memory and must not be taken back more than once.
+    #[must_use]
+    #[inline]
+    pub unsafe fn from_raw(raw: usize) -> Self {
+        // SAFETY: `raw` is the address of a boxed consumer, see above.
+        *unsafe { Box::from_raw(ptr::with_exposed_provenance_mut(raw)) }
+    }
+}
+
+/// Bumps `event` and wakes its waiters if the other half is `waiting`.
+#[inline]
+fn notify(waiting: &AtomicU32, event: &AtomicU32) {
+    // Pairs with the fence in `block`: either the waiter sees the counter
+    // advanced, or this half sees the waiter.
+    fence(SeqCst);
+    if waiting.load(Relaxed) != 0 {
+        let _ = event.fetch_add(1, Relaxed);
+        sys::wake(event);
+    }
+}
+
+/// Blocks until `ready` returns true, flagging `waiting` meanwhile so the
+/// other half bumps `event`, or until a wait for `event` times out.
+/// Returns `ready`.
+#[inline]
+fn block(
+    waiting: &AtomicU32,
+    event: &AtomicU32,
+    timeout: Option<Duration>,
+    ready: impl Fn() -> bool,
+) -> bool {
+    loop {
+        if ready() {
+            return true;
+        }
+        waiting.store(1, Relaxed);
+        fence(SeqCst);
+        let seen = event.load(Relaxed);
+        if ready() {
+            waiting.store(0, Relaxed);
+            return true;
+        }
+        let timed_out = sys::wait(event, seen, timeout);
+        waiting.store(0, Relaxed);
+        if timed_out {
+            return ready();
+        }
+    }
+}
+
+#[cfg(all(target_arch = "wasm32", target_feature = "atomics"))]
+mod sys {
+    use ::core::arch::wasm32;
+    use ::core::convert::TryFrom as _;
+    use ::core::option::Option;
+    use ::core::sync::atomic::AtomicU32;
+    use ::core::time::Duration;
+
+    /// Waits until `word` is woken, unless it no longer holds `expected`.
+    /// Returns whether `timeout` passed.
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+        let timeout = timeout.map_or(-1, |timeout| {
+            i64::try_from(timeout.as_nanos()).unwrap_or(i64::MAX)
+        });
+        // SAFETY: word is a valid, aligned 32-bit atomic in shared memory
+        //         for the duration of the call.
+        let status = unsafe {
+            wasm32::memory_atomic_wait32(word.as_ptr().cast(), expected.cast_signed(), timeout)
+        };
+        status == 2
+    }
+
+    #[inline]
+    pub fn wake(word: &AtomicU32) {
+        // SAFETY: see `wait`.
+        let _ = unsafe { wasm32::memory_atomic_notify(word.as_ptr().cast(), u32::MAX) };
+    }
+}
+
+#[cfg(not(all(target_arch = "wasm32", target_feature = "atomics")))]
+mod sys {
+    use crate::ordering::Relaxed;
+    use ::core::option::Option;
+    use ::core::sync::atomic::AtomicU32;
+    use ::core::time::Duration;
+    use ::std::thread;
+    use ::std::time::Instant;
+
+    /// Polls until `word` no longer holds `expected`. Returns whether
+    /// `timeout` passed.
+    #[inline]
+    pub fn wait(word: &AtomicU32, expected: u32, timeout: Option<Duration>) -> bool {
+        let start = Instant::now();
+        while word.load(Relaxed) == expected {
+            if timeout.is_some_and(|timeout| start.elapsed() >= timeout) {
+                return true;
+            }
+            thread::yield_now();
+        }
+        false
+    }
+
+    #[inline]
+    pub const fn wake(_: &AtomicU32) {}
+}
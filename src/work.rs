@@ -0,0 +1,150 @@
+//! Several consumers sharing the work of draining one ring.
+//!
+//! Every [`Worker`] claims a chunk of whole frames, processes it and commits
+//! it, concurrently with the others; every byte is consumed by exactly one
+//! worker. Chunks may be committed out of order, the space is handed back to
+//! the producer once all chunks before it are committed too.
+
+use ::alloc::collections::BTreeMap;
+use ::alloc::sync::Arc;
+use ::core::clone::Clone;
+use ::core::cmp::Ord as _;
+use ::core::ops::{Drop, FnOnce};
+use ::core::option::Option::Some;
+use ::core::result::Result::{self, Ok};
+use ::std::sync::{Mutex, MutexGuard, PoisonError};
+
+use crate::ordering::{Acquire, Relaxed, Release};
+use crate::{Buffer, BufferError, Builder, ConsumerError, Producer, filled_ranges};
+
+/// Creates a producer and a first worker sharing a ring buffer of `size`
+/// bytes, aligned to `align`, carrying frames of `frame` bytes. Clone the
+/// worker for more.
+///
+/// # Errors
+///
+/// Returns an error when a parameter is not a power of two, the frame is
+/// larger than the buffer, or the allocation fails.
+#[inline]
+pub fn new(size: usize, align: usize, frame: usize) -> Result<(Producer, Worker), BufferError> {
+    let (producer, consumer) = Builder::new(size).align(align).frame_size(frame).build()?;
+    let worker = Worker {
+        buffer: Arc::clone(&consumer.buffer),
+        claims: Arc::new(Mutex::new(Claims {
+            claimed: 0,
+            committed: BTreeMap::new(),
+        })),
+    };
+    Ok((producer, worker))
+}
+
+#[derive(Debug)]
+struct Claims {
+    /// End of the last chunk claimed.
+    claimed: usize,
+    /// Chunks committed ahead of an older one, start to end.
+    committed: BTreeMap<usize, usize>,
+}
+
+/// One of the consumers sharing a ring, see [`new`]. Clones share the
+/// work.
+#[derive(Debug, Clone)]
+pub struct Worker {
+    buffer: Arc<Buffer>,
+    claims: Arc<Mutex<Claims>>,
+}
+
+impl Worker {
+    #[inline]
+    fn lock(&self) -> MutexGuard<'_, Claims> {
+        // Claims are consistent between statements, and chunks of panicking
+        // closures are committed by the guard below.
+        self.claims.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Claims up to `max` bytes of whole frames and calls the passed closure
+    /// with a pair of `&[u8]` mapping them and their total length. The chunk
+    /// is committed once the closure returns, also if it fails or panics.
+    /// Returns the length of the chunk, without calling the closure if there
+    /// was none.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConsumerError::Callback`] with the closure's error
+    /// unchanged.
+    #[inline]
+    pub fn chunk<E>(
+        &self,
+        max: usize,
+        f: impl FnOnce(&[&[u8]], usize) -> Result<(), E>,
+    ) -> Result<usize, ConsumerError<E>> {
+        let buffer = &*self.buffer;
+        let start;
+        let len;
+        {
+            let mut claims = self.lock();
+            start = claims.claimed;
+            let w = buffer.write.load(Acquire);
+            len = w.wrapping_sub(start).min(max) & !buffer.frame.wrapping_sub(1);
+            if len == 0 {
+                return Ok(0);
+            }
+            claims.claimed = start.wrapping_add(len);
+        }
+
+        let end = start.wrapping_add(len);
+        let _commit = Commit {
+            worker: self,
+            start,
+            end,
+        };
+        let (ranges, _) = filled_ranges(buffer.data.len(), buffer.mask, start, end);
+        // SAFETY: ranges map a chunk of the filled region claimed by this
+        //         call only. The producer doesn't write to it before it is
+        //         committed, and neither does any other worker read it.
+        let bufs = unsafe { buffer.data.slices(ranges) };
+
+        f(&bufs, len).map_err(ConsumerError::Callback)?;
+        Ok(len)
+    }
+
+    /// Commits the claimed chunk `start..end`, handing back the space of it
+    /// and any chunks committed ahead of it once it is the oldest.
+    #[inline]
+    fn commit(&self, start: usize, end: usize) {
+        let mut claims = self.lock();
+        let read = self.buffer.read.load(Relaxed);
+        if start != read {
+            claims.committed.insert(start, end);
+            return;
+        }
+
+        let mut read = end;
+        while let Some(end) = claims.committed.remove(&read) {
+            read = end;
+        }
+        self.buffer.read.store(read, Release);
+    }
+
+    #[doc(hidden)]
+    #[must_use]
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.lock().claimed == self.buffer.write.load(Relaxed)
+    }
+}
+
+/// Commits a chunk when dropped, so a panicking closure does not hold back
+/// the producer for good.
+struct Commit<'a> {
+    worker: &'a Worker,
+    start: usize,
+    end: usize,
+}
+
+impl Drop for Commit<'_> {
+    #[inline]
+    fn drop(&mut self) {
+        self.worker.commit(self.start, self.end);
+    }
+}